@@ -48,8 +48,11 @@ ROOK_VALUE = 5
 QUEEN_VALUE = 10
 WIN_REWARD = 100
 LOSS_REWARD = -100
+DRAW_REWARD = 0
 INVALID_ACTION_REWARD = -10
 VALID_ACTION_REWARD = 10
+# game statuses of `ChessEngine.get_game_status` that draw the game
+DRAW_STATUSES = ("stalemate", "fifty_moves", "insufficient_material")
 
 
 @dataclass
@@ -137,11 +140,15 @@ class ChessEnvV2(gym.Env):
         opponent="random",
         log=True,
         initial_board=DEFAULT_BOARD,
+        draw_statuses=("stalemate",),
     ):
         # constants
         self.moves_max = 149
         self.log = log
         self.initial_board = initial_board
+        # statuses ending the episode with DRAW_REWARD: pass DRAW_STATUSES to
+        # also end it by the fifty-move rule and insufficient material
+        self.draw_statuses = draw_statuses
 
         # engine
         self.engine = ChessEngine()
@@ -187,7 +194,6 @@ class ChessEnvV2(gym.Env):
         Outputs -> observation : the initial observation of the space. (Initial reward is assumed to be 0.)
         """
         self.board = self.initial_board
-        # self.prev_board = None ## TODO: use prev_board to check for en-passant mvoes
         self.done = False
         self.current_player = WHITE
         self.saved_boards = defaultdict(lambda: 0)
@@ -199,6 +205,9 @@ class ChessEnvV2(gym.Env):
         self.black_queen_castle_is_possible = True
         self.white_king_is_checked = False
         self.black_king_is_checked = False
+        self.en_passant_square = None
+        self.halfmove_clock = 0
+        self.fullmove_number = 1
         self.white_king_on_the_board = self.piece_is_on_board(self.board, KING_ID)
         self.black_king_on_the_board = self.piece_is_on_board(self.board, -KING_ID)
         # update state with engine
@@ -271,6 +280,9 @@ class ChessEnvV2(gym.Env):
         if not self.possible_moves and self.king_is_checked(player=opponent_player):
             self.done = True
             reward += WIN_REWARD
+        elif self.is_draw():
+            self.done = True
+            reward += DRAW_REWARD
         if self.done:
             return self.state, reward, self.done, self.info
 
@@ -287,6 +299,9 @@ class ChessEnvV2(gym.Env):
             if not self.possible_moves and self.king_is_checked(player=agent_player):
                 self.done = True
                 reward += LOSS_REWARD
+            elif self.is_draw():
+                self.done = True
+                reward += DRAW_REWARD
 
         # increment count on WHITE
         if self.current_player == WHITE:
@@ -303,7 +318,6 @@ class ChessEnvV2(gym.Env):
     def state(self):
         return dict(
             board=self.board,
-            # prev_board=self.prev_board,
             current_player=self.current_player,
             white_king_castle_is_possible=self.white_king_castle_is_possible,
             white_queen_castle_is_possible=self.white_queen_castle_is_possible,
@@ -311,6 +325,9 @@ class ChessEnvV2(gym.Env):
             black_queen_castle_is_possible=self.black_queen_castle_is_possible,
             white_king_is_checked=self.white_king_is_checked,
             black_king_is_checked=self.black_king_is_checked,
+            en_passant_square=self.en_passant_square,
+            halfmove_clock=self.halfmove_clock,
+            fullmove_number=self.fullmove_number,
         )
 
     @state.setter
@@ -322,6 +339,9 @@ class ChessEnvV2(gym.Env):
         self.black_queen_castle_is_possible = state.get("black_queen_castle_is_possible")
         self.white_king_is_checked = state.get("white_king_is_checked")
         self.black_king_is_checked = state.get("black_king_is_checked")
+        self.en_passant_square = state.get("en_passant_square")
+        self.halfmove_clock = state.get("halfmove_clock", 0)
+        self.fullmove_number = state.get("fullmove_number", 1)
 
     @property
     def possible_moves(self):
@@ -367,6 +387,9 @@ class ChessEnvV2(gym.Env):
     def current_player_is_black(self):
         return not self.current_player_is_white
 
+    def is_draw(self):
+        return self.engine.get_game_status(self.state)["status"] in self.draw_statuses
+
     def king_is_checked(self, player):
         if player == WHITE:
             return self.white_king_is_checked
@@ -445,8 +468,11 @@ class ChessEnvV2(gym.Env):
         if mode != "human":
             return outfile
 
-    def render(self, mode="human"):
-        """Render the playing board"""
+    def render(self, mode="human", heatmap=False):
+        """Render the playing board ("svg" returns an SVG image, with the
+        net board control of each square as an overlay if `heatmap`)"""
+        if mode == "svg":
+            return self.engine.render_svg(self.state, heatmap)
         grid = self.board_to_grid()
         out = self.render_grid(grid, mode=mode)
         return out
@@ -0,0 +1,111 @@
+from copy import copy
+
+import numpy as np
+from gym_chess import ChessEnvV2
+from gym_chess.envs.chess_v2 import (
+    KING_ID,
+    ROOK_ID,
+    BISHOP_ID,
+    KNIGHT_ID,
+    PAWN_ID,
+)
+from gym_chess.test.utils import run_test_funcs
+
+
+# Blank board
+BASIC_BOARD = np.array([[0] * 8] * 8, dtype=np.int8)
+
+
+# The king can't step back along the line of a rook check
+def test_king_rook_xray():
+    BOARD = copy(BASIC_BOARD)
+    BOARD[4, 4] = KING_ID
+    BOARD[0, 4] = -ROOK_ID
+    BOARD[0, 0] = -KING_ID
+    env = ChessEnvV2(opponent="none", initial_board=BOARD)
+    moves = env.get_possible_moves()
+    env.render_moves(moves)
+    expected_moves = set([(3, 3), (4, 3), (5, 3), (3, 5), (4, 5), (5, 5)])
+    squares_moved_to = set([tuple(move[1]) for move in moves])
+    assert squares_moved_to == expected_moves
+    assert env.white_king_is_checked
+
+
+# The king can't step back along the line of a bishop check
+def test_king_bishop_xray():
+    BOARD = copy(BASIC_BOARD)
+    BOARD[4, 4] = KING_ID
+    BOARD[1, 1] = -BISHOP_ID
+    BOARD[0, 7] = -KING_ID
+    env = ChessEnvV2(opponent="none", initial_board=BOARD)
+    moves = env.get_possible_moves()
+    env.render_moves(moves)
+    expected_moves = set([(3, 4), (5, 4), (4, 3), (4, 5), (3, 5), (5, 3)])
+    squares_moved_to = set([tuple(move[1]) for move in moves])
+    assert squares_moved_to == expected_moves
+    assert env.white_king_is_checked
+
+
+# Pawns attack both diagonal squares, whatever is on them
+def test_pawn_attacks():
+    BOARD = copy(BASIC_BOARD)
+    BOARD[4, 4] = PAWN_ID
+    BOARD[3, 3] = ROOK_ID
+    BOARD[3, 5] = -KNIGHT_ID
+    BOARD[7, 7] = KING_ID
+    BOARD[0, 0] = -KING_ID
+    env = ChessEnvV2(opponent="none", initial_board=BOARD)
+    moves = env.get_possible_moves(attack=True)
+    env.render_moves(moves)
+    squares_attacked = set([tuple(move[1]) for move in moves if tuple(move[0]) == (4, 4)])
+    assert squares_attacked == set([(3, 3), (3, 5)])
+
+
+# A pinned piece still attacks its squares (it can't move, but it guards them)
+def test_pinned_piece_attacks():
+    BOARD = copy(BASIC_BOARD)
+    BOARD[7, 4] = KING_ID
+    BOARD[6, 4] = KNIGHT_ID
+    BOARD[0, 4] = -ROOK_ID
+    BOARD[0, 0] = -KING_ID
+    env = ChessEnvV2(opponent="none", initial_board=BOARD)
+    moves = env.get_possible_moves()
+    env.render_moves(moves)
+    assert not [move for move in moves if tuple(move[0]) == (6, 4)]
+    moves = env.get_possible_moves(attack=True)
+    squares_attacked = set([tuple(move[1]) for move in moves if tuple(move[0]) == (6, 4)])
+    assert squares_attacked == set([(4, 3), (4, 5), (5, 2), (5, 6), (7, 2), (7, 6)])
+
+
+# The king can't capture a piece defended by the other king
+def test_king_capture_defended_by_king():
+    BOARD = copy(BASIC_BOARD)
+    BOARD[7, 4] = KING_ID
+    BOARD[6, 3] = PAWN_ID
+    BOARD[5, 4] = -KING_ID
+    env = ChessEnvV2(opponent="none", initial_board=BOARD)
+    moves = env.get_possible_moves(player="BLACK")
+    env.render_moves(moves)
+    expected_moves = set([(5, 3), (4, 3), (4, 4), (5, 5), (4, 5)])
+    squares_moved_to = set([tuple(move[1]) for move in moves])
+    assert squares_moved_to == expected_moves
+
+
+# Kings next to each other (inconsistent board): no panic, and neither king
+# can capture the other
+def test_kings_next_to_each_other():
+    BOARD = copy(BASIC_BOARD)
+    BOARD[4, 4] = KING_ID
+    BOARD[3, 4] = -KING_ID
+    for player in ["WHITE", "BLACK"]:
+        env = ChessEnvV2(opponent="none", initial_board=BOARD)
+        moves = env.get_possible_moves(player=player)
+        env.render_moves(moves)
+        squares_moved_to = set([tuple(move[1]) for move in moves])
+        assert (4, 4) not in squares_moved_to
+        assert (3, 4) not in squares_moved_to
+        env.get_possible_moves(player=player, attack=True)
+
+
+if __name__ == "__main__":
+    run_test_funcs(__name__)
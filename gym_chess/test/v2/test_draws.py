@@ -0,0 +1,52 @@
+from copy import copy
+
+import numpy as np
+from gym_chess import ChessEnvV2
+from gym_chess.envs.chess_v2 import DRAW_STATUSES, KING_ID, KNIGHT_ID, QUEEN_ID, ROOK_ID
+from gym_chess.test.utils import run_test_funcs
+
+
+# Blank board
+BASIC_BOARD = np.array([[0] * 8] * 8, dtype=np.int8)
+
+
+def play(board, move, halfmove_clock=0, **kwargs):
+    env = ChessEnvV2(opponent="none", initial_board=board, log=False, **kwargs)
+    env.halfmove_clock = halfmove_clock
+    _, _, done, _ = env.step(env.move_to_action(move))
+    return done
+
+
+# Stalemating the opponent ends the episode
+def test_stalemate():
+    BOARD = copy(BASIC_BOARD)
+    BOARD[0, 7] = -KING_ID
+    BOARD[2, 6] = KING_ID
+    BOARD[1, 0] = QUEEN_ID
+    assert play(BOARD, [(1, 0), (1, 5)])
+    assert not play(BOARD, [(1, 0), (1, 1)])
+
+
+# Taking the last piece but the kings ends the episode only with DRAW_STATUSES
+def test_insufficient_material():
+    BOARD = copy(BASIC_BOARD)
+    BOARD[0, 0] = -KING_ID
+    BOARD[6, 4] = -KNIGHT_ID
+    BOARD[7, 4] = KING_ID
+    assert play(BOARD, [(7, 4), (6, 4)], draw_statuses=DRAW_STATUSES)
+    assert not play(BOARD, [(7, 4), (6, 4)])
+
+
+# The hundredth quiet half-move ends the episode only with DRAW_STATUSES
+def test_fifty_moves():
+    BOARD = copy(BASIC_BOARD)
+    BOARD[0, 7] = -KING_ID
+    BOARD[7, 0] = ROOK_ID
+    BOARD[7, 4] = KING_ID
+    assert play(BOARD, [(7, 0), (6, 0)], halfmove_clock=99, draw_statuses=DRAW_STATUSES)
+    assert not play(BOARD, [(7, 0), (6, 0)], halfmove_clock=99)
+    assert not play(BOARD, [(7, 0), (6, 0)], halfmove_clock=98, draw_statuses=DRAW_STATUSES)
+
+
+if __name__ == "__main__":
+    run_test_funcs(__name__)
@@ -0,0 +1,115 @@
+from gym_chess.gym_chess import ChessEnv
+from gym_chess.test.utils import run_test_funcs
+
+
+MATE_IN_ONE_FEN = "6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1"
+
+
+def raises_value_error(func, *args):
+    try:
+        func(*args)
+    except ValueError:
+        return True
+    return False
+
+
+# An illegal move or action raises ValueError and leaves the environment as
+# it was, the opponent not answering
+def test_illegal_step_is_atomic():
+    for env in [ChessEnv(), ChessEnv(opponent="random")]:
+        observation, info = env.reset(seed=1)
+        fen = env.fen
+        for action in ["e2e5", "e7e5", "xyz", 0, 4096]:
+            assert raises_value_error(env.step, action)
+            assert env.fen == fen
+            assert env.observation() == observation
+            assert env.info()["ply"] == info["ply"]
+            assert not env.done
+        env.step("e2e4")
+        assert env.fen != fen
+
+
+# With an "illegal_move" reward, an illegal action returns it with the error
+# in info instead of raising, and the next legal step has no error
+def test_illegal_move_reward():
+    env = ChessEnv(rewards={"illegal_move": -1.0})
+    observation, _ = env.reset()
+    fen = env.fen
+    new_observation, reward, done, info = env.step("e2e5")
+    assert reward == -1.0
+    assert not done
+    assert info["error"]["kind"] == "illegal_move"
+    assert info["ply"] == 0
+    assert new_observation == observation
+    assert env.fen == fen
+    _, reward, done, info = env.step("e2e4")
+    assert reward == 0.0
+    assert "error" not in info
+    assert info["ply"] == 1
+
+
+# Once the episode is over every step fails, and a reset starts a new one
+def test_step_after_done():
+    env = ChessEnv()
+    env.reset(fen=MATE_IN_ONE_FEN)
+    _, reward, done, info = env.step("a1a8")
+    assert done
+    assert reward == 100.0
+    assert info["result"] == "1-0"
+    assert info["termination"] == "checkmate"
+    assert info["legal_moves"] == []
+    fen = env.fen
+    assert raises_value_error(env.step, "g1f1")
+    assert env.fen == fen
+    env_with_penalty = ChessEnv(rewards={"illegal_move": -1.0})
+    env_with_penalty.reset(fen=MATE_IN_ONE_FEN)
+    env_with_penalty.step("a1a8")
+    _, reward, done, info = env_with_penalty.step("g1f1")
+    assert reward == -1.0
+    assert done
+    assert info["error"]["kind"] == "illegal_move"
+    env.reset()
+    assert not env.done
+
+
+# Actions are from_square * 64 + to_square from a8, castling being the
+# king's move, and play the same move as its string
+def test_actions():
+    env = ChessEnv()
+    env.reset()
+    assert 52 * 64 + 36 in env.legal_actions()
+    env.step(52 * 64 + 36)
+    assert env.fen == "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1"
+    env.reset(fen="r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1")
+    assert 60 * 64 + 62 in env.legal_actions()
+    env.step(60 * 64 + 62)
+    assert env.fen == "r3k2r/8/8/8/8/8/8/R4RK1 b kq - 1 1"
+    assert len(env.legal_actions()) == len(env.legal_moves())
+
+
+# With auto_queen the promotion actions give a queen and the action space
+# ends at 4096; without it the underpromotions follow, by file of the pawn,
+# direction and piece, for either side
+def test_promotion_actions():
+    fen = "8/4P3/k7/8/8/8/8/4K3 w - - 0 1"
+    env = ChessEnv()
+    env.reset(fen=fen)
+    assert 12 * 64 + 4 in env.legal_actions()
+    assert raises_value_error(env.step, 4096)
+    env.step(12 * 64 + 4)
+    assert env.fen == "4Q3/8/k7/8/8/8/8/4K3 b - - 0 1"
+    env = ChessEnv(auto_queen=False)
+    env.reset(fen=fen)
+    promotions = [12 * 64 + 4, 4096 + 4 * 9 + 3, 4096 + 4 * 9 + 4, 4096 + 4 * 9 + 5]
+    assert all(action in env.legal_actions() for action in promotions)
+    env.step(4096 + 4 * 9 + 5)
+    assert env.fen == "4N3/8/k7/8/8/8/8/4K3 b - - 0 1"
+    env.reset(fen="4k3/8/8/8/8/8/p7/4K3 b - - 0 1")
+    assert raises_value_error(env.step, 4096)
+    assert raises_value_error(env.step, 4096 + 8 * 9)
+    env.step(4096 + 0 * 9 + 3 + 2)
+    assert env.fen == "4k3/8/8/8/8/8/8/n3K3 w - - 0 2"
+
+
+if __name__ == "__main__":
+    run_test_funcs(__name__)
@@ -0,0 +1,181 @@
+from gym_chess import ChessEngine
+from gym_chess.test.utils import run_test_funcs
+
+
+START_FEN = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1"
+
+
+def play(fen, moves):
+    engine = ChessEngine()
+    state = ChessEngine.state_from_fen(fen)
+    for move in moves:
+        state, _ = engine.next_state(state, state["current_player"], move)
+    return state
+
+
+def raises_value_error(func, *args):
+    try:
+        func(*args)
+    except ValueError:
+        return True
+    return False
+
+
+# FEN -> state -> FEN keeps every field, the move counters included
+def test_round_trip():
+    for fen in [
+        START_FEN,
+        "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3",
+        "r3k2r/8/8/8/8/8/8/R3K2R b Kq - 17 42",
+        "4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 30",
+    ]:
+        assert ChessEngine.to_fen(ChessEngine.state_from_fen(fen)) == fen
+
+
+# The halfmove clock counts quiet moves and resets on pawn moves and
+# captures; the fullmove number goes up after black's moves
+def test_move_counters():
+    fen = "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3"
+    state = play(fen, ["f1c4"])
+    assert state["fen"].endswith(" 3 3")
+    state = play(fen, ["f1c4", "g8f6"])
+    assert state["fen"].endswith(" 4 4")
+    state = play(fen, ["f1c4", "g8f6", "d2d3"])
+    assert state["fen"].endswith(" 0 4")
+    state = play(fen, ["f1c4", "g8f6", "f3e5"])
+    assert state["fen"].endswith(" 0 4")
+    assert ChessEngine.to_fen(ChessEngine.state_from_fen(state["fen"])) == state["fen"]
+
+
+# A double pawn step sets the en passant square, which the capture uses
+def test_en_passant():
+    state = play("4k3/8/8/8/4p3/8/3P4/4K3 w - - 0 1", ["d2d4"])
+    assert state["fen"] == "4k3/8/8/8/3Pp3/8/8/4K3 b - d3 0 1"
+    state = play(state["fen"], ["e4d3"])
+    assert state["fen"] == "4k3/8/8/8/8/3p4/8/4K3 w - - 0 2"
+
+
+# The en passant square is only set when an enemy pawn stands beside the
+# pawn that made the double step
+def test_en_passant_square_needs_a_pawn_beside():
+    state = play("4k3/8/8/8/8/4p3/3P4/4K3 w - - 0 1", ["d2d4"])
+    assert state["en_passant_square"] is None
+    assert state["fen"] == "4k3/8/8/8/3P4/4p3/8/4K3 b - - 0 1"
+    state = play("4k3/8/8/8/p7/8/7P/4K3 w - - 0 1", ["h2h4"])
+    assert state["en_passant_square"] is None
+    state = play("4k3/8/8/8/6p1/8/7P/4K3 w - - 0 1", ["h2h4"])
+    assert tuple(state["en_passant_square"]) == (5, 7)
+
+
+# The en passant square survives the state dict: the capture is generated
+# and played from the dict, and its FEN keeps the square
+def test_en_passant_state_dict():
+    engine = ChessEngine()
+    state = play("4k3/8/8/8/4p3/8/3P4/4K3 w - - 0 1", ["d2d4"])
+    assert tuple(state["en_passant_square"]) == (5, 3)
+    assert ChessEngine.to_fen(state) == "4k3/8/8/8/3Pp3/8/8/4K3 b - d3 0 1"
+    assert "e4d3" in engine.get_possible_moves(state, "BLACK")
+    state, _ = engine.next_state(state, "BLACK", "e4d3")
+    assert state["board"][4][3] == 0
+    assert state["en_passant_square"] is None
+
+
+# A king move loses both castling rights of its side, black's included; a
+# rook leaving or captured on its home square loses that side's right
+def test_castling_rights():
+    fen = "r3k2r/8/8/8/8/8/8/R3K2R b KQkq - 0 1"
+    state = play(fen, ["e8e7"])
+    assert state["fen"] == "r6r/4k3/8/8/8/8/8/R3K2R w KQ - 1 2"
+    state = play(fen, ["a8a1"])
+    assert state["fen"] == "4k2r/8/8/8/8/8/8/r3K2R w Kk - 0 2"
+    state = play("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1", ["h1h8"])
+    assert state["fen"] == "r3k2R/8/8/8/8/8/8/R3K3 b Qq - 0 1"
+
+
+# The FEN of a next_state result passes the position validator, castling
+# rights included
+def test_next_state_fen_is_valid():
+    state = play(START_FEN, ["e2e4", "e7e5", "e1e2", "e8e7", "e2e1", "e7e8"])
+    assert state["fen"] == "rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w - - 4 4"
+    state = play("r3k2r/8/8/8/8/8/8/R3K2R b KQkq - 0 1", ["a8a1"])
+    assert ChessEngine.validate_position(state) == []
+    assert ChessEngine.validate_position(ChessEngine.state_from_fen(state["fen"])) == []
+
+
+# Promotions keep their piece through the FEN
+def test_promotion():
+    for move, piece in [("a7a8", "Q"), ("a7a8q", "Q"), ("a7a8n", "N"), ("a7a8r", "R")]:
+        state = play("8/P6k/8/8/8/8/8/K7 w - - 0 1", [move])
+        assert state["fen"] == piece + "7/7k/8/8/8/8/8/K7 b - - 0 1"
+        assert ChessEngine.to_fen(ChessEngine.state_from_fen(state["fen"])) == state["fen"]
+
+
+# Each UCI promotion suffix gives its piece, and the reward of a queen
+# promotion or of the piece's value for an underpromotion
+def test_underpromotion_piece_and_reward():
+    engine = ChessEngine()
+    for suffix, piece_id, reward in [("q", 2, 10), ("r", 3, 5), ("b", 4, 3), ("n", 5, 3)]:
+        state = ChessEngine.state_from_fen("8/4P3/k7/8/8/8/8/4K3 w - - 0 1")
+        state, move_reward = engine.next_state(state, "WHITE", "e7e8" + suffix)
+        assert state["board"][0][4] == piece_id
+        assert move_reward == reward
+        state = ChessEngine.state_from_fen("4k3/8/8/8/8/8/4p3/K7 b - - 0 1")
+        state, move_reward = engine.next_state(state, "BLACK", "e2e1" + suffix)
+        assert state["board"][7][4] == -piece_id
+        assert move_reward == reward
+
+
+# SAN moves give the same positions as UCI moves
+def test_san_moves():
+    san = play(START_FEN, ["e4", "d5", "exd5", "Qxd5", "Nc3", "Qa5"])
+    uci = play(START_FEN, ["e2e4", "d7d5", "e4d5", "d8d5", "b1c3", "d5a5"])
+    assert san["fen"] == uci["fen"]
+    assert san["fen"] == "rnb1kbnr/ppp1pppp/8/q7/8/2N5/PPPP1PPP/R1BQKBNR w KQkq - 2 4"
+    state = play("8/P6k/8/8/8/8/8/K7 w - - 0 1", ["a8=N"])
+    assert state["fen"] == "N7/7k/8/8/8/8/8/K7 b - - 0 1"
+
+
+# SAN resolves castles, disambiguation, underpromotions and en passant, and
+# ignores a pinned piece; ambiguous, illegal and malformed SAN raise ValueError
+def test_san_edge_cases():
+    state = play("4k3/8/8/8/8/8/8/R3K2R w KQ - 0 1", ["O-O"])
+    assert state["fen"] == "4k3/8/8/8/8/8/8/R4RK1 b - - 1 1"
+    state = play("4k3/8/8/8/8/8/8/R3K2R w KQ - 0 1", ["0-0-0"])
+    assert state["fen"] == "4k3/8/8/8/8/8/8/2KR3R b - - 1 1"
+    state = play("4k3/8/8/8/8/8/8/1N2KN2 w - - 0 1", ["Nbd2"])
+    assert state["fen"] == "4k3/8/8/8/8/8/3N4/4KN2 b - - 1 1"
+    state = play("k2r4/4P3/8/8/8/8/8/4K3 w - - 0 1", ["exd8=N"])
+    assert state["fen"] == "k2N4/8/8/8/8/8/8/4K3 b - - 0 1"
+    state = play("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 30", ["exd6"])
+    assert state["fen"] == "4k3/8/3P4/8/8/8/8/4K3 b - - 0 30"
+    state = play("4r1k1/8/8/1N6/8/8/4N3/4K3 w - - 0 1", ["Nc3"])
+    assert state["fen"] == "4r1k1/8/8/8/8/2N5/4N3/4K3 b - - 1 1"
+
+    engine = ChessEngine()
+    for fen, san in [
+        ("4k3/8/8/8/8/8/8/1N2KN2 w - - 0 1", "Nd2"),
+        ("4r1k1/8/8/8/8/8/8/4K3 w - - 0 1", "Ke2"),
+        ("4k3/8/8/8/8/8/4P3/4K3 w - - 0 1", "e5"),
+        ("4k3/8/8/8/8/8/8/4K3 w - - 0 1", "Nf3"),
+        ("4k3/8/8/8/8/8/8/4K3 w - - 0 1", "xyz"),
+        ("4k3/8/8/8/8/8/8/4K3 w - - 0 1", ""),
+    ]:
+        state = ChessEngine.state_from_fen(fen)
+        assert raises_value_error(engine.next_state, state, "WHITE", san)
+        assert ChessEngine.to_fen(state) == fen
+
+
+# Bad moves and states raise ValueError: a move from an empty square, a move
+# of the other player's piece, an unknown piece id on the board
+def test_bad_moves_raise_value_error():
+    engine = ChessEngine()
+    state = ChessEngine.state_from_fen(START_FEN)
+    assert raises_value_error(engine.next_state, state, "WHITE", "e3e4")
+    assert raises_value_error(engine.next_state, state, "WHITE", "e7e5")
+    state["board"][4][4] = 9
+    assert raises_value_error(engine.next_state, state, "WHITE", "e2e4")
+    assert raises_value_error(ChessEngine.to_fen, state)
+
+
+if __name__ == "__main__":
+    run_test_funcs(__name__)
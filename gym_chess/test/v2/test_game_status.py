@@ -0,0 +1,72 @@
+from gym_chess import ChessEngine
+from gym_chess.test.utils import run_test_funcs
+
+
+def status(fen):
+    return ChessEngine().get_game_status(ChessEngine.state_from_fen(fen))
+
+
+# A mated side to move loses, even with the halfmove clock at 100
+def test_checkmate():
+    for fen in [
+        "R5k1/5ppp/8/8/8/8/8/6K1 b - - 0 1",
+        "R5k1/5ppp/8/8/8/8/8/6K1 b - - 100 80",
+    ]:
+        game_status = status(fen)
+        assert game_status["terminal"]
+        assert game_status["status"] == "checkmate"
+        assert game_status["winner"] == "WHITE"
+        assert game_status["result"] == "1-0"
+
+
+# Stalemate is a draw without winner, and comes before the fifty-move rule
+def test_stalemate():
+    engine = ChessEngine()
+    for fen in [
+        "7k/5Q2/6K1/8/8/8/8/8 b - - 0 1",
+        "7k/5Q2/6K1/8/8/8/8/8 b - - 100 80",
+    ]:
+        game_status = status(fen)
+        assert game_status["status"] == "stalemate"
+        assert game_status["winner"] is None
+        assert game_status["result"] == "1/2-1/2"
+        assert engine.is_stalemate(ChessEngine.state_from_fen(fen))
+    assert not engine.is_stalemate(ChessEngine.state_from_fen("R5k1/5ppp/8/8/8/8/8/6K1 b - - 0 1"))
+
+
+# The fifty-move rule draws from a halfmove clock of 100, reached by a quiet
+# move at 99
+def test_fifty_moves():
+    fen = "4k3/8/8/8/8/8/8/R3K3 w - - 99 80"
+    game_status = status(fen)
+    assert not game_status["terminal"]
+    assert game_status["status"] is None
+    assert game_status["result"] is None
+    state, _ = ChessEngine().next_state(ChessEngine.state_from_fen(fen), "WHITE", "a1a2")
+    assert state["terminal"]
+    assert state["status"] == "fifty_moves"
+    assert state["winner"] is None
+    assert state["result"] == "1/2-1/2"
+
+
+# Two bare kings are a draw, a single minor piece or rook left is not
+def test_insufficient_material():
+    game_status = status("4k3/8/8/8/8/8/8/4K3 w - - 0 1")
+    assert game_status["status"] == "insufficient_material"
+    assert game_status["result"] == "1/2-1/2"
+    assert not status("4k3/8/8/8/8/8/8/4KN2 w - - 0 1")["terminal"]
+    assert not status("4k3/8/8/8/8/8/8/3rK3 w - - 0 1")["terminal"]
+
+
+# next_state reports the status of the position it reaches
+def test_next_state_status():
+    state, _ = ChessEngine().next_state(
+        ChessEngine.state_from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1"), "WHITE", "a1a8"
+    )
+    assert state["status"] == "checkmate"
+    assert state["winner"] == "WHITE"
+    assert state["result"] == "1-0"
+
+
+if __name__ == "__main__":
+    run_test_funcs(__name__)
@@ -0,0 +1,56 @@
+from gym_chess import ChessEngine
+from gym_chess.test.utils import run_test_funcs
+
+MB = 1024 * 1024
+# memory budgeted per move cache entry
+MOVE_CACHE_ENTRY_BYTES = 1024
+
+
+def table_bytes(engine):
+    tt_bytes = engine.memory_usage()["tt"]
+    return tt_bytes + engine.move_cache_stats()["capacity"] * MOVE_CACHE_ENTRY_BYTES
+
+
+# Only the tables the engine has are reported
+def test_usage():
+    engine = ChessEngine(hash_mb=4)
+    usage = engine.memory_usage()
+    assert sorted(usage) == ["max_memory_mb", "move_cache", "total", "tt"]
+    assert 3 * MB < usage["tt"] <= 4 * MB
+    assert usage["move_cache"] == 0
+    assert usage["total"] == usage["tt"]
+    assert usage["max_memory_mb"] is None
+
+    state = ChessEngine.state_from_fen("4k3/8/8/8/8/8/8/R3K3 w - - 0 1")
+    engine.get_possible_moves(state, "WHITE")
+    usage = engine.memory_usage()
+    assert usage["move_cache"] > 0
+    assert usage["total"] == usage["tt"] + usage["move_cache"]
+
+
+# The cap resizes the transposition table and move cache, also when the move
+# cache grows later, and lifting it does not grow them back
+def test_cap():
+    engine = ChessEngine(hash_mb=64)
+    engine.set_move_cache_size(16384)
+    assert table_bytes(engine) > 64 * MB
+
+    engine.set_max_memory(48)
+    assert engine.memory_usage()["max_memory_mb"] == 48
+    assert engine.move_cache_stats()["capacity"] == 9830
+    assert engine.tt_stats()["size_mb"] <= 38
+    assert table_bytes(engine) <= 48 * MB
+
+    engine.set_move_cache_size(16384)
+    assert table_bytes(engine) <= 48 * MB
+
+    engine.set_max_memory(None)
+    capacity = engine.move_cache_stats()["capacity"]
+    assert engine.memory_usage()["max_memory_mb"] is None
+    assert table_bytes(engine) <= 48 * MB
+    engine.set_move_cache_size(capacity * 2)
+    assert engine.move_cache_stats()["capacity"] == capacity * 2
+
+
+if __name__ == "__main__":
+    run_test_funcs(__name__)
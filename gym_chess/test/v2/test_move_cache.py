@@ -0,0 +1,78 @@
+from gym_chess import ChessEngine
+from gym_chess.test.utils import run_test_funcs
+
+
+# A repeated query is a hit returning the same moves
+def test_hit():
+    engine = ChessEngine()
+    state = ChessEngine.state_from_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1")
+    moves = engine.get_possible_moves(state, "WHITE")
+    assert engine.get_possible_moves(state, "WHITE") == moves
+    stats = engine.move_cache_stats()
+    assert stats["hits"] == 1
+    assert stats["misses"] == 1
+    assert stats["entries"] == 1
+
+
+# Positions differing only by castling rights or en passant square are cached
+# apart, as are the moves of the two players and the attack moves
+def test_keys():
+    engine = ChessEngine()
+    state = ChessEngine.state_from_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1")
+    assert "CASTLE_KING_SIDE_WHITE" in engine.get_possible_moves(state, "WHITE")
+    state = ChessEngine.state_from_fen("r3k2r/8/8/8/8/8/8/R3K2R w kq - 0 1")
+    assert "CASTLE_KING_SIDE_WHITE" not in engine.get_possible_moves(state, "WHITE")
+    assert "e8d8" in engine.get_possible_moves(state, "BLACK")
+    assert "e1d1" not in engine.get_possible_moves(state, "BLACK")
+
+    state = ChessEngine.state_from_fen("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 30")
+    assert "e5d6" in engine.get_possible_moves(state, "WHITE")
+    state = ChessEngine.state_from_fen("4k3/8/8/3pP3/8/8/8/4K3 w - - 0 30")
+    assert "e5d6" not in engine.get_possible_moves(state, "WHITE")
+
+    # the pinned knight attacks squares it can't move to
+    state = ChessEngine.state_from_fen("4r1k1/8/8/8/8/8/4N3/4K3 w - - 0 1")
+    assert "e2c3" not in engine.get_possible_moves(state, "WHITE")
+    assert "e2c3" in engine.get_possible_moves(state, "WHITE", attack=True)
+    assert engine.move_cache_stats()["hits"] == 0
+
+
+# A size of 0 disables the cache, a smaller size evicts the oldest entries,
+# and clearing resets the counters
+def test_size():
+    engine = ChessEngine()
+    fens = [
+        "4k3/8/8/8/8/8/8/R3K3 w - - 0 1",
+        "4k3/8/8/8/8/8/8/3RK3 w - - 0 1",
+        "4k3/8/8/8/8/8/8/2R1K3 w - - 0 1",
+    ]
+    for fen in fens:
+        engine.get_possible_moves(ChessEngine.state_from_fen(fen), "WHITE")
+    engine.set_move_cache_size(2)
+    stats = engine.move_cache_stats()
+    assert stats["capacity"] == 2
+    assert stats["entries"] == 2
+    assert stats["evictions"] == 1
+    engine.get_possible_moves(ChessEngine.state_from_fen(fens[0]), "WHITE")
+    assert engine.move_cache_stats()["hits"] == 0
+    engine.get_possible_moves(ChessEngine.state_from_fen(fens[2]), "WHITE")
+    assert engine.move_cache_stats()["hits"] == 1
+
+    engine.clear_move_cache()
+    stats = engine.move_cache_stats()
+    assert stats["entries"] == 0
+    assert stats["hits"] == 0
+    assert stats["misses"] == 0
+
+    engine.set_move_cache_size(0)
+    state = ChessEngine.state_from_fen(fens[0])
+    moves = engine.get_possible_moves(state, "WHITE")
+    assert engine.get_possible_moves(state, "WHITE") == moves
+    stats = engine.move_cache_stats()
+    assert stats["entries"] == 0
+    assert stats["hits"] == 0
+    assert stats["misses"] == 2
+
+
+if __name__ == "__main__":
+    run_test_funcs(__name__)
@@ -0,0 +1,70 @@
+import os
+import tempfile
+
+from gym_chess.gym_chess import PgnReader
+from gym_chess.test.utils import run_test_funcs
+
+
+PGN = """[Event "scholar's mate"]
+[Result "1-0"]
+
+1. e4 e5 2. Qh5 Nc6 3. Bc4 Nf6 4. Qxf7# 1-0
+
+[Event "illegal"]
+[Result "*"]
+
+1. e4 Nf3 *
+
+[Event "underpromotion"]
+[SetUp "1"]
+[FEN "k2r4/4P3/8/8/8/8/8/4K3 w - - 0 1"]
+[Result "*"]
+
+1. exd8=N Ka7 *
+"""
+
+
+def next_raises_value_error(games):
+    try:
+        next(games)
+    except ValueError as e:
+        return str(e)
+    return None
+
+
+# The reader yields the games one at a time, a game that does not parse
+# raising ValueError without ending the iteration
+def test_reader():
+    path = os.path.join(tempfile.mkdtemp(), "games.pgn")
+    with open(path, "w") as f:
+        f.write(PGN)
+    games = PgnReader(path)
+
+    game = next(games)
+    assert game["tags"]["Event"] == "scholar's mate"
+    assert game["result"] == "1-0"
+    assert [move["move"] for move in game["moves"]][-1] == "h5f7"
+    assert games.games_read() == 1
+
+    assert next_raises_value_error(games).startswith("game 2: ")
+    assert games.games_read() == 2
+
+    game = next(games)
+    assert game["fen"] == "k2r4/4P3/8/8/8/8/8/4K3 w - - 0 1"
+    assert [move["san"] for move in game["moves"]] == ["exd8=N", "Ka7"]
+    assert [move["move"] for move in game["moves"]] == ["e7d8n", "a8a7"]
+    assert list(games) == []
+    assert games.games_read() == 3
+
+
+# A missing file raises ValueError
+def test_missing_file():
+    try:
+        PgnReader(os.path.join(tempfile.mkdtemp(), "missing.pgn"))
+    except ValueError:
+        return
+    assert False
+
+
+if __name__ == "__main__":
+    run_test_funcs(__name__)
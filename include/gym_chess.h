@@ -0,0 +1,48 @@
+/*
+ * C interface of the gym_chess engine.
+ *
+ * Build the shared library with:
+ *     cargo build --release --no-default-features --features ffi
+ *
+ * Strings are NUL-terminated UTF-8. Inputs are only read during the call.
+ * The string returned by gc_engine_output() is owned by the engine and stays
+ * valid until the next call on the same handle. Functions returning int
+ * return 0 on success and -1 on error, gc_engine_output() then holds the
+ * error message; an internal engine error is reported the same way. Moves
+ * are in UCI notation: "e1g1" castles, "e7e8n" underpromotes.
+ */
+#ifndef GYM_CHESS_H
+#define GYM_CHESS_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef struct Engine GcEngine;
+
+/* Engine at the start position with a hash_mb MB transposition table, NULL on failure */
+GcEngine *gc_engine_new(size_t hash_mb);
+void gc_engine_free(GcEngine *engine);
+
+/* Output of the last call on engine */
+const char *gc_engine_output(const GcEngine *engine);
+
+/* Output: empty. Fails on a FEN that does not parse or an illegal position */
+int gc_engine_set_position(GcEngine *engine, const char *fen);
+/* Output: FEN of the current position */
+int gc_engine_get_fen(GcEngine *engine);
+/* Output: space separated legal moves ("e2e4 g1f3 ... e1g1 ... e7e8q e7e8r e7e8b e7e8n") */
+int gc_engine_legal_moves(GcEngine *engine);
+/* Output: FEN after the move ("e7e8" promotes to a queen) */
+int gc_engine_make_move(GcEngine *engine, const char *move);
+/* Output: "<best move> <score>", movetime_ms = 0 means no time limit */
+int gc_engine_search(GcEngine *engine, uint32_t depth, uint64_t movetime_ms);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* GYM_CHESS_H */
@@ -0,0 +1,194 @@
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+
+use crate::arena::{ArenaConfig, Opponent, OpponentPool};
+use crate::eval::Handcrafted;
+use crate::rating::DEFAULT_K_FACTOR;
+use crate::selfplay::GameResult;
+use crate::{evaluate_with_weights, Color, EvalWeights, State};
+
+//
+// Structs
+//
+/// A term of the handcrafted evaluation, disabled by zeroing its weights
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum EvalTerm {
+    /// piece values, the king's excepted (it decides won positions)
+    Material,
+    PawnAdvance,
+    Center,
+    Mobility,
+    /// x-ray aware attackers and defenders of attacked pieces
+    AttackDefense,
+}
+
+impl EvalTerm {
+    pub const ALL: [EvalTerm; 5] = [
+        EvalTerm::Material,
+        EvalTerm::PawnAdvance,
+        EvalTerm::Center,
+        EvalTerm::Mobility,
+        EvalTerm::AttackDefense,
+    ];
+
+    pub fn from_name(name: &str) -> Result<Self, String> {
+        EvalTerm::ALL
+            .iter()
+            .find(|term| term.name() == name)
+            .copied()
+            .ok_or(format!(
+                "Invalid eval term '{}': expected one of {:?}",
+                name,
+                EvalTerm::ALL
+                    .iter()
+                    .map(|term| term.name())
+                    .collect::<Vec<_>>()
+            ))
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            EvalTerm::Material => "material",
+            EvalTerm::PawnAdvance => "pawn_advance",
+            EvalTerm::Center => "center",
+            EvalTerm::Mobility => "mobility",
+            EvalTerm::AttackDefense => "attack_defense",
+        }
+    }
+
+    /// `weights` with this term disabled
+    pub fn disable(&self, weights: &EvalWeights) -> EvalWeights {
+        let mut weights = *weights;
+        match self {
+            EvalTerm::Material => {
+                weights.pawn = 0;
+                weights.knight = 0;
+                weights.bishop = 0;
+                weights.rook = 0;
+                weights.queen = 0;
+            }
+            EvalTerm::PawnAdvance => weights.pawn_advance = 0,
+            EvalTerm::Center => weights.center = 0,
+            EvalTerm::Mobility => weights.mobility = 0,
+            EvalTerm::AttackDefense => weights.attack_defense = 0,
+        }
+        weights
+    }
+}
+
+/// How `ablation_match` plays its games
+#[derive(Debug, Clone)]
+pub struct AblationConfig {
+    /// the games played for each term
+    pub arena: ArenaConfig,
+    /// search depth of both engines
+    pub depth: u32,
+    /// transposition table size of each engine
+    pub hash_mb: usize,
+    pub seed: Option<u64>,
+}
+
+/// Games of an engine without a term against the full engine, counted from
+/// the side of the ablated engine
+#[derive(Debug, Copy, Clone)]
+pub struct AblationResult {
+    pub term: EvalTerm,
+    pub wins: usize,
+    pub draws: usize,
+    pub losses: usize,
+}
+
+impl AblationResult {
+    pub fn games(&self) -> usize {
+        self.wins + self.draws + self.losses
+    }
+
+    /// score of the ablated engine, 0.5 per draw (None without games)
+    pub fn score(&self) -> Option<f64> {
+        if self.games() == 0 {
+            return None;
+        }
+        Some((self.wins as f64 + self.draws as f64 / 2.0) / self.games() as f64)
+    }
+
+    /// Elo the engine loses without the term (None without games or when a
+    /// side scored every point)
+    pub fn elo_loss(&self) -> Option<f64> {
+        self.score().and_then(elo_difference).map(|elo| -elo)
+    }
+}
+
+/// Score of `state` for `player` with all terms, and how much each of
+/// `terms` contributes to it (the full score minus the score without it)
+pub fn term_deltas(
+    state: &State,
+    player: Color,
+    weights: &EvalWeights,
+    terms: &[EvalTerm],
+) -> (isize, Vec<(EvalTerm, isize)>) {
+    let score = evaluate_with_weights(state, player, weights);
+    let deltas = terms
+        .iter()
+        .map(|term| {
+            (
+                *term,
+                score - evaluate_with_weights(state, player, &term.disable(weights)),
+            )
+        })
+        .collect();
+    (score, deltas)
+}
+
+/// Play the games of `config` between an engine without each of `terms` and
+/// the engine with `weights`. Returns the results of the terms whose match
+/// was not interrupted by `stop`.
+pub fn ablation_match(
+    weights: &EvalWeights,
+    terms: &[EvalTerm],
+    config: &AblationConfig,
+    stop: &Arc<AtomicBool>,
+) -> Result<Vec<AblationResult>, String> {
+    let mut results: Vec<AblationResult> = vec![];
+    for &term in terms.iter() {
+        let mut pool = OpponentPool::new(DEFAULT_K_FACTOR, config.seed);
+        let full = Arc::new(Handcrafted { weights: *weights });
+        let ablated = Arc::new(Handcrafted {
+            weights: term.disable(weights),
+        });
+        pool.add(Opponent::new("full", full, config.depth, config.hash_mb))?;
+        pool.add(Opponent::new(term.name(), ablated, config.depth, config.hash_mb))?;
+        let arena_games = pool.play(term.name(), &config.arena, stop)?;
+        if arena_games.len() < config.arena.games {
+            break;
+        }
+        let mut result = AblationResult {
+            term,
+            wins: 0,
+            draws: 0,
+            losses: 0,
+        };
+        for arena_game in arena_games.iter() {
+            match (arena_game.game.result, arena_game.learner_color) {
+                (GameResult::Draw, _) => result.draws += 1,
+                (GameResult::WhiteWin, Color::White) | (GameResult::BlackWin, Color::Black) => {
+                    result.wins += 1
+                }
+                _ => result.losses += 1,
+            }
+        }
+        results.push(result);
+    }
+    Ok(results)
+}
+
+// HELPER FUNCTIONS
+// ---------------------------------------------------------
+// ---------------------------------------------------------
+
+/// Elo difference giving an expected `score` (None for 0 and 1)
+fn elo_difference(score: f64) -> Option<f64> {
+    if score <= 0.0 || score >= 1.0 {
+        return None;
+    }
+    Some(-400.0 * (1.0 / score - 1.0).log10())
+}
@@ -0,0 +1,320 @@
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use crate::eval::{Evaluator, Handcrafted};
+use crate::openings::{Opening, OpeningSuite};
+use crate::rating::{Rating, DEFAULT_ELO};
+use crate::rng::Rng;
+use crate::rules::Standard;
+use crate::selfplay::{play_game, search_move, Adjudication, Game, GameResult};
+use crate::tt::TranspositionTable;
+use crate::uci_client::{UciEngine, UciLimit};
+use crate::{Color, State, DEFAULT_BOARD, DEFAULT_EVAL_WEIGHTS};
+
+//
+// Structs
+//
+/// An engine configuration (or network checkpoint) of the pool with its rating
+pub struct Opponent {
+    pub name: String,
+    pub evaluator: Arc<dyn Evaluator>,
+    pub depth: u32,
+    pub movetime: Option<Duration>,
+    pub hash_mb: usize,
+    /// relative probability of being sampled as an opponent (0 never is)
+    pub weight: f64,
+    pub rating: Rating,
+    /// external engine choosing the moves instead of the search (searching
+    /// `movetime` if set, else to `depth`)
+    pub uci: Option<Arc<UciEngine>>,
+}
+
+impl Opponent {
+    pub fn new(name: &str, evaluator: Arc<dyn Evaluator>, depth: u32, hash_mb: usize) -> Self {
+        Opponent {
+            name: name.to_string(),
+            evaluator,
+            depth,
+            movetime: None,
+            hash_mb,
+            weight: 1.0,
+            rating: Rating::new(DEFAULT_ELO),
+            uci: None,
+        }
+    }
+
+    /// An opponent played by an external UCI engine
+    pub fn uci(name: &str, engine: Arc<UciEngine>, depth: u32, movetime: Option<Duration>) -> Self {
+        let evaluator = Arc::new(Handcrafted {
+            weights: DEFAULT_EVAL_WEIGHTS,
+        });
+        let mut opponent = Opponent::new(name, evaluator, depth, 0);
+        opponent.movetime = movetime;
+        opponent.uci = Some(engine);
+        opponent
+    }
+}
+
+/// How `OpponentPool::play` plays its games
+#[derive(Debug, Clone)]
+pub struct ArenaConfig {
+    /// games of the learner, alternating colors
+    pub games: usize,
+    /// the games start from positions of this suite (the initial position if None)
+    pub openings: Option<Arc<OpeningSuite>>,
+    /// the first plies are played at random to diversify the games
+    pub random_plies: u32,
+    pub adjudication: Adjudication,
+    /// games played at the same time
+    pub threads: usize,
+}
+
+/// A game of the learner against an opponent sampled from the pool
+pub struct ArenaGame {
+    pub opponent: String,
+    pub learner_color: Color,
+    pub game: Game,
+}
+
+/// Opponents for league-style training: the learner plays opponents sampled
+/// by weight and the Elo of both sides is updated after each game
+pub struct OpponentPool {
+    opponents: Vec<Opponent>,
+    pub k_factor: f64,
+    rng: Rng,
+}
+
+impl OpponentPool {
+    pub fn new(k_factor: f64, seed: Option<u64>) -> Self {
+        OpponentPool {
+            opponents: vec![],
+            k_factor,
+            rng: match seed {
+                Some(seed) => Rng::new(seed),
+                None => Rng::from_entropy(),
+            },
+        }
+    }
+
+    pub fn opponents(&self) -> &[Opponent] {
+        &self.opponents
+    }
+
+    pub fn add(&mut self, opponent: Opponent) -> Result<(), String> {
+        if self.index(&opponent.name).is_ok() {
+            return Err(format!(
+                "opponent '{}' is already in the pool",
+                opponent.name
+            ));
+        }
+        if !(opponent.weight >= 0.0 && opponent.weight.is_finite()) {
+            return Err(format!(
+                "invalid weight {} for '{}'",
+                opponent.weight, opponent.name
+            ));
+        }
+        self.opponents.push(opponent);
+        Ok(())
+    }
+
+    pub fn remove(&mut self, name: &str) -> Result<Opponent, String> {
+        let i = self.index(name)?;
+        Ok(self.opponents.remove(i))
+    }
+
+    pub fn set_weight(&mut self, name: &str, weight: f64) -> Result<(), String> {
+        if !(weight >= 0.0 && weight.is_finite()) {
+            return Err(format!("invalid weight {}", weight));
+        }
+        let i = self.index(name)?;
+        self.opponents[i].weight = weight;
+        Ok(())
+    }
+
+    /// Pick an opponent with probability proportional to its weight, skipping
+    /// `exclude` (usually the learner); None if no opponent has a weight
+    pub fn sample(&mut self, exclude: Option<&str>) -> Option<usize> {
+        let weight_of = |opponent: &Opponent| match exclude {
+            Some(name) if opponent.name == name => 0.0,
+            _ => opponent.weight,
+        };
+        let total: f64 = self.opponents.iter().map(weight_of).sum();
+        if total <= 0.0 {
+            return None;
+        }
+        let mut target = self.rng.next_f64() * total;
+        let mut last = None;
+        for (i, opponent) in self.opponents.iter().enumerate() {
+            let weight = weight_of(opponent);
+            if weight <= 0.0 {
+                continue;
+            }
+            if target < weight {
+                return Some(i);
+            }
+            target -= weight;
+            last = Some(i);
+        }
+        // rounding left `target` just above the last weight
+        last
+    }
+
+    /// Update the ratings after a game, `score` being 1, 0.5 or 0 for `a`
+    pub fn record(&mut self, a: usize, b: usize, score: f64) {
+        let (elo_a, glicko_a) = (
+            self.opponents[a].rating.elo,
+            self.opponents[a].rating.glicko,
+        );
+        let (elo_b, glicko_b) = (
+            self.opponents[b].rating.elo,
+            self.opponents[b].rating.glicko,
+        );
+        let k_factor = self.k_factor;
+        self.opponents[a]
+            .rating
+            .record(elo_b, glicko_b, score, k_factor);
+        self.opponents[b]
+            .rating
+            .record(elo_a, glicko_a, 1.0 - score, k_factor);
+    }
+
+    /// Play the games of `config` between `learner` and sampled opponents;
+    /// the ratings are updated in game order. With an opening suite, each
+    /// sampled opening is played twice, once with each color. Returns the
+    /// games finished before `stop` was raised (games where an external
+    /// engine failed even after a restart are dropped).
+    pub fn play(
+        &mut self,
+        learner: &str,
+        config: &ArenaConfig,
+        stop: &Arc<AtomicBool>,
+    ) -> Result<Vec<ArenaGame>, String> {
+        let learner = self.index(learner)?;
+        let learner_name = self.opponents[learner].name.clone();
+        let mut pairings: Vec<(usize, Color, u64, Option<&Opening>)> = vec![];
+        let mut opening: Option<&Opening> = None;
+        for i in 0..config.games {
+            if i % 2 == 0 {
+                opening = config
+                    .openings
+                    .as_deref()
+                    .map(|suite| suite.sample(&mut self.rng));
+            }
+            let opponent = self
+                .sample(Some(&learner_name))
+                .ok_or("no opponent with a positive weight in the pool")?;
+            let learner_color = if i % 2 == 0 {
+                Color::White
+            } else {
+                Color::Black
+            };
+            pairings.push((opponent, learner_color, self.rng.next_u64(), opening));
+        }
+
+        let results: Vec<Mutex<Option<Game>>> = pairings.iter().map(|_| Mutex::new(None)).collect();
+        let next_index = AtomicUsize::new(0);
+        let opponents = &self.opponents;
+        thread::scope(|scope| {
+            for _ in 0..config.threads.max(1).min(config.games.max(1)) {
+                scope.spawn(|| loop {
+                    let i = next_index.fetch_add(1, Ordering::SeqCst);
+                    if i >= pairings.len() || stop.load(Ordering::SeqCst) {
+                        break;
+                    }
+                    let (opponent, learner_color, seed, opening) = pairings[i];
+                    let (white, black) = match learner_color {
+                        Color::White => (&opponents[learner], &opponents[opponent]),
+                        Color::Black => (&opponents[opponent], &opponents[learner]),
+                    };
+                    *results[i].lock().unwrap() = play_pairing(
+                        white,
+                        black,
+                        opening,
+                        config.random_plies,
+                        &config.adjudication,
+                        seed,
+                        stop,
+                    );
+                });
+            }
+        });
+
+        let mut arena_games: Vec<ArenaGame> = vec![];
+        for ((opponent, learner_color, _, _), game) in pairings.into_iter().zip(results) {
+            let game = match game.into_inner().unwrap() {
+                Some(game) => game,
+                None => continue,
+            };
+            let score = match (game.result, learner_color) {
+                (GameResult::Draw, _) => 0.5,
+                (GameResult::WhiteWin, Color::White) | (GameResult::BlackWin, Color::Black) => 1.0,
+                _ => 0.0,
+            };
+            self.record(learner, opponent, score);
+            arena_games.push(ArenaGame {
+                opponent: self.opponents[opponent].name.clone(),
+                learner_color,
+                game,
+            });
+        }
+        Ok(arena_games)
+    }
+
+    fn index(&self, name: &str) -> Result<usize, String> {
+        self.opponents
+            .iter()
+            .position(|opponent| opponent.name == name)
+            .ok_or_else(|| format!("no opponent named '{}'", name))
+    }
+}
+
+// HELPER FUNCTIONS
+// ---------------------------------------------------------
+// ---------------------------------------------------------
+
+/// each side searches with its own settings and transposition table
+fn play_pairing(
+    white: &Opponent,
+    black: &Opponent,
+    opening: Option<&Opening>,
+    random_plies: u32,
+    adjudication: &Adjudication,
+    seed: u64,
+    stop: &Arc<AtomicBool>,
+) -> Option<Game> {
+    let mut rng = Rng::new(seed);
+    let mut white_tt = TranspositionTable::new(white.hash_mb);
+    let mut black_tt = TranspositionTable::new(black.hash_mb);
+    let start = match opening {
+        Some(opening) => opening.state,
+        None => State::new(DEFAULT_BOARD, Color::White, true, true, true, true),
+    };
+    let game = play_game(&Standard, start, random_plies, adjudication, &mut rng, stop, |state, player| {
+        let (side, tt) = match player {
+            Color::White => (white, &mut white_tt),
+            Color::Black => (black, &mut black_tt),
+        };
+        if let Some(engine) = &side.uci {
+            let limit = match side.movetime {
+                Some(movetime) => UciLimit::MoveTime(movetime),
+                None => UciLimit::Depth(side.depth),
+            };
+            return engine.analyse(state, limit).ok().map(|analysis| analysis.to_search_info());
+        }
+        search_move(
+            state,
+            player,
+            &side.evaluator,
+            side.depth,
+            side.movetime,
+            tt,
+            stop,
+        )
+    });
+    game.map(|mut game| {
+        game.opening = opening.map(|opening| opening.name.clone());
+        game
+    })
+}
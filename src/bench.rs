@@ -0,0 +1,63 @@
+use std::time::Instant;
+
+use crate::{
+    _get_possible_castle_moves, _get_pseudo_legal_moves, get_other_player,
+    get_squares_under_attack_by_player, move_leaves_king_checked, Move, State,
+};
+
+//
+// Structs
+//
+/// Move generator timings of the side to move of a position, in nanoseconds
+/// per call averaged over the iterations
+#[derive(Debug, Copy, Clone)]
+pub struct MovegenTiming {
+    /// squares attacked by the opponent (needed by king moves and castles)
+    pub attack_map_ns: f64,
+    /// moves of every piece and castles, given the attack map
+    pub pseudo_legal_ns: f64,
+    /// dropping the pseudo-legal moves that leave the king checked
+    pub legal_filter_ns: f64,
+    pub pseudo_legal_moves: usize,
+    pub legal_moves: usize,
+}
+
+/// Time each stage of the move generation of `state` over `iterations` runs
+/// (at least one)
+pub fn benchmark_movegen(state: &State, iterations: u32) -> MovegenTiming {
+    let iterations = iterations.max(1);
+    let player = state.current_player;
+    let other_player = get_other_player(player);
+    let mut attack_map_ns: u128 = 0;
+    let mut pseudo_legal_ns: u128 = 0;
+    let mut legal_filter_ns: u128 = 0;
+    let mut pseudo_legal_moves = 0;
+    let mut legal_moves = 0;
+    for _ in 0..iterations {
+        let start = Instant::now();
+        let squares_under_attack_map = get_squares_under_attack_by_player(state, other_player);
+        attack_map_ns += start.elapsed().as_nanos();
+
+        let start = Instant::now();
+        let mut moves: Vec<Move> =
+            _get_pseudo_legal_moves(state, player, &squares_under_attack_map);
+        let castle_moves =
+            _get_possible_castle_moves(state, player, false, &squares_under_attack_map);
+        pseudo_legal_ns += start.elapsed().as_nanos();
+        pseudo_legal_moves = moves.len() + castle_moves.len();
+
+        // castles are only generated when legal
+        let start = Instant::now();
+        moves.retain(|_move: &Move| !move_leaves_king_checked(state, player, *_move));
+        legal_filter_ns += start.elapsed().as_nanos();
+        legal_moves = moves.len() + castle_moves.len();
+    }
+    let per_call = |total: u128| total as f64 / iterations as f64;
+    MovegenTiming {
+        attack_map_ns: per_call(attack_map_ns),
+        pseudo_legal_ns: per_call(pseudo_legal_ns),
+        legal_filter_ns: per_call(legal_filter_ns),
+        pseudo_legal_moves,
+        legal_moves,
+    }
+}
@@ -0,0 +1,233 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::mem::size_of;
+use std::path::Path;
+
+use crate::rng::Rng;
+use crate::selfplay::GameResult;
+use crate::{
+    convert_move_union_to_string, from_fen, next_state, parse_move, to_fen, Color, MoveStruct,
+    State,
+};
+
+//
+// Constants
+//
+/// plies of each game learned when the book does not set a depth
+pub const DEFAULT_BOOK_PLIES: u32 = 16;
+/// weight of a move not in the book yet (that of a book move without games)
+const NEW_MOVE_WEIGHT: f64 = 0.5;
+
+//
+// Structs
+//
+/// A book move with the results of the games that played it, counted for
+/// the side that played it
+#[derive(Clone)]
+pub struct BookEntry {
+    pub _move: MoveStruct,
+    pub wins: u32,
+    pub draws: u32,
+    pub losses: u32,
+}
+
+impl BookEntry {
+    pub fn games(&self) -> u32 {
+        self.wins + self.draws + self.losses
+    }
+
+    /// Expected score of the move with one virtual win and one virtual loss,
+    /// so that new moves start at 0.5 and lines that score badly fade out
+    pub fn weight(&self) -> f64 {
+        (self.wins as f64 + self.draws as f64 / 2.0 + 1.0) / (self.games() as f64 + 2.0)
+    }
+}
+
+/// Opening book learned from game results: every move of the first `plies`
+/// plies of a game is credited with the game's result, so the book prefers
+/// the lines the engine scores well with
+#[derive(Clone)]
+pub struct Book {
+    /// book moves by FEN of the position
+    positions: HashMap<String, Vec<BookEntry>>,
+    pub plies: u32,
+}
+
+impl Book {
+    pub fn new(plies: u32) -> Self {
+        Book {
+            positions: HashMap::new(),
+            plies,
+        }
+    }
+
+    /// Credit the first `plies` moves of a game from `start` with `result`
+    /// (moves are played in order; an illegal one ends the learning)
+    pub fn learn(&mut self, start: &State, moves: &[MoveStruct], result: GameResult) {
+        let mut state = *start;
+        for _move in moves.iter().take(self.plies as usize) {
+            let player = state.current_player;
+            let entries = self.positions.entry(position_fen(&state)).or_default();
+            let uci = convert_move_union_to_string(_move);
+            let index = match entries
+                .iter()
+                .position(|entry| convert_move_union_to_string(&entry._move) == uci)
+            {
+                Some(index) => index,
+                None => {
+                    entries.push(BookEntry {
+                        _move: _move.clone(),
+                        wins: 0,
+                        draws: 0,
+                        losses: 0,
+                    });
+                    entries.len() - 1
+                }
+            };
+            let entry = &mut entries[index];
+            match (result, player) {
+                (GameResult::Draw, _) => entry.draws += 1,
+                (GameResult::WhiteWin, Color::White) | (GameResult::BlackWin, Color::Black) => {
+                    entry.wins += 1
+                }
+                _ => entry.losses += 1,
+            }
+            state = next_state(&state, player, _move.clone()).0;
+        }
+    }
+
+    /// Book moves of `state`, highest weight first
+    pub fn moves(&self, state: &State) -> Vec<BookEntry> {
+        let mut entries = self
+            .positions
+            .get(&position_fen(state))
+            .cloned()
+            .unwrap_or_default();
+        entries.sort_by(|a, b| b.weight().partial_cmp(&a.weight()).unwrap());
+        entries
+    }
+
+    /// A book move of `state` drawn with probability proportional to its
+    /// weight. None out of book, or to let the search try a move, as likely
+    /// as a book move of weight NEW_MOVE_WEIGHT.
+    pub fn probe(&self, state: &State, rng: &mut Rng) -> Option<MoveStruct> {
+        let entries = self.positions.get(&position_fen(state))?;
+        let total: f64 = entries.iter().map(|entry| entry.weight()).sum();
+        let mut target = rng.next_f64() * (total + NEW_MOVE_WEIGHT);
+        for entry in entries.iter() {
+            if target < entry.weight() {
+                return Some(entry._move.clone());
+            }
+            target -= entry.weight();
+        }
+        None
+    }
+
+    pub fn num_positions(&self) -> usize {
+        self.positions.len()
+    }
+
+    /// Memory held by the positions and their moves (estimated)
+    pub fn size_bytes(&self) -> usize {
+        self
+            .positions
+            .iter()
+            .map(|(fen, entries)| {
+                size_of::<(String, Vec<BookEntry>)>()
+                    + fen.capacity()
+                    + entries.capacity() * size_of::<BookEntry>()
+            })
+            .sum()
+    }
+
+    /// One book move per line: FEN, move, wins, draws and losses separated
+    /// by tabs (lines starting with '#' are comments)
+    pub fn from_text(text: &str, plies: u32) -> Result<Self, String> {
+        let mut book = Book::new(plies);
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let fields: Vec<&str> = line.split('\t').collect();
+            let parse_count = |field: &str| {
+                field
+                    .parse::<u32>()
+                    .map_err(|_| format!("line {}: invalid count '{}'", i + 1, field))
+            };
+            let (fen, entry) = match fields.as_slice() {
+                [fen, _move, wins, draws, losses] => {
+                    // keyed like `position_fen` writes positions
+                    let state = from_fen(fen).map_err(|e| format!("line {}: {}", i + 1, e))?;
+                    let entry = BookEntry {
+                        _move: parse_move(_move).map_err(|e| format!("line {}: {}", i + 1, e))?,
+                        wins: parse_count(wins)?,
+                        draws: parse_count(draws)?,
+                        losses: parse_count(losses)?,
+                    };
+                    (position_fen(&state), entry)
+                }
+                _ => return Err(format!("line {}: expected 5 tab-separated fields", i + 1)),
+            };
+            book.positions
+                .entry(fen)
+                .or_default()
+                .push(entry);
+        }
+        Ok(book)
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut fens: Vec<&String> = self.positions.keys().collect();
+        fens.sort();
+        let mut text = String::new();
+        for fen in fens {
+            for entry in self.positions[fen].iter() {
+                text.push_str(&format!(
+                    "{}\t{}\t{}\t{}\t{}\n",
+                    fen,
+                    convert_move_union_to_string(&entry._move),
+                    entry.wins,
+                    entry.draws,
+                    entry.losses
+                ));
+            }
+        }
+        text
+    }
+
+    pub fn load(path: &Path, plies: u32) -> Result<Self, String> {
+        let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        Self::from_text(&text, plies).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        fs::write(path, self.to_text()).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+}
+
+impl fmt::Debug for Book {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Book {{ positions: {}, plies: {} }}",
+            self.positions.len(),
+            self.plies
+        )
+    }
+}
+
+// HELPER FUNCTIONS
+// ---------------------------------------------------------
+// ---------------------------------------------------------
+
+/// FEN of the position without its halfmove clock and move number, which
+/// don't change the book moves
+fn position_fen(state: &State) -> String {
+    to_fen(State {
+        halfmove_clock: 0,
+        fullmove_number: 1,
+        ..*state
+    })
+}
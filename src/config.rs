@@ -0,0 +1,317 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use crate::memory::fit_tables;
+use crate::move_cache::DEFAULT_MOVE_CACHE_ENTRIES;
+use crate::rules::rules_for;
+use crate::search::DEFAULT_MOVE_OVERHEAD_MS;
+use crate::selfplay::Adjudication;
+use crate::tt::DEFAULT_HASH_MB;
+use crate::{EvalWeights, DEFAULT_EVAL_WEIGHTS};
+
+//
+// Structs
+//
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConfigValue {
+    Str(String),
+    Int(i64),
+    Float(f64),
+    Bool(bool),
+}
+
+/// Engine settings, usually read from a TOML file:
+///
+/// ```toml
+/// [search]
+/// depth = 4
+/// movetime_ms = 1000
+/// move_overhead_ms = 10    # time lost per move outside the search (GUI, bridge)
+/// latency_ms = 50          # network round trip per move
+/// verify_pv = false        # debug: check every PV move is legal
+/// deterministic = false    # reproducible searches: no time cutoffs, empty private tables
+///
+/// [eval]
+/// queen = 900
+/// mobility = 2
+///
+/// [engine]
+/// hash_mb = 64
+/// move_cache_entries = 4096  # legal move lists kept, 0 disables the cache
+/// max_memory_mb = 48       # scale the tables above down to fit
+/// threads = 4              # batches, self-play, and the root split of deterministic searches
+/// variant = "standard"     # or "los_alamos", "gardner" (see `small_board::SmallBoard`)
+/// evaluator = "nnue"       # or "handcrafted" (default)
+/// nnue_path = "net.bin"
+/// book_path = "book.txt"      # optional data: reported missing, never an error
+/// tablebase_path = "syzygy/"
+/// telemetry_dir = "reports/"
+///
+/// [adjudication]           # self-play and arena games
+/// resign_score = 800
+/// resign_moves = 4
+/// draw_score = 10
+/// draw_moves = 8
+/// draw_min_ply = 80
+/// likely_draw = false      # also adjudicate dead draws (material, locked pawns)
+/// max_plies = 200
+/// ```
+#[derive(Debug, Clone)]
+pub struct EngineConfig {
+    pub depth: Option<u32>,
+    pub movetime_ms: Option<u64>,
+    /// time lost on each move outside the search, see `SearchLimits`
+    pub move_overhead_ms: u64,
+    pub latency_ms: u64,
+    /// replay each PV and fail the search on an illegal move (debug)
+    pub verify_pv: bool,
+    /// ignore movetimes and search from an empty private transposition table
+    /// (one per thread with `threads`, see `Searcher::threads`) so that
+    /// results, also of multi-threaded searches and batches, are reproducible
+    pub deterministic: bool,
+    pub eval: EvalWeights,
+    pub hash_mb: usize,
+    pub move_cache_entries: usize,
+    /// cap of the transposition table and move cache together, which are
+    /// scaled down to fit, see `memory::fit_tables`
+    pub max_memory_mb: Option<usize>,
+    pub threads: usize,
+    pub variant: String,
+    /// "handcrafted" or "nnue"
+    pub evaluator: String,
+    pub nnue_path: Option<String>,
+    /// opening book (`OpeningBook` text format), searched without it
+    pub book_path: Option<String>,
+    /// directory of Syzygy tables (only reported, they are not probed)
+    pub tablebase_path: Option<String>,
+    /// write a JSON report in this directory after each search
+    pub telemetry_dir: Option<String>,
+    pub adjudication: Adjudication,
+}
+
+impl Default for EngineConfig {
+    fn default() -> Self {
+        Self {
+            depth: None,
+            movetime_ms: None,
+            move_overhead_ms: DEFAULT_MOVE_OVERHEAD_MS,
+            latency_ms: 0,
+            verify_pv: false,
+            deterministic: false,
+            eval: DEFAULT_EVAL_WEIGHTS,
+            hash_mb: DEFAULT_HASH_MB,
+            move_cache_entries: DEFAULT_MOVE_CACHE_ENTRIES,
+            max_memory_mb: None,
+            threads: 1,
+            variant: "standard".to_string(),
+            evaluator: "handcrafted".to_string(),
+            nnue_path: None,
+            book_path: None,
+            tablebase_path: None,
+            telemetry_dir: None,
+            adjudication: Adjudication::default(),
+        }
+    }
+}
+
+impl EngineConfig {
+    pub fn from_file(path: &Path) -> Result<Self, String> {
+        let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        Self::from_toml(&text)
+    }
+
+    pub fn from_toml(text: &str) -> Result<Self, String> {
+        let values = parse_toml(text)?;
+        let mut config = Self::default();
+        for (key, value) in values.iter() {
+            match key.as_str() {
+                "search.depth" => config.depth = Some(as_int(key, value)? as u32),
+                "search.movetime_ms" => config.movetime_ms = Some(as_int(key, value)? as u64),
+                "search.move_overhead_ms" => config.move_overhead_ms = as_int(key, value)? as u64,
+                "search.latency_ms" => config.latency_ms = as_int(key, value)? as u64,
+                "search.verify_pv" => config.verify_pv = as_bool(key, value)?,
+                "search.deterministic" => config.deterministic = as_bool(key, value)?,
+                "eval.pawn" => config.eval.pawn = as_int(key, value)? as i32,
+                "eval.knight" => config.eval.knight = as_int(key, value)? as i32,
+                "eval.bishop" => config.eval.bishop = as_int(key, value)? as i32,
+                "eval.rook" => config.eval.rook = as_int(key, value)? as i32,
+                "eval.queen" => config.eval.queen = as_int(key, value)? as i32,
+                "eval.king" => config.eval.king = as_int(key, value)? as i32,
+                "eval.pawn_advance" => config.eval.pawn_advance = as_int(key, value)? as i32,
+                "eval.center" => config.eval.center = as_int(key, value)? as i32,
+                "eval.mobility" => config.eval.mobility = as_int(key, value)? as i32,
+                "eval.attack_defense" => config.eval.attack_defense = as_int(key, value)? as i32,
+                "engine.hash_mb" => config.hash_mb = as_int(key, value)? as usize,
+                "engine.move_cache_entries" => {
+                    config.move_cache_entries = as_int(key, value)? as usize
+                }
+                "engine.max_memory_mb" => config.max_memory_mb = Some(as_int(key, value)? as usize),
+                "engine.threads" => config.threads = (as_int(key, value)? as usize).max(1),
+                "engine.variant" => config.variant = as_str(key, value)?,
+                "engine.evaluator" => config.evaluator = as_str(key, value)?,
+                "engine.nnue_path" => config.nnue_path = Some(as_str(key, value)?),
+                "engine.book_path" => config.book_path = Some(as_str(key, value)?),
+                "engine.tablebase_path" => config.tablebase_path = Some(as_str(key, value)?),
+                "engine.telemetry_dir" => config.telemetry_dir = Some(as_str(key, value)?),
+                "adjudication.resign_score" => {
+                    config.adjudication.resign_score = Some(as_int(key, value)? as isize)
+                }
+                "adjudication.resign_moves" => {
+                    config.adjudication.resign_moves = (as_int(key, value)? as u32).max(1)
+                }
+                "adjudication.draw_score" => {
+                    config.adjudication.draw_score = Some(as_int(key, value)? as isize)
+                }
+                "adjudication.draw_moves" => {
+                    config.adjudication.draw_moves = (as_int(key, value)? as u32).max(1)
+                }
+                "adjudication.draw_min_ply" => {
+                    config.adjudication.draw_min_ply = as_int(key, value)? as u32
+                }
+                "adjudication.likely_draw" => {
+                    config.adjudication.likely_draw = as_bool(key, value)?
+                }
+                "adjudication.max_plies" => config.adjudication.max_plies = as_int(key, value)? as u32,
+                _ => return Err(format!("unknown config key '{}'", key)),
+            }
+        }
+        rules_for(&config.variant)?;
+        config.fit_memory();
+        if config.evaluator != "handcrafted" && config.evaluator != "nnue" {
+            return Err(format!("unknown evaluator '{}'", config.evaluator));
+        }
+        Ok(config)
+    }
+
+    /// Scale `hash_mb` and `move_cache_entries` down to fit in `max_memory_mb`
+    pub fn fit_memory(&mut self) {
+        if let Some(max_memory_mb) = self.max_memory_mb {
+            let (hash_mb, move_cache_entries) =
+                fit_tables(self.hash_mb, self.move_cache_entries, max_memory_mb);
+            self.hash_mb = hash_mb;
+            self.move_cache_entries = move_cache_entries;
+        }
+    }
+}
+
+// HELPER FUNCTIONS
+// ---------------------------------------------------------
+// ---------------------------------------------------------
+fn as_int(key: &str, value: &ConfigValue) -> Result<i64, String> {
+    match value {
+        ConfigValue::Int(x) if *x >= 0 => Ok(*x),
+        _ => Err(format!("'{}' must be a non-negative integer", key)),
+    }
+}
+
+fn as_bool(key: &str, value: &ConfigValue) -> Result<bool, String> {
+    match value {
+        ConfigValue::Bool(x) => Ok(*x),
+        _ => Err(format!("'{}' must be a boolean", key)),
+    }
+}
+
+fn as_str(key: &str, value: &ConfigValue) -> Result<String, String> {
+    match value {
+        ConfigValue::Str(x) => Ok(x.clone()),
+        _ => Err(format!("'{}' must be a string", key)),
+    }
+}
+
+/// Parse the subset of TOML used by config files: `[table]` headers and
+/// `key = value` pairs with string, integer, float and boolean values.
+/// Keys are returned as "table.key".
+pub fn parse_toml(text: &str) -> Result<HashMap<String, ConfigValue>, String> {
+    let mut values: HashMap<String, ConfigValue> = HashMap::new();
+    let mut table = String::new();
+
+    for (i, raw_line) in text.lines().enumerate() {
+        let line_number = i + 1;
+        let line = strip_comment(raw_line).trim();
+        if line.is_empty() {
+            continue;
+        }
+        if line.starts_with('[') {
+            if !line.ends_with(']') {
+                return Err(format!("line {}: malformed table header", line_number));
+            }
+            table = line[1..line.len() - 1].trim().to_string();
+            continue;
+        }
+        let (key, value) = match line.find('=') {
+            Some(index) => (line[..index].trim(), line[index + 1..].trim()),
+            None => return Err(format!("line {}: expected 'key = value'", line_number)),
+        };
+        if key.is_empty() {
+            return Err(format!("line {}: missing key", line_number));
+        }
+        let value = parse_value(value)
+            .ok_or_else(|| format!("line {}: invalid value '{}'", line_number, value))?;
+        let full_key = if table.is_empty() {
+            key.to_string()
+        } else {
+            format!("{}.{}", table, key)
+        };
+        if values.insert(full_key.clone(), value).is_some() {
+            return Err(format!("line {}: duplicate key '{}'", line_number, full_key));
+        }
+    }
+    Ok(values)
+}
+
+/// remove a trailing `# comment`, ignoring '#' inside strings
+fn strip_comment(line: &str) -> &str {
+    let mut in_string = false;
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            '\\' if in_string => escaped = !escaped,
+            '"' if !escaped => in_string = !in_string,
+            '#' if !in_string => return &line[..i],
+            _ => escaped = false,
+        }
+    }
+    line
+}
+
+fn parse_value(value: &str) -> Option<ConfigValue> {
+    if value.len() >= 2 && value.starts_with('"') && value.ends_with('"') {
+        return parse_string(&value[1..value.len() - 1]).map(ConfigValue::Str);
+    }
+    match value {
+        "true" => return Some(ConfigValue::Bool(true)),
+        "false" => return Some(ConfigValue::Bool(false)),
+        _ => {}
+    }
+    let number = value.replace('_', "");
+    if let Ok(x) = number.parse::<i64>() {
+        return Some(ConfigValue::Int(x));
+    }
+    if let Ok(x) = number.parse::<f64>() {
+        return Some(ConfigValue::Float(x));
+    }
+    None
+}
+
+fn parse_string(value: &str) -> Option<String> {
+    let mut out = String::new();
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c == '"' {
+            return None;
+        }
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next()? {
+            'n' => out.push('\n'),
+            't' => out.push('\t'),
+            '"' => out.push('"'),
+            '\\' => out.push('\\'),
+            _ => return None,
+        }
+    }
+    Some(out)
+}
@@ -0,0 +1,78 @@
+use std::fs;
+use std::path::Path;
+
+use crate::book::{Book, DEFAULT_BOOK_PLIES};
+
+//
+// Constants
+//
+/// extensions of Syzygy WDL and DTZ tables
+const TABLEBASE_EXTENSIONS: [&str; 2] = ["rtbw", "rtbz"];
+
+//
+// Structs
+//
+/// Status of an optional data file (or directory) of the config: a missing
+/// file is reported rather than an error, and the engine searches instead
+#[derive(Debug, Clone, PartialEq)]
+pub enum DataStatus {
+    NotConfigured,
+    Available,
+    /// configured but unusable, with why
+    Unavailable(String),
+}
+
+impl DataStatus {
+    pub fn is_available(&self) -> bool {
+        *self == DataStatus::Available
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            DataStatus::NotConfigured => "not_configured",
+            DataStatus::Available => "available",
+            DataStatus::Unavailable(_) => "unavailable",
+        }
+    }
+
+    pub fn error(&self) -> Option<&str> {
+        match self {
+            DataStatus::Unavailable(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+/// The opening book at `path` (see `Book::load`), None if not configured or
+/// unreadable
+pub fn load_book(path: Option<&str>) -> (Option<Book>, DataStatus) {
+    let path = match path {
+        Some(path) => path,
+        None => return (None, DataStatus::NotConfigured),
+    };
+    match Book::load(Path::new(path), DEFAULT_BOOK_PLIES) {
+        Ok(book) => (Some(book), DataStatus::Available),
+        Err(e) => (None, DataStatus::Unavailable(e)),
+    }
+}
+
+/// Whether `path` is a directory holding Syzygy tables
+pub fn tablebase_status(path: Option<&str>) -> DataStatus {
+    let path = match path {
+        Some(path) => path,
+        None => return DataStatus::NotConfigured,
+    };
+    let entries = match fs::read_dir(path) {
+        Ok(entries) => entries,
+        Err(e) => return DataStatus::Unavailable(format!("{}: {}", path, e)),
+    };
+    let has_tables = entries.flatten().any(|entry| {
+        entry.path().extension().map_or(false, |extension| {
+            TABLEBASE_EXTENSIONS.iter().any(|&e| extension == e)
+        })
+    });
+    if !has_tables {
+        return DataStatus::Unavailable(format!("{}: no Syzygy tables", path));
+    }
+    DataStatus::Available
+}
@@ -0,0 +1,113 @@
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+
+use crate::eval::Evaluator;
+use crate::search::Searcher;
+use crate::tactics;
+use crate::tt::TranspositionTable;
+use crate::{convert_move_union_to_string, get_other_player, legal_move_strings, State};
+
+//
+// Constants
+//
+pub const DEFAULT_DIFFICULTY_DEPTH: u32 = 4;
+/// the searches are shallow, and a fresh table keeps the estimate reproducible
+const DIFFICULTY_HASH_MB: usize = 8;
+/// scores are clamped to this many centipawns (mates included)
+const SCORE_CLAMP: isize = 1000;
+/// each component is 1 from these values on
+const MAX_BRANCHING: f64 = 40.0;
+const MAX_VOLATILITY: f64 = 150.0;
+const MAX_TACTICS: f64 = 4.0;
+/// weights of the branching factor, eval volatility, best move changes and
+/// tactics in the score
+const WEIGHTS: [f64; 4] = [0.2, 0.35, 0.25, 0.2];
+
+//
+// Structs
+//
+/// How hard a position is to play, from 0 (trivial) to 100
+#[derive(Debug, Clone)]
+pub struct Difficulty {
+    pub score: f64,
+    /// legal moves of the side to move
+    pub branching: usize,
+    /// mean change of the score (centipawns) from one depth to the one two
+    /// plies deeper
+    pub volatility: f64,
+    /// times the best move changed from one depth to the next
+    pub best_move_changes: u32,
+    /// tactical motifs of both sides and pieces en prise
+    pub tactics: usize,
+    /// depths searched
+    pub depth: u32,
+}
+
+/// Difficulty of `state` for its side to move: a weighted mix of the
+/// branching factor, how much the score and the best move of searches at
+/// depths 1 to `depth` move around, and the tactics on the board (see
+/// `tactics::find_motifs` and `tactics::get_threats`)
+pub fn estimate_difficulty(
+    state: &State,
+    evaluator: &Arc<dyn Evaluator>,
+    depth: u32,
+    stop: Arc<AtomicBool>,
+) -> Difficulty {
+    let player = state.current_player;
+    let branching = legal_move_strings(state).len();
+
+    let mut iterations: Vec<(isize, Option<String>)> = vec![];
+    let mut tt = TranspositionTable::new(DIFFICULTY_HASH_MB);
+    let mut searcher = Searcher::new(stop, &mut tt);
+    searcher.evaluator = evaluator.clone();
+    searcher.iterative_deepening(state, player, depth, None, |info| {
+        let score = info.score.clamp(-SCORE_CLAMP, SCORE_CLAMP);
+        iterations.push((score, info.pv.first().map(convert_move_union_to_string)));
+    });
+    // depths of the same parity, the scores of shallow searches swinging
+    // with the side that moves last
+    let changes: Vec<isize> = iterations
+        .windows(3)
+        .map(|window| (window[2].0 - window[0].0).abs())
+        .collect();
+    let volatility = if changes.is_empty() {
+        0.0
+    } else {
+        changes.iter().sum::<isize>() as f64 / changes.len() as f64
+    };
+    let best_move_changes = iterations
+        .windows(2)
+        .filter(|pair| pair[1].1 != pair[0].1)
+        .count() as u32;
+
+    let other_player = get_other_player(player);
+    let tactics = tactics::find_motifs(&state.board, player, None).len()
+        + tactics::find_motifs(&state.board, other_player, None).len()
+        + tactics::get_threats(&state.board, player).len()
+        + tactics::get_threats(&state.board, other_player).len();
+
+    let components = [
+        (branching as f64 / MAX_BRANCHING).min(1.0),
+        (volatility / MAX_VOLATILITY).min(1.0),
+        if iterations.len() > 1 {
+            best_move_changes as f64 / (iterations.len() - 1) as f64
+        } else {
+            0.0
+        },
+        (tactics as f64 / MAX_TACTICS).min(1.0),
+    ];
+    let score = 100.0
+        * components
+            .iter()
+            .zip(WEIGHTS.iter())
+            .map(|(component, weight)| component * weight)
+            .sum::<f64>();
+    Difficulty {
+        score,
+        branching,
+        volatility,
+        best_move_changes,
+        tactics,
+        depth: iterations.len() as u32,
+    }
+}
@@ -0,0 +1,151 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use crate::eval::Evaluator;
+use crate::selfplay::search_move;
+use crate::tt::TranspositionTable;
+use crate::uci_client::{UciEngine, UciLimit};
+use crate::{convert_move_union_to_string, next_state, to_fen, MoveStruct, State};
+
+//
+// Constants
+//
+pub const DEFAULT_EVAL_THRESHOLD: isize = 150;
+pub const DEFAULT_MOVE_THRESHOLD: isize = 100;
+
+//
+// Structs
+//
+/// How both engines search and how far apart they must be
+#[derive(Debug, Copy, Clone)]
+pub struct MinerConfig {
+    /// depth of this engine's searches
+    pub depth: u32,
+    pub hash_mb: usize,
+    pub reference_limit: UciLimit,
+    /// scores at least this far apart (centipawns, side to move's point of
+    /// view) make an evaluation disagreement
+    pub eval_threshold: isize,
+    /// a best move the reference scores at least this much below its own
+    /// makes a move disagreement
+    pub move_threshold: isize,
+}
+
+/// A position where this engine and the reference engine disagree
+#[derive(Clone)]
+pub struct Disagreement {
+    /// line of the position in its file
+    pub line: usize,
+    pub state: State,
+    pub own_move: MoveStruct,
+    pub own_score: isize,
+    pub reference_move: MoveStruct,
+    pub reference_score: isize,
+    /// the reference's score of `own_move` (None when both chose the same move)
+    pub own_move_reference_score: Option<isize>,
+}
+
+impl Disagreement {
+    pub fn eval_gap(&self) -> isize {
+        (self.own_score - self.reference_score).abs()
+    }
+
+    /// centipawns lost by playing this engine's move, according to the reference
+    pub fn move_loss(&self) -> isize {
+        self.own_move_reference_score
+            .map_or(0, |score| self.reference_score - score)
+    }
+
+    /// EPD line of the position: `ce` and `ref_move` hold the reference's
+    /// score and move, `own_ce` and `own_move` this engine's, and
+    /// `move_loss` the cost of this engine's move (moves as "e2e4")
+    pub fn to_epd(&self) -> String {
+        let fen = to_fen(self.state);
+        let position: Vec<&str> = fen.split_whitespace().take(4).collect();
+        format!(
+            "{} id \"line {}\"; ce {}; ref_move {}; own_ce {}; own_move {}; move_loss {};",
+            position.join(" "),
+            self.line,
+            self.reference_score,
+            convert_move_union_to_string(&self.reference_move),
+            self.own_score,
+            convert_move_union_to_string(&self.own_move),
+            self.move_loss()
+        )
+    }
+}
+
+/// Search each position with this engine (`evaluator`) and the reference
+/// `engine`, and keep those where the scores or the best moves disagree by
+/// the thresholds of `config`, the largest disagreements first. When the
+/// best moves differ, the reference also scores the position after this
+/// engine's move. Positions without legal moves are skipped;
+/// `on_progress(done, total)` is called after each position.
+pub fn mine_disagreements<F: FnMut(usize, usize)>(
+    positions: &[(usize, State)],
+    evaluator: &Arc<dyn Evaluator>,
+    engine: &UciEngine,
+    config: &MinerConfig,
+    stop: &Arc<AtomicBool>,
+    mut on_progress: F,
+) -> Result<Vec<Disagreement>, String> {
+    let mut tt = TranspositionTable::new(config.hash_mb);
+    let mut disagreements: Vec<Disagreement> = vec![];
+    for (i, &(line, state)) in positions.iter().enumerate() {
+        if stop.load(Ordering::SeqCst) {
+            break;
+        }
+        let player = state.current_player;
+        let own = match search_move(&state, player, evaluator, config.depth, None, &mut tt, stop) {
+            Some(info) if !info.pv.is_empty() => info,
+            _ => {
+                on_progress(i + 1, positions.len());
+                continue;
+            }
+        };
+        let reference = engine.analyse(&state, config.reference_limit)?;
+
+        let own_move = own.pv[0].clone();
+        let own_move_str = convert_move_union_to_string(&own_move);
+        let same_move = own_move_str == convert_move_union_to_string(&reference.best_move);
+        let own_move_reference_score = if same_move {
+            None
+        } else {
+            let after = next_state(&state, player, own_move.clone()).0;
+            Some(-engine.analyse(&after, config.reference_limit)?.score)
+        };
+        let disagreement = Disagreement {
+            line,
+            state,
+            own_move,
+            own_score: own.score,
+            reference_move: reference.best_move,
+            reference_score: reference.score,
+            own_move_reference_score,
+        };
+        if disagreement.eval_gap() >= config.eval_threshold
+            || disagreement.move_loss() >= config.move_threshold
+        {
+            disagreements.push(disagreement);
+        }
+        on_progress(i + 1, positions.len());
+    }
+    disagreements.sort_by_key(|d| -d.eval_gap().max(d.move_loss()));
+    Ok(disagreements)
+}
+
+/// Write the disagreements as an EPD suite (see `Disagreement::to_epd`)
+pub fn write_epd(path: &Path, disagreements: &[Disagreement]) -> Result<(), String> {
+    let file = File::create(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let mut writer = BufWriter::new(file);
+    for disagreement in disagreements {
+        writeln!(writer, "{}", disagreement.to_epd())
+            .map_err(|e| format!("{}: {}", path.display(), e))?;
+    }
+    writer
+        .flush()
+        .map_err(|e| format!("{}: {}", path.display(), e))
+}
@@ -0,0 +1,134 @@
+use crate::{Board, State, BISHOP_ID, KING_ID, KNIGHT_ID, PAWN_ID, QUEEN_ID, ROOK_ID};
+
+//
+// Constants
+//
+/// recent search scores needed to judge the evaluation stable
+pub const DRAW_STABLE_MOVES: usize = 8;
+/// the stable scores stay within this many centipawns of 0
+const DRAW_STABLE_SCORE: isize = 30;
+/// pawnless material differences up to a minor piece are drawish
+const DRAWISH_MATERIAL_DIFF: isize = 3;
+
+//
+// Structs
+//
+/// Signals of a dead-drawn position, see `likely_draw`
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct DrawSignals {
+    /// pawnless with at most a minor piece of difference (a lone pair of
+    /// knights included), or bishops of opposite colours alone with at most
+    /// one pawn of difference
+    pub drawish_material: bool,
+    /// only kings and pawns, every pawn blocked by an opposing pawn and none
+    /// able to capture
+    pub locked_pawns: bool,
+    /// the last DRAW_STABLE_MOVES scores stayed near 0 (None with fewer scores)
+    pub stable_eval: Option<bool>,
+}
+
+impl DrawSignals {
+    pub fn likely_draw(&self) -> bool {
+        (self.drawish_material || self.locked_pawns) && self.stable_eval != Some(false)
+    }
+}
+
+/// Draw signals of `state`, `scores` being the recent search scores (any
+/// point of view, the last one most recent; empty if unknown)
+pub fn draw_signals(state: &State, scores: &[isize]) -> DrawSignals {
+    let stable_eval = if scores.len() >= DRAW_STABLE_MOVES {
+        Some(
+            scores[scores.len() - DRAW_STABLE_MOVES..]
+                .iter()
+                .all(|score| score.abs() <= DRAW_STABLE_SCORE),
+        )
+    } else {
+        None
+    };
+    DrawSignals {
+        drawish_material: drawish_material(&state.board),
+        locked_pawns: locked_pawns(&state.board),
+        stable_eval,
+    }
+}
+
+/// Whether `state` is likely a dead draw: drawish material or locked pawns,
+/// unless the recent `scores` say otherwise
+pub fn likely_draw(state: &State, scores: &[isize]) -> bool {
+    draw_signals(state, scores).likely_draw()
+}
+
+// HELPER FUNCTIONS
+// ---------------------------------------------------------
+// ---------------------------------------------------------
+
+fn drawish_material(board: &Board) -> bool {
+    // white and black counts of pawns, knights, bishops, rooks and queens
+    let mut counts = [[0isize; 5]; 2];
+    let mut bishop_square_colors = [vec![], vec![]];
+    for (row, board_row) in board.iter().enumerate() {
+        for (col, &piece) in board_row.iter().enumerate() {
+            let side = if piece > 0 { 0 } else { 1 };
+            let index = match piece.abs() {
+                PAWN_ID => 0,
+                KNIGHT_ID => 1,
+                BISHOP_ID => 2,
+                ROOK_ID => 3,
+                QUEEN_ID => 4,
+                _ => continue,
+            };
+            counts[side][index] += 1;
+            if index == 2 {
+                bishop_square_colors[side].push((row + col) % 2);
+            }
+        }
+    }
+    let pawns = counts[0][0] + counts[1][0];
+    if pawns == 0 {
+        let value = |c: &[isize; 5]| 3 * (c[1] + c[2]) + 5 * c[3] + 9 * c[4];
+        let two_knights = |c: &[isize; 5]| c[1] == 2 && c[2] + c[3] + c[4] == 0;
+        let (white, black) = (&counts[0], &counts[1]);
+        let diff = (value(white) - value(black)).abs();
+        return diff <= DRAWISH_MATERIAL_DIFF
+            || (two_knights(white) && value(black) == 0)
+            || (two_knights(black) && value(white) == 0);
+    }
+    let bishops_only = counts
+        .iter()
+        .all(|c| c[1] == 0 && c[2] == 1 && c[3] == 0 && c[4] == 0);
+    bishops_only
+        && bishop_square_colors[0] != bishop_square_colors[1]
+        && (counts[0][0] - counts[1][0]).abs() <= 1
+}
+
+fn locked_pawns(board: &Board) -> bool {
+    let mut pawns = 0;
+    for row in 0..8 {
+        for col in 0..8 {
+            let piece = board[row][col];
+            match piece.abs() {
+                0 | KING_ID => continue,
+                PAWN_ID => pawns += 1,
+                _ => return false,
+            }
+            // white pawns move towards row 0
+            let ahead = row as isize - piece.signum();
+            if !(0..8).contains(&ahead) {
+                return false;
+            }
+            let ahead = ahead as usize;
+            if board[ahead][col] != -piece {
+                return false;
+            }
+            for capture_col in [col as isize - 1, col as isize + 1].iter() {
+                if (0..8).contains(capture_col) {
+                    let target = board[ahead][*capture_col as usize];
+                    if target * piece < 0 {
+                        return false;
+                    }
+                }
+            }
+        }
+    }
+    pawns > 0
+}
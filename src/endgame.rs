@@ -0,0 +1,117 @@
+use crate::{Board, Color, Square, KING_ID, PAWN_ID};
+
+//
+// Structs
+//
+/// A pawn ending decided by a pawn race: `winner` queens an unstoppable
+/// passed pawn first, in `moves` of its moves
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct PawnRace {
+    pub winner: Color,
+    pub moves: u32,
+}
+
+/// The pawn race of a pawn ending (only kings and pawns) with `to_move` to
+/// play. A passed pawn is unstoppable when the opponent king is outside its
+/// square (it cannot reach the promotion square in time) and its own king
+/// does not block it. The side queening first wins the race; None when
+/// neither side has an unstoppable pawn, or the position is no pawn ending.
+pub fn pawn_race(board: &Board, to_move: Color) -> Option<PawnRace> {
+    let is_pawn_ending = board.iter().all(|row| {
+        row.iter()
+            .all(|&piece| piece == 0 || piece.abs() == KING_ID || piece.abs() == PAWN_ID)
+    });
+    if !is_pawn_ending {
+        return None;
+    }
+    let white = moves_to_queen(board, Color::White, to_move);
+    let black = moves_to_queen(board, Color::Black, to_move);
+    // the side to move queens first when both need as many moves
+    let plies = |moves: u32, player: Color| 2 * moves - if to_move == player { 1 } else { 0 };
+    let winner = match (white, black) {
+        (Some(w), Some(b)) if plies(w, Color::White) < plies(b, Color::Black) => Color::White,
+        (Some(_), Some(_)) => Color::Black,
+        (Some(_), None) => Color::White,
+        (None, Some(_)) => Color::Black,
+        (None, None) => return None,
+    };
+    let moves = match winner {
+        Color::White => white.unwrap(),
+        Color::Black => black.unwrap(),
+    };
+    Some(PawnRace { winner, moves })
+}
+
+// HELPER FUNCTIONS
+// ---------------------------------------------------------
+// ---------------------------------------------------------
+
+/// moves `player` needs to queen its fastest unstoppable passed pawn
+fn moves_to_queen(board: &Board, player: Color, to_move: Color) -> Option<u32> {
+    let sign = match player {
+        Color::White => 1,
+        Color::Black => -1,
+    };
+    let other_king = find_piece(board, -sign * KING_ID)?;
+    let own_king = find_piece(board, sign * KING_ID);
+    let mut fastest: Option<u32> = None;
+    for row in 0..8isize {
+        for col in 0..8isize {
+            if board[row as usize][col as usize] != sign * PAWN_ID
+                || !is_passed(board, (row, col), sign)
+            {
+                continue;
+            }
+            // white pawns move towards row 0, two squares from their first move
+            let (promotion_row, start_row) = if sign == 1 { (0, 6) } else { (7, 1) };
+            let mut distance = (row - promotion_row).unsigned_abs() as u32;
+            if row == start_row {
+                distance -= 1;
+            }
+            let blocked = own_king.map_or(false, |king| {
+                king.1 == col && (king.0 - row).signum() == -sign
+            });
+            if blocked {
+                continue;
+            }
+            let promotion: Square = (promotion_row, col);
+            let king_distance = chebyshev(other_king, promotion);
+            // the defending king moves first when it is its turn
+            let tempo = if to_move == player { 0 } else { 1 };
+            if king_distance > distance + tempo {
+                fastest = Some(fastest.map_or(distance, |moves| moves.min(distance)));
+            }
+        }
+    }
+    fastest
+}
+
+/// no opponent pawn in front of the pawn on its file or the neighbouring ones
+fn is_passed(board: &Board, pawn: Square, sign: isize) -> bool {
+    for row in 0..8isize {
+        if (row - pawn.0).signum() != -sign {
+            continue;
+        }
+        for col in (pawn.1 - 1).max(0)..=(pawn.1 + 1).min(7) {
+            if board[row as usize][col as usize] == -sign * PAWN_ID {
+                return false;
+            }
+        }
+    }
+    true
+}
+
+fn find_piece(board: &Board, piece: isize) -> Option<Square> {
+    for (row, pieces) in board.iter().enumerate() {
+        for (col, &piece_id) in pieces.iter().enumerate() {
+            if piece_id == piece {
+                return Some((row as isize, col as isize));
+            }
+        }
+    }
+    None
+}
+
+fn chebyshev(a: Square, b: Square) -> u32 {
+    (a.0 - b.0).abs().max((a.1 - b.1).abs()) as u32
+}
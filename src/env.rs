@@ -0,0 +1,967 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::panic::{self, AssertUnwindSafe};
+use std::thread;
+
+use crate::exploration::{exploration_signals, DEFAULT_EXPLORATION_DEPTH};
+use crate::policies::OpponentPolicy;
+use crate::rng::Rng;
+use crate::rules::FIFTY_MOVES_PLIES;
+use crate::selfplay::{only_kings_left, winner, GameResult, DEFAULT_MAX_PLIES};
+use crate::tactics::{self, SquareControl};
+use crate::tt::position_key;
+use crate::{
+    convert_castle_move_to_string, convert_move_to_string, convert_move_to_type, from_fen,
+    get_other_player, is_promotion, king_is_checked, legal_move_strings,
+    legal_move_strings_all_promotions, next_state_promoting, promotion_of, update_state,
+    validate_position, Castle, Color, MoveStruct, State, BISHOP_ID, DEFAULT_BOARD, ID_TO_VALUE,
+    KING_ID, KNIGHT_ID, PAWN_ID, ROOK_ID,
+};
+
+//
+// Constants
+//
+/// 6 piece planes for each side and 2 repetition planes
+pub const PLANES_PER_POSITION: usize = 14;
+/// side to move, the 4 castling rights, the repetition count and the
+/// halfmove clock
+pub const CONSTANT_PLANES: usize = 7;
+pub const WIN_REWARD: f64 = 100.0;
+/// actions are from_square * 64 + to_square, squares numbered row by row from
+/// a8 (castling is the king's move, promotions give a queen)
+pub const NUM_ACTIONS: usize = 4096;
+/// without auto-queening, underpromotions follow: for each file of the pawn,
+/// capture towards the a-file, push and capture towards the h-file, each to a
+/// rook, a bishop and a knight
+pub const NUM_ACTIONS_ALL_PROMOTIONS: usize = NUM_ACTIONS + 8 * 3 * 3;
+/// underpromotion pieces, in the order of their actions
+const UNDERPROMOTION_IDS: [isize; 3] = [ROOK_ID, BISHOP_ID, KNIGHT_ID];
+/// material lead (pawn = 1) from which a position counts as won
+pub const DEFAULT_WINNING_MATERIAL: isize = 3;
+
+//
+// Types
+//
+/// environment after the last move, observations of all the positions and
+/// rewards of the moves, as returned by `Env::replay`
+pub type Replay = (Env, Vec<Vec<f32>>, Vec<f64>);
+
+//
+// Structs
+//
+/// Rewards of the mover. The engine only rewards captures and promotions, so
+/// the shaping bonuses (0 disables them) give a denser signal early in training.
+#[derive(Debug, Copy, Clone)]
+pub struct RewardConfig {
+    pub win: f64,
+    /// factor of the engine's capture and promotion reward
+    pub material: f64,
+    /// bonus for giving check
+    pub check: f64,
+    /// bonus for castling
+    pub castle: f64,
+    /// bonus for each passed pawn the move creates
+    pub passed_pawn: f64,
+    /// penalty of the side ahead by `winning_material` or more when the game
+    /// ends in stalemate or by the fifty-move rule, to learn converting won
+    /// endgames. When the other side made the last move it gets the penalty
+    /// as a bonus instead, the rewards being zero-sum.
+    pub unconverted_win: f64,
+    pub winning_material: isize,
+    /// reward of an illegal move, which leaves the environment unchanged
+    /// (None: the step fails)
+    pub illegal_move: Option<f64>,
+    /// intrinsic bonus per unit of log branching factor of the position
+    /// reached by a step (see `exploration_signals`)
+    pub exploration_branching: f64,
+    /// intrinsic bonus per pawn of eval uncertainty across search depths of
+    /// the position reached by a step
+    pub exploration_uncertainty: f64,
+    /// depth of the search measuring the uncertainty
+    pub exploration_depth: u32,
+}
+
+impl Default for RewardConfig {
+    fn default() -> Self {
+        Self {
+            win: WIN_REWARD,
+            material: 1.0,
+            check: 0.0,
+            castle: 0.0,
+            passed_pawn: 0.0,
+            unconverted_win: 0.0,
+            winning_material: DEFAULT_WINNING_MATERIAL,
+            illegal_move: None,
+            exploration_branching: 0.0,
+            exploration_uncertainty: 0.0,
+            exploration_depth: DEFAULT_EXPLORATION_DEPTH,
+        }
+    }
+}
+
+/// Why `Env::step` failed, the environment being left as it was
+#[derive(Debug, Clone, PartialEq)]
+pub enum StepError {
+    /// the move is not legal, or the episode is over
+    IllegalMove(String),
+    /// the engine panicked while playing the move
+    Internal(String),
+}
+
+impl StepError {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            StepError::IllegalMove(_) => "illegal_move",
+            StepError::Internal(_) => "internal_error",
+        }
+    }
+
+    pub fn message(&self) -> &str {
+        match self {
+            StepError::IllegalMove(message) | StepError::Internal(message) => message,
+        }
+    }
+}
+
+impl fmt::Display for StepError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message())
+    }
+}
+
+/// What a side did during the episode
+#[derive(Debug, Default, Copy, Clone)]
+pub struct EpisodeStats {
+    /// pieces captured, by piece id - 1 (queen, rook, bishop, knight and pawn
+    /// from index 1)
+    pub captures: [u32; 6],
+    /// moves giving check (mates included)
+    pub checks: u32,
+    pub promotions: u32,
+    pub castles: u32,
+}
+
+/// RL environment over the full game, keeping the positions played so far so
+/// that the observation can stack the last `history` of them
+#[derive(Debug, Clone)]
+pub struct Env {
+    pub history: usize,
+    pub max_plies: u32,
+    pub rewards: RewardConfig,
+    /// positions of the episode, the current one last, each with the number
+    /// of times it had occurred when it was reached
+    positions: Vec<(State, u32)>,
+    repetitions: HashMap<u64, u32>,
+    /// control map of the current position, updated move by move once requested
+    control: Option<[[SquareControl; 8]; 8]>,
+    /// result and termination once the episode is over
+    pub outcome: Option<(GameResult, &'static str)>,
+    /// randomness of the environment (`random_move`), reseeded by the caller
+    pub rng: Rng,
+    /// statistics of the episode for white and black
+    pub stats: [EpisodeStats; 2],
+    /// pawns always promote to a queen: moves ("e7e8") and actions (below
+    /// NUM_ACTIONS) don't name the piece. Otherwise promotions name it
+    /// ("e7e8q", "e7e8n") and underpromotions have their own actions.
+    pub auto_queen: bool,
+    /// scripted opponent answering every move, the agent playing
+    /// `agent_color` (None plays both sides)
+    pub opponent: Option<OpponentPolicy>,
+    pub agent_color: Color,
+    /// exploration bonus included in the reward of the last step (0 if it
+    /// failed)
+    pub intrinsic_reward: f64,
+}
+
+impl Env {
+    pub fn new(history: usize, max_plies: Option<u32>) -> Result<Self, String> {
+        if history == 0 {
+            return Err("history must stack at least 1 position".to_string());
+        }
+        let mut env = Env {
+            history,
+            max_plies: max_plies.unwrap_or(DEFAULT_MAX_PLIES),
+            rewards: RewardConfig::default(),
+            positions: vec![],
+            repetitions: HashMap::new(),
+            control: None,
+            outcome: None,
+            rng: Rng::from_entropy(),
+            stats: Default::default(),
+            auto_queen: true,
+            opponent: None,
+            agent_color: Color::White,
+            intrinsic_reward: 0.0,
+        };
+        env.reset(State::new(DEFAULT_BOARD, Color::White, true, true, true, true));
+        Ok(env)
+    }
+
+    /// Start a new episode from `start` (the opponent moves first if the
+    /// agent's side is not to move)
+    pub fn reset(&mut self, start: State) {
+        self.start_episode(start);
+        self.opponent_move();
+    }
+
+    /// Start a new episode from a FEN position, rejected if it does not parse
+    /// or fails `validate_position`. The halfmove clock of the FEN, if any,
+    /// counts towards the fifty-move rule.
+    pub fn reset_from_fen(&mut self, fen: &str) -> Result<(), String> {
+        let start = from_fen(fen)?;
+        let problems = validate_position(&start);
+        if !problems.is_empty() {
+            return Err(format!(
+                "Invalid position '{}': {}",
+                fen,
+                problems.join("; ")
+            ));
+        }
+        self.start_episode(start);
+        self.opponent_move();
+        Ok(())
+    }
+
+    pub fn state(&self) -> &State {
+        &self.positions.last().unwrap().0
+    }
+
+    /// positions of the episode, the start first
+    pub fn positions(&self) -> Vec<State> {
+        self.positions.iter().map(|(state, _)| *state).collect()
+    }
+
+    /// plies played since the reset
+    pub fn ply(&self) -> usize {
+        self.positions.len() - 1
+    }
+
+    /// times the current position occurred in the episode (1 the first time)
+    pub fn repetitions(&self) -> u32 {
+        self.positions.last().unwrap().1
+    }
+
+    /// plies since the last capture or pawn move
+    pub fn halfmove_clock(&self) -> u32 {
+        self.state().halfmove_clock
+    }
+
+    /// Play a legal move for the side to move, returning the reward of the
+    /// mover (see `RewardConfig`). With an opponent, it answers the move and
+    /// the reward of its answer is taken off the agent's. The exploration
+    /// bonus of the position reached, if enabled, is added. Atomic: when the
+    /// move is illegal or the engine panics, the environment is restored as
+    /// it was before the call.
+    pub fn step(&mut self, _move: &str) -> Result<f64, StepError> {
+        let snapshot = self.clone();
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            let reward = self.play(_move).map_err(StepError::IllegalMove)?;
+            let reward = reward - self.opponent_move();
+            self.intrinsic_reward = self.exploration_bonus();
+            Ok(reward + self.intrinsic_reward)
+        }));
+        let error = match result {
+            Ok(Ok(reward)) => return Ok(reward),
+            Ok(Err(error)) => error,
+            Err(payload) => {
+                let message = if let Some(message) = payload.downcast_ref::<String>() {
+                    message.clone()
+                } else if let Some(message) = payload.downcast_ref::<&str>() {
+                    message.to_string()
+                } else {
+                    "unknown panic".to_string()
+                };
+                StepError::Internal(format!("internal engine error: {}", message))
+            }
+        };
+        *self = snapshot;
+        self.intrinsic_reward = 0.0;
+        Err(error)
+    }
+
+    /// The opponent's move if it is to move, returning its reward (0 without
+    /// a move)
+    fn opponent_move(&mut self) -> f64 {
+        let opponent = match self.opponent {
+            Some(opponent) if self.outcome.is_none() => opponent,
+            _ => return 0.0,
+        };
+        if self.state().current_player == self.agent_color {
+            return 0.0;
+        }
+        let state = *self.state();
+        let mut _move = match opponent.choose_move(&state, &mut self.rng) {
+            Some(_move) => _move,
+            None => return 0.0,
+        };
+        // the policies write queen promotions without suffix, and only the
+        // searching ones underpromote
+        if is_promotion(&state, &convert_move_to_type(&_move)) {
+            if self.auto_queen {
+                _move.truncate(4);
+            } else if _move.len() == 4 {
+                _move.push('q');
+            }
+        }
+        // the policies only choose legal moves
+        self.play(&_move).unwrap()
+    }
+
+    /// Intrinsic reward of the current position (0 once the episode is over
+    /// or without exploration rewards)
+    fn exploration_bonus(&self) -> f64 {
+        let rewards = &self.rewards;
+        if self.outcome.is_some()
+            || (rewards.exploration_branching == 0.0 && rewards.exploration_uncertainty == 0.0)
+        {
+            return 0.0;
+        }
+        let depth = if rewards.exploration_uncertainty == 0.0 {
+            0
+        } else {
+            rewards.exploration_depth
+        };
+        let signals = exploration_signals(self.state(), depth);
+        rewards.exploration_branching * signals.branching
+            + rewards.exploration_uncertainty * signals.uncertainty
+    }
+
+    /// `step` without the opponent's answer
+    fn play(&mut self, _move: &str) -> Result<f64, String> {
+        if self.outcome.is_some() {
+            return Err("the episode is over, call reset()".to_string());
+        }
+        let state = *self.state();
+        let player = state.current_player;
+        if !legal_moves(&state, self.auto_queen).iter().any(|m| m == _move) {
+            return Err(format!("Illegal move '{}'", _move));
+        }
+        let move_struct: MoveStruct = convert_move_to_type(_move);
+        let is_castle = move_struct.is_castle;
+        let (new_state, material) =
+            next_state_promoting(&state, player, move_struct, promotion_of(_move));
+        self.push_position(new_state);
+        self.update_stats(&state, _move);
+        if let Some(control) = self.control.as_mut() {
+            tactics::update_control_map(control, &state.board, &new_state.board);
+        }
+
+        let rewards = &self.rewards;
+        let mut reward = rewards.material * material as f64;
+        if let Some((_, "checkmate")) = self.outcome {
+            reward += rewards.win;
+        }
+        if rewards.check != 0.0 && king_is_checked(self.state(), self.state().current_player) {
+            reward += rewards.check;
+        }
+        if is_castle {
+            reward += rewards.castle;
+        }
+        if let Some((_, "stalemate")) | Some((_, "fifty_moves")) = self.outcome {
+            let lead = material_count(self.state(), player)
+                - material_count(self.state(), get_other_player(player));
+            if lead >= rewards.winning_material {
+                reward -= rewards.unconverted_win;
+            } else if -lead >= rewards.winning_material {
+                reward += rewards.unconverted_win;
+            }
+        }
+        if rewards.passed_pawn != 0.0 {
+            let created = passed_pawns(self.state(), player) - passed_pawns(&state, player);
+            reward += rewards.passed_pawn * created.max(0) as f64;
+        }
+        Ok(reward)
+    }
+
+    /// Play `actions` from the initial position or `start_fen` in a new
+    /// environment with the same settings (the current episode is kept),
+    /// returning the environment after the last move, the observations of all
+    /// the positions (the start included) and the rewards of the moves.
+    /// Fails at the first illegal move, giving its index.
+    pub fn replay(
+        &self,
+        start_fen: Option<&str>,
+        actions: &[Action],
+    ) -> Result<Replay, String> {
+        let mut env = self.clone();
+        match start_fen {
+            Some(fen) => env.reset_from_fen(fen)?,
+            None => env.reset(State::new(DEFAULT_BOARD, Color::White, true, true, true, true)),
+        }
+        let mut observations = vec![env.observation()];
+        let mut rewards = vec![];
+        for (i, action) in actions.iter().enumerate() {
+            let reward = action
+                .to_move(env.state(), env.auto_queen)
+                .and_then(|_move| env.step(&_move).map_err(|e| e.to_string()))
+                .map_err(|e| format!("action {}: {}", i, e))?;
+            observations.push(env.observation());
+            rewards.push(reward);
+        }
+        Ok((env, observations, rewards))
+    }
+
+    /// Move string of a legal action (see NUM_ACTIONS) of the side to move
+    pub fn check_action(&self, action: usize) -> Result<String, String> {
+        if self.outcome.is_some() {
+            return Err("the episode is over".to_string());
+        }
+        let _move = action_to_move(self.state(), action, self.auto_queen)?;
+        if !self.legal_moves().contains(&_move) {
+            return Err(format!("Illegal move '{}'", _move));
+        }
+        Ok(_move)
+    }
+
+    /// A legal move of the side to move drawn from `rng` (None once the
+    /// episode is over)
+    pub fn random_move(&mut self) -> Option<String> {
+        if self.outcome.is_some() {
+            return None;
+        }
+        let moves = self.legal_moves();
+        if moves.is_empty() {
+            return None;
+        }
+        let i = self.rng.below(moves.len());
+        Some(moves[i].clone())
+    }
+
+    /// Legal moves of the side to move (see `auto_queen`)
+    pub fn legal_moves(&self) -> Vec<String> {
+        legal_moves(self.state(), self.auto_queen)
+    }
+
+    /// Size of the action space (see `num_actions`)
+    pub fn num_actions(&self) -> usize {
+        num_actions(self.auto_queen)
+    }
+
+    /// Whether each action of the action space is legal for the side to move
+    /// (none once the episode is over)
+    pub fn action_mask(&self) -> Vec<bool> {
+        let mut mask = vec![false; self.num_actions()];
+        if self.outcome.is_none() {
+            for _move in self.legal_moves() {
+                mask[move_to_action(&_move)] = true;
+            }
+        }
+        mask
+    }
+
+    /// Control map of the current position (see `tactics::control_map`).
+    /// Computed on the first call, then updated incrementally by `step`.
+    pub fn control_map(&mut self) -> &[[SquareControl; 8]; 8] {
+        let board = self.state().board;
+        self
+            .control
+            .get_or_insert_with(|| tactics::control_map(&board))
+    }
+
+    pub fn num_planes(&self) -> usize {
+        self.history * PLANES_PER_POSITION + CONSTANT_PLANES
+    }
+
+    /// Planes of 8x8 squares, flattened, from the point of view of the side to
+    /// move (the board is flipped for black). For each of the last `history`
+    /// positions, most recent first: the 6 piece types of the side to move,
+    /// the 6 of the opponent, then whether the position had occurred at least
+    /// 2 and 3 times; positions before the start of the episode are empty.
+    /// Then constant planes for the side to move (1 for white), the castling
+    /// rights (king side and queen side of the side to move, then of the
+    /// opponent), the repetitions of the current position (0 the first time,
+    /// 1 from the third time) and the halfmove clock (1 at the fifty-move
+    /// draw).
+    pub fn observation(&self) -> Vec<f32> {
+        let mut planes = vec![0.0; self.num_planes() * 64];
+        let player = self.state().current_player;
+        let oriented_row = |row: usize| match player {
+            Color::White => row,
+            Color::Black => 7 - row,
+        };
+
+        for (t, (state, repetitions)) in self.positions.iter().rev().take(self.history).enumerate()
+        {
+            let offset = t * PLANES_PER_POSITION;
+            for (row, pieces) in state.board.iter().enumerate() {
+                for (col, &piece_id) in pieces.iter().enumerate() {
+                    if piece_id == 0 {
+                        continue;
+                    }
+                    let own = (piece_id > 0) == (player == Color::White);
+                    let plane =
+                        offset + (piece_id.abs() - KING_ID) as usize + if own { 0 } else { 6 };
+                    planes[plane * 64 + oriented_row(row) * 8 + col] = 1.0;
+                }
+            }
+            fill_plane(&mut planes, offset + 12, *repetitions >= 2);
+            fill_plane(&mut planes, offset + 13, *repetitions >= 3);
+        }
+
+        let state = self.state();
+        let castling = match player {
+            Color::White => [
+                state.white_king_castle_is_possible,
+                state.white_queen_castle_is_possible,
+                state.black_king_castle_is_possible,
+                state.black_queen_castle_is_possible,
+            ],
+            Color::Black => [
+                state.black_king_castle_is_possible,
+                state.black_queen_castle_is_possible,
+                state.white_king_castle_is_possible,
+                state.white_queen_castle_is_possible,
+            ],
+        };
+        let offset = self.history * PLANES_PER_POSITION;
+        fill_plane(&mut planes, offset, player == Color::White);
+        for (i, &castle_is_possible) in castling.iter().enumerate() {
+            fill_plane(&mut planes, offset + 1 + i, castle_is_possible);
+        }
+        let repetitions = (self.repetitions() - 1) as f32 / 2.0;
+        set_plane(&mut planes, offset + 5, repetitions.min(1.0));
+        let halfmove_clock = self.halfmove_clock() as f32 / FIFTY_MOVES_PLIES as f32;
+        set_plane(&mut planes, offset + 6, halfmove_clock.min(1.0));
+        planes
+    }
+
+    /// Count the move `_move` played from `before` to the current position
+    fn update_stats(&mut self, before: &State, _move: &str) {
+        let player = before.current_player;
+        let after = *self.state();
+        let gives_check = match player {
+            Color::White => after.black_king_is_checked,
+            Color::Black => after.white_king_is_checked,
+        };
+        let move_struct = convert_move_to_type(_move);
+        let stats = &mut self.stats[if player == Color::White { 0 } else { 1 }];
+        if gives_check {
+            stats.checks += 1;
+        }
+        if move_struct.is_castle {
+            stats.castles += 1;
+            return;
+        }
+        let to = unsafe { move_struct.data.normal_move.1 };
+        let mut captured = before.board[to.0 as usize][to.1 as usize];
+        if Some(to) == before.en_passant_square
+            && before.board[to.0 as usize][to.1 as usize] == 0
+            && unsafe { move_struct.data.normal_move.0 .1 } != to.1
+        {
+            captured = -player.to_int() * PAWN_ID;
+        }
+        if captured != 0 {
+            stats.captures[(captured.abs() - KING_ID) as usize] += 1;
+        }
+        if is_promotion(before, &move_struct) {
+            stats.promotions += 1;
+        }
+    }
+
+    fn start_episode(&mut self, start: State) {
+        self.positions.clear();
+        self.repetitions.clear();
+        self.control = None;
+        self.outcome = None;
+        self.intrinsic_reward = 0.0;
+        self.stats = Default::default();
+        self.push_position(start);
+    }
+
+    fn push_position(&mut self, mut state: State) {
+        update_state(&mut state);
+        let repetitions = self.repetitions.entry(position_key(&state)).or_insert(0);
+        *repetitions += 1;
+        let repetitions = *repetitions;
+        self.positions.push((state, repetitions));
+
+        let player = state.current_player;
+        self.outcome = if legal_move_strings(&state).is_empty() {
+            if king_is_checked(&state, player) {
+                Some((winner(player), "checkmate"))
+            } else {
+                Some((GameResult::Draw, "stalemate"))
+            }
+        } else if repetitions >= 3 {
+            Some((GameResult::Draw, "repetition"))
+        } else if state.halfmove_clock >= FIFTY_MOVES_PLIES {
+            Some((GameResult::Draw, "fifty_moves"))
+        } else if only_kings_left(&state) {
+            Some((GameResult::Draw, "insufficient_material"))
+        } else if self.ply() as u32 >= self.max_plies {
+            Some((GameResult::Draw, "max_plies"))
+        } else {
+            None
+        };
+    }
+}
+
+/// A move string or an action (see NUM_ACTIONS)
+#[derive(Debug, Clone)]
+pub enum Action {
+    Move(String),
+    Index(usize),
+}
+
+impl Action {
+    pub fn to_move(&self, state: &State, auto_queen: bool) -> Result<String, String> {
+        match self {
+            Action::Move(_move) => Ok(_move.clone()),
+            Action::Index(action) => action_to_move(state, *action, auto_queen),
+        }
+    }
+}
+
+/// `num_envs` independent environments stepped together, split among
+/// `threads` threads
+#[derive(Debug, Clone)]
+pub struct VecEnv {
+    pub envs: Vec<Env>,
+    pub threads: usize,
+}
+
+impl VecEnv {
+    pub fn new(
+        num_envs: usize,
+        history: usize,
+        max_plies: Option<u32>,
+        threads: usize,
+    ) -> Result<Self, String> {
+        if num_envs == 0 {
+            return Err("num_envs must be at least 1".to_string());
+        }
+        let env = Env::new(history, max_plies)?;
+        Ok(VecEnv {
+            envs: vec![env; num_envs],
+            threads: threads.max(1),
+        })
+    }
+
+    /// Start a new episode in every environment from the initial position,
+    /// seeding environment i with `seed + i` if given
+    pub fn reset(&mut self, seed: Option<u64>) {
+        for (i, env) in self.envs.iter_mut().enumerate() {
+            env.reset(State::new(DEFAULT_BOARD, Color::White, true, true, true, true));
+            if let Some(seed) = seed {
+                env.rng = Rng::new(seed.wrapping_add(i as u64));
+            }
+        }
+    }
+
+    /// Play one action (see NUM_ACTIONS) in each environment, returning the
+    /// rewards of the movers. All the actions are checked first: nothing is
+    /// played if one of them is illegal or its environment is done.
+    pub fn step(&mut self, actions: &[usize]) -> Result<Vec<f64>, String> {
+        if actions.len() != self.envs.len() {
+            return Err(format!(
+                "expected {} actions, got {}",
+                self.envs.len(),
+                actions.len()
+            ));
+        }
+        let chunk_size = (self.envs.len() + self.threads - 1) / self.threads;
+        let mut moves: Vec<Result<String, String>> = vec![Ok(String::new()); self.envs.len()];
+        thread::scope(|scope| {
+            for ((envs, actions), moves) in self
+                .envs
+                .chunks(chunk_size)
+                .zip(actions.chunks(chunk_size))
+                .zip(moves.chunks_mut(chunk_size))
+            {
+                scope.spawn(move || {
+                    for ((env, &action), _move) in envs.iter().zip(actions).zip(moves) {
+                        *_move = env.check_action(action);
+                    }
+                });
+            }
+        });
+        let moves = moves
+            .into_iter()
+            .enumerate()
+            .map(|(i, _move)| _move.map_err(|e| format!("env {}: {}", i, e)))
+            .collect::<Result<Vec<String>, String>>()?;
+
+        let mut rewards = vec![0.0; self.envs.len()];
+        thread::scope(|scope| {
+            for ((envs, moves), rewards) in self
+                .envs
+                .chunks_mut(chunk_size)
+                .zip(moves.chunks(chunk_size))
+                .zip(rewards.chunks_mut(chunk_size))
+            {
+                scope.spawn(move || {
+                    for ((env, _move), reward) in envs.iter_mut().zip(moves).zip(rewards) {
+                        // checked above
+                        *reward = env.step(_move).unwrap();
+                    }
+                });
+            }
+        });
+        Ok(rewards)
+    }
+
+    /// Reset the environments whose episode is over, returning their indices
+    pub fn reset_done(&mut self) -> Vec<usize> {
+        let mut reset = vec![];
+        for (i, env) in self.envs.iter_mut().enumerate() {
+            if env.outcome.is_some() {
+                env.reset(State::new(DEFAULT_BOARD, Color::White, true, true, true, true));
+                reset.push(i);
+            }
+        }
+        reset
+    }
+
+    /// Observations of all the environments, one after the other
+    pub fn observations(&self) -> Vec<f32> {
+        let mut observations = Vec::with_capacity(self.envs.len() * self.envs[0].num_planes() * 64);
+        for env in self.envs.iter() {
+            observations.extend(env.observation());
+        }
+        observations
+    }
+}
+
+/// Legal moves of the side to move, promotions being written without their
+/// piece with `auto_queen` ("e7e8") and once per piece otherwise ("e7e8q",
+/// "e7e8r", "e7e8b", "e7e8n")
+pub fn legal_moves(state: &State, auto_queen: bool) -> Vec<String> {
+    if auto_queen {
+        return legal_move_strings(state);
+    }
+    legal_move_strings_all_promotions(state)
+}
+
+/// Legal action of the side to move sampled from the policy `logits` (one
+/// per action, see `num_actions`) with its move string: illegal actions are
+/// masked out and the others drawn with probability softmax(logit /
+/// `temperature`), the most likely one at temperature 0. Legal actions with
+/// a logit of -inf are never drawn, unless all of them have one (uniform).
+pub fn sample_action(
+    logits: &[f64],
+    state: &State,
+    auto_queen: bool,
+    temperature: f64,
+    rng: &mut Rng,
+) -> Result<(usize, String), String> {
+    if logits.len() != num_actions(auto_queen) {
+        return Err(format!(
+            "expected {} logits, got {}",
+            num_actions(auto_queen),
+            logits.len()
+        ));
+    }
+    if temperature.is_nan() || temperature < 0.0 || temperature.is_infinite() {
+        return Err(format!("Invalid temperature {}", temperature));
+    }
+    let moves = legal_moves(state, auto_queen);
+    if moves.is_empty() {
+        return Err("no legal moves".to_string());
+    }
+    let actions: Vec<usize> = moves.iter().map(|_move| move_to_action(_move)).collect();
+    let legal_logits: Vec<f64> = actions.iter().map(|&action| logits[action]).collect();
+    if legal_logits.iter().any(|logit| logit.is_nan() || *logit == f64::INFINITY) {
+        return Err("the logits of legal actions must be finite or -inf".to_string());
+    }
+
+    let max_logit = legal_logits.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+    let best = legal_logits.iter().position(|&logit| logit == max_logit).unwrap();
+    let index = if max_logit == f64::NEG_INFINITY {
+        rng.below(actions.len())
+    } else if temperature == 0.0 {
+        best
+    } else {
+        // softmax shifted by the max logit, so that exp never overflows
+        let weights: Vec<f64> = legal_logits
+            .iter()
+            .map(|logit| ((logit - max_logit) / temperature).exp())
+            .collect();
+        let total: f64 = weights.iter().sum();
+        let mut target = rng.next_f64() * total;
+        // rounding may leave some target past the last weight
+        let mut index = best;
+        for (i, weight) in weights.iter().enumerate() {
+            if target < *weight {
+                index = i;
+                break;
+            }
+            target -= weight;
+        }
+        index
+    };
+    Ok((actions[index], moves[index].clone()))
+}
+
+/// Actions of the from-to encoding: NUM_ACTIONS with `auto_queen`,
+/// NUM_ACTIONS_ALL_PROMOTIONS with the underpromotions otherwise
+pub fn num_actions(auto_queen: bool) -> usize {
+    if auto_queen {
+        return NUM_ACTIONS;
+    }
+    NUM_ACTIONS_ALL_PROMOTIONS
+}
+
+/// Action of a move string (see NUM_ACTIONS and NUM_ACTIONS_ALL_PROMOTIONS)
+pub fn move_to_action(_move: &str) -> usize {
+    let move_struct = convert_move_to_type(_move);
+    let promotion = promotion_of(_move);
+    if let Some(piece) = UNDERPROMOTION_IDS.iter().position(|&id| id == promotion) {
+        let (from, to) = unsafe { move_struct.data.normal_move };
+        let direction = (to.1 - from.1 + 1) as usize;
+        return NUM_ACTIONS + (from.1 as usize * 3 + direction) * 3 + piece;
+    }
+    let (from, to) = if move_struct.is_castle {
+        match unsafe { move_struct.data.castle } {
+            Castle::KingSideWhite => ((7, 4), (7, 6)),
+            Castle::QueenSideWhite => ((7, 4), (7, 2)),
+            Castle::KingSideBlack => ((0, 4), (0, 6)),
+            Castle::QueenSideBlack => ((0, 4), (0, 2)),
+        }
+    } else {
+        unsafe { move_struct.data.normal_move }
+    };
+    (from.0 * 8 + from.1) as usize * 64 + (to.0 * 8 + to.1) as usize
+}
+
+/// Move string of an action in `state` (a king moving two squares castles),
+/// written as in `legal_moves`
+pub fn action_to_move(state: &State, action: usize, auto_queen: bool) -> Result<String, String> {
+    let num_actions = num_actions(auto_queen);
+    if action >= num_actions {
+        return Err(format!(
+            "Invalid action {}: must be below {}",
+            action, num_actions
+        ));
+    }
+    if action >= NUM_ACTIONS {
+        // underpromotion of the side to move
+        let index = action - NUM_ACTIONS;
+        let (from_row, to_row) = match state.current_player {
+            Color::White => (1, 0),
+            Color::Black => (6, 7),
+        };
+        let file = (index / 9) as isize;
+        let to_file = file + (index / 3 % 3) as isize - 1;
+        if !(0..8).contains(&to_file) {
+            return Err(format!("Invalid action {}: captures off the board", action));
+        }
+        let piece = match UNDERPROMOTION_IDS[index % 3] {
+            ROOK_ID => 'r',
+            BISHOP_ID => 'b',
+            _ => 'n',
+        };
+        return Ok(format!(
+            "{}{}",
+            convert_move_to_string(((from_row, file), (to_row, to_file))),
+            piece
+        ));
+    }
+    let from = ((action / 64 / 8) as isize, (action / 64 % 8) as isize);
+    let to = ((action % 64 / 8) as isize, (action % 8) as isize);
+    let piece_id = state.board[from.0 as usize][from.1 as usize];
+    if piece_id.abs() == KING_ID && from.0 == to.0 && (to.1 - from.1).abs() == 2 {
+        let castle = match (piece_id > 0, to.1 > from.1) {
+            (true, true) => Castle::KingSideWhite,
+            (true, false) => Castle::QueenSideWhite,
+            (false, true) => Castle::KingSideBlack,
+            (false, false) => Castle::QueenSideBlack,
+        };
+        return Ok(convert_castle_move_to_string(castle));
+    }
+    let _move = convert_move_to_string((from, to));
+    if !auto_queen && is_promotion(state, &convert_move_to_type(&_move)) {
+        return Ok(format!("{}q", _move));
+    }
+    Ok(_move)
+}
+
+/// Observations (see `Env::observation`) of the positions of `fens`, each
+/// the start of an episode, concatenated. The FENs are split among `threads`
+/// threads; fails at the first invalid one, giving its index.
+pub fn fens_to_planes(fens: &[String], history: usize, threads: usize) -> Result<Vec<f32>, String> {
+    let env = Env::new(history, None)?;
+    let size = env.num_planes() * 64;
+    let mut planes = vec![0.0; fens.len() * size];
+    if fens.is_empty() {
+        return Ok(planes);
+    }
+    let threads = threads.max(1);
+    let chunk_size = (fens.len() + threads - 1) / threads;
+    let mut results: Vec<Result<(), String>> = vec![];
+    thread::scope(|scope| {
+        let handles: Vec<_> = fens
+            .chunks(chunk_size)
+            .zip(planes.chunks_mut(chunk_size * size))
+            .enumerate()
+            .map(|(chunk, (fens, planes))| {
+                let mut env = env.clone();
+                scope.spawn(move || {
+                    for (i, (fen, planes)) in fens.iter().zip(planes.chunks_mut(size)).enumerate() {
+                        env.reset_from_fen(fen)
+                            .map_err(|e| format!("FEN {}: {}", chunk * chunk_size + i, e))?;
+                        planes.copy_from_slice(&env.observation());
+                    }
+                    Ok(())
+                })
+            })
+            .collect();
+        results = handles.into_iter().map(|handle| handle.join().unwrap()).collect();
+    });
+    results.into_iter().collect::<Result<(), String>>()?;
+    Ok(planes)
+}
+
+// HELPER FUNCTIONS
+// ---------------------------------------------------------
+// ---------------------------------------------------------
+
+fn fill_plane(planes: &mut [f32], plane: usize, value: bool) {
+    if value {
+        set_plane(planes, plane, 1.0);
+    }
+}
+
+fn set_plane(planes: &mut [f32], plane: usize, value: f32) {
+    for square in planes[plane * 64..(plane + 1) * 64].iter_mut() {
+        *square = value;
+    }
+}
+
+/// Material of `player` with pawn = 1
+fn material_count(state: &State, player: Color) -> isize {
+    let mut material = 0;
+    for row in state.board.iter() {
+        for &piece_id in row.iter() {
+            if piece_id * player.to_int() > 0 {
+                material += ID_TO_VALUE.get(&piece_id).unwrap();
+            }
+        }
+    }
+    material
+}
+
+/// Pawns of `player` with no opposing pawn ahead of them on their file or the
+/// adjacent files
+fn passed_pawns(state: &State, player: Color) -> isize {
+    let pawn_id = PAWN_ID * player.to_int();
+    let mut count = 0;
+    for row in 0..8 {
+        for col in 0..8 {
+            if state.board[row][col] != pawn_id {
+                continue;
+            }
+            // white pawns move towards row 0
+            let mut ahead = match player {
+                Color::White => 0..row,
+                Color::Black => row + 1..8,
+            };
+            let blocked = ahead.any(|r| {
+                (col.saturating_sub(1)..(col + 2).min(8)).any(|c| state.board[r][c] == -pawn_id)
+            });
+            if !blocked {
+                count += 1;
+            }
+        }
+    }
+    count
+}
@@ -0,0 +1,180 @@
+use std::fs::File;
+use std::io::{self, BufReader, Read};
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::config::EngineConfig;
+use crate::rng::Rng;
+use crate::simd;
+use crate::tt::position_key;
+use crate::{evaluate_with_weights, Color, EvalWeights, State};
+
+//
+// Constants
+//
+const NNUE_MAGIC: &[u8; 4] = b"GCNN";
+const NNUE_VERSION: u32 = 1;
+/// 2 colors (side to move first) x 6 piece types x 64 squares
+const NNUE_INPUTS: usize = 768;
+const NNUE_QA: i32 = 255;
+const NNUE_QB: i32 = 64;
+const NNUE_SCALE: i32 = 400;
+
+/// Scores positions for the search
+pub trait Evaluator: Send + Sync {
+    /// score of `state` from `player`'s point of view, in centipawns
+    fn evaluate(&self, state: &State, player: Color) -> isize;
+
+    /// error raised by the evaluator since the last call, if any
+    /// (the search keeps going with a score of 0 for the failed positions)
+    fn take_error(&self) -> Option<String> {
+        None
+    }
+}
+
+/// Material, pawn advance, center control and mobility with tunable weights
+pub struct Handcrafted {
+    pub weights: EvalWeights,
+}
+
+impl Evaluator for Handcrafted {
+    fn evaluate(&self, state: &State, player: Color) -> isize {
+        evaluate_with_weights(state, player, &self.weights)
+    }
+}
+
+/// NNUE-style network: 768 piece-square inputs -> `hidden` clipped ReLU -> 1,
+/// with quantized i16 weights. The accumulator is recomputed for each position,
+/// with the vectorized kernels of `simd`.
+///
+/// File layout (little endian): magic "GCNN", u32 version, u32 hidden size,
+/// i16 input weights (768 x hidden, input major), i16 hidden biases (hidden),
+/// i16 output weights (hidden), i32 output bias.
+pub struct Nnue {
+    hidden: usize,
+    input_weights: Vec<i16>,
+    hidden_biases: Vec<i16>,
+    output_weights: Vec<i16>,
+    output_bias: i32,
+}
+
+impl Nnue {
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let mut reader = BufReader::new(File::open(path)?);
+        let mut magic = [0u8; 4];
+        reader.read_exact(&mut magic)?;
+        if &magic != NNUE_MAGIC || read_u32(&mut reader)? != NNUE_VERSION {
+            return Err(invalid_data("not a network file"));
+        }
+        let hidden = read_u32(&mut reader)? as usize;
+        if hidden == 0 {
+            return Err(invalid_data("empty hidden layer"));
+        }
+        let input_weights = read_i16s(&mut reader, NNUE_INPUTS * hidden)?;
+        let hidden_biases = read_i16s(&mut reader, hidden)?;
+        let output_weights = read_i16s(&mut reader, hidden)?;
+        let output_bias = read_u32(&mut reader)? as i32;
+        Ok(Self {
+            hidden,
+            input_weights,
+            hidden_biases,
+            output_weights,
+            output_bias,
+        })
+    }
+}
+
+impl Evaluator for Nnue {
+    fn evaluate(&self, state: &State, player: Color) -> isize {
+        let mut accumulator: Vec<i32> = self.hidden_biases.iter().map(|&b| b as i32).collect();
+        for row in 0..8 {
+            for col in 0..8 {
+                let piece = state.board[row][col];
+                if piece == 0 {
+                    continue;
+                }
+                let feature = nnue_feature(piece, row, col, player);
+                let weights = &self.input_weights[feature * self.hidden..(feature + 1) * self.hidden];
+                simd::add_i16s(&mut accumulator, weights);
+            }
+        }
+        let output = simd::clipped_dot(&accumulator, &self.output_weights, NNUE_QA) + self.output_bias as i64;
+        (output * NNUE_SCALE as i64 / (NNUE_QA * NNUE_QB) as i64) as isize
+    }
+}
+
+/// `inner` plus Gaussian noise of standard deviation `sigma` centipawns to
+/// diversify self-play games. The noise is drawn from `seed` and the
+/// position, so a position keeps its score within a search (and across
+/// threads) and the games are reproducible from the seed.
+pub struct NoisyEvaluator {
+    pub inner: Arc<dyn Evaluator>,
+    pub sigma: f64,
+    pub seed: u64,
+}
+
+impl Evaluator for NoisyEvaluator {
+    fn evaluate(&self, state: &State, player: Color) -> isize {
+        // drawn from white's point of view so that both sides see the same noise
+        let noise = Rng::new(self.seed ^ position_key(state)).next_gaussian() * self.sigma;
+        self.inner.evaluate(state, player) + noise.round() as isize * player.to_int()
+    }
+
+    fn take_error(&self) -> Option<String> {
+        self.inner.take_error()
+    }
+}
+
+/// Build the evaluator selected in the config ("handcrafted" or "nnue")
+pub fn from_config(config: &EngineConfig) -> Result<Arc<dyn Evaluator>, String> {
+    match config.evaluator.as_str() {
+        "handcrafted" => Ok(Arc::new(Handcrafted {
+            weights: config.eval,
+        })),
+        "nnue" => {
+            let path = config
+                .nnue_path
+                .as_ref()
+                .ok_or("the nnue evaluator needs 'engine.nnue_path'")?;
+            let nnue = Nnue::load(Path::new(path)).map_err(|e| format!("{}: {}", path, e))?;
+            Ok(Arc::new(nnue))
+        }
+        other => Err(format!("unknown evaluator '{}'", other)),
+    }
+}
+
+// HELPER FUNCTIONS
+// ---------------------------------------------------------
+// ---------------------------------------------------------
+
+/// input index of a piece, seen from `player` (own pieces first, board
+/// mirrored for black so that the network always plays "up" the board)
+fn nnue_feature(piece: isize, row: usize, col: usize, player: Color) -> usize {
+    let own = (piece > 0) == (player == Color::White);
+    let piece_type = (6 - piece.abs()) as usize; // P N B R Q K
+    let rank = match player {
+        Color::White => 7 - row,
+        Color::Black => row,
+    };
+    let color_offset = if own { 0 } else { 6 };
+    (color_offset + piece_type) * 64 + rank * 8 + col
+}
+
+fn read_u32(reader: &mut impl Read) -> io::Result<u32> {
+    let mut bytes = [0u8; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_i16s(reader: &mut impl Read, count: usize) -> io::Result<Vec<i16>> {
+    let mut bytes = vec![0u8; count * 2];
+    reader.read_exact(&mut bytes)?;
+    Ok(bytes
+        .chunks_exact(2)
+        .map(|b| i16::from_le_bytes([b[0], b[1]]))
+        .collect())
+}
+
+fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_string())
+}
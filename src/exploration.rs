@@ -0,0 +1,67 @@
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+
+use crate::search::Searcher;
+use crate::tt::TranspositionTable;
+use crate::{legal_move_strings, State};
+
+//
+// Constants
+//
+pub const DEFAULT_EXPLORATION_DEPTH: u32 = 3;
+/// the signals only need shallow searches
+const EXPLORATION_HASH_MB: usize = 1;
+/// search scores are capped to this many centipawns so that a mate found at
+/// one depth does not dwarf the uncertainty of the others
+const SCORE_CAP: isize = 2000;
+
+//
+// Structs
+//
+/// Search quantities of a position hinting at how much is left to explore
+/// there, for exploration bonuses
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ExplorationSignals {
+    /// log of the number of legal moves (0 without moves)
+    pub branching: f64,
+    /// standard deviation in pawns of the search scores at depths 1 to
+    /// `depth`: how much the evaluation changes as the search looks deeper
+    pub uncertainty: f64,
+}
+
+/// Exploration signals of the side to move of `state`, searching to `depth`
+/// with the default evaluation (the uncertainty is 0 without `depth`)
+pub fn exploration_signals(state: &State, depth: u32) -> ExplorationSignals {
+    let moves = legal_move_strings(state).len();
+    if moves == 0 {
+        return ExplorationSignals {
+            branching: 0.0,
+            uncertainty: 0.0,
+        };
+    }
+    let mut scores: Vec<f64> = vec![];
+    if depth > 0 {
+        let mut tt = TranspositionTable::new(EXPLORATION_HASH_MB);
+        let mut searcher = Searcher::new(Arc::new(AtomicBool::new(false)), &mut tt);
+        searcher.iterative_deepening(state, state.current_player, depth, None, |info| {
+            scores.push(info.score.clamp(-SCORE_CAP, SCORE_CAP) as f64 / 100.0);
+        });
+    }
+    ExplorationSignals {
+        branching: (moves as f64).ln(),
+        uncertainty: standard_deviation(&scores),
+    }
+}
+
+// HELPER FUNCTIONS
+// ---------------------------------------------------------
+// ---------------------------------------------------------
+
+fn standard_deviation(values: &[f64]) -> f64 {
+    if values.len() < 2 {
+        return 0.0;
+    }
+    let mean = values.iter().sum::<f64>() / values.len() as f64;
+    let variance = values.iter().map(|value| (value - mean).powi(2)).sum::<f64>() / values.len() as f64;
+    variance.sqrt()
+}
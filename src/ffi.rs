@@ -0,0 +1,251 @@
+//! C interface for embedding the engine in non-Python hosts, built with
+//! `cargo build --release --no-default-features --features ffi`.
+//! The matching declarations are in `include/gym_chess.h`.
+//!
+//! An engine handle owns a position and a transposition table. Strings are
+//! NUL-terminated UTF-8: inputs are borrowed for the duration of the call, and
+//! outputs are owned by the engine and stay valid until the next call on the
+//! same handle. Functions returning `int` return 0 on success and -1 on error;
+//! `gc_engine_output` then holds the error message. Moves are in UCI notation
+//! ("e1g1" castles, "e7e8n" underpromotes). No call unwinds into the host:
+//! an engine panic is returned as an error.
+use std::any::Any;
+use std::ffi::{CStr, CString};
+use std::os::raw::{c_char, c_int};
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::search::{Searcher, TimeBudget};
+use crate::tt::TranspositionTable;
+use crate::{
+    from_fen, legal_move_strings_uci, make_uci_move_str, move_to_uci, try_to_fen, validate_position,
+    State, START_FEN,
+};
+
+pub struct Engine {
+    state: State,
+    tt: TranspositionTable,
+    output: CString,
+}
+
+impl Engine {
+    fn set_output(&mut self, result: Result<String, String>) -> c_int {
+        let (code, text) = match result {
+            Ok(text) => (0, text),
+            Err(text) => (-1, text),
+        };
+        // interior NULs cannot come from the engine, but never panic across the boundary
+        self.output = CString::new(text.replace('\0', "")).unwrap_or_default();
+        code
+    }
+}
+
+/// Run `f` on `engine` and set its output, a panic becoming an error
+unsafe fn run(engine: *mut Engine, f: impl FnOnce(&mut Engine) -> Result<String, String>) -> c_int {
+    let engine = &mut *engine;
+    let result = panic::catch_unwind(AssertUnwindSafe(|| f(&mut *engine)));
+    engine.set_output(result.unwrap_or_else(|payload| Err(panic_message(payload))))
+}
+
+fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    let message = if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else {
+        "unknown panic".to_string()
+    };
+    format!("internal engine error: {}", message)
+}
+
+unsafe fn read_str<'a>(ptr: *const c_char) -> Result<&'a str, String> {
+    if ptr.is_null() {
+        return Err("null string".to_string());
+    }
+    CStr::from_ptr(ptr).to_str().map_err(|e| e.to_string())
+}
+
+/// Create an engine set up at the start position, or NULL on failure
+#[no_mangle]
+pub extern "C" fn gc_engine_new(hash_mb: usize) -> *mut Engine {
+    let engine = panic::catch_unwind(|| {
+        from_fen(START_FEN).map(|state| Engine {
+            state,
+            tt: TranspositionTable::new(hash_mb),
+            output: CString::default(),
+        })
+    });
+    match engine {
+        Ok(Ok(engine)) => Box::into_raw(Box::new(engine)),
+        _ => ptr::null_mut(),
+    }
+}
+
+/// # Safety
+/// `engine` must come from `gc_engine_new` (or be NULL) and not be used afterwards
+#[no_mangle]
+pub unsafe extern "C" fn gc_engine_free(engine: *mut Engine) {
+    if !engine.is_null() {
+        // a panicking drop is not reported: the handle is gone either way
+        let _ = panic::catch_unwind(AssertUnwindSafe(|| drop(Box::from_raw(engine))));
+    }
+}
+
+/// Output of the last call on `engine`
+///
+/// # Safety
+/// `engine` must be a live handle
+#[no_mangle]
+pub unsafe extern "C" fn gc_engine_output(engine: *const Engine) -> *const c_char {
+    (*engine).output.as_ptr()
+}
+
+/// Set the position from a FEN string, which must pass `validate_position`.
+/// Output: empty
+///
+/// # Safety
+/// `engine` must be a live handle and `fen` a NUL-terminated string
+#[no_mangle]
+pub unsafe extern "C" fn gc_engine_set_position(engine: *mut Engine, fen: *const c_char) -> c_int {
+    run(engine, |engine| {
+        let state = from_fen(read_str(fen)?)?;
+        let problems = validate_position(&state);
+        if !problems.is_empty() {
+            return Err(problems.join("; "));
+        }
+        engine.state = state;
+        Ok(String::new())
+    })
+}
+
+/// Output: FEN of the current position
+///
+/// # Safety
+/// `engine` must be a live handle
+#[no_mangle]
+pub unsafe extern "C" fn gc_engine_get_fen(engine: *mut Engine) -> c_int {
+    run(engine, |engine| try_to_fen(engine.state))
+}
+
+/// Output: space separated legal moves ("e2e4 g1f3 ... e1g1 ... e7e8q e7e8r e7e8b e7e8n")
+///
+/// # Safety
+/// `engine` must be a live handle
+#[no_mangle]
+pub unsafe extern "C" fn gc_engine_legal_moves(engine: *mut Engine) -> c_int {
+    run(engine, |engine| Ok(legal_move_strings_uci(&engine.state).join(" ")))
+}
+
+/// Play a legal UCI move on the current position (a promotion without its
+/// piece promotes to a queen). Output: FEN after the move
+///
+/// # Safety
+/// `engine` must be a live handle and `move_str` a NUL-terminated string
+#[no_mangle]
+pub unsafe extern "C" fn gc_engine_make_move(engine: *mut Engine, move_str: *const c_char) -> c_int {
+    run(engine, |engine| {
+        let state = make_uci_move_str(&engine.state, read_str(move_str)?)?;
+        let fen = try_to_fen(state)?;
+        engine.state = state;
+        Ok(fen)
+    })
+}
+
+/// Search the current position up to `depth` plies (at least 1), stopping after
+/// `movetime_ms` milliseconds when it is not 0.
+/// Output: "<best move> <score>" (best move is empty when there is no legal move)
+///
+/// # Safety
+/// `engine` must be a live handle
+#[no_mangle]
+pub unsafe extern "C" fn gc_engine_search(engine: *mut Engine, depth: u32, movetime_ms: u64) -> c_int {
+    run(engine, |engine| Ok(search(engine, depth, movetime_ms)))
+}
+
+fn search(engine: &mut Engine, depth: u32, movetime_ms: u64) -> String {
+    let movetime = if movetime_ms > 0 {
+        Some(Duration::from_millis(movetime_ms))
+    } else {
+        None
+    };
+    let state = engine.state;
+    let info = {
+        let mut searcher = Searcher::new(Arc::new(AtomicBool::new(false)), &mut engine.tt);
+        searcher.iterative_deepening(
+            &state,
+            state.current_player,
+            depth.max(1),
+            movetime.map(TimeBudget::fixed),
+            |_| {},
+        )
+    };
+    match info {
+        Some(info) => {
+            let best_move = info.pv.first().map(|_move| move_to_uci(&state, _move));
+            format!("{} {}", best_move.unwrap_or_default(), info.score)
+        }
+        None => "".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    unsafe fn call(engine: *mut Engine, code: c_int) -> (c_int, String) {
+        let output = CStr::from_ptr(gc_engine_output(engine)).to_str().unwrap().to_string();
+        (code, output)
+    }
+
+    unsafe fn set_position(engine: *mut Engine, fen: &str) -> (c_int, String) {
+        let fen = CString::new(fen).unwrap();
+        call(engine, gc_engine_set_position(engine, fen.as_ptr()))
+    }
+
+    unsafe fn make_move(engine: *mut Engine, _move: &str) -> (c_int, String) {
+        let _move = CString::new(_move).unwrap();
+        call(engine, gc_engine_make_move(engine, _move.as_ptr()))
+    }
+
+    #[test]
+    fn positions_are_validated() {
+        unsafe {
+            let engine = gc_engine_new(1);
+            assert_eq!(set_position(engine, "4k3/8/8/8/8/8/8/4K3 w - - 0 1").0, 0);
+            let (code, output) = set_position(engine, "4k3/8/8/8/8/8/8/4KK2 w - - 0 1");
+            assert_eq!(code, -1);
+            assert!(!output.is_empty());
+            assert_eq!(call(engine, gc_engine_get_fen(engine)).1, "4k3/8/8/8/8/8/8/4K3 w - - 0 1");
+            assert_eq!(set_position(engine, "not a fen").0, -1);
+            gc_engine_free(engine);
+        }
+    }
+
+    #[test]
+    fn moves_are_uci() {
+        unsafe {
+            let engine = gc_engine_new(1);
+            set_position(engine, "4k3/1P6/8/8/8/8/8/R3K3 w Q - 0 1");
+            let (_, moves) = call(engine, gc_engine_legal_moves(engine));
+            let moves: Vec<&str> = moves.split(' ').collect();
+            for _move in ["e1c1", "b7b8q", "b7b8r", "b7b8b", "b7b8n"].iter() {
+                assert!(moves.contains(_move), "{} not in {:?}", _move, moves);
+            }
+            assert!(!moves.iter().any(|_move| _move.starts_with("CASTLE")));
+
+            assert_eq!(make_move(engine, "e1c1"), (0, "4k3/1P6/8/8/8/8/8/2KR4 b - - 1 1".to_string()));
+            set_position(engine, "4k3/1P6/8/8/8/8/8/R3K3 w Q - 0 1");
+            assert_eq!(make_move(engine, "b7b8n"), (0, "1N2k3/8/8/8/8/8/8/R3K3 b Q - 0 1".to_string()));
+            assert_eq!(make_move(engine, "e1g1").0, -1);
+
+            set_position(engine, "4k3/1P6/8/8/8/8/8/4K3 w - - 0 1");
+            let (code, output) = call(engine, gc_engine_search(engine, 2, 0));
+            assert_eq!(code, 0);
+            assert!(output.starts_with("b7b8q "), "{}", output);
+            gc_engine_free(engine);
+        }
+    }
+}
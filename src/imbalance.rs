@@ -0,0 +1,216 @@
+use crate::rng::Rng;
+use crate::selfplay::all_moves;
+use crate::{
+    next_state, Board, Color, State, BISHOP_ID, DEFAULT_BOARD, KNIGHT_ID, PAWN_ID, QUEEN_ID,
+    ROOK_ID,
+};
+
+//
+// Constants
+//
+/// random games played per requested position before giving up
+const GAMES_PER_POSITION: usize = 10;
+/// the first plies of the random games are too close to the initial position
+const MIN_SAMPLE_PLY: usize = 6;
+const MAX_GAME_PLIES: usize = 200;
+
+//
+// Structs
+//
+/// Material balance of a position, counting queens, rooks, minor pieces
+/// (bishops and knights alike) and pawns
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Imbalance {
+    /// same pieces and pawns
+    Equal,
+    /// same pieces, one side has more pawns
+    PawnUp,
+    /// one side has an extra bishop or knight
+    MinorUp,
+    /// a rook against a minor piece
+    ExchangeUp,
+    /// one side has an extra rook
+    RookUp,
+    /// one side has an extra queen (queen odds)
+    QueenUp,
+    /// any other difference (two minors against a rook, a queen against two
+    /// rooks, ...)
+    Other,
+}
+
+impl Imbalance {
+    pub const ALL: [Imbalance; 7] = [
+        Imbalance::Equal,
+        Imbalance::PawnUp,
+        Imbalance::MinorUp,
+        Imbalance::ExchangeUp,
+        Imbalance::RookUp,
+        Imbalance::QueenUp,
+        Imbalance::Other,
+    ];
+
+    pub fn from_name(name: &str) -> Result<Self, String> {
+        Imbalance::ALL
+            .iter()
+            .find(|imbalance| imbalance.name() == name)
+            .copied()
+            .ok_or(format!(
+                "Invalid imbalance '{}': expected one of {:?}",
+                name,
+                Imbalance::ALL
+                    .iter()
+                    .map(|imbalance| imbalance.name())
+                    .collect::<Vec<_>>()
+            ))
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Imbalance::Equal => "equal",
+            Imbalance::PawnUp => "pawn_up",
+            Imbalance::MinorUp => "minor_up",
+            Imbalance::ExchangeUp => "exchange_up",
+            Imbalance::RookUp => "rook_up",
+            Imbalance::QueenUp => "queen_up",
+            Imbalance::Other => "other",
+        }
+    }
+}
+
+/// Imbalance of `board` and the side it favours (None when equal). Pieces
+/// decide the class before pawns: a side a minor piece up is MinorUp whatever
+/// the pawns. The side ahead is the one with the extra piece, the rook for an
+/// exchange, or the most material for Other.
+pub fn classify_imbalance(board: &Board) -> (Imbalance, Option<Color>) {
+    // white minus black for queens, rooks, minor pieces and pawns
+    let mut diff = [0isize; 4];
+    let mut material = 0;
+    for row in board.iter() {
+        for &piece in row.iter() {
+            let (index, value) = match piece.abs() {
+                QUEEN_ID => (0, 9),
+                ROOK_ID => (1, 5),
+                BISHOP_ID | KNIGHT_ID => (2, 3),
+                PAWN_ID => (3, 1),
+                _ => continue,
+            };
+            diff[index] += piece.signum();
+            material += piece.signum() * value;
+        }
+    }
+    let side = |count: isize| match count.signum() {
+        1 => Some(Color::White),
+        -1 => Some(Color::Black),
+        _ => None,
+    };
+    
+    match diff {
+        [0, 0, 0, 0] => (Imbalance::Equal, None),
+        [0, 0, 0, pawns] => (Imbalance::PawnUp, side(pawns)),
+        [0, 0, minors, _] if minors.abs() == 1 => (Imbalance::MinorUp, side(minors)),
+        [0, rooks, minors, _] if rooks.abs() == 1 && minors == -rooks => {
+            (Imbalance::ExchangeUp, side(rooks))
+        }
+        [0, rooks, 0, _] if rooks.abs() == 1 => (Imbalance::RookUp, side(rooks)),
+        [queens, 0, 0, _] if queens.abs() == 1 => (Imbalance::QueenUp, side(queens)),
+        _ => (Imbalance::Other, side(material)),
+    }
+}
+
+/// Keep at most `n_per_class` of `positions` for each of the requested
+/// `classes`, in their order, stopping once every class is full. Returns the
+/// kept positions with their imbalance and favoured side.
+pub fn filter_by_imbalance<I: IntoIterator<Item = State>>(
+    positions: I,
+    classes: &[Imbalance],
+    n_per_class: usize,
+) -> Vec<(State, Imbalance, Option<Color>)> {
+    let mut sampler = Sampler::new(classes, n_per_class);
+    for state in positions {
+        if sampler.is_full() {
+            break;
+        }
+        sampler.offer(state);
+    }
+    sampler.samples
+}
+
+/// Sample up to `n_per_class` positions of each of the requested `classes`
+/// from random games, at most one position per class and game so that the
+/// samples of a class differ. Fewer positions are returned for a class if
+/// none are found within GAMES_PER_POSITION games per requested position.
+pub fn sample_imbalanced_positions(
+    classes: &[Imbalance],
+    n_per_class: usize,
+    rng: &mut Rng,
+) -> Vec<(State, Imbalance, Option<Color>)> {
+    let mut sampler = Sampler::new(classes, n_per_class);
+    for _ in 0..classes.len() * n_per_class * GAMES_PER_POSITION {
+        if sampler.is_full() {
+            break;
+        }
+        let mut state = State::new(DEFAULT_BOARD, Color::White, true, true, true, true);
+        let mut sampled: Vec<Imbalance> = vec![];
+        for ply in 0..MAX_GAME_PLIES {
+            let moves = all_moves(&state, state.current_player);
+            if moves.is_empty() {
+                break;
+            }
+            if ply >= MIN_SAMPLE_PLY {
+                let (imbalance, _) = classify_imbalance(&state.board);
+                if !sampled.contains(&imbalance) && sampler.offer(state) {
+                    sampled.push(imbalance);
+                }
+            }
+            let _move = moves[rng.below(moves.len())].clone();
+            state = next_state(&state, state.current_player, _move).0;
+        }
+    }
+    sampler.samples
+}
+
+// HELPER FUNCTIONS
+// ---------------------------------------------------------
+// ---------------------------------------------------------
+
+/// Positions kept so far and how many each class still needs
+struct Sampler {
+    samples: Vec<(State, Imbalance, Option<Color>)>,
+    needed: Vec<(Imbalance, usize)>,
+}
+
+impl Sampler {
+    fn new(classes: &[Imbalance], n_per_class: usize) -> Self {
+        let mut needed: Vec<(Imbalance, usize)> = vec![];
+        for &imbalance in classes.iter() {
+            if !needed.iter().any(|(class, _)| *class == imbalance) {
+                needed.push((imbalance, n_per_class));
+            }
+        }
+        Sampler {
+            samples: vec![],
+            needed,
+        }
+    }
+
+    fn is_full(&self) -> bool {
+        self.needed.iter().all(|(_, n)| *n == 0)
+    }
+
+    /// Keep `state` if its class still needs positions
+    fn offer(&mut self, state: State) -> bool {
+        let (imbalance, ahead) = classify_imbalance(&state.board);
+        match self
+            .needed
+            .iter_mut()
+            .find(|(class, n)| *class == imbalance && *n > 0)
+        {
+            Some((_, n)) => {
+                *n -= 1;
+                self.samples.push((state, imbalance, ahead));
+                true
+            }
+            None => false,
+        }
+    }
+}
@@ -0,0 +1,91 @@
+use crate::tactics::attackers;
+use crate::{get_other_player, Board, Color, Square, KING_ID, PAWN_ID};
+
+//
+// Constants
+//
+/// weight of an attacker of the king zone, indexed by piece id (K Q R B N P)
+const ATTACKER_WEIGHTS: [i32; 7] = [0, 0, 5, 3, 2, 2, 1];
+
+//
+// Structs
+//
+/// King safety sub-scores of one side, as raw features (not centipawns)
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct KingSafety {
+    /// own pawns on the king's file and its neighbours, one or two ranks in
+    /// front of the king (0 to 6)
+    pub pawn_shield: i32,
+    /// opponent pieces attacking the king or a square next to it, each counted
+    /// once and weighted by type: queen 5, rook 3, minor piece 2, pawn 1
+    pub attacker_weight: i32,
+    /// files among the king's file and its neighbours without an own pawn
+    /// (0 to 3)
+    pub open_files: i32,
+}
+
+/// King safety of `player` on `board` (None without its king)
+pub fn king_safety(board: &Board, player: Color) -> Option<KingSafety> {
+    let king = find_king(board, player)?;
+    let sign = match player {
+        Color::White => 1,
+        Color::Black => -1,
+    };
+    // white pawns move towards row 0
+    let forward = -sign;
+
+    let mut pawn_shield = 0;
+    let mut open_files = 0;
+    for col in (king.1 - 1).max(0)..=(king.1 + 1).min(7) {
+        for distance in 1..=2 {
+            let row = king.0 + forward * distance;
+            if (0..8).contains(&row) && board[row as usize][col as usize] == sign * PAWN_ID {
+                pawn_shield += 1;
+            }
+        }
+        if !(0..8).any(|row| board[row][col as usize] == sign * PAWN_ID) {
+            open_files += 1;
+        }
+    }
+
+    let other_player = get_other_player(player);
+    let mut zone_attackers: Vec<Square> = vec![];
+    for row in (king.0 - 1).max(0)..=(king.0 + 1).min(7) {
+        for col in (king.1 - 1).max(0)..=(king.1 + 1).min(7) {
+            for attacker in attackers(board, (row, col), other_player) {
+                if !zone_attackers.contains(&attacker) {
+                    zone_attackers.push(attacker);
+                }
+            }
+        }
+    }
+    let attacker_weight = zone_attackers
+        .iter()
+        .map(|&(row, col)| ATTACKER_WEIGHTS[board[row as usize][col as usize].unsigned_abs()])
+        .sum();
+
+    Some(KingSafety {
+        pawn_shield,
+        attacker_weight,
+        open_files,
+    })
+}
+
+// HELPER FUNCTIONS
+// ---------------------------------------------------------
+// ---------------------------------------------------------
+
+fn find_king(board: &Board, player: Color) -> Option<Square> {
+    let king = match player {
+        Color::White => KING_ID,
+        Color::Black => -KING_ID,
+    };
+    for (row, pieces) in board.iter().enumerate() {
+        for (col, &piece_id) in pieces.iter().enumerate() {
+            if piece_id == king {
+                return Some((row as isize, col as isize));
+            }
+        }
+    }
+    None
+}
@@ -0,0 +1,239 @@
+use std::collections::HashMap;
+
+use crate::{
+    _get_pseudo_legal_moves, convert_square_to_string, get_other_player,
+    get_squares_under_attack_by_player, is_legal_move_str, is_promotion, king_is_checked,
+    parse_move, player_enum_to_string, square_tuple_to_flat, Castle, Color, Square, State,
+    BISHOP_ID, KING_ID, PAWN_ID, QUEEN_ID, ROOK_ID,
+};
+
+//
+// Constants
+//
+/// piece names indexed by piece id (K Q R B N P)
+const PIECE_NAMES: [&str; 7] = ["", "king", "queen", "rook", "bishop", "knight", "pawn"];
+
+/// Why `_move` (a move string, see `parse_move`) can't be played by the side
+/// to move of `state`, in words for a teaching UI: wrong side to move, the
+/// piece can't move that way, the move leaves the king in check, castling out
+/// of, through or into check, ... None if the move is legal.
+pub fn why_illegal(state: &State, _move: &str) -> Option<String> {
+    if is_legal_move_str(state, _move) {
+        return None;
+    }
+    let move_struct = match parse_move(_move) {
+        Ok(move_struct) => move_struct,
+        Err(e) => return Some(e),
+    };
+    let player = state.current_player;
+    let other_attacks = get_squares_under_attack_by_player(state, get_other_player(player));
+    let reason = if move_struct.is_castle {
+        let castle = unsafe { move_struct.data.castle };
+        castle_reason(state, player, castle, &other_attacks)
+    } else {
+        let (_from, _to) = unsafe { move_struct.data.normal_move };
+        let promotes = move_struct.promotion.is_some();
+        if promotes && !is_promotion(state, &move_struct) {
+            Some("only a pawn reaching the last rank can promote".to_string())
+        } else {
+            move_reason(state, player, (_from, _to), &other_attacks)
+        }
+    };
+    Some(reason.unwrap_or_else(|| "it is not a legal move in this position".to_string()))
+}
+
+// HELPER FUNCTIONS
+// ---------------------------------------------------------
+// ---------------------------------------------------------
+
+fn move_reason(
+    state: &State,
+    player: Color,
+    (_from, _to): (Square, Square),
+    other_attacks: &HashMap<usize, bool>,
+) -> Option<String> {
+    let from_str = convert_square_to_string(_from);
+    let to_str = convert_square_to_string(_to);
+    let piece_id = state.board[_from.0 as usize][_from.1 as usize];
+    if piece_id == 0 {
+        return Some(format!("there is no piece on {}", from_str));
+    }
+    let name = PIECE_NAMES[piece_id.unsigned_abs()];
+    let piece_player = if piece_id > 0 {
+        Color::White
+    } else {
+        Color::Black
+    };
+    if piece_player != player {
+        return Some(format!(
+            "wrong side to move: the {} on {} is {} and {} is to move",
+            name,
+            from_str,
+            side_name(piece_player),
+            side_name(player)
+        ));
+    }
+
+    let is_pseudo_legal = _get_pseudo_legal_moves(state, player, other_attacks)
+        .iter()
+        .any(|&(from, to)| from == _from && to == _to);
+    if is_pseudo_legal {
+        // only moves leaving the king checked are filtered out
+        if king_is_checked(state, player) {
+            return Some(format!("it leaves the {} king in check", side_name(player)));
+        }
+        return Some(format!(
+            "the {} on {} is pinned: moving it exposes the {} king to check",
+            name,
+            from_str,
+            side_name(player)
+        ));
+    }
+
+    let target = state.board[_to.0 as usize][_to.1 as usize];
+    if target * piece_id > 0 {
+        return Some(format!(
+            "{} is occupied by a {} piece",
+            to_str,
+            side_name(player)
+        ));
+    }
+    if target.abs() == KING_ID {
+        return Some("the king can't be captured".to_string());
+    }
+    if piece_id.abs() == KING_ID {
+        let home_row = if player == Color::White { 7 } else { 0 };
+        if _from == (home_row, 4) && _to.0 == home_row && (_to.1 - _from.1).abs() == 2 {
+            let castle = match (player, _to.1) {
+                (Color::White, 6) => Castle::KingSideWhite,
+                (Color::White, _) => Castle::QueenSideWhite,
+                (Color::Black, 6) => Castle::KingSideBlack,
+                (Color::Black, _) => Castle::QueenSideBlack,
+            };
+            return castle_reason(state, player, castle, other_attacks)
+                .or_else(|| Some(format!("castling is written {}", castle.to_str())));
+        }
+        let is_step = (_to.0 - _from.0).abs() <= 1 && (_to.1 - _from.1).abs() <= 1;
+        if is_step && other_attacks.contains_key(&square_tuple_to_flat(_to)) {
+            return Some(format!("the king can't move into check on {}", to_str));
+        }
+    }
+    if let Some(blocker) = first_blocker(state, _from, _to) {
+        return Some(format!(
+            "the {} on {} is blocked by the piece on {}",
+            name,
+            from_str,
+            convert_square_to_string(blocker)
+        ));
+    }
+    Some(format!(
+        "a {} can't move from {} to {}",
+        name, from_str, to_str
+    ))
+}
+
+/// first occupied square strictly between `_from` and `_to` when they share a
+/// line the piece on `_from` slides along (pawn steps included)
+fn first_blocker(state: &State, _from: Square, _to: Square) -> Option<Square> {
+    let piece_id = state.board[_from.0 as usize][_from.1 as usize].abs();
+    let (d_row, d_col) = (_to.0 - _from.0, _to.1 - _from.1);
+    let straight = d_row == 0 || d_col == 0;
+    let diagonal = d_row.abs() == d_col.abs();
+    let slides = match piece_id {
+        QUEEN_ID => straight || diagonal,
+        ROOK_ID => straight,
+        BISHOP_ID => diagonal,
+        PAWN_ID => d_col == 0 && d_row.abs() == 2,
+        _ => false,
+    };
+    if !slides {
+        return None;
+    }
+    let step = (d_row.signum(), d_col.signum());
+    let mut square = (_from.0 + step.0, _from.1 + step.1);
+    while square != _to {
+        if state.board[square.0 as usize][square.1 as usize] != 0 {
+            return Some(square);
+        }
+        square = (square.0 + step.0, square.1 + step.1);
+    }
+    None
+}
+
+fn castle_reason(
+    state: &State,
+    player: Color,
+    castle: Castle,
+    other_attacks: &HashMap<usize, bool>,
+) -> Option<String> {
+    let (castle_player, king_side, right) = match castle {
+        Castle::KingSideWhite => (Color::White, true, state.white_king_castle_is_possible),
+        Castle::QueenSideWhite => (Color::White, false, state.white_queen_castle_is_possible),
+        Castle::KingSideBlack => (Color::Black, true, state.black_king_castle_is_possible),
+        Castle::QueenSideBlack => (Color::Black, false, state.black_queen_castle_is_possible),
+    };
+    if castle_player != player {
+        return Some(format!(
+            "wrong side to move: {} can't castle when {} is to move",
+            side_name(castle_player),
+            side_name(player)
+        ));
+    }
+    let side = if king_side { "king side" } else { "queen side" };
+    if !right {
+        return Some(format!(
+            "{} has lost the right to castle {}",
+            side_name(player),
+            side
+        ));
+    }
+
+    let row = if player == Color::White { 7 } else { 0 };
+    let sign = player.to_int();
+    let (rook_col, between, king_path): (isize, &[isize], [isize; 2]) = if king_side {
+        (7, &[5, 6], [5, 6])
+    } else {
+        (0, &[1, 2, 3], [3, 2])
+    };
+    if state.board[row as usize][4] != KING_ID * sign {
+        return Some(format!(
+            "the king is not on {}",
+            convert_square_to_string((row, 4))
+        ));
+    }
+    if state.board[row as usize][rook_col as usize] != ROOK_ID * sign {
+        return Some(format!(
+            "the rook is not on {}",
+            convert_square_to_string((row, rook_col))
+        ));
+    }
+    for &col in between.iter() {
+        if state.board[row as usize][col as usize] != 0 {
+            return Some(format!(
+                "{} between the king and the rook is not empty",
+                convert_square_to_string((row, col))
+            ));
+        }
+    }
+    let attacked = |col: isize| other_attacks.contains_key(&square_tuple_to_flat((row, col)));
+    if attacked(4) {
+        return Some("the king can't castle out of check".to_string());
+    }
+    if attacked(king_path[0]) {
+        return Some(format!(
+            "the king can't castle through check: {} is attacked",
+            convert_square_to_string((row, king_path[0]))
+        ));
+    }
+    if attacked(king_path[1]) {
+        return Some(format!(
+            "the king can't castle into check: {} is attacked",
+            convert_square_to_string((row, king_path[1]))
+        ));
+    }
+    None
+}
+
+fn side_name(player: Color) -> String {
+    player_enum_to_string(&player).to_lowercase()
+}
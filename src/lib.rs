@@ -1,14 +1,64 @@
+// most of the engine is only called by the Python bindings
+#![cfg_attr(not(feature = "python"), allow(dead_code))]
+
 use lazy_static::lazy_static;
 
-use pyo3::exceptions::{ModuleNotFoundError, PyException};
-use pyo3::prelude::*;
-use pyo3::types::{PyDict, PyTuple};
+use std::cell::RefCell;
 use std::collections::HashMap;
-use std::fs::File;
-use std::io::{self, prelude::*, BufReader};
-use std::io::Result;
-use std::path::Path;
-use std::process::Command;
+
+use crate::endgame::pawn_race;
+use crate::tactics::attack_defense_balance;
+use crate::render::RenderOptions;
+
+mod ablation;
+mod arena;
+mod bench;
+mod book;
+mod config;
+mod data_files;
+mod difficulty;
+mod draw;
+mod disagreement;
+mod endgame;
+mod env;
+mod eval;
+mod exploration;
+#[cfg(feature = "ffi")]
+mod ffi;
+mod imbalance;
+mod json;
+mod king_safety;
+mod legality;
+mod mate;
+mod memory;
+mod move_cache;
+mod odds;
+mod openings;
+mod pgn;
+mod policies;
+mod positions;
+mod puzzles;
+pub mod prelude;
+#[cfg(feature = "python")]
+mod python;
+mod rating;
+mod render;
+mod report;
+mod rng;
+mod rules;
+mod san;
+mod search;
+mod selfplay;
+mod simd;
+mod small_board;
+mod svg;
+mod tactics;
+mod telemetry;
+mod tt;
+mod uci_client;
+#[cfg(feature = "wasm")]
+mod wasm;
+mod zobrist;
 
 //
 // Constants
@@ -20,6 +70,8 @@ pub const ROOK_ID: isize = 3;
 pub const BISHOP_ID: isize = 4;
 pub const KNIGHT_ID: isize = 5;
 pub const PAWN_ID: isize = 6;
+/// pieces a pawn may promote to, a queen unless another one is asked for
+pub const PROMOTION_IDS: [isize; 4] = [QUEEN_ID, ROOK_ID, BISHOP_ID, KNIGHT_ID];
 
 const CONVERT_PAWN_TO_QUEEN_REWARD: isize = 10;
 const PAWN_VALUE: isize = 1;
@@ -28,21 +80,31 @@ const BISHOP_VALUE: isize = 3;
 const ROOK_VALUE: isize = 5;
 const QUEEN_VALUE: isize = 10;
 const KING_VALUE: isize = 0;
+/// centipawns a won pawn race is worth less per move still needed to queen
+const PAWN_RACE_MOVE_PENALTY: i32 = 10;
 // const WIN_REWARD: isize = 100;
 // const LOSS_REWARD: isize = -100;
 
-const KING_DESC: &str = &"K";
-const QUEEN_DESC: &str = &"Q";
-const ROOK_DESC: &str = &"R";
-const BISHOP_DESC: &str = &"B";
-const KNIGHT_DESC: &str = &"N";
-const PAWN_DESC: &str = &" ";
+const KING_DESC: &str = "K";
+const QUEEN_DESC: &str = "Q";
+const ROOK_DESC: &str = "R";
+const BISHOP_DESC: &str = "B";
+const KNIGHT_DESC: &str = "N";
+const PAWN_DESC: &str = " ";
 
 const CASTLE_KING_SIDE_WHITE: &str = "CASTLE_KING_SIDE_WHITE";
 const CASTLE_QUEEN_SIDE_WHITE: &str = "CASTLE_QUEEN_SIDE_WHITE";
 const CASTLE_KING_SIDE_BLACK: &str = "CASTLE_KING_SIDE_BLACK";
 const CASTLE_QUEEN_SIDE_BLACK: &str = "CASTLE_QUEEN_SIDE_BLACK";
 
+/// UCI king move, king id and move string of each castle
+const UCI_CASTLES: [(&str, isize, &str); 4] = [
+    ("e1g1", KING_ID, CASTLE_KING_SIDE_WHITE),
+    ("e1c1", KING_ID, CASTLE_QUEEN_SIDE_WHITE),
+    ("e8g8", -KING_ID, CASTLE_KING_SIDE_BLACK),
+    ("e8c8", -KING_ID, CASTLE_QUEEN_SIDE_BLACK),
+];
+
 pub const DEFAULT_BOARD: Board = [
     [-3, -5, -4, -2, -1, -4, -5, -3],
     [-6, -6, -6, -6, -6, -6, -6, -6],
@@ -54,6 +116,9 @@ pub const DEFAULT_BOARD: Board = [
     [3, 5, 4, 2, 1, 4, 5, 3],
 ];
 
+/// FEN of the start position, `DEFAULT_BOARD` with white to move
+pub const START_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
 //
 // Structs
 //
@@ -75,7 +140,7 @@ pub enum Color {
 }
 
 impl Color {
-    fn to_int(&self) -> isize {
+    fn to_int(self) -> isize {
         match self {
             Self::White => 1,
             Self::Black => -1,
@@ -99,7 +164,7 @@ pub enum Castle {
 }
 
 impl Castle {
-    fn to_str(&self) -> &str {
+    fn to_str(self) -> &'static str {
         match self {
             Castle::KingSideWhite => CASTLE_KING_SIDE_WHITE,
             Castle::QueenSideWhite => CASTLE_QUEEN_SIDE_WHITE,
@@ -107,14 +172,10 @@ impl Castle {
             Castle::QueenSideBlack => CASTLE_QUEEN_SIDE_BLACK,
         }
     }
-
-    fn to_string(&self) -> String {
-        self.to_str().to_string()
-    }
 }
 
 #[derive(Debug)]
-pub struct Piece<'a> {
+pub(crate) struct Piece<'a> {
     id: isize,
     _type: PieceType,
     color: Color,
@@ -123,7 +184,7 @@ pub struct Piece<'a> {
     value: isize,
 }
 
-pub const PIECES: [Piece; 13] = [
+pub(crate) const PIECES: [Piece; 13] = [
     Piece {
         icon: '♙',
         desc: PAWN_DESC,
@@ -174,7 +235,7 @@ pub const PIECES: [Piece; 13] = [
     },
     Piece {
         icon: '.',
-        desc: &" ",
+        desc: " ",
         color: Color::White, // doesn't matter but must be set to avoid using Option<Color>
         _type: PieceType::Empty,
         id: EMPTY_SQUARE_ID,
@@ -278,6 +339,9 @@ pub union MoveUnion {
 pub struct MoveStruct {
     pub is_castle: bool,
     data: MoveUnion,
+    /// piece a pawn reaching the last rank becomes (a positive id of
+    /// `PROMOTION_IDS`), a queen if None
+    pub promotion: Option<isize>,
 }
 
 impl Clone for MoveStruct {
@@ -285,6 +349,7 @@ impl Clone for MoveStruct {
         Self{
             is_castle: self.is_castle,
             data: self.data,
+            promotion: self.promotion,
         }
     }
     
@@ -292,6 +357,9 @@ impl Clone for MoveStruct {
 //
 // State struct
 //
+/// A position. Boards missing a king (study positions) are supported: the
+/// side without a king cannot castle and is never in check (nothing is
+/// pinned and it is never mated), and the evaluation only counts material.
 #[derive(Debug, Copy, Clone)]
 pub struct State {
     pub board: Board,
@@ -304,18 +372,24 @@ pub struct State {
     pub black_queen_castle_is_possible: bool,
     pub white_king_is_checked: bool,
     pub black_king_is_checked: bool,
+    /// square passed over by a pawn's two-square step on the last move, where
+    /// it can be captured en passant
+    pub en_passant_square: Option<Square>,
+    /// plies since the last capture or pawn move, for the fifty-move rule
+    pub halfmove_clock: u32,
+    /// starts at 1 and is incremented after each black move
+    pub fullmove_number: u32,
 }
 
 impl State {
     pub fn new(
         board: Board,
-        current_player: &str,
+        current_player: Color,
         white_king_castle_is_possible: bool,
         white_queen_castle_is_possible: bool,
         black_king_castle_is_possible: bool,
         black_queen_castle_is_possible: bool,
     ) -> Self {
-        let _current_player: Color = player_string_to_enum(current_player);
         // check if kings are on board
         // this affects castling and king under attack checks
         let white_king_on_board = piece_is_on_board(&board, KING_ID);
@@ -326,27 +400,36 @@ impl State {
         let mut _black_king_castle_is_possible = black_king_castle_is_possible;
         let mut _black_queen_castle_is_possible = black_queen_castle_is_possible;
 
-        if white_king_on_board == false {
+        if !white_king_on_board {
             _white_king_castle_is_possible = false;
             _white_queen_castle_is_possible = false;
         }
-        if black_king_on_board == false {
+        if !black_king_on_board {
             _black_king_castle_is_possible = false;
             _black_queen_castle_is_possible = false;
         }
 
-        return Self {
+        Self {
             board,
             white_king_on_board,
             black_king_on_board,
-            current_player: _current_player,
+            current_player,
             white_king_castle_is_possible: _white_king_castle_is_possible,
             white_queen_castle_is_possible: _white_queen_castle_is_possible,
             black_king_castle_is_possible: _black_king_castle_is_possible,
             black_queen_castle_is_possible: _black_queen_castle_is_possible,
             white_king_is_checked: false,
             black_king_is_checked: false,
-        };
+            en_passant_square: None,
+            halfmove_clock: 0,
+            fullmove_number: 1,
+        }
+    }
+
+    /// Zobrist hash of the position (see `zobrist::hash`), failing on an
+    /// invalid piece id or en passant square
+    pub fn hash(&self) -> Result<u64, String> {
+        zobrist::hash(self)
     }
 
     pub fn update_player_king_checked(
@@ -357,56 +440,14 @@ impl State {
         match player {
             Color::White => {
                 self.white_king_is_checked =
-                    _king_is_checked(&self, Color::White, squares_under_attack_map);
+                    _king_is_checked(self, Color::White, squares_under_attack_map);
             }
             Color::Black => {
                 self.black_king_is_checked =
-                    _king_is_checked(&self, Color::Black, squares_under_attack_map);
+                    _king_is_checked(self, Color::Black, squares_under_attack_map);
             }
         }
     }
-
-    pub fn to_py_object(&self, dict: &PyDict) {
-        dict.set_item(
-            "white_king_castle_is_possible",
-            self.white_king_castle_is_possible,
-        )
-        .unwrap();
-        dict.set_item(
-            "white_queen_castle_is_possible",
-            self.white_queen_castle_is_possible,
-        )
-        .unwrap();
-        dict.set_item(
-            "black_king_castle_is_possible",
-            self.black_king_castle_is_possible,
-        )
-        .unwrap();
-        dict.set_item(
-            "black_queen_castle_is_possible",
-            self.black_queen_castle_is_possible,
-        )
-        .unwrap();
-        dict.set_item("white_king_is_checked", self.white_king_is_checked)
-            .unwrap();
-        dict.set_item("black_king_is_checked", self.black_king_is_checked)
-            .unwrap();
-
-        let board: &[&[isize]] = &[
-            &self.board[0],
-            &self.board[1],
-            &self.board[2],
-            &self.board[3],
-            &self.board[4],
-            &self.board[5],
-            &self.board[6],
-            &self.board[7],
-        ];
-
-        dict.set_item("board", array2d_to_vec2d(board)).unwrap();
-        let current_player: &str = player_enum_to_string(&self.current_player);
-        dict.set_item("current_player", current_player).unwrap();
-    }
 }
 
 pub fn render_state(state: &State) {
@@ -414,44 +455,19 @@ pub fn render_state(state: &State) {
 }
 
 pub fn render_board(board: &Board) {
-    print!("\n   ------------------------");
-    for (j, row) in board.iter().enumerate() {
-        print!("\n{} |", 8 - j);
-        for piece_id in row.iter() {
-            let piece_icon = ID_TO_ICON.get(piece_id);
-            print!(" {} ", piece_icon.unwrap().to_string());
-        }
-        print!("|");
-    }
-    println!("\n   ------------------------");
-    println!("    a  b  c  d  e  f  g  h");
-}
-
-fn array2d_to_vec2d(arr: &[&[isize]]) -> Vec<Vec<isize>> {
-    let mut vec: Vec<Vec<isize>> = Vec::new();
-    for &row in arr.iter() {
-        vec.push(row.iter().cloned().collect());
-    }
-    return vec;
+    print!("{}", render::board_to_text(board, &RenderOptions::default()));
 }
 
-fn player_string_to_enum(player: &str) -> Color {
-    let mut _player: Color = Color::White;
+/// Color of "WHITE" or "BLACK"
+pub fn parse_player(player: &str) -> Result<Color, String> {
     match player {
-        "WHITE" => {
-            _player = Color::White;
-        }
-        "BLACK" => {
-            _player = Color::Black;
-        }
-        _ => {
-            let gil = Python::acquire_gil();
-            let py = gil.python();
-            println!("Invalid Color. Must be 'WHITE' or 'BLACK'");
-            PyException::new_err("Invalid Color. Must be 'WHITE' or 'BLACK'").restore(py);
-        }
+        "WHITE" => Ok(Color::White),
+        "BLACK" => Ok(Color::Black),
+        _ => Err(format!(
+            "Invalid Color '{}'. Must be 'WHITE' or 'BLACK'",
+            player
+        )),
     }
-    return _player;
 }
 
 fn player_enum_to_string<'a>(player: &Color) -> &'a str {
@@ -462,7 +478,7 @@ fn player_enum_to_string<'a>(player: &Color) -> &'a str {
         }
         _ => _player = "BLACK",
     }
-    return _player;
+    _player
 }
 
 //
@@ -471,7 +487,7 @@ fn player_enum_to_string<'a>(player: &Color) -> &'a str {
 // ---------------------------------------------------------
 
 // shortcut
-pub fn get_all_possible_moves(
+pub(crate) fn get_all_possible_moves(
     state: &State,
     player: Color,
     attack: bool,
@@ -479,15 +495,15 @@ pub fn get_all_possible_moves(
     // squares under attack
     let other_player: Color = get_other_player(player);
     let mut squares_under_attack_map: HashMap<usize, bool> = HashMap::new();
-    if attack != true {
-        squares_under_attack_map = get_squares_under_attack_by_player(&state, other_player);
+    if !attack {
+        squares_under_attack_map = get_squares_under_attack_by_player(state, other_player);
     }
     //
-    return _get_all_possible_moves(state, player, attack, &squares_under_attack_map);
+    _get_all_possible_moves(state, player, attack, &squares_under_attack_map)
 }
 
 // get all moves (normal + castles)
-pub fn _get_all_possible_moves(
+pub(crate) fn _get_all_possible_moves(
     state: &State,
     player: Color,
     attack: bool,
@@ -495,8 +511,8 @@ pub fn _get_all_possible_moves(
 ) -> (Vec<Move>, Vec<Castle>) {
     let moves: Vec<Move> = _get_possible_moves(state, player, attack, squares_under_attack_map);
     let castle_moves: Vec<Castle> =
-        _get_possible_castle_moves(state, player, attack, &squares_under_attack_map);
-    return (moves, castle_moves);
+        _get_possible_castle_moves(state, player, attack, squares_under_attack_map);
+    (moves, castle_moves)
 }
 
 // shortcut function
@@ -504,19 +520,36 @@ pub fn get_possible_moves(state: &State, player: Color, attack: bool) -> Vec<Mov
     // squares under attack
     let other_player: Color = get_other_player(player);
     let mut squares_under_attack_map: HashMap<usize, bool> = HashMap::new();
-    if attack != true {
-        squares_under_attack_map = get_squares_under_attack_by_player(&state, other_player);
+    if !attack {
+        squares_under_attack_map = get_squares_under_attack_by_player(state, other_player);
     }
     //
-    return _get_possible_moves(state, player, attack, &squares_under_attack_map);
+    _get_possible_moves(state, player, attack, &squares_under_attack_map)
 }
 
 // function to be used in the Python api func
-pub fn _get_possible_moves(
+pub(crate) fn _get_possible_moves(
     state: &State,
     player: Color,
     attack: bool,
     squares_under_attack_map: &HashMap<usize, bool>,
+) -> Vec<Move> {
+    if attack {
+        return attack_moves(state, player);
+    }
+
+    let mut moves: Vec<Move> = _get_pseudo_legal_moves(state, player, squares_under_attack_map);
+
+    // Filter out moves that leave the king checked
+    moves.retain(|_move: &Move| !move_leaves_king_checked(state, player, *_move));
+    moves
+}
+
+// moves of the player's pieces, including those leaving its king checked
+pub(crate) fn _get_pseudo_legal_moves(
+    state: &State,
+    player: Color,
+    squares_under_attack_map: &HashMap<usize, bool>,
 ) -> Vec<Move> {
     // calculate possible moves
     let mut moves: Vec<Move> = vec![];
@@ -539,57 +572,50 @@ pub fn _get_possible_moves(
             match piece_type {
                 PieceType::King => {
                     let _moves: Vec<Move> =
-                        king_moves(&state, player, (i, j), squares_under_attack_map, attack);
+                        king_moves(state, player, (i, j), squares_under_attack_map);
                     moves.extend_from_slice(&_moves);
                 }
                 PieceType::Queen => {
-                    let _moves: Vec<Move> = queen_moves(&state, player, (i, j), attack);
+                    let _moves: Vec<Move> = queen_moves(state, player, (i, j));
                     moves.extend_from_slice(&_moves);
                 }
                 PieceType::Rook => {
-                    let _moves: Vec<Move> = rook_moves(&state, player, (i, j), attack);
+                    let _moves: Vec<Move> = rook_moves(state, player, (i, j));
                     moves.extend_from_slice(&_moves);
                 }
                 PieceType::Bishop => {
-                    let _moves: Vec<Move> = bishop_moves(&state, player, (i, j), attack);
+                    let _moves: Vec<Move> = bishop_moves(state, player, (i, j));
                     moves.extend_from_slice(&_moves);
                 }
                 PieceType::Knight => {
-                    let _moves: Vec<Move> = knight_moves(&state, player, (i, j), attack);
+                    let _moves: Vec<Move> = knight_moves(state, player, (i, j));
                     moves.extend_from_slice(&_moves);
                 }
                 PieceType::Pawn => {
-                    let _moves: Vec<Move> = pawn_moves(&state, player, (i, j), attack);
+                    let _moves: Vec<Move> = pawn_moves(state, player, (i, j));
                     moves.extend_from_slice(&_moves);
                 }
                 _ => {}
             }
         }
     }
-
-    if attack == true {
-        return moves;
-    }
-
-    // Filter out moves that leave the king checked
-    moves.retain(|_move: &Move| !move_leaves_king_checked(state, player, *_move));
-    return moves;
+    moves
 }
 
 // shortcut function
-pub fn get_possible_castle_moves(state: &State, player: Color, attack: bool) -> Vec<Castle> {
+pub(crate) fn get_possible_castle_moves(state: &State, player: Color, attack: bool) -> Vec<Castle> {
     // squares under attack
     let other_player: Color = get_other_player(player);
     let mut squares_under_attack_map: HashMap<usize, bool> = HashMap::new();
-    if attack != true {
-        squares_under_attack_map = get_squares_under_attack_by_player(&state, other_player);
+    if !attack {
+        squares_under_attack_map = get_squares_under_attack_by_player(state, other_player);
     }
     //
-    return _get_possible_castle_moves(state, player, attack, &squares_under_attack_map);
+    _get_possible_castle_moves(state, player, attack, &squares_under_attack_map)
 }
 
 // function to be used in the Python api func
-pub fn _get_possible_castle_moves(
+pub(crate) fn _get_possible_castle_moves(
     state: &State,
     player: Color,
     attack: bool,
@@ -599,28 +625,28 @@ pub fn _get_possible_castle_moves(
     let mut castle_moves: Vec<Castle> = vec![];
 
     // castling aren not attacking moves
-    if attack == true {
+    if attack {
         return castle_moves;
     }
 
     // King not present on the board (for testing pruposes)
-    if (player == Color::White && !state.white_king_on_board == true)
-        || (player == Color::Black && !state.black_king_on_board == true)
+    if (player == Color::White && !state.white_king_on_board)
+        || (player == Color::Black && !state.black_king_on_board)
     {
         return castle_moves;
     }
 
     if (player == Color::White
-        && (state.white_king_castle_is_possible == true
-            || state.white_queen_castle_is_possible == true))
+        && (state.white_king_castle_is_possible
+            || state.white_queen_castle_is_possible))
         || (player == Color::Black
-            && (state.black_king_castle_is_possible == true
-                || state.black_queen_castle_is_possible == true))
+            && (state.black_king_castle_is_possible
+                || state.black_queen_castle_is_possible))
     {
         castle_moves = calc_castle_moves(state, player, squares_under_attack_map);
     }
 
-    return castle_moves;
+    castle_moves
 }
 
 fn move_leaves_king_checked(state: &State, player: Color, _move: Move) -> bool {
@@ -634,15 +660,16 @@ fn move_leaves_king_checked(state: &State, player: Color, _move: Move) -> bool {
     let move_struct = MoveStruct {
         is_castle: false,
         data: MoveUnion { normal_move: _move },
+        promotion: None,
     };
     let (_next_state, _) = next_state(state, player, move_struct);
-    return king_is_checked(&_next_state, player);
+    king_is_checked(&_next_state, player)
 }
 
 fn king_is_checked(state: &State, player: Color) -> bool {
     let other_player = get_other_player(player);
     let squares_under_attack_map = get_squares_under_attack_by_player(state, other_player);
-    return _king_is_checked(state, player, &squares_under_attack_map);
+    _king_is_checked(state, player, &squares_under_attack_map)
 }
 
 fn _king_is_checked(
@@ -650,8 +677,7 @@ fn _king_is_checked(
     player: Color,
     squares_under_attack_map: &HashMap<usize, bool>,
 ) -> bool {
-    // TODO:
-    // King not present on the board (for testing pruposes)
+    // a player without a king (study positions) is never in check
     let mut king_square: Option<Square> = None;
     let king_id = KING_ID * player.to_int();
 
@@ -668,13 +694,13 @@ fn _king_is_checked(
 
     match king_square {
         None => {
-            return false;
+            false
         }
         Some(square) => {
             let square_flat = square_tuple_to_flat(square);
             match squares_under_attack_map.get(&square_flat) {
-                Some(&_) => return true,
-                None => return false,
+                Some(&_) => true,
+                None => false,
             }
         }
     }
@@ -682,48 +708,105 @@ fn _king_is_checked(
 
 fn get_squares_under_attack_by_player(state: &State, player: Color) -> HashMap<usize, bool> {
     let mut squares_under_attack_map: HashMap<usize, bool> = HashMap::new();
-    let moves = _get_possible_moves(&state, player, true, &squares_under_attack_map);
+    let moves = _get_possible_moves(state, player, true, &squares_under_attack_map);
     for _move in moves.iter() {
         let square_flat = square_tuple_to_flat(_move.1);
         squares_under_attack_map.insert(square_flat, true);
     }
-    return squares_under_attack_map;
+    squares_under_attack_map
+}
+
+/// Play a move of the move generator, a pawn reaching the last rank becoming
+/// the move's promotion piece (a queen by default). Crate internal: other
+/// moves go through `try_next_state`.
+pub(crate) fn next_state(state: &State, player: Color, move_struct: MoveStruct) -> (State, isize) {
+    let promotion = move_struct.promotion.unwrap_or(QUEEN_ID);
+    next_state_promoting(state, player, move_struct, promotion)
+}
+
+/// `next_state` with a pawn reaching the last rank becoming `promotion` (a
+/// positive id of `PROMOTION_IDS`) whatever the move's promotion piece.
+/// Panics on a move the move generator can't give (no piece to move, an
+/// unknown piece id), so only the crate calls it on its own moves.
+pub(crate) fn next_state_promoting(
+    state: &State,
+    player: Color,
+    move_struct: MoveStruct,
+    promotion: isize,
+) -> (State, isize) {
+    match play_move(state, player, move_struct, promotion) {
+        Ok(result) => result,
+        Err(message) => panic!("{}", message),
+    }
 }
 
-pub fn next_state(state: &State, player: Color, move_struct: MoveStruct) -> (State, isize) {
-    let mut new_state = state.clone();
+fn play_move(
+    state: &State,
+    player: Color,
+    move_struct: MoveStruct,
+    promotion: isize,
+) -> Result<(State, isize), String> {
+    let mut new_state = *state;
     let mut reward: isize = 0;
+    new_state.en_passant_square = None;
+    new_state.halfmove_clock = state.halfmove_clock + 1;
 
     unsafe {
         match move_struct {
             MoveStruct {
                 is_castle: false,
                 data: MoveUnion { normal_move },
+                ..
             } => {
                 let _from = (normal_move.0 .0 as usize, normal_move.0 .1 as usize);
                 let _to = (normal_move.1 .0 as usize, normal_move.1 .1 as usize);
                 let piece_to_move = new_state.board[_from.0][_from.1];
                 let captured_piece = new_state.board[_to.0][_to.1];
                 if piece_to_move == 0 {
-                    panic!("Bad move - piece is empty !");
+                    return Err("Bad move - piece is empty !".to_string());
                 }
                 new_state.board[_from.0][_from.1] = 0;
                 new_state.board[_to.0][_to.1] = piece_to_move;
-                reward += *ID_TO_VALUE.get(&captured_piece).unwrap();
+                reward += piece_value(captured_piece)?;
 
-                // Pawn becomes Queen
-                let piece_type = *ID_TO_TYPE.get(&piece_to_move).unwrap();
+                let piece_type = piece_type(piece_to_move)?;
+                // captures and pawn moves reset the fifty-move clock
+                if captured_piece != 0 || piece_type == PieceType::Pawn {
+                    new_state.halfmove_clock = 0;
+                }
                 if piece_type == PieceType::Pawn {
-                    if (player == Color::White && _to.0 == 7)
-                        || (player == Color::Black && _to.0 == 0)
-                    {
-                        new_state.board[_to.0][_to.1] = QUEEN_ID * player.to_int();
-                        reward += CONVERT_PAWN_TO_QUEEN_REWARD;
+                    // En passant: the pawn beside is captured
+                    if _from.1 != _to.1 && Some(normal_move.1) == state.en_passant_square {
+                        let captured_pawn = new_state.board[_from.0][_to.1];
+                        new_state.board[_from.0][_to.1] = EMPTY_SQUARE_ID;
+                        reward += piece_value(captured_pawn)?;
+                    }
+                    // a double step can be taken en passant only by an enemy
+                    // pawn beside the square it lands on
+                    let enemy_pawn = -piece_to_move;
+                    let enemy_pawn_beside = [_to.1.wrapping_sub(1), _to.1 + 1]
+                        .iter()
+                        .any(|&col| col < 8 && new_state.board[_to.0][col] == enemy_pawn);
+                    if (_from.0 as isize - _to.0 as isize).abs() == 2 && enemy_pawn_beside {
+                        let passed = (_from.0 + _to.0) / 2;
+                        new_state.en_passant_square = Some((passed as isize, _from.1 as isize));
                     }
                 }
 
-                // Keep track if castling is still possible
-                if piece_to_move == KING_ID {
+                // Pawn promotes
+                if piece_type == PieceType::Pawn && _to.0 == promotion_row(player) {
+                    new_state.board[_to.0][_to.1] = promotion * player.to_int();
+                    reward += if promotion == QUEEN_ID {
+                        CONVERT_PAWN_TO_QUEEN_REWARD
+                    } else {
+                        piece_value(promotion)?
+                    };
+                }
+
+                // Keep track if castling is still possible: a king move loses
+                // both sides, a rook leaving or captured on its home square
+                // loses that side
+                if piece_to_move.abs() == KING_ID {
                     if player == Color::White {
                         new_state.white_king_castle_is_possible = false;
                         new_state.white_queen_castle_is_possible = false;
@@ -731,25 +814,14 @@ pub fn next_state(state: &State, player: Color, move_struct: MoveStruct) -> (Sta
                         new_state.black_king_castle_is_possible = false;
                         new_state.black_queen_castle_is_possible = false;
                     }
-                } else if piece_to_move == ROOK_ID {
-                    if _from.1 == 0 {
-                        if player == Color::White {
-                            new_state.white_queen_castle_is_possible = false;
-                        } else {
-                            new_state.black_queen_castle_is_possible = false;
-                        }
-                    } else if _from.1 == 7 {
-                        if player == Color::White {
-                            new_state.white_king_castle_is_possible = false;
-                        } else {
-                            new_state.black_king_castle_is_possible = false;
-                        }
-                    }
                 }
+                clear_rook_castle_right(&mut new_state, _from);
+                clear_rook_castle_right(&mut new_state, _to);
             }
             MoveStruct {
                 is_castle: true,
                 data: MoveUnion { castle },
+                ..
             } => match castle {
                 Castle::KingSideWhite => {
                     new_state.board[7][4] = EMPTY_SQUARE_ID;
@@ -792,9 +864,172 @@ pub fn next_state(state: &State, player: Color, move_struct: MoveStruct) -> (Sta
     // change player
     let other_player = get_other_player(player);
     new_state.current_player = other_player;
+    if player == Color::Black {
+        new_state.fullmove_number += 1;
+    }
     // render_state(&new_state);
 
-    return (new_state, reward);
+    Ok((new_state, reward))
+}
+
+fn piece_value(piece_id: isize) -> Result<isize, String> {
+    ID_TO_VALUE.get(&piece_id).copied().ok_or_else(|| format!("Invalid piece id {}", piece_id))
+}
+
+fn piece_type(piece_id: isize) -> Result<PieceType, String> {
+    ID_TO_TYPE.get(&piece_id).copied().ok_or_else(|| format!("Invalid piece id {}", piece_id))
+}
+
+/// Drop the castling right of the rook whose home square is `square`, once
+/// a piece moves from or to it
+fn clear_rook_castle_right(state: &mut State, square: (usize, usize)) {
+    match square {
+        (7, 0) => state.white_queen_castle_is_possible = false,
+        (7, 7) => state.white_king_castle_is_possible = false,
+        (0, 0) => state.black_queen_castle_is_possible = false,
+        (0, 7) => state.black_king_castle_is_possible = false,
+        _ => {}
+    }
+}
+
+/// The position with the other side to move, as if the side to move passed
+/// (for threat analysis and null-move pruning). Not allowed in check, the
+/// king would be left attacked.
+pub fn null_move(state: &State) -> Result<State, String> {
+    if king_is_checked(state, state.current_player) {
+        return Err("A null move is not allowed in check".to_string());
+    }
+    let mut new_state = *state;
+    new_state.current_player = get_other_player(state.current_player);
+    new_state.en_passant_square = None;
+    Ok(new_state)
+}
+
+/// State after `player` plays `move_struct`, a pawn reaching the last rank
+/// becoming `promotion`, and the reward of the move. Errors when the move
+/// leaves the board, its piece is missing, unknown or not `player`'s, or
+/// when an underpromotion is not a promotion.
+pub fn try_next_state(
+    state: &State,
+    player: Color,
+    move_struct: MoveStruct,
+    promotion: isize,
+) -> Result<(State, isize), String> {
+    if !move_struct.is_castle {
+        let (_from, _to) = unsafe { move_struct.data.normal_move };
+        if !square_is_on_board(_from) || !square_is_on_board(_to) {
+            return Err("Bad move - square outside the board".to_string());
+        }
+        if square_is_empty(state, _from) {
+            return Err("Bad move - piece is empty !".to_string());
+        }
+        let piece_id = state.board[_from.0 as usize][_from.1 as usize];
+        match ID_TO_COLOR.get(&piece_id) {
+            None => return Err(format!("Invalid piece id {}", piece_id)),
+            Some(&color) if color != player => {
+                return Err("Bad move - the piece belongs to the other player".to_string())
+            }
+            _ => {}
+        }
+    }
+    if promotion != QUEEN_ID && !is_promotion(state, &move_struct) {
+        return Err("Bad move - only a pawn reaching the last rank promotes".to_string());
+    }
+    play_move(state, player, move_struct, promotion)
+}
+
+/// Whether `move_struct` is a pawn reaching the last rank
+pub fn is_promotion(state: &State, move_struct: &MoveStruct) -> bool {
+    if move_struct.is_castle {
+        return false;
+    }
+    let (_from, _to) = unsafe { move_struct.data.normal_move };
+    let piece_id = state.board[_from.0 as usize][_from.1 as usize];
+    piece_id.abs() == PAWN_ID
+        && _to.0 == promotion_row(if piece_id > 0 { Color::White } else { Color::Black }) as isize
+}
+
+/// Piece a move string promotes to (positive id): the piece of its UCI
+/// suffix ("e7e8n"), a queen without one
+pub fn promotion_of(_move: &str) -> isize {
+    match _move.chars().nth(4) {
+        Some('r') => ROOK_ID,
+        Some('b') => BISHOP_ID,
+        Some('n') => KNIGHT_ID,
+        _ => QUEEN_ID,
+    }
+}
+
+/// Error if the opponent of `player` (the side to move) is in check: `player`
+/// could capture its king, so the position can't be searched or evaluated
+pub(crate) fn check_turn(state: &State, player: Color) -> Result<(), String> {
+    let other_player = get_other_player(player);
+    if king_is_checked(state, other_player) {
+        return Err(format!(
+            "Invalid position: {} is in check but {} is to move",
+            player_enum_to_string(&other_player).to_lowercase(),
+            player_enum_to_string(&player).to_lowercase()
+        ));
+    }
+    Ok(())
+}
+
+// ATTACKS
+// ---------------------------------------------------------
+// ---------------------------------------------------------
+
+/// Moves of the pieces of `player` to the squares they attack or defend, i.e.
+/// the squares the opponent king can't move to. Unlike the move generation:
+/// - sliding pieces see through the opponent king, which can't step back
+///   along the line of a check
+/// - pawns attack both diagonal squares, whatever is on them
+/// - the king attacks all its neighbour squares
+fn attack_moves(state: &State, player: Color) -> Vec<Move> {
+    let mut moves: Vec<Move> = vec![];
+    let straight: [Square; 4] = [(-1, 0), (1, 0), (0, -1), (0, 1)];
+    let diagonal: [Square; 4] = [(-1, -1), (-1, 1), (1, -1), (1, 1)];
+    let knight: [Square; 8] = [
+        (-2, -1),
+        (-2, 1),
+        (2, -1),
+        (2, 1),
+        (-1, -2),
+        (-1, 2),
+        (1, -2),
+        (1, 2),
+    ];
+    // white pawns move towards row 0
+    let pawn: [Square; 2] = [(-player.to_int(), -1), (-player.to_int(), 1)];
+    let other_king_id = -KING_ID * player.to_int();
+
+    for (_i, row) in state.board.iter().enumerate() {
+        for (_j, &piece_id) in row.iter().enumerate() {
+            if piece_id * player.to_int() <= 0 {
+                continue;
+            }
+            let coords: Square = (_i as isize, _j as isize);
+            let (steps, sliding): (Vec<Square>, bool) = match piece_id.abs() {
+                KING_ID => ([straight, diagonal].concat(), false),
+                QUEEN_ID => ([straight, diagonal].concat(), true),
+                ROOK_ID => (straight.to_vec(), true),
+                BISHOP_ID => (diagonal.to_vec(), true),
+                KNIGHT_ID => (knight.to_vec(), false),
+                _ => (pawn.to_vec(), false),
+            };
+            for step in steps.iter() {
+                let mut square: Square = (coords.0 + step.0, coords.1 + step.1);
+                while square_is_on_board(square) {
+                    moves.push((coords, square));
+                    let target = state.board[square.0 as usize][square.1 as usize];
+                    if !sliding || (target != EMPTY_SQUARE_ID && target != other_king_id) {
+                        break;
+                    }
+                    square = (square.0 + step.0, square.1 + step.1);
+                }
+            }
+        }
+    }
+    moves
 }
 
 // PIECE MOVEMENTS
@@ -805,7 +1040,6 @@ fn king_moves(
     player: Color,
     coords: Square,
     squares_under_attack_map: &HashMap<usize, bool>,
-    attack: bool,
 ) -> Vec<Move> {
     let mut moves: Vec<Move> = vec![];
     let steps: [Square; 8] = [
@@ -820,48 +1054,41 @@ fn king_moves(
     ];
     for step in steps.iter() {
         let square: Square = (coords.0 + step.0, coords.1 + step.1);
-        if attack == true {
-            let add = king_attacking_move(state, player, square, squares_under_attack_map);
-            if add == true {
-                moves.push((coords, square));
-            }
-        } else {
-            let add = king_playable_move(state, player, square, squares_under_attack_map);
-            if add == true {
-                moves.push((coords, square));
-            }
+        let add = king_playable_move(state, player, square, squares_under_attack_map);
+        if add {
+            moves.push((coords, square));
         }
     }
-    return moves;
+    moves
 }
 
-fn queen_moves(state: &State, player: Color, coords: Square, attack: bool) -> Vec<Move> {
+fn queen_moves(state: &State, player: Color, coords: Square) -> Vec<Move> {
     let mut moves: Vec<Move> = vec![];
-    let moves_rook: Vec<Move> = rook_moves(state, player, coords, attack);
+    let moves_rook: Vec<Move> = rook_moves(state, player, coords);
     moves.extend_from_slice(&moves_rook);
-    let moves_bishop: Vec<Move> = bishop_moves(state, player, coords, attack);
+    let moves_bishop: Vec<Move> = bishop_moves(state, player, coords);
     moves.extend_from_slice(&moves_bishop);
-    return moves;
+    moves
 }
 
-fn rook_moves(state: &State, player: Color, coords: Square, attack: bool) -> Vec<Move> {
+fn rook_moves(state: &State, player: Color, coords: Square) -> Vec<Move> {
     let mut moves: Vec<Move> = vec![];
     let steps: [Square; 4] = [(-1, 0), (1, 0), (0, -1), (0, 1)];
     for step in steps.iter() {
-        let _moves: Vec<Move> = iterativesteps(state, player, coords, *step, attack);
+        let _moves: Vec<Move> = iterativesteps(state, player, coords, *step);
         moves.extend_from_slice(&_moves)
     }
-    return moves;
+    moves
 }
 
-fn bishop_moves(state: &State, player: Color, coords: Square, attack: bool) -> Vec<Move> {
+fn bishop_moves(state: &State, player: Color, coords: Square) -> Vec<Move> {
     let mut moves: Vec<Move> = vec![];
     let steps: [Square; 4] = [(-1, -1), (-1, 1), (1, -1), (1, 1)];
     for step in steps.iter() {
-        let _moves: Vec<Move> = iterativesteps(state, player, coords, *step, attack);
+        let _moves: Vec<Move> = iterativesteps(state, player, coords, *step);
         moves.extend_from_slice(&_moves)
     }
-    return moves;
+    moves
 }
 
 fn iterativesteps(
@@ -869,38 +1096,25 @@ fn iterativesteps(
     player: Color,
     coords: Square,
     step: Square,
-    attack: bool,
 ) -> Vec<Move> {
     let mut moves: Vec<Move> = vec![];
     let mut k: isize = 1;
     loop {
         let square = (coords.0 + k * step.0, coords.1 + k * step.1);
-        if attack == true {
-            let (add, stop) = attacking_move(state, player, square);
-            if add == true {
-                moves.push((coords, square));
-            }
-            if stop == true {
-                break;
-            } else {
-                k += 1;
-            }
+        let (add, stop) = playable_move(state, player, square);
+        if add {
+            moves.push((coords, square));
+        }
+        if stop {
+            break;
         } else {
-            let (add, stop) = playable_move(state, player, square);
-            if add == true {
-                moves.push((coords, square));
-            }
-            if stop == true {
-                break;
-            } else {
-                k += 1;
-            }
+            k += 1;
         }
     }
-    return moves;
+    moves
 }
 
-fn knight_moves(state: &State, player: Color, coords: Square, attack: bool) -> Vec<Move> {
+fn knight_moves(state: &State, player: Color, coords: Square) -> Vec<Move> {
     let mut moves: Vec<Move> = vec![];
     let steps: [Square; 8] = [
         (-2, -1),
@@ -914,67 +1128,53 @@ fn knight_moves(state: &State, player: Color, coords: Square, attack: bool) -> V
     ];
     for step in steps.iter() {
         let square = (coords.0 + step.0, coords.1 + step.1);
-        if attack == true {
-            let (add, _) = attacking_move(state, player, square);
-            if add == true {
-                moves.push((coords, square));
-            }
-        } else {
-            let (add, _) = playable_move(state, player, square);
-            if add == true {
-                moves.push((coords, square));
-            }
+        let (add, _) = playable_move(state, player, square);
+        if add {
+            moves.push((coords, square));
         }
     }
-    return moves;
+    moves
 }
 
-fn pawn_moves(state: &State, player: Color, coords: Square, attack: bool) -> Vec<Move> {
+fn pawn_moves(state: &State, player: Color, coords: Square) -> Vec<Move> {
     let mut moves: Vec<Move> = vec![];
     let player_int: isize = player.to_int();
     let attack_squares: [Square; 2] = [
         (coords.0 - player_int, coords.1 + 1),
         (coords.0 - player_int, coords.1 - 1),
     ];
-    let one_step_square: Square = (coords.0 + (1 * -player_int), coords.1);
+    let one_step_square: Square = (coords.0 + -player_int, coords.1);
     let two_step_square: Square = (coords.0 + (2 * -player_int), coords.1);
 
-    if attack == true {
-        for square in attack_squares.iter().cloned() {
-            if square_is_on_board(square) && !is_king_from_player(state, player, square) {
-                moves.push((coords, square));
-            }
-        }
-    } else {
-        {
-            let x = one_step_square.0 as usize;
-            let y = one_step_square.1 as usize;
-            if square_is_on_board(one_step_square) && state.board[x][y] == 0 {
-                moves.push((coords, one_step_square));
-            }
+    {
+        let x = one_step_square.0 as usize;
+        let y = one_step_square.1 as usize;
+        if square_is_on_board(one_step_square) && state.board[x][y] == 0 {
+            moves.push((coords, one_step_square));
         }
-        {
-            let x = two_step_square.0 as usize;
-            let y = two_step_square.1 as usize;
-            if square_is_on_board(two_step_square) {
-                if (player == Color::White && coords.0 == 6)
-                    || (player == Color::Black && coords.0 == 1)
-                {
-                    if state.board[x][y] == 0 {
-                        moves.push((coords, two_step_square));
-                    }
+    }
+    {
+        let x = two_step_square.0 as usize;
+        let y = two_step_square.1 as usize;
+        if square_is_on_board(two_step_square)
+            && ((player == Color::White && coords.0 == 6)
+                || (player == Color::Black && coords.0 == 1))
+                && state.board[x][y] == 0 {
+                    moves.push((coords, two_step_square));
                 }
-            }
+    }
+    for square in attack_squares.iter().cloned() {
+        if square_is_on_board(square) && is_piece_from_other_player(state, player, square) {
+            moves.push((coords, square));
         }
-        for square in attack_squares.iter().cloned() {
-            if square_is_on_board(square) && is_piece_from_other_player(state, player, square) {
-                moves.push((coords, square));
-            }
+        // en passant: the pawn that passed over the square stands beside this one
+        if Some(square) == state.en_passant_square
+            && state.board[coords.0 as usize][square.1 as usize] == -PAWN_ID * player_int
+        {
+            moves.push((coords, square));
         }
-        // TODO: implement en-passant pawn capture
-        //
     }
-    return moves;
+    moves
 }
 
 fn calc_castle_moves(
@@ -1066,13 +1266,14 @@ fn calc_castle_moves(
         }
     }
 
-    return castle_moves;
+    castle_moves
 }
 
 ///
 /// return squares to which a piece can move
-/// - empty squares
-/// - opponent pieces (excluding king)
+///   - empty squares
+///   - opponent pieces (excluding king)
+///
 /// => return [<bool> playable, <bool> stop_iteration]
 fn playable_move(state: &State, player: Color, square: Square) -> (bool, bool) {
     let other_player = get_other_player(player);
@@ -1085,80 +1286,24 @@ fn playable_move(state: &State, player: Color, square: Square) -> (bool, bool) {
     if is_piece_from_player(state, player, square) {
         return (false, true);
     }
-    if is_piece_from_player(state, other_player, square) {
-        return (true, true);
-    }
+    // the opponent king is never captured (it can only be in reach on an
+    // inconsistent board, e.g. a user supplied one)
     if is_king_from_player(state, other_player, square) {
         return (false, true);
     }
-    panic!("PLAYABLE MOVE ERROR");
-}
-
-///
-/// return squares that are attacked or defended
-/// - empty squares
-/// - opponent pieces (opponent king is ignored)
-/// - own pieces
-/// => return [<bool> playable, <bool> stop_iteration]
-fn attacking_move(state: &State, player: Color, square: Square) -> (bool, bool) {
-    let other_player = get_other_player(player);
-    if !square_is_on_board(square) {
-        return (false, true);
-    }
-    if square_is_empty(state, square) {
-        return (true, false);
-    }
-    if is_piece_from_player(state, player, square)
-        || is_piece_from_player(state, other_player, square)
-        || is_king_from_player(state, other_player, square)
-    {
-        return (true, true);
-    }
-    panic!("ATTACKING MOVE ERROR");
+    (true, true)
 }
 
 ///
 /// return squares to which the king can move,
 /// i.e. unattacked squares that can be:
-/// - empty squares
-/// - opponent pieces (excluding king)
-/// If opponent king is encountered, then there's a problem...
-/// => return <bool> is_playable
-fn king_playable_move(
-    state: &State,
-    player: Color,
-    square: Square,
-    squares_under_attack_map: &HashMap<usize, bool>,
-) -> bool {
-    let other_player = get_other_player(player);
-    if !square_is_on_board(square) {
-        return false;
-    }
-
-    let square_flat = square_tuple_to_flat(square);
-    match squares_under_attack_map.get(&square_flat) {
-        Some(&_) => return false,
-        None => {}
-    }
-
-    if square_is_empty(state, square) || is_piece_from_player(state, other_player, square) {
-        return true;
-    }
-    if is_piece_from_player(state, player, square) {
-        return false;
-    }
-    if is_king_from_player(state, other_player, square) {
-        panic!("KINGS NEXT TO EACH OTHER ERROR");
-    }
-    panic!("KING PLAYABLE MOVE ERROR");
-}
-
+///   - empty squares
+///   - opponent pieces (excluding king)
 ///
-/// return all the squares that the king can attack, except:
-/// - squares outside the board
-/// If opponent king is encountered, then there's a problem...
+/// The opponent king's square is never playable (kings next to each other
+/// only happen on inconsistent boards).
 /// => return <bool> is_playable
-fn king_attacking_move(
+fn king_playable_move(
     state: &State,
     player: Color,
     square: Square,
@@ -1170,21 +1315,13 @@ fn king_attacking_move(
     }
 
     let square_flat = square_tuple_to_flat(square);
-    match squares_under_attack_map.get(&square_flat) {
-        Some(&_) => return false,
-        _ => {}
-    }
+    if let Some(&_) = squares_under_attack_map.get(&square_flat) { return false }
 
-    if square_is_empty(state, square)
-        || is_piece_from_player(state, player, square)
-        || is_piece_from_player(state, other_player, square)
-    {
+    if square_is_empty(state, square) {
         return true;
     }
-    if is_king_from_player(state, other_player, square) {
-        panic!("KINGS NEXT TO EACH OTHER ERROR");
-    }
-    panic!("KING PLAYABLE MOVE ERROR");
+    is_piece_from_player(state, other_player, square)
+        && !is_king_from_player(state, other_player, square)
 }
 
 // HELPER FUNCTIONS
@@ -1193,23 +1330,41 @@ fn king_attacking_move(
 fn get_other_player(player: Color) -> Color {
     match player {
         Color::White => {
-            return Color::Black;
+            Color::Black
         }
         _ => {
-            return Color::White;
+            Color::White
         }
     }
 }
 
+/// Row of the last rank of `player`'s pawns
+fn promotion_row(player: Color) -> usize {
+    match player {
+        Color::White => 0,
+        Color::Black => 7,
+    }
+}
+
+/// UCI suffix of a promotion piece (positive id)
+pub(crate) fn promotion_char(piece_id: isize) -> char {
+    match piece_id {
+        ROOK_ID => 'r',
+        BISHOP_ID => 'b',
+        KNIGHT_ID => 'n',
+        _ => 'q',
+    }
+}
+
 fn square_is_on_board(square: Square) -> bool {
-    return !(square.0 < 0 || square.0 > 7 || square.1 < 0 || square.1 > 7);
+    !(square.0 < 0 || square.0 > 7 || square.1 < 0 || square.1 > 7)
 }
 
 fn square_is_empty(state: &State, square: Square) -> bool {
     let row = square.0 as usize;
     let col = square.1 as usize;
     let piece_id = state.board[row][col];
-    return piece_id == 0;
+    piece_id == 0
 }
 
 fn is_piece_from_player(state: &State, player: Color, square: Square) -> bool {
@@ -1220,12 +1375,12 @@ fn is_piece_from_player(state: &State, player: Color, square: Square) -> bool {
         return false;
     }
     let piece_color = *ID_TO_COLOR.get(&piece_id).unwrap();
-    return player == piece_color;
+    player == piece_color
 }
 
 fn is_piece_from_other_player(state: &State, player: Color, square: Square) -> bool {
     let other_player = get_other_player(player);
-    return is_piece_from_player(state, other_player, square);
+    is_piece_from_player(state, other_player, square)
 }
 
 fn is_king_from_player(state: &State, player: Color, square: Square) -> bool {
@@ -1238,7 +1393,7 @@ fn is_king_from_player(state: &State, player: Color, square: Square) -> bool {
         return false;
     }
     let piece_color = *ID_TO_COLOR.get(&piece_id).unwrap();
-    return piece_color == player;
+    piece_color == player
 }
 
 // fn is_king_from_other_player(state: &State, player: Color, square: Square) -> bool {
@@ -1257,36 +1412,9 @@ fn square_tuple_to_flat(square: Square) -> usize {
 //     (row as isize, col as isize)
 // }
 
-fn convert_py_state<'a>(_py: Python<'a>, state_py: &'a PyDict) -> PyResult<State> {
-    let board: Board = state_py.get_item("board").unwrap().extract()?;
-    let current_player: &str = state_py.get_item("current_player").unwrap().extract()?;
-    let white_king_castle_is_possible: bool = state_py
-        .get_item("white_king_castle_is_possible")
-        .unwrap()
-        .extract()?;
-    let white_queen_castle_is_possible: bool = state_py
-        .get_item("white_queen_castle_is_possible")
-        .unwrap()
-        .extract()?;
-    let black_king_castle_is_possible: bool = state_py
-        .get_item("black_king_castle_is_possible")
-        .unwrap()
-        .extract()?;
-    let black_queen_castle_is_possible: bool = state_py
-        .get_item("black_queen_castle_is_possible")
-        .unwrap()
-        .extract()?;
-
-    // create state
-    let state = State::new(
-        board,
-        current_player,
-        white_king_castle_is_possible,
-        white_queen_castle_is_possible,
-        black_king_castle_is_possible,
-        black_queen_castle_is_possible,
-    );
-    return Ok(state);
+fn convert_square_to_string(square: Square) -> String {
+    let cols = ["a", "b", "c", "d", "e", "f", "g", "h"];
+    format!("{}{}", cols[square.1 as usize], 8 - square.0)
 }
 
 fn convert_move_to_string(_move: Move) -> String {
@@ -1300,27 +1428,58 @@ fn convert_move_to_string(_move: Move) -> String {
         cols[_to.1],
         8 - _to.0
     );
-    return from_str;
+    from_str
 }
 
 fn convert_castle_move_to_string(castle_move: Castle) -> String {
-    castle_move.to_string()
-}
-
-// fn convert_move_union_to_string(move_struct: MoveStruct) -> String {
-//     unsafe {
-//         match move_struct {
-//             MoveStruct {
-//                 is_castle: false,
-//                 data: MoveUnion { normal_move },
-//             } => convert_move_to_string(normal_move),
-//             MoveStruct {
-//                 is_castle: true,
-//                 data: MoveUnion { castle },
-//             } => convert_castle_move_to_string(castle),
-//         }
-//     }
-// }
+    castle_move.to_str().to_string()
+}
+
+pub fn convert_move_union_to_string(move_struct: &MoveStruct) -> String {
+    unsafe {
+        match move_struct {
+            MoveStruct {
+                is_castle: false,
+                data: MoveUnion { normal_move },
+                promotion,
+            } => {
+                // queen promotions are written without suffix, like the
+                // move generator lists them
+                let mut _move = convert_move_to_string(*normal_move);
+                match promotion {
+                    Some(piece_id) if *piece_id != QUEEN_ID => _move.push(promotion_char(*piece_id)),
+                    _ => {}
+                }
+                _move
+            }
+            MoveStruct {
+                is_castle: true,
+                data: MoveUnion { castle },
+                ..
+            } => convert_castle_move_to_string(*castle),
+        }
+    }
+}
+
+/// `convert_move_to_type` for move strings that do not come from the engine:
+/// a castle move or a "e2e4" move, with a UCI promotion suffix ("e7e8n")
+pub fn parse_move(_move: &str) -> Result<MoveStruct, String> {
+    let castle_moves = [
+        CASTLE_KING_SIDE_WHITE,
+        CASTLE_QUEEN_SIDE_WHITE,
+        CASTLE_KING_SIDE_BLACK,
+        CASTLE_QUEEN_SIDE_BLACK,
+    ];
+    let chars: Vec<char> = _move.chars().collect();
+    let is_square = |file: char, rank: char| ('a'..='h').contains(&file) && ('1'..='8').contains(&rank);
+    let is_normal_move = (chars.len() == 4 || (chars.len() == 5 && "qrbn".contains(chars[4])))
+        && is_square(chars[0], chars[1])
+        && is_square(chars[2], chars[3]);
+    if !is_normal_move && !castle_moves.contains(&_move) {
+        return Err(format!("Invalid move '{}'", _move));
+    }
+    Ok(convert_move_to_type(_move))
+}
 
 fn convert_move_to_type(_move: &str) -> MoveStruct {
     let letters: HashMap<&str, isize> = [
@@ -1339,36 +1498,40 @@ fn convert_move_to_type(_move: &str) -> MoveStruct {
 
     match _move {
         CASTLE_KING_SIDE_WHITE => {
-            return MoveStruct {
+            MoveStruct {
                 is_castle: true,
                 data: MoveUnion {
                     castle: Castle::KingSideWhite,
                 },
-            };
+                promotion: None,
+            }
         }
         CASTLE_QUEEN_SIDE_WHITE => {
-            return MoveStruct {
+            MoveStruct {
                 is_castle: true,
                 data: MoveUnion {
                     castle: Castle::QueenSideWhite,
                 },
-            };
+                promotion: None,
+            }
         }
         CASTLE_KING_SIDE_BLACK => {
-            return MoveStruct {
+            MoveStruct {
                 is_castle: true,
                 data: MoveUnion {
                     castle: Castle::KingSideBlack,
                 },
-            };
+                promotion: None,
+            }
         }
         CASTLE_QUEEN_SIDE_BLACK => {
-            return MoveStruct {
+            MoveStruct {
                 is_castle: true,
                 data: MoveUnion {
                     castle: Castle::QueenSideBlack,
                 },
-            };
+                promotion: None,
+            }
         }
         _ => {
             let _from_0: isize = _move[1..2].parse::<isize>().unwrap();
@@ -1377,11 +1540,13 @@ fn convert_move_to_type(_move: &str) -> MoveStruct {
             let _to_1: &str = &_move[2..3];
             let _from = (8 - _from_0, *letters.get(_from_1).unwrap());
             let _to = (8 - _to_0, *letters.get(_to_1).unwrap());
+            let promotion = if _move.len() == 5 { Some(promotion_of(_move)) } else { None };
             let _move: Move = (_from, _to);
-            return MoveStruct {
+            MoveStruct {
                 is_castle: false,
                 data: MoveUnion { normal_move: _move },
-            };
+                promotion,
+            }
         }
     }
 }
@@ -1394,7 +1559,7 @@ fn piece_is_on_board(board: &Board, piece_id: isize) -> bool {
             }
         }
     }
-    return false;
+    false
 }
 
 fn update_state(state: &mut State) {
@@ -1406,17 +1571,27 @@ fn update_state(state: &mut State) {
     state.update_player_king_checked(Color::Black, &squares_under_attack_by_white);
 }
 
-fn to_fen(state: State) -> String {
+/// FEN of a state of the engine. Panics on an unknown piece id, which
+/// states from `from_fen` or the move generator never have; states from
+/// outside go through `try_to_fen`.
+pub(crate) fn to_fen(state: State) -> String {
+    match try_to_fen(state) {
+        Ok(fen) => fen,
+        Err(message) => panic!("{}", message),
+    }
+}
+
+/// FEN of `state`, erroring on an unknown piece id
+pub fn try_to_fen(state: State) -> Result<String, String> {
     let mut fen = String::new();
   
     // Loop through each rank (row)
-    for rank in (0..8) {
+    for rank in 0..8 {
       let mut empty_squares = 0;
       for file in 0..8 {
         let piece_code = state.board[rank][file];
-        let piece = match piece_code as i32 {
-          value => get_piece_char(value)
-        };
+        let piece = get_piece_char(piece_code as i32)
+          .ok_or_else(|| format!("Invalid piece id {} at board[{}][{}]", piece_code, rank, file))?;
         if piece != '.' {
           if empty_squares > 0 {
             fen.push_str(&empty_squares.to_string());
@@ -1440,22 +1615,21 @@ fn to_fen(state: State) -> String {
     fen.push(match state.current_player {
         Color::White => 'w',
         Color::Black => 'b',
-        _ => panic!("Invalid current player"),
     });
 
     // Add castling rights
     fen.push(' ');
     let mut castling = String::new();
-    if state.white_king_castle_is_possible == true {
+    if state.white_king_castle_is_possible {
       castling.push('K');
     }
-    if state.white_queen_castle_is_possible == true {
+    if state.white_queen_castle_is_possible {
       castling.push('Q');
     }
-    if state.black_king_castle_is_possible == true {
+    if state.black_king_castle_is_possible {
       castling.push('k');
     }
-    if state.black_queen_castle_is_possible == true {
+    if state.black_queen_castle_is_possible {
       castling.push('q');
     }
     if castling.is_empty() {
@@ -1464,370 +1638,545 @@ fn to_fen(state: State) -> String {
       fen.push_str(&castling);
     }
   
-    // Add en passant target square (omitted here for simplicity)
+    // Add en passant target square
     fen.push(' ');
-    fen.push('-');
+    match state.en_passant_square {
+      Some(square) => fen.push_str(&convert_square_to_string(square)),
+      None => fen.push('-'),
+    }
   
-    // Add halfmove clock (omitted here for simplicity)
+    // Add halfmove clock
     fen.push(' ');
-    fen.push('0');
+    fen.push_str(&state.halfmove_clock.to_string());
   
     // Add fullmove number
     fen.push(' ');
-    fen.push('1');
+    fen.push_str(&state.fullmove_number.to_string());
   
-    fen
+    Ok(fen)
   }
   
-  fn get_piece_char(code: i32) -> char {
+  fn get_piece_char(code: i32) -> Option<char> {
     match code {
-      1 => 'K',
-      2 => 'Q',
-      3 => 'R',
-      4 => 'B',
-      5 => 'N',
-      6 => 'P',
-      0 => '.',
-     -1 => 'k',
-     -2 => 'q',
-     -3 => 'r',
-     -4 => 'b',
-     -5 => 'n',
-     -6 => 'p',
-      _ => panic!("Invalid piece code"),
+      1 => Some('K'),
+      2 => Some('Q'),
+      3 => Some('R'),
+      4 => Some('B'),
+      5 => Some('N'),
+      6 => Some('P'),
+      0 => Some('.'),
+     -1 => Some('k'),
+     -2 => Some('q'),
+     -3 => Some('r'),
+     -4 => Some('b'),
+     -5 => Some('n'),
+     -6 => Some('p'),
+      _ => None,
     }
   }
-  
 
-// Function to evaluate the score of a state for a player
-fn evaluate(state: &State, player: Color) -> isize {
-    // Implement logic to evaluate the state for the given player (maximize for player, minimize for opponent)
-    // let fen_str = to_fen(*state);
-    // let output = Command::new("python")
-    //     .arg("./src/evaluate.py")
-    //     .arg(fen_str)
-    //     .output()
-    //     .expect("failed to execute process");
-    
-    // let mut score = 0;
-    // if !output.status.success() {
-    //     let exit = output.status.code().unwrap_or(1);
-    //     score = exit as isize;
-    // }
-
-    // return score;
-    let mut score = 0;
+fn get_piece_id(c: char) -> Option<isize> {
+    match c {
+        'K' => Some(KING_ID),
+        'Q' => Some(QUEEN_ID),
+        'R' => Some(ROOK_ID),
+        'B' => Some(BISHOP_ID),
+        'N' => Some(KNIGHT_ID),
+        'P' => Some(PAWN_ID),
+        'k' => Some(-KING_ID),
+        'q' => Some(-QUEEN_ID),
+        'r' => Some(-ROOK_ID),
+        'b' => Some(-BISHOP_ID),
+        'n' => Some(-KNIGHT_ID),
+        'p' => Some(-PAWN_ID),
+        _ => None,
+    }
+}
 
-    // Material evaluation (piece values)
-    for rank in 0..8 {
-      for file in 0..8 {
-        if let piece = (*state).board[rank][file] {
-          score += get_value(piece) * if get_color(piece) == Some(player as Color) {
-            1
-          } else {
-            -1
-          };
+/// Build a State from a FEN string: board, side to move, castling rights,
+/// en passant square and the halfmove clock and fullmove number (0 and 1
+/// when the counters are missing)
+pub fn from_fen(fen: &str) -> Result<State, String> {
+    let fields: Vec<&str> = fen.split_whitespace().collect();
+    if fields.len() < 2 {
+        return Err(format!("Invalid FEN '{}': expected at least 2 fields", fen));
+    }
+
+    let mut board: Board = [[EMPTY_SQUARE_ID; 8]; 8];
+    let ranks: Vec<&str> = fields[0].split('/').collect();
+    if ranks.len() != 8 {
+        return Err(format!("Invalid FEN '{}': expected 8 ranks", fen));
+    }
+    for (row, rank) in ranks.iter().enumerate() {
+        let mut col: usize = 0;
+        for c in rank.chars() {
+            if let Some(empty_squares) = c.to_digit(10) {
+                col += empty_squares as usize;
+            } else {
+                let piece_id =
+                    get_piece_id(c).ok_or(format!("Invalid FEN '{}': bad piece '{}'", fen, c))?;
+                if col < 8 {
+                    board[row][col] = piece_id;
+                }
+                col += 1;
+            }
         }
-      }
-    }
-  
-    // Simple positional evaluation (pawns)
-    for rank in 2..6 {
-      for file in 0..8 {
-        if let piece = (*state).board[rank][file] {
-          if piece == 6 || piece == -6 {
-            let pawn_rank_bonus = match get_color(piece) {
-              Some(Color::White) => rank - 1,
-              Some(Color::Black) => 6 - rank,
-              _ => 0,
-            } as i32;
-            score += pawn_rank_bonus * if get_color(piece) == Some(player as Color) {
-                1
-                } else {
-                -1
-                
-            };
-          }
+        if col != 8 {
+            return Err(format!("Invalid FEN '{}': rank {} is not 8 squares", fen, 8 - row));
         }
-      }
     }
-  
-    // Additional positional factors (basic example)
-    for rank in 0..8 {
-      for file in 0..8 {
-        if let piece = (*state).board[rank][file] {
-          if get_color(piece) == Some(player as Color) {
-            // Center control bonus
-            if (rank == 3 || rank == 4) && (file == 3 || file == 4) {
-              score += 10;
-            }
-            // Mobility bonus (very simple example)
-            score += get_mobility(piece,state,(rank,file)) * if get_color(piece) == Some(player as Color){
-                1
-                } else {
-                -1
-            };
-          }
-        }
-      }
+
+    let current_player = match fields[1] {
+        "w" => Color::White,
+        "b" => Color::Black,
+        _ => return Err(format!("Invalid FEN '{}': bad side to move", fen)),
+    };
+    let castling = fields.get(2).copied().unwrap_or("-");
+    if castling != "-" && !castling.chars().all(|c| "KQkq".contains(c)) {
+        return Err(format!("Invalid FEN '{}': bad castling rights", fen));
     }
-  
-    score as isize
+
+    let en_passant = fields.get(3).copied().unwrap_or("-");
+    let en_passant_square = match en_passant.as_bytes() {
+        b"-" => None,
+        [file @ b'a'..=b'h', rank @ (b'3' | b'6')] => {
+            Some(((b'8' - rank) as isize, (file - b'a') as isize))
+        }
+        _ => return Err(format!("Invalid FEN '{}': bad en passant square", fen)),
+    };
+    let halfmove_clock = match fields.get(4) {
+        Some(clock) => clock
+            .parse::<u32>()
+            .map_err(|_| format!("Invalid FEN '{}': bad halfmove clock", fen))?,
+        None => 0,
+    };
+    let fullmove_number = match fields.get(5) {
+        Some(number) => number
+            .parse::<u32>()
+            .ok()
+            .filter(|&number| number >= 1)
+            .ok_or(format!("Invalid FEN '{}': bad fullmove number", fen))?,
+        None => 1,
+    };
+
+    let mut state = State::new(
+        board,
+        current_player,
+        castling.contains('K'),
+        castling.contains('Q'),
+        castling.contains('k'),
+        castling.contains('q'),
+    );
+    state.en_passant_square = en_passant_square;
+    state.halfmove_clock = halfmove_clock;
+    state.fullmove_number = fullmove_number;
+    Ok(state)
 }
 
-fn get_mobility(piece: isize, state: &State,position: (usize,usize)) -> i32 {
-    let mut mobility = 0;
-    for rank_delta in -1..=1 {
-      for file_delta in -1..=1 {
-        let new_rank = (position.0 as i32) + rank_delta;
-        let new_file = (position.1 as i32) + file_delta;
-        if 0 <= new_rank && new_rank < 8 && 0 <= new_file && new_file < 8 {
-          if (*state).board[new_rank as usize][new_file as usize] == 0
-             || get_color((*state).board[new_rank as usize][new_file as usize]) != get_color(piece) {
-            mobility += 1;
-          }
+/// Board of a diagram of 8 rows of 8 squares from rank 8 down, pieces as FEN
+/// letters and empty squares as '.' or '-'
+pub fn board_from_diagram(rows: &[Vec<char>]) -> Result<Board, String> {
+    if rows.len() != 8 {
+        return Err(format!("Invalid diagram: expected 8 rows, found {}", rows.len()));
+    }
+    let mut board: Board = [[EMPTY_SQUARE_ID; 8]; 8];
+    for (row, squares) in rows.iter().enumerate() {
+        if squares.len() != 8 {
+            return Err(format!(
+                "Invalid diagram: rank {} has {} squares",
+                8 - row,
+                squares.len()
+            ));
+        }
+        for (col, &c) in squares.iter().enumerate() {
+            board[row][col] = match c {
+                '.' | '-' => EMPTY_SQUARE_ID,
+                _ => get_piece_id(c)
+                    .ok_or(format!("Invalid diagram: bad piece '{}' on rank {}", c, 8 - row))?,
+            };
         }
-      }
     }
-    mobility
-  }
+    Ok(board)
+}
 
-fn get_value(piece: isize) -> i32 {
-    match piece.abs() {
-      6 => 100, // pawn
-      5 => 300, // knight
-      4 => 325, // bishop
-      3 => 500, // rook
-      2 => 900, // queen
-      1 => 20000, // King is very valuable (essentially infinite in the endgame)
-        _ => 0,
-    }
+/// `board_from_diagram` of a multiline string, one rank per line; blank
+/// lines, spaces between squares and the labels of `board_to_diagram` are
+/// ignored
+pub fn board_from_diagram_text(text: &str) -> Result<Board, String> {
+    let rows: Vec<Vec<char>> = text
+        .lines()
+        .map(|line| line.chars().filter(|c| !c.is_whitespace()).collect::<Vec<char>>())
+        .filter(|squares| !squares.is_empty() && squares.iter().collect::<String>() != "abcdefgh")
+        .map(|squares| match squares.first() {
+            Some('1'..='8') if squares.len() == 9 => squares[1..].to_vec(),
+            _ => squares,
+        })
+        .collect();
+    board_from_diagram(&rows)
 }
 
-fn get_color(piece: isize) -> Option<Color> {
-    if piece > 0 {
-      return Some(Color::White);
-    } else if piece < 0 {
-      return Some(Color::Black);
-    } else {
-      return None;
+/// ASCII diagram of `board`, ranks from 8 down with their labels and the
+/// files below, empty squares as '.' and unknown ids as '?': the inverse of
+/// `board_from_diagram_text`
+pub fn board_to_diagram(board: &Board) -> String {
+    let mut lines: Vec<String> = vec![];
+    for (row, pieces) in board.iter().enumerate() {
+        let squares: Vec<String> = pieces
+            .iter()
+            .map(|&piece_id| get_piece_char(piece_id as i32).unwrap_or('?').to_string())
+            .collect();
+        lines.push(format!("{} {}", 8 - row, squares.join(" ")));
     }
+    lines.push("  a b c d e f g h".to_string());
+    lines.join("\n")
 }
 
-// Recursive minimax function
-fn _minimax(state: &State, player: Color, depth: u32, mut alpha: isize, mut beta: isize, max: Color) -> (isize, Option<MoveStruct>) {
-    // Check if terminal state or depth reached
-    let (moves, castle_moves): (Vec<Move>, Vec<Castle>) =
-            get_all_possible_moves(&state, player, false);
+/// Problems making `state` unreachable in a legal game (empty if valid): king
+/// count, pawns on the back ranks, too many pawns or pieces, the side that just
+/// moved left in check, castling rights without the king and rook at home
+pub fn validate_position(state: &State) -> Vec<String> {
+    let mut problems: Vec<String> = vec![];
+    for &(player, name) in [(Color::White, "white"), (Color::Black, "black")].iter() {
+        let sign = player.to_int();
+        let count = |piece_id: isize| -> usize {
+            state
+                .board
+                .iter()
+                .map(|row| row.iter().filter(|&&x| x == piece_id * sign).count())
+                .sum()
+        };
+        let kings = count(KING_ID);
+        if kings != 1 {
+            problems.push(format!("{} has {} kings", name, kings));
+        }
+        let pawns = count(PAWN_ID);
+        if pawns > 8 {
+            problems.push(format!("{} has {} pawns", name, pawns));
+        }
+        let pieces: usize = (KING_ID..=PAWN_ID).map(count).sum();
+        if pieces > 16 {
+            problems.push(format!("{} has {} pieces", name, pieces));
+        }
+        let back_rank_pawns = [0, 7]
+            .iter()
+            .any(|&row| state.board[row].iter().any(|&x| x == PAWN_ID * sign));
+        if back_rank_pawns {
+            problems.push(format!("{} has pawns on the first or last rank", name));
+        }
+    }
 
-    let mut all_moves: Vec<MoveStruct> = moves.iter().map(|&x| MoveStruct {
-        is_castle: false,
-        data: MoveUnion { normal_move: x },
-    }).collect();
-    let mut all_castle_moves: Vec<MoveStruct> = castle_moves.iter().map(|&x| MoveStruct {
-        is_castle: true,
-        data: MoveUnion { castle: x },
-    }).collect();
-    all_moves.append(&mut all_castle_moves);
-    let size = all_moves.len();
-    if  size == 0 || depth == 0 {
-        let score = evaluate(state, player);
-        if max == Color::White {
-            return (score, None);
-        } else {
-            return (-score, None);
+    let other_player = get_other_player(state.current_player);
+    let other_king_on_board = match other_player {
+        Color::White => state.white_king_on_board,
+        Color::Black => state.black_king_on_board,
+    };
+    if other_king_on_board && king_is_checked(state, other_player) {
+        problems.push(format!(
+            "{} is in check but it is not its turn",
+            player_enum_to_string(&other_player).to_lowercase()
+        ));
+    }
+
+    let castling = [
+        (state.white_king_castle_is_possible, 7, 7, "K"),
+        (state.white_queen_castle_is_possible, 7, 0, "Q"),
+        (state.black_king_castle_is_possible, 0, 7, "k"),
+        (state.black_queen_castle_is_possible, 0, 0, "q"),
+    ];
+    for &(castle_is_possible, row, rook_col, right) in castling.iter() {
+        let sign = if row == 7 { 1 } else { -1 };
+        if castle_is_possible
+            && (state.board[row][4] != KING_ID * sign || state.board[row][rook_col] != ROOK_ID * sign)
+        {
+            problems.push(format!(
+                "castling right '{}' without the king and rook on their squares",
+                right
+            ));
         }
     }
-    let min = if max == Color::White { Color::Black } else { Color::White };
-    let mut best_score = if player == max { isize::MIN } else { isize::MAX };
-    let mut best_move: Option<MoveStruct> = None;
-
-    // Loop through all possible moves
-    for _move in all_moves {
-        let state_ = state.clone();
-        let (next_state, _) = next_state(&state_, player, _move.clone());
-        let (score, _) = _minimax(&next_state, if player == max { min } else { max }, depth - 1, alpha, beta, max);
-
-        if player == max {
-            best_score = best_score.max(score);
-            if best_score > alpha {
-                alpha = best_score;
-                best_move = Some(_move); // Clone to avoid ownership issues
-            }
+    problems
+}
+
+/// Legal moves of the side to move as strings ("e2e4", "CASTLE_KING_SIDE_WHITE")
+pub fn legal_move_strings(state: &State) -> Vec<String> {
+    let (moves, castle_moves) = get_all_possible_moves(state, state.current_player, false);
+    let mut moves_str: Vec<String> = moves.iter().map(|&x| convert_move_to_string(x)).collect();
+    moves_str.extend(castle_moves.iter().map(|&x| convert_castle_move_to_string(x)));
+    moves_str
+}
+
+/// `legal_move_strings` with each promotion listed once per piece it can
+/// promote to ("e7e8q", "e7e8r", "e7e8b", "e7e8n")
+pub fn legal_move_strings_all_promotions(state: &State) -> Vec<String> {
+    let mut moves_str: Vec<String> = vec![];
+    for _move in legal_move_strings(state) {
+        if is_promotion(state, &convert_move_to_type(&_move)) {
+            moves_str.extend(
+                PROMOTION_IDS
+                    .iter()
+                    .map(|&piece_id| format!("{}{}", _move, promotion_char(piece_id))),
+            );
         } else {
-            best_score = best_score.min(score);
-            if best_score < beta {
-                beta = best_score;
-            }
+            moves_str.push(_move);
         }
     }
+    moves_str
+}
 
-    return (best_score, best_move);
+/// Legal moves of the side to move in UCI notation: castles as the king's
+/// move ("e1g1") and promotions once per piece ("e7e8q", "e7e8n")
+pub fn legal_move_strings_uci(state: &State) -> Vec<String> {
+    legal_move_strings_all_promotions(state)
+        .into_iter()
+        .map(|_move| match UCI_CASTLES.iter().find(|&&(_, _, castle)| castle == _move) {
+            Some(&(uci, _, _)) => uci.to_string(),
+            None => _move,
+        })
+        .collect()
 }
 
-// PYTHON MODULE
-// ---------------------------------------------------------
-// ---------------------------------------------------------
-#[pymodule]
-fn gym_chess(_py: Python<'_>, m: &PyModule) -> PyResult<()> {
-    m.add_class::<ChessEngine>()?;
+/// UCI string of a move of `state`: castles as the king's move ("e1g1"),
+/// promotions with their piece ("e7e8q")
+pub fn move_to_uci(state: &State, move_struct: &MoveStruct) -> String {
+    let mut _move = convert_move_union_to_string(move_struct);
+    if let Some(&(uci, _, _)) = UCI_CASTLES.iter().find(|&&(_, _, castle)| castle == _move) {
+        return uci.to_string();
+    }
+    if is_promotion(state, move_struct) && move_struct.promotion.unwrap_or(QUEEN_ID) == QUEEN_ID {
+        _move.push('q');
+    }
+    _move
+}
 
-    // #[pyfn(m, "state_to_python_dict")]
-    // pub fn state_to_python_dict_py(_py: Python, state: State) -> PyResult<&PyDict> {
-    //     let dict = PyDict::new(_py);
-    //     let out = state_to_python_dict(dict, state);
-    //     Ok(out)
-    // }
+/// `make_move_str` also taking castles as the king's move ("e1g1")
+pub fn make_uci_move_str(state: &State, _move: &str) -> Result<State, String> {
+    let castle = UCI_CASTLES.iter().find(|&&(uci, king_id, _)| {
+        let row = if king_id > 0 { 7 } else { 0 };
+        uci == _move && state.board[row][4] == king_id
+    });
+    match castle {
+        Some(&(_, _, castle)) => make_move_str(state, castle),
+        None => make_move_str(state, _move),
+    }
+}
 
-    Ok(())
+/// Whether `_move` is legal for the side to move, promotions being written
+/// with their piece ("e7e8n") or without it for a queen ("e7e8")
+pub fn is_legal_move_str(state: &State, _move: &str) -> bool {
+    legal_move_strings_all_promotions(state)
+        .iter()
+        .any(|m| m == _move || (m.len() == 5 && m.ends_with('q') && m[..4] == *_move))
 }
 
+/// Play a move given as a string, rejecting illegal moves
+pub fn make_move_str(state: &State, _move: &str) -> Result<State, String> {
+    if !is_legal_move_str(state, _move) {
+        return Err(format!(
+            "Illegal move '{}' ({} to move):\n{}",
+            _move,
+            player_enum_to_string(&state.current_player).to_lowercase(),
+            board_to_diagram(&state.board)
+        ));
+    }
+    let (new_state, _) = next_state_promoting(
+        state,
+        state.current_player,
+        convert_move_to_type(_move),
+        promotion_of(_move),
+    );
+    Ok(new_state)
+}
 
 
-#[pyclass]
-pub struct ChessEngine {}
+/// Tunable weights of the handcrafted evaluation
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct EvalWeights {
+    pub pawn: i32,
+    pub knight: i32,
+    pub bishop: i32,
+    pub rook: i32,
+    pub queen: i32,
+    pub king: i32,
+    pub pawn_advance: i32,
+    pub center: i32,
+    pub mobility: i32,
+    /// per x-ray defender minus attacker of the attacked pieces
+    pub attack_defense: i32,
+}
 
-#[pymethods]
-impl ChessEngine {
-    #[new]
-    fn new() -> Self {
-        ChessEngine {}
+pub const DEFAULT_EVAL_WEIGHTS: EvalWeights = EvalWeights {
+    pawn: 100,
+    knight: 300,
+    bishop: 325,
+    rook: 500,
+    queen: 900,
+    king: 20000, // King is very valuable (essentially infinite in the endgame)
+    pawn_advance: 1,
+    center: 10,
+    mobility: 1,
+    attack_defense: 5,
+};
+
+impl EvalWeights {
+    fn piece_value(&self, piece: isize) -> i32 {
+        match piece.abs() {
+            6 => self.pawn,
+            5 => self.knight,
+            4 => self.bishop,
+            3 => self.rook,
+            2 => self.queen,
+            1 => self.king,
+            _ => 0,
+        }
     }
+}
 
-    fn next_state<'a>(
-        &mut self,
-        _py: Python<'a>,
-        state_py: &'a PyDict,
-        _player: &str,
-        _move: &str,
-    ) -> PyResult<(&'a PyDict, isize)> {
-        // parse state
-        let state: State = convert_py_state(_py, state_py)?;
-
-        // parse arguments
-        let player: Color = player_string_to_enum(_player);
-
-        // next state
-        let move_union = convert_move_to_type(_move);
-        let (mut new_state, reward) = next_state(&state, player, move_union);
-
-        // update kings under attack
-        update_state(&mut new_state);
-        // if both kings are checked, this position is impossible => raise exception
-        if new_state.white_king_is_checked == true && new_state.black_king_is_checked == true {
-            println!("Both Kings are in check: this position is impossible");
-            PyException::new_err("Both Kings are in check: this position is impossible")
-                .restore(_py);
+fn evaluate_with_weights(state: &State, player: Color, weights: &EvalWeights) -> isize {
+    // Implement logic to evaluate the state for the given player (maximize for player, minimize for opponent)
+    // (other evaluators plug into the search through `eval::Evaluator`)
+    if !state.white_king_on_board || !state.black_king_on_board {
+        return material_without_kings(state, player, weights);
+    }
+    // Material and pawn advance (piece-square table, summed with SIMD)
+    let material = MATERIAL_PST.with(|cache| {
+        let mut cache = cache.borrow_mut();
+        if cache.as_ref().map(|(cached, _)| cached) != Some(weights) {
+            *cache = Some((*weights, material_pst(weights)));
         }
-
-        // return new state
-        let new_state_py = PyDict::new(_py);
-        new_state.to_py_object(new_state_py);
-        return Ok((new_state_py, reward));
+        simd::pst_sum(&state.board, &cache.as_ref().unwrap().1)
+    });
+    let mut score = if player == Color::White { material } else { -material };
+  
+    // Additional positional factors (basic example)
+    for rank in 0..8 {
+      for file in 0..8 {
+        let piece = state.board[rank][file];
+        if get_color(piece) == Some(player as Color) {
+          // Center control bonus
+          if (rank == 3 || rank == 4) && (file == 3 || file == 4) {
+            score += weights.center;
+          }
+          // Mobility bonus (very simple example)
+          score += weights.mobility * get_mobility(piece,state,(rank,file)) * if get_color(piece) == Some(player as Color){
+              1
+              } else {
+              -1
+          };
+        }
+      }
     }
 
-    #[args(attack = false)]
-    fn get_possible_moves<'a>(
-        &mut self,
-        _py: Python<'a>,
-        state_py: &'a PyDict,
-        _player: &str,
-        attack: bool,
-    ) -> PyResult<Vec<String>> {
-        // parse state
-        let state: State = convert_py_state(_py, state_py)?;
-
-        // parse arguments
-        let player: Color = player_string_to_enum(_player);
-
-        let (mut moves, castle_moves): (Vec<Move>, Vec<Castle>) =
-            get_all_possible_moves(&state, player, attack);
-        // let moves: Vec<Move>le_moves(&st = get_possibate, player, attack);
-        // let castle_moves: Vec<Castle> = get_possible_castle_moves(&state, player, attack);
-
-        moves.retain(|_move: &Move| !move_leaves_king_checked(&state, player, *_move));
-
-        let mut moves_str: Vec<String> = moves.iter().map(|&x| convert_move_to_string(x)).collect();
-        let castle_moves_str: Vec<String> = castle_moves
-            .iter()
-            .map(|&x| convert_castle_move_to_string(x))
-            .collect();
-        moves_str.extend(castle_moves_str);
-        return Ok(moves_str);
+    // Attackers and defenders of the attacked pieces, stacked sliders included
+    if weights.attack_defense != 0 {
+      score += weights.attack_defense * attack_defense_balance(&state.board, player);
     }
 
-    fn get_castle_moves<'a>(
-        &mut self,
-        _py: Python<'a>,
-        state_py: &'a PyDict,
-        _player: &str,
-    ) -> PyResult<Vec<String>> {
-        // parse state
-        let state: State = convert_py_state(_py, state_py)?;
-
-        // parse arguments
-        let player: Color = player_string_to_enum(_player);
-
-        let castle_moves: Vec<Castle> = get_possible_castle_moves(&state, player, false);
-        let castle_moves_str: Vec<String> = castle_moves
-            .iter()
-            .map(|&x| convert_castle_move_to_string(x))
-            .collect();
-        return Ok(castle_moves_str);
+    // Pawn races: an unstoppable passed pawn is almost a queen, sooner is better
+    if let Some(race) = pawn_race(&state.board, state.current_player) {
+      let bonus = weights.queen - weights.pawn - PAWN_RACE_MOVE_PENALTY * race.moves as i32;
+      score += if race.winner == player { bonus } else { -bonus };
     }
+  
+    score as isize
+}
+
+thread_local! {
+    /// Material table of the last weights evaluated on this thread
+    static MATERIAL_PST: RefCell<Option<(EvalWeights, Box<[i32; simd::PST_SIZE]>)>> = const { RefCell::new(None) };
+}
 
-    fn update_state<'a>(&mut self, _py: Python<'a>, state_py: &'a PyDict) -> PyResult<&'a PyDict> {
-        // parse state
-        let mut state: State = convert_py_state(_py, state_py)?;
-        // update kings under attack
-        update_state(&mut state);
-        // Python state
-        let state_py = PyDict::new(_py);
-        state.to_py_object(state_py);
-        return Ok(state_py);
+/// White's material and pawn advance for every piece on every square, in
+/// the layout of `simd::pst_sum`
+fn material_pst(weights: &EvalWeights) -> Box<[i32; simd::PST_SIZE]> {
+    let mut pst = Box::new([0; simd::PST_SIZE]);
+    for piece in -6..=6isize {
+        for square in 0..64 {
+            let rank = square / 8;
+            let mut value = weights.piece_value(piece) * piece.signum() as i32;
+            if (2..6).contains(&rank) && piece == 6 {
+                value += weights.pawn_advance * (rank as i32 - 1);
+            }
+            if (2..6).contains(&rank) && piece == -6 {
+                value -= weights.pawn_advance * (6 - rank as i32);
+            }
+            pst[(piece + 6) as usize * 64 + square] = value;
+        }
     }
+    pst
+}
 
-    fn minimax<'a>(
-        &mut self,
-        _py: Python<'a>,
-        state_py: &'a PyDict,
-        depth: usize,
-        player: &str,
-    ) -> PyResult<Py<PyTuple>> {
-        // parse state
-        let state: State = convert_py_state(_py, state_py)?;
-        // let data = to_fen(state);
-        // let mut file = File::create("fen.txt")?;
-        // file.write_all(data.as_bytes())?;
-
-        // parse arguments
-        let player: Color = player_string_to_enum(player);
-
-        let mut alpha: isize = std::isize::MIN;
-        let mut beta: isize = std::isize::MAX;
-        let mut best_move: Option<MoveStruct> = None;
-        let mut best_score: isize = std::isize::MIN;
-
-        let (best_score, best_move) = _minimax(&state, player, depth as u32, alpha, beta, player);
-        let result = Ok((best_score, best_move));
-        match result {
-            Ok((best_score, best_move)) => {
-                let gil = Python::acquire_gil();
-                let py = gil.python();
-                let best_score = best_score.to_object(py);
-                unsafe {
-                    let best_move_: PyObject = match best_move {
-                        Some(m) => match m.is_castle {
-                            true => convert_castle_move_to_string(m.data.castle).to_object(py),
-                            false => convert_move_to_string(m.data.normal_move).to_object(py),
-                        }
-                        None => "".to_string().to_object(py),
-                    };
-                    let tuple = PyTuple::new(py, vec![best_score, best_move_]);
-                    return Ok(tuple.into());
-                }
-            },
-            Err(e) => Err(e),
+/// Evaluation of study positions missing a king: material only, kings excluded
+fn material_without_kings(state: &State, player: Color, weights: &EvalWeights) -> isize {
+    let mut score = 0;
+    for row in state.board.iter() {
+        for &piece in row.iter() {
+            if piece.abs() == KING_ID {
+                continue;
+            }
+            let sign = if get_color(piece) == Some(player) { 1 } else { -1 };
+            score += weights.piece_value(piece) * sign;
         }
     }
+    score as isize
+}
+
+fn get_mobility(piece: isize, state: &State,position: (usize,usize)) -> i32 {
+    let mut mobility = 0;
+    for rank_delta in -1..=1 {
+      for file_delta in -1..=1 {
+        let new_rank = (position.0 as i32) + rank_delta;
+        let new_file = (position.1 as i32) + file_delta;
+        if (0..8).contains(&new_rank) && (0..8).contains(&new_file)
+          && (state.board[new_rank as usize][new_file as usize] == 0
+             || get_color(state.board[new_rank as usize][new_file as usize]) != get_color(piece)) {
+            mobility += 1;
+          }
+      }
+    }
+    mobility
+  }
+
+fn get_color(piece: isize) -> Option<Color> {
+    if piece > 0 {
+      Some(Color::White)
+    } else if piece < 0 {
+      Some(Color::Black)
+    } else {
+      None
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn en_passant() {
+        let state = from_fen("4k3/8/8/8/4p3/8/3P4/4K3 w - - 0 1").unwrap();
+
+        // the double push sets the square passed over, kept by the FEN
+        let pushed = make_move_str(&state, "d2d4").unwrap();
+        assert_eq!(pushed.en_passant_square, Some((5, 3)));
+        let fen = try_to_fen(pushed).unwrap();
+        assert_eq!(fen, "4k3/8/8/8/3Pp3/8/8/4K3 b - d3 0 1");
+        assert_eq!(try_to_fen(from_fen(&fen).unwrap()).unwrap(), fen);
+        assert_eq!(from_fen(&fen).unwrap().en_passant_square, Some((5, 3)));
+
+        // the capture lands behind the pawn and removes it
+        assert!(legal_move_strings(&pushed).contains(&"e4d3".to_string()));
+        let captured = make_move_str(&pushed, "e4d3").unwrap();
+        assert_eq!(captured.board[5][3], -PAWN_ID);
+        assert_eq!(captured.board[4][3], EMPTY_SQUARE_ID);
+        assert_eq!(captured.board[4][4], EMPTY_SQUARE_ID);
+        assert_eq!(captured.en_passant_square, None);
+        assert_eq!(try_to_fen(captured).unwrap(), "4k3/8/8/8/8/3p4/8/4K3 w - - 0 2");
+
+        // and it is only allowed on the next ply
+        let waited = make_move_str(&pushed, "e8d8").unwrap();
+        assert_eq!(waited.en_passant_square, None);
+        let waited = make_move_str(&waited, "e1e2").unwrap();
+        assert!(!legal_move_strings(&waited).contains(&"e4d3".to_string()));
+        assert!(make_move_str(&waited, "e4d3").is_err());
+    }
 }
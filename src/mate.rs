@@ -0,0 +1,149 @@
+use crate::rng::Rng;
+use crate::selfplay::all_moves;
+use crate::{king_is_checked, next_state, to_fen, Color, MoveStruct, State, DEFAULT_BOARD};
+
+//
+// Constants
+//
+pub const DEFAULT_MAX_MATE_DEPTH: u32 = 2;
+/// random games played per requested puzzle before giving up
+const GAMES_PER_PUZZLE: usize = 20;
+/// the first plies of the random games are too close to the initial position
+const MIN_PUZZLE_PLY: usize = 10;
+const MAX_GAME_PLIES: usize = 300;
+
+//
+// Structs
+//
+/// A position where the side to move mates in `mate_in` moves, with a single
+/// first move doing so
+#[derive(Clone)]
+pub struct MatePuzzle {
+    pub state: State,
+    pub mate_in: u32,
+    /// the mating line, the defender playing the longest resistance
+    pub solution: Vec<MoveStruct>,
+}
+
+/// Whether the side to move mates in at most `moves` moves whatever the defence
+pub fn mates_within(state: &State, moves: u32) -> bool {
+    if moves == 0 {
+        return false;
+    }
+    all_moves(state, state.current_player)
+        .into_iter()
+        .any(|_move| defender_lost_within(state, _move, moves - 1))
+}
+
+/// First moves of the side to move that mate in at most `moves` moves
+pub fn mating_moves(state: &State, moves: u32) -> Vec<MoveStruct> {
+    if moves == 0 {
+        return vec![];
+    }
+    all_moves(state, state.current_player)
+        .into_iter()
+        .filter(|_move| defender_lost_within(state, _move.clone(), moves - 1))
+        .collect()
+}
+
+/// Shortest forced mate of the side to move in at most `max_moves` moves:
+/// its length and a mating line, the defender playing the longest resistance
+pub fn solve_mate(state: &State, max_moves: u32) -> Option<(u32, Vec<MoveStruct>)> {
+    let mate_in = (1..=max_moves).find(|&moves| mates_within(state, moves))?;
+    Some((mate_in, mating_line(state, mate_in)))
+}
+
+/// Look for `n` mate puzzles of at most `max_mate_depth` moves in the
+/// positions of random games. A position makes a puzzle when the shortest
+/// mate has a single first move. Fewer puzzles are returned if none are found
+/// within GAMES_PER_PUZZLE games per puzzle.
+pub fn generate_mate_puzzles(n: usize, max_mate_depth: u32, rng: &mut Rng) -> Vec<MatePuzzle> {
+    let mut puzzles: Vec<MatePuzzle> = vec![];
+    for _ in 0..n * GAMES_PER_PUZZLE {
+        if puzzles.len() >= n {
+            break;
+        }
+        let mut state = State::new(DEFAULT_BOARD, Color::White, true, true, true, true);
+        for ply in 0..MAX_GAME_PLIES {
+            let moves = all_moves(&state, state.current_player);
+            if moves.is_empty() {
+                break;
+            }
+            if ply >= MIN_PUZZLE_PLY {
+                if let Some(puzzle) = mate_puzzle(&state, max_mate_depth) {
+                    // one puzzle per game, the next positions are alike
+                    if !puzzles
+                        .iter()
+                        .any(|p| to_fen(p.state) == to_fen(puzzle.state))
+                    {
+                        puzzles.push(puzzle);
+                    }
+                    break;
+                }
+            }
+            let _move = moves[rng.below(moves.len())].clone();
+            state = next_state(&state, state.current_player, _move).0;
+        }
+    }
+    puzzles
+}
+
+// HELPER FUNCTIONS
+// ---------------------------------------------------------
+// ---------------------------------------------------------
+
+/// Whether `_move` leaves the defender mated, or mated within `moves` more
+/// moves of the attacker whatever it replies
+fn defender_lost_within(state: &State, _move: MoveStruct, moves: u32) -> bool {
+    let attacker = state.current_player;
+    let after = next_state(state, attacker, _move).0;
+    let defender = after.current_player;
+    let replies = all_moves(&after, defender);
+    if replies.is_empty() {
+        return king_is_checked(&after, defender);
+    }
+    if moves == 0 {
+        return false;
+    }
+    replies.into_iter().all(|reply| {
+        let position = next_state(&after, defender, reply).0;
+        mates_within(&position, moves)
+    })
+}
+
+/// A line of the forced mate in `mate_in` moves of the side to move
+fn mating_line(state: &State, mate_in: u32) -> Vec<MoveStruct> {
+    let attacker = state.current_player;
+    let _move = mating_moves(state, mate_in).remove(0);
+    let after = next_state(state, attacker, _move.clone()).0;
+    let mut line = vec![_move];
+    let defender = after.current_player;
+    // the reply delaying the mate the longest
+    let longest = all_moves(&after, defender)
+        .into_iter()
+        .map(|reply| {
+            let position = next_state(&after, defender, reply.clone()).0;
+            let moves = (1..mate_in).find(|&moves| mates_within(&position, moves));
+            (moves.unwrap_or(mate_in - 1), reply, position)
+        })
+        .max_by_key(|(moves, _, _)| *moves);
+    if let Some((moves, reply, position)) = longest {
+        line.push(reply);
+        line.extend(mating_line(&position, moves));
+    }
+    line
+}
+
+/// The puzzle of `state` if its side to move has a forced mate of at most
+/// `max_mate_depth` moves with a single first move
+fn mate_puzzle(state: &State, max_mate_depth: u32) -> Option<MatePuzzle> {
+    let mate_in = (1..=max_mate_depth).find(|&moves| mates_within(state, moves))?;
+    if mating_moves(state, mate_in).len() != 1 {
+        return None;
+    }
+    Some(MatePuzzle {
+        state: *state,
+        mate_in,
+        solution: mating_line(state, mate_in),
+    })
+}
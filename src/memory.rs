@@ -0,0 +1,70 @@
+use crate::move_cache::MOVE_CACHE_ENTRY_BYTES;
+
+//
+// Structs
+//
+/// Memory held by the tables of an engine, in bytes (there is no pawn hash
+/// table, and the tablebases are not probed so nothing of them is cached)
+#[derive(Debug, Default, Copy, Clone)]
+pub struct MemoryUsage {
+    pub tt: usize,
+    pub move_cache: usize,
+    /// None without an opening book
+    pub book: Option<usize>,
+}
+
+impl MemoryUsage {
+    pub fn total(&self) -> usize {
+        self.tt + self.move_cache + self.book.unwrap_or(0)
+    }
+}
+
+/// Transposition table size and move cache capacity fitting in `max_mb`:
+/// when the tables add up to more, both are scaled down by the same factor
+/// (the transposition table keeps at least 1 MB)
+pub fn fit_tables(hash_mb: usize, move_cache_entries: usize, max_mb: usize) -> (usize, usize) {
+    let max_bytes = max_mb * 1024 * 1024;
+    let hash_bytes = hash_mb * 1024 * 1024;
+    let move_cache_bytes = move_cache_entries * MOVE_CACHE_ENTRY_BYTES;
+    let total = hash_bytes + move_cache_bytes;
+    if total <= max_bytes {
+        return (hash_mb, move_cache_entries);
+    }
+    let scale = max_bytes as f64 / total as f64;
+    let hash_mb = ((hash_mb as f64 * scale) as usize).max(1);
+    let move_cache_entries = (move_cache_entries as f64 * scale) as usize;
+    (hash_mb, move_cache_entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::EngineConfig;
+    use crate::move_cache::MoveCache;
+    use crate::tt::TranspositionTable;
+
+    const MB: usize = 1024 * 1024;
+
+    #[test]
+    fn tables_fit_the_cap() {
+        // 64 MB and 16 MB scaled down by 48 / 80
+        assert_eq!(fit_tables(64, 16384, 48), (38, 9830));
+        assert_eq!(fit_tables(16, 1024, 48), (16, 1024));
+        assert_eq!(fit_tables(64, 16384, 0), (1, 0));
+
+        let mut config = EngineConfig::from_toml(
+            "[engine]\nhash_mb = 64\nmove_cache_entries = 16384\nmax_memory_mb = 48\n",
+        )
+        .unwrap();
+        assert_eq!((config.hash_mb, config.move_cache_entries), (38, 9830));
+        config.move_cache_entries = 16384;
+        config.fit_memory();
+        let tt = TranspositionTable::new(config.hash_mb);
+        let move_cache = MoveCache::new(config.move_cache_entries);
+        assert!(tt.size_bytes() + move_cache.capacity() * MOVE_CACHE_ENTRY_BYTES <= 48 * MB);
+        config.max_memory_mb = None;
+        config.fit_memory();
+        assert_eq!(config.move_cache_entries, move_cache.capacity());
+    }
+
+}
@@ -0,0 +1,123 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, VecDeque};
+use std::hash::Hasher;
+use std::mem::size_of;
+
+use crate::tt::position_key;
+use crate::{Color, State};
+
+//
+// Constants
+//
+pub const DEFAULT_MOVE_CACHE_ENTRIES: usize = 4096;
+/// typical memory of an entry (some 30 moves), to size the cache for a budget
+pub const MOVE_CACHE_ENTRY_BYTES: usize = 1024;
+
+//
+// Structs
+//
+/// Counters collected since the cache was created or cleared
+#[derive(Debug, Default, Copy, Clone)]
+pub struct MoveCacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    /// entries dropped to make room for new ones
+    pub evictions: u64,
+}
+
+/// Legal move lists keyed by `move_cache_key` (from the Zobrist hash of the
+/// position), evicting the oldest entry once `capacity` positions are stored
+/// (a capacity of 0 disables the cache)
+pub struct MoveCache {
+    entries: HashMap<u64, Vec<String>>,
+    /// keys in insertion order, oldest first
+    order: VecDeque<u64>,
+    capacity: usize,
+    pub stats: MoveCacheStats,
+}
+
+impl MoveCache {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            entries: HashMap::new(),
+            order: VecDeque::new(),
+            capacity,
+            stats: MoveCacheStats::default(),
+        }
+    }
+
+    pub fn num_entries(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Memory held by the stored move lists (estimated from their lengths)
+    pub fn size_bytes(&self) -> usize {
+        let lists: usize = self
+            .entries
+            .values()
+            .map(|moves| {
+                size_of::<Vec<String>>()
+                    + moves.capacity() * size_of::<String>()
+                    + moves.iter().map(|_move| _move.capacity()).sum::<usize>()
+            })
+            .sum();
+        lists + self.entries.len() * 2 * size_of::<u64>()
+    }
+
+    /// Change the number of positions kept, evicting the oldest ones if needed
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        self.evict();
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.order.clear();
+        self.stats = MoveCacheStats::default();
+    }
+
+    /// Moves stored for `key`, or the ones `generate` returns, which are stored
+    pub fn get_or_insert_with<F: FnOnce() -> Vec<String>>(
+        &mut self,
+        key: u64,
+        generate: F,
+    ) -> Vec<String> {
+        if let Some(moves) = self.entries.get(&key) {
+            self.stats.hits += 1;
+            return moves.clone();
+        }
+        self.stats.misses += 1;
+        let moves = generate();
+        if self.capacity > 0 {
+            self.entries.insert(key, moves.clone());
+            self.order.push_back(key);
+            self.evict();
+        }
+        moves
+    }
+
+    fn evict(&mut self) {
+        while self.entries.len() > self.capacity {
+            let key = self.order.pop_front().unwrap();
+            self.entries.remove(&key);
+            self.stats.evictions += 1;
+        }
+    }
+}
+
+/// Key of the moves of `player` in `state` (the player is not necessarily the
+/// side to move, and attack moves are cached apart from legal moves)
+pub fn move_cache_key(state: &State, player: Color, attack: bool) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    hasher.write_u64(position_key(state));
+    hasher.write_u8(match player {
+        Color::White => 0,
+        Color::Black => 1,
+    });
+    hasher.write_u8(attack as u8);
+    hasher.finish()
+}
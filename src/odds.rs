@@ -0,0 +1,67 @@
+use crate::{Color, State, DEFAULT_BOARD, EMPTY_SQUARE_ID};
+
+//
+// Types
+//
+/// (name, squares emptied for a white giver, giver also gives the move)
+type Odds = (&'static str, &'static [(usize, usize)], bool);
+
+//
+// Constants
+//
+const ODDS: [Odds; 8] = [
+    ("pawn", &[(6, 5)], false),
+    ("pawn_and_move", &[(6, 5)], true),
+    ("knight", &[(7, 1)], false),
+    ("bishop", &[(7, 2)], false),
+    ("rook", &[(7, 0)], false),
+    ("two_knights", &[(7, 1), (7, 6)], false),
+    ("rook_and_knight", &[(7, 0), (7, 1)], false),
+    ("queen", &[(7, 3)], false),
+];
+
+/// Names of the odds accepted by `odds_position`
+pub fn odds_names() -> Vec<&'static str> {
+    ODDS.iter().map(|(name, _, _)| *name).collect()
+}
+
+/// Initial position with `giver` playing without the material of the named
+/// odds (f-pawn, queen's knight, queen's bishop, queen's rook, ...). Castling
+/// on the side of a removed rook is not allowed; with "pawn_and_move" the
+/// other side moves first.
+pub fn odds_position(name: &str, giver: Color) -> Result<State, String> {
+    let (_, squares, gives_move) = ODDS
+        .iter()
+        .find(|(odds_name, _, _)| *odds_name == name)
+        .ok_or(format!(
+            "unknown odds '{}', expected one of {:?}",
+            name,
+            odds_names()
+        ))?;
+
+    let mut board = DEFAULT_BOARD;
+    for &(row, col) in squares.iter() {
+        let row = match giver {
+            Color::White => row,
+            Color::Black => 7 - row,
+        };
+        board[row][col] = EMPTY_SQUARE_ID;
+    }
+    let giver_queen_castle = !squares.contains(&(7, 0));
+    let giver_king_castle = !squares.contains(&(7, 7));
+    // white moves first unless a white giver also gives the move
+    let white_to_move = !(*gives_move && giver == Color::White);
+    let (white_king_castle, white_queen_castle, black_king_castle, black_queen_castle) = match giver
+    {
+        Color::White => (giver_king_castle, giver_queen_castle, true, true),
+        Color::Black => (true, true, giver_king_castle, giver_queen_castle),
+    };
+    Ok(State::new(
+        board,
+        if white_to_move { Color::White } else { Color::Black },
+        white_king_castle,
+        white_queen_castle,
+        black_king_castle,
+        black_queen_castle,
+    ))
+}
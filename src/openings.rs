@@ -0,0 +1,312 @@
+use std::fs;
+use std::path::Path;
+
+use crate::pgn::split_pgn;
+use crate::rng::Rng;
+use crate::san::parse_san;
+use crate::{from_fen, next_state, Color, State, DEFAULT_BOARD};
+
+//
+// Types
+//
+/// SAN move and its position in the movetext
+type MovetextMove = (usize, String);
+/// unbalanced brace or parenthesis: position, text, problem
+type MovetextProblem = (usize, String, &'static str);
+
+//
+// Structs
+//
+/// A starting position of an opening suite
+#[derive(Debug, Clone)]
+pub struct Opening {
+    pub name: String,
+    pub state: State,
+    /// relative probability of being sampled
+    pub weight: f64,
+}
+
+/// Part of a PGN file the lenient reader skipped
+#[derive(Debug, Clone)]
+pub struct SkippedMovetext {
+    /// byte offset of the skipped text in the file
+    pub offset: usize,
+    /// 1-based game number
+    pub game: usize,
+    pub text: String,
+    pub reason: String,
+}
+
+/// Starting positions for self-play and match games, sampled by weight
+#[derive(Debug, Clone)]
+pub struct OpeningSuite {
+    pub openings: Vec<Opening>,
+}
+
+impl OpeningSuite {
+    /// Load an EPD (".epd") or PGN (any other extension) suite
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let suite = match path.extension().and_then(|ext| ext.to_str()) {
+            Some("epd") => Self::from_epd(&text),
+            _ => Self::from_pgn(&text),
+        };
+        suite.map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    /// Like `load`, but PGN files are read by `from_pgn_lenient`
+    pub fn load_lenient(path: &Path) -> Result<(Self, Vec<SkippedMovetext>), String> {
+        let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let suite = match path.extension().and_then(|ext| ext.to_str()) {
+            Some("epd") => Self::from_epd(&text).map(|suite| (suite, vec![])),
+            _ => Self::from_pgn_lenient(&text),
+        };
+        suite.map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    /// One position per line: the 4 FEN position fields followed by
+    /// operations, of which `id "name";` and `weight 2.5;` are used
+    pub fn from_epd(text: &str) -> Result<Self, String> {
+        let mut openings: Vec<Opening> = vec![];
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let fields: Vec<&str> = line.splitn(5, char::is_whitespace).collect();
+            if fields.len() < 4 {
+                return Err(format!("line {}: expected 4 FEN fields", i + 1));
+            }
+            let state =
+                from_fen(&fields[..4].join(" ")).map_err(|e| format!("line {}: {}", i + 1, e))?;
+            let mut name = format!("line {}", i + 1);
+            let mut weight = 1.0;
+            for operation in fields.get(4).unwrap_or(&"").split(';') {
+                let operation = operation.trim();
+                let (opcode, operand) = match operation.find(char::is_whitespace) {
+                    Some(j) => (&operation[..j], operation[j..].trim()),
+                    None => (operation, ""),
+                };
+                match opcode {
+                    "id" => name = operand.trim_matches('"').to_string(),
+                    "weight" => {
+                        weight =
+                            parse_weight(operand).map_err(|e| format!("line {}: {}", i + 1, e))?
+                    }
+                    _ => {}
+                }
+            }
+            openings.push(Opening {
+                name,
+                state,
+                weight,
+            });
+        }
+        Self::new(openings)
+    }
+
+    /// The final positions of the games of a PGN file. The name comes from the
+    /// `Opening`, `ECO` or `Event` tags, the weight from a `Weight` tag and the
+    /// games start from their `FEN` tag if there is one. Comments, variations
+    /// and annotations are skipped.
+    pub fn from_pgn(text: &str) -> Result<Self, String> {
+        Self::read_pgn(text, false).map(|(suite, _)| suite)
+    }
+
+    /// `from_pgn` for messy databases: instead of failing, a game with an
+    /// invalid `FEN` tag is skipped, an invalid `Weight` counts as 1, a game
+    /// ends at its first unplayable move (the rest of its movetext is
+    /// skipped) and unbalanced comments and variations are reported; each
+    /// problem is returned with its byte offset in `text`
+    pub fn from_pgn_lenient(text: &str) -> Result<(Self, Vec<SkippedMovetext>), String> {
+        Self::read_pgn(text, true)
+    }
+
+    fn read_pgn(text: &str, lenient: bool) -> Result<(Self, Vec<SkippedMovetext>), String> {
+        let mut openings: Vec<Opening> = vec![];
+        let mut skipped: Vec<SkippedMovetext> = vec![];
+        for (i, game) in split_pgn(text).iter().enumerate() {
+            let tag = |name: &str| game.tag(name).map(|(value, _)| value);
+            let game_error = |e: String| format!("game {}: {}", i + 1, e);
+            let mut skip = |offset: usize, text: &str, reason: String| {
+                skipped.push(SkippedMovetext {
+                    offset,
+                    game: i + 1,
+                    text: text.to_string(),
+                    reason,
+                })
+            };
+            let mut state = match tag("FEN").map(from_fen) {
+                Some(Ok(state)) => state,
+                Some(Err(e)) if lenient => {
+                    let (fen, offset) = game.tag("FEN").unwrap();
+                    skip(offset, fen, format!("{}, game skipped", e));
+                    continue;
+                }
+                Some(Err(e)) => return Err(game_error(e)),
+                None => State::new(DEFAULT_BOARD, Color::White, true, true, true, true),
+            };
+            let (moves, problems) = movetext_moves(&game.movetext);
+            if lenient {
+                for (pos, text, reason) in problems {
+                    skip(game.movetext_offset(pos), &text, reason.to_string());
+                }
+            }
+            for (pos, san) in moves {
+                match parse_san(&state, &san) {
+                    Ok(_move) => state = next_state(&state, state.current_player, _move).0,
+                    Err(e) if lenient => {
+                        skip(
+                            game.movetext_offset(pos),
+                            &san,
+                            format!("{}, rest of the game skipped", e),
+                        );
+                        break;
+                    }
+                    Err(e) => return Err(game_error(e)),
+                }
+            }
+            let name = tag("Opening")
+                .or_else(|| tag("ECO"))
+                .or_else(|| tag("Event"))
+                .map(|name| name.to_string())
+                .unwrap_or_else(|| format!("game {}", i + 1));
+            let weight = match tag("Weight").map(parse_weight) {
+                Some(Ok(weight)) => weight,
+                Some(Err(e)) if lenient => {
+                    let (weight, offset) = game.tag("Weight").unwrap();
+                    skip(offset, weight, format!("{}, weight 1 used", e));
+                    1.0
+                }
+                Some(Err(e)) => return Err(game_error(e)),
+                None => 1.0,
+            };
+            openings.push(Opening {
+                name,
+                state,
+                weight,
+            });
+        }
+        Self::new(openings).map(|suite| (suite, skipped))
+    }
+
+    fn new(openings: Vec<Opening>) -> Result<Self, String> {
+        if !openings.iter().any(|opening| opening.weight > 0.0) {
+            return Err("no opening with a positive weight".to_string());
+        }
+        Ok(OpeningSuite { openings })
+    }
+
+    /// Pick an opening with probability proportional to its weight
+    pub fn sample(&self, rng: &mut Rng) -> &Opening {
+        let total: f64 = self.openings.iter().map(|opening| opening.weight).sum();
+        let mut target = rng.next_f64() * total;
+        for opening in self.openings.iter() {
+            if target < opening.weight {
+                return opening;
+            }
+            target -= opening.weight;
+        }
+        // rounding left `target` just above the last weight
+        self
+            .openings
+            .iter()
+            .rev()
+            .find(|opening| opening.weight > 0.0)
+            .unwrap()
+    }
+}
+
+// HELPER FUNCTIONS
+// ---------------------------------------------------------
+// ---------------------------------------------------------
+
+fn parse_weight(text: &str) -> Result<f64, String> {
+    match text.trim().trim_matches('"').parse::<f64>() {
+        Ok(weight) if weight >= 0.0 && weight.is_finite() => Ok(weight),
+        _ => Err(format!("invalid weight '{}'", text)),
+    }
+}
+
+/// SAN moves of the main line with their position in `movetext`, without
+/// move numbers, comments, variations, annotations and the result; and the
+/// unbalanced braces and parentheses (position, text, problem)
+fn movetext_moves(movetext: &str) -> (Vec<MovetextMove>, Vec<MovetextProblem>) {
+    let mut moves: Vec<MovetextMove> = vec![];
+    let mut problems: Vec<MovetextProblem> = vec![];
+    let mut variation_starts: Vec<usize> = vec![];
+    let mut comment_start: Option<usize> = None;
+    let mut token = String::new();
+    let mut token_start = 0;
+    let mut chars = movetext.char_indices().peekable();
+    while let Some((pos, c)) = chars.next() {
+        if comment_start.is_some() {
+            if c == '}' {
+                comment_start = None;
+            }
+            continue;
+        }
+        let ends_token = c.is_whitespace() || "{}();".contains(c);
+        if ends_token {
+            if variation_starts.is_empty() {
+                push_move(&mut moves, token_start, &token);
+            }
+            token.clear();
+        }
+        match c {
+            '{' => comment_start = Some(pos),
+            '}' => problems.push((pos, c.to_string(), "unmatched '}'")),
+            '(' => variation_starts.push(pos),
+            ')'
+                if variation_starts.pop().is_none() => {
+                    problems.push((pos, c.to_string(), "unmatched ')'"));
+                }
+            ';' => {
+                // comment up to the end of the line
+                while chars.peek().map_or(false, |&(_, c)| c != '\n') {
+                    chars.next();
+                }
+            }
+            _ if !ends_token => {
+                if token.is_empty() {
+                    token_start = pos;
+                }
+                token.push(c)
+            }
+            _ => {}
+        }
+    }
+    if variation_starts.is_empty() {
+        push_move(&mut moves, token_start, &token);
+    }
+    if let Some(pos) = comment_start {
+        problems.push((
+            pos,
+            "{".to_string(),
+            "unterminated comment, rest of the game skipped",
+        ));
+    }
+    if let Some(&pos) = variation_starts.first() {
+        problems.push((
+            pos,
+            "(".to_string(),
+            "unterminated variation, rest of the game skipped",
+        ));
+    }
+    (moves, problems)
+}
+
+fn push_move(moves: &mut Vec<MovetextMove>, start: usize, token: &str) {
+    if ["1-0", "0-1", "1/2-1/2", "*"].contains(&token) {
+        return;
+    }
+    // "12." and "12..." prefixes may be glued to the move
+    let (start, token) = match token.rfind('.') {
+        Some(i) => (start + i + 1, &token[i + 1..]),
+        None => (start, token),
+    };
+    if token.is_empty() || token.starts_with('$') || token.chars().all(|c| c.is_ascii_digit()) {
+        return;
+    }
+    moves.push((start, token.to_string()));
+}
@@ -0,0 +1,760 @@
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+use crate::rules::{Rules, Standard};
+use crate::san::{parse_san, san_promotion, to_san};
+use crate::{
+    convert_move_union_to_string, from_fen, is_legal_move_str, next_state_promoting, to_fen,
+    update_state, Color, MoveStruct, State, DEFAULT_BOARD, QUEEN_ID,
+};
+
+//
+// Constants
+//
+/// movetext lines are wrapped at this width
+const LINE_WIDTH: usize = 80;
+const RESULTS: [&str; 4] = ["1-0", "0-1", "1/2-1/2", "*"];
+/// tags every PGN game starts with, in this order
+pub const SEVEN_TAG_ROSTER: [&str; 7] = ["Event", "Site", "Date", "Round", "White", "Black", "Result"];
+/// NAG of each move suffix annotation
+const SUFFIX_NAGS: [(&str, u32); 6] = [
+    ("!", 1),
+    ("?", 2),
+    ("!!", 3),
+    ("??", 4),
+    ("!?", 5),
+    ("?!", 6),
+];
+
+//
+// Structs
+//
+/// A move of a PGN game with its annotations and the recursive annotation
+/// variations (RAV) replacing it
+#[derive(Clone)]
+pub struct PgnMove {
+    pub _move: MoveStruct,
+    /// piece promoted to (positive id), a queen for the other moves
+    pub promotion: isize,
+    pub san: String,
+    /// numeric annotation glyphs ("$2", or "?" after the move)
+    pub nags: Vec<u32>,
+    /// comment after the move
+    pub comment: Option<String>,
+    /// alternatives to this move, each a line played from the position
+    /// before it
+    pub variations: Vec<Vec<PgnMove>>,
+}
+
+impl PgnMove {
+    /// `_move` of the side to move of `state`, without annotations
+    pub fn new(state: &State, _move: MoveStruct, promotion: isize) -> Self {
+        PgnMove {
+            san: to_san(state, &_move, promotion),
+            _move,
+            promotion,
+            nags: vec![],
+            comment: None,
+            variations: vec![],
+        }
+    }
+
+    /// UCI string of the move ("e7e8n" for an underpromotion)
+    pub fn to_uci(&self) -> String {
+        convert_move_union_to_string(&MoveStruct {
+            promotion: Some(self.promotion),
+            ..self._move.clone()
+        })
+    }
+}
+
+/// A game of a PGN file with its variations
+#[derive(Clone)]
+pub struct PgnGame {
+    pub tags: Vec<(String, String)>,
+    /// position before the first move: the `FEN` tag or the initial position
+    pub start: State,
+    /// number of the first move (the last field of the `FEN` tag)
+    pub move_number: u32,
+    /// comment before the first move
+    pub comment: Option<String>,
+    pub moves: Vec<PgnMove>,
+    /// "1-0", "0-1", "1/2-1/2" or "*"
+    pub result: String,
+}
+
+/// Token of PGN movetext
+enum Token {
+    Move(String),
+    Nag(u32),
+    Comment(String),
+    Open,
+    Close,
+    Result(String),
+}
+
+/// The games of a PGN file with their comments, NAGs and variations. The
+/// comments before the first move of a variation are dropped.
+pub fn parse_pgn(text: &str) -> Result<Vec<PgnGame>, String> {
+    let mut games: Vec<PgnGame> = vec![];
+    for (i, pgn_text) in split_pgn(text).iter().enumerate() {
+        let game = parse_pgn_text(pgn_text).map_err(|e| format!("game {}: {}", i + 1, e))?;
+        games.push(game);
+    }
+    Ok(games)
+}
+
+/// Games of a PGN stream read one at a time, so that files too large to
+/// load (e.g. database dumps) can be parsed lazily. Each item is a game or
+/// why it failed to parse ("game 3: ..."); a game that fails does not end
+/// the iteration, a read error does.
+pub struct PgnReader<R: BufRead> {
+    reader: R,
+    splitter: PgnSplitter,
+    /// games split so far
+    games: usize,
+    done: bool,
+}
+
+impl PgnReader<BufReader<File>> {
+    /// Reader of the PGN file at `path`
+    pub fn open(path: &Path) -> Result<Self, String> {
+        let file = File::open(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        Ok(PgnReader::new(BufReader::new(file)))
+    }
+}
+
+impl<R: BufRead> PgnReader<R> {
+    pub fn new(reader: R) -> Self {
+        PgnReader {
+            reader,
+            splitter: PgnSplitter::new(),
+            games: 0,
+            done: false,
+        }
+    }
+
+    /// Number of games read so far
+    pub fn games_read(&self) -> usize {
+        self.games
+    }
+
+    /// Tags and movetext of the next game, None at the end of the stream
+    fn next_text(&mut self) -> Result<Option<PgnText>, String> {
+        let mut line = String::new();
+        while !self.done {
+            line.clear();
+            let read = self
+                .reader
+                .read_line(&mut line)
+                .map_err(|e| format!("game {}: {}", self.games + 1, e))?;
+            if read == 0 {
+                self.done = true;
+                return Ok(self.splitter.finish());
+            }
+            if let Some(pgn_text) = self.splitter.push_line(&line) {
+                return Ok(Some(pgn_text));
+            }
+        }
+        Ok(None)
+    }
+}
+
+impl<R: BufRead> Iterator for PgnReader<R> {
+    type Item = Result<PgnGame, String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let pgn_text = match self.next_text() {
+            Ok(pgn_text) => pgn_text?,
+            Err(e) => {
+                self.done = true;
+                return Some(Err(e));
+            }
+        };
+        self.games += 1;
+        let game_number = self.games;
+        Some(parse_pgn_text(&pgn_text).map_err(|e| format!("game {}: {}", game_number, e)))
+    }
+}
+
+/// PGN text of `game`: its tags, then the movetext with the comments, NAGs
+/// and variations, wrapped at LINE_WIDTH columns
+pub fn write_pgn(game: &PgnGame) -> String {
+    let mut pgn = String::new();
+    for (name, value) in game.tags.iter() {
+        pgn.push_str(&format!(
+            "[{} \"{}\"]\n",
+            name,
+            value.replace('\\', "\\\\").replace('"', "\\\"")
+        ));
+    }
+    if !game.tags.is_empty() {
+        pgn.push('\n');
+    }
+    let mut tokens: Vec<String> = vec![];
+    if let Some(comment) = &game.comment {
+        tokens.push(format_comment(comment));
+    }
+    write_line(&game.moves, &game.start, game.move_number, &mut tokens);
+    tokens.push(game.result.clone());
+
+    let mut line = String::new();
+    for token in tokens {
+        if !line.is_empty() && line.len() + 1 + token.len() > LINE_WIDTH {
+            pgn.push_str(&line);
+            pgn.push('\n');
+            line.clear();
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(&token);
+    }
+    pgn.push_str(&line);
+    pgn.push('\n');
+    pgn
+}
+
+/// PGN moves of `moves` played from `state`
+pub fn line_from_moves(state: &State, moves: &[MoveStruct]) -> Vec<PgnMove> {
+    let mut state = *state;
+    let mut line: Vec<PgnMove> = vec![];
+    for _move in moves {
+        let promotion = _move.promotion.unwrap_or(QUEEN_ID);
+        line.push(PgnMove::new(&state, _move.clone(), promotion));
+        state = next_state_promoting(&state, state.current_player, _move.clone(), promotion).0;
+    }
+    line
+}
+
+/// Game from `state` whose main line is the first of the search `lines`
+/// (score, PV) and whose other lines are variations of its first move, each
+/// line's score (centipawns, side to move's point of view) commented after
+/// its first move
+pub fn lines_to_game(
+    state: &State,
+    lines: &[(isize, Vec<MoveStruct>)],
+    tags: Vec<(String, String)>,
+) -> PgnGame {
+    let mut lines_pgn: Vec<Vec<PgnMove>> = lines
+        .iter()
+        .filter(|(_, pv)| !pv.is_empty())
+        .map(|(score, pv)| {
+            let mut line = line_from_moves(state, pv);
+            line[0].comment = Some(format!("{:+.2}", *score as f64 / 100.0));
+            line
+        })
+        .collect();
+    let mut moves = if lines_pgn.is_empty() {
+        vec![]
+    } else {
+        lines_pgn.remove(0)
+    };
+    if let Some(first) = moves.first_mut() {
+        first.variations = lines_pgn;
+    }
+    PgnGame {
+        tags,
+        start: *state,
+        move_number: 1,
+        comment: None,
+        moves,
+        result: "*".to_string(),
+    }
+}
+
+/// A game recorded as it is played: the seven tag roster ("?" until set,
+/// the date "????.??.??") and the SetUp and FEN tags of a custom start,
+/// then the moves in SAN. The result is set when a move ends the game.
+#[derive(Clone)]
+pub struct PgnWriter {
+    pub game: PgnGame,
+    /// position after the last move
+    pub state: State,
+}
+
+impl PgnWriter {
+    pub fn new(start: State) -> Self {
+        let mut tags: Vec<(String, String)> = SEVEN_TAG_ROSTER
+            .iter()
+            .map(|&name| (name.to_string(), "?".to_string()))
+            .collect();
+        tags[2].1 = "????.??.??".to_string();
+        tags[6].1 = "*".to_string();
+        let fen = to_fen(start);
+        let initial = State::new(DEFAULT_BOARD, Color::White, true, true, true, true);
+        if fen != to_fen(initial) {
+            tags.push(("SetUp".to_string(), "1".to_string()));
+            tags.push(("FEN".to_string(), fen));
+        }
+        PgnWriter {
+            game: PgnGame {
+                tags,
+                start,
+                move_number: start.fullmove_number,
+                comment: None,
+                moves: vec![],
+                result: "*".to_string(),
+            },
+            state: start,
+        }
+    }
+
+    /// Set tag `name`, the roster tags keeping their place; a "Result" tag
+    /// sets the result
+    pub fn set_tag(&mut self, name: &str, value: &str) -> Result<(), String> {
+        if name.is_empty() || name.contains(char::is_whitespace) {
+            return Err(format!("invalid tag name '{}'", name));
+        }
+        if name == "Result" {
+            return self.set_result(value);
+        }
+        match self.game.tags.iter_mut().find(|(key, _)| key == name) {
+            Some(tag) => tag.1 = value.to_string(),
+            None => self.game.tags.push((name.to_string(), value.to_string())),
+        }
+        Ok(())
+    }
+
+    /// Set the result: "1-0", "0-1", "1/2-1/2" or "*"
+    pub fn set_result(&mut self, result: &str) -> Result<(), String> {
+        if !RESULTS.contains(&result) {
+            return Err(format!("invalid result '{}'", result));
+        }
+        self.game.result = result.to_string();
+        self.game.tags[6].1 = result.to_string();
+        Ok(())
+    }
+
+    /// Play `_move` (promoting to `promotion`, a queen for the other moves)
+    /// and return its SAN; the result is set if it ends the game
+    pub fn push(
+        &mut self,
+        _move: MoveStruct,
+        promotion: isize,
+        comment: Option<String>,
+    ) -> Result<String, String> {
+        if self.game.result != "*" {
+            return Err(format!("the game is over ({})", self.game.result));
+        }
+        let uci = convert_move_union_to_string(&MoveStruct {
+            promotion: Some(promotion),
+            .._move.clone()
+        });
+        if !is_legal_move_str(&self.state, &uci) {
+            return Err(format!("Illegal move '{}'", uci));
+        }
+        let mut pgn_move = PgnMove::new(&self.state, _move.clone(), promotion);
+        pgn_move.comment = comment;
+        let san = pgn_move.san.clone();
+        self.game.moves.push(pgn_move);
+        let player = self.state.current_player;
+        self.state = next_state_promoting(&self.state, player, _move, promotion).0;
+        update_state(&mut self.state);
+
+        let player = self.state.current_player;
+        let legal_moves = Standard.legal_moves(&self.state, player);
+        if let Some(terminal) = Standard.terminal(&self.state, player, &legal_moves) {
+            self.set_result(terminal.result(player).to_str())?;
+        }
+        Ok(san)
+    }
+
+    /// PGN text of the game (see `write_pgn`)
+    pub fn to_pgn(&self) -> String {
+        write_pgn(&self.game)
+    }
+}
+
+// HELPER FUNCTIONS
+// ---------------------------------------------------------
+// ---------------------------------------------------------
+
+/// Tag pairs and movetext of a PGN game, with where they are in the file
+pub(crate) struct PgnText {
+    /// name, value and byte offset of each tag pair
+    pub tags: Vec<(String, String, usize)>,
+    pub movetext: String,
+    /// byte offset of the game's first line
+    pub offset: usize,
+    /// (position in `movetext`, byte offset in the file) of each movetext line
+    pub line_offsets: Vec<(usize, usize)>,
+}
+
+impl PgnText {
+    fn new(offset: usize) -> Self {
+        PgnText {
+            tags: vec![],
+            movetext: String::new(),
+            offset,
+            line_offsets: vec![],
+        }
+    }
+
+    /// Value and byte offset of the tag pair `name`
+    pub fn tag(&self, name: &str) -> Option<(&str, usize)> {
+        self
+            .tags
+            .iter()
+            .find(|(key, _, _)| key == name)
+            .map(|(_, value, offset)| (value.as_str(), *offset))
+    }
+
+    /// Byte offset in the file of position `pos` of the movetext
+    pub fn movetext_offset(&self, pos: usize) -> usize {
+        match self
+            .line_offsets
+            .iter()
+            .rev()
+            .find(|(start, _)| *start <= pos)
+        {
+            Some((start, offset)) => offset + pos - start,
+            None => self.offset,
+        }
+    }
+}
+
+/// Game of the tags and movetext of `pgn_text`
+fn parse_pgn_text(pgn_text: &PgnText) -> Result<PgnGame, String> {
+    let (start, move_number) = match pgn_text.tag("FEN") {
+        Some((fen, _)) => (
+            from_fen(fen)?,
+            fen.split_whitespace()
+                .nth(5)
+                .and_then(|number| number.parse().ok())
+                .unwrap_or(1),
+        ),
+        None => (
+            State::new(DEFAULT_BOARD, Color::White, true, true, true, true),
+            1,
+        ),
+    };
+    let tokens = tokenize(&pgn_text.movetext)?;
+    let mut pos = 0;
+    let mut comment: Option<String> = None;
+    let mut result: Option<String> = None;
+    let moves = parse_line(&tokens, &mut pos, &start, 0, &mut comment, &mut result)?;
+    let result = result
+        .or_else(|| pgn_text.tag("Result").map(|(result, _)| result.to_string()))
+        .unwrap_or_else(|| "*".to_string());
+    Ok(PgnGame {
+        tags: pgn_text
+            .tags
+            .iter()
+            .map(|(name, value, _)| (name.clone(), value.clone()))
+            .collect(),
+        start,
+        move_number,
+        comment,
+        moves,
+        result,
+    })
+}
+
+/// Tags and movetext of each game of a PGN file
+pub(crate) fn split_pgn(text: &str) -> Vec<PgnText> {
+    let mut games: Vec<PgnText> = vec![];
+    let mut splitter = PgnSplitter::new();
+    for raw_line in text.split_inclusive('\n') {
+        games.extend(splitter.push_line(raw_line));
+    }
+    games.extend(splitter.finish());
+    games
+}
+
+/// Splits the lines of a PGN file into games as they are read
+struct PgnSplitter {
+    game: PgnText,
+    /// byte offset of the next line
+    offset: usize,
+}
+
+impl PgnSplitter {
+    fn new() -> Self {
+        PgnSplitter {
+            game: PgnText::new(0),
+            offset: 0,
+        }
+    }
+
+    /// Add `raw_line` (with its line break) and return the game it ends:
+    /// a tag after some movetext starts the next game
+    fn push_line(&mut self, raw_line: &str) -> Option<PgnText> {
+        let line = raw_line.trim();
+        let line_offset = self.offset + raw_line.len() - raw_line.trim_start().len();
+        self.offset += raw_line.len();
+        let mut finished: Option<PgnText> = None;
+        let game = &mut self.game;
+        if line.starts_with('[') && line.ends_with(']') {
+            if !game.movetext.trim().is_empty() {
+                finished = Some(std::mem::replace(game, PgnText::new(line_offset)));
+            }
+            if game.tags.is_empty() {
+                game.offset = line_offset;
+            }
+            let inner = &line[1..line.len() - 1];
+            if let Some(j) = inner.find(char::is_whitespace) {
+                let value = inner[j..].trim().trim_matches('"').to_string();
+                game.tags.push((inner[..j].to_string(), value, line_offset));
+            }
+        } else if !line.starts_with('%') {
+            if game.movetext.trim().is_empty() && game.tags.is_empty() {
+                game.offset = line_offset;
+            }
+            game.line_offsets.push((game.movetext.len(), line_offset));
+            game.movetext.push_str(line);
+            game.movetext.push('\n');
+        }
+        finished
+    }
+
+    /// The last game, if any, once every line was added
+    fn finish(&mut self) -> Option<PgnText> {
+        let game = std::mem::replace(&mut self.game, PgnText::new(self.offset));
+        if !game.movetext.trim().is_empty() || !game.tags.is_empty() {
+            return Some(game);
+        }
+        None
+    }
+}
+
+/// Tokens of `movetext`, without the move numbers
+fn tokenize(movetext: &str) -> Result<Vec<Token>, String> {
+    let mut tokens: Vec<Token> = vec![];
+    let mut word = String::new();
+    let mut chars = movetext.chars();
+    while let Some(c) = chars.next() {
+        if c.is_whitespace() || "{}();".contains(c) {
+            push_word(&mut tokens, &word)?;
+            word.clear();
+        }
+        match c {
+            '{' => {
+                let mut comment = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(c) => comment.push(c),
+                        None => return Err("unterminated comment".to_string()),
+                    }
+                }
+                tokens.push(Token::Comment(comment.trim().to_string()));
+            }
+            ';' => {
+                let comment: String = chars.by_ref().take_while(|&c| c != '\n').collect();
+                tokens.push(Token::Comment(comment.trim().to_string()));
+            }
+            '(' => tokens.push(Token::Open),
+            ')' => tokens.push(Token::Close),
+            '}' => return Err("unmatched '}'".to_string()),
+            _ if !c.is_whitespace() => word.push(c),
+            _ => {}
+        }
+    }
+    push_word(&mut tokens, &word)?;
+    Ok(tokens)
+}
+
+/// Token of a word of movetext: a result, a NAG, or a move followed by the
+/// NAG of its suffix annotation; move numbers are dropped
+fn push_word(tokens: &mut Vec<Token>, word: &str) -> Result<(), String> {
+    if RESULTS.contains(&word) {
+        tokens.push(Token::Result(word.to_string()));
+        return Ok(());
+    }
+    if let Some(nag) = word.strip_prefix('$') {
+        let nag = nag.parse().map_err(|_| format!("invalid NAG '{}'", word))?;
+        tokens.push(Token::Nag(nag));
+        return Ok(());
+    }
+    // "12." and "12..." prefixes may be glued to the move
+    let word = match word.rfind('.') {
+        Some(i) => &word[i + 1..],
+        None => word,
+    };
+    if word.is_empty() || word.chars().all(|c| c.is_ascii_digit()) {
+        return Ok(());
+    }
+    let san = word.trim_end_matches(['!', '?']);
+    tokens.push(Token::Move(san.to_string()));
+    let suffix = &word[san.len()..];
+    if !suffix.is_empty() {
+        match SUFFIX_NAGS.iter().find(|(text, _)| *text == suffix) {
+            Some((_, nag)) => tokens.push(Token::Nag(*nag)),
+            None => return Err(format!("invalid annotation '{}'", word)),
+        }
+    }
+    Ok(())
+}
+
+/// Moves of the line starting at `tokens[*pos]` played from `start`, with
+/// their variations; a nested line (`depth` above 0) ends at its ')'. The
+/// comment before the first move of the game and the result go to `comment`
+/// and `result`.
+fn parse_line(
+    tokens: &[Token],
+    pos: &mut usize,
+    start: &State,
+    depth: usize,
+    comment: &mut Option<String>,
+    result: &mut Option<String>,
+) -> Result<Vec<PgnMove>, String> {
+    let mut moves: Vec<PgnMove> = vec![];
+    let mut state = *start;
+    // position before the last move, where its variations start
+    let mut before = *start;
+    while *pos < tokens.len() {
+        match &tokens[*pos] {
+            Token::Move(san) => {
+                let _move = parse_san(&state, san)?;
+                let pgn_move = PgnMove::new(&state, _move.clone(), san_promotion(san));
+                before = state;
+                state =
+                    next_state_promoting(&state, state.current_player, _move, pgn_move.promotion).0;
+                moves.push(pgn_move);
+            }
+            Token::Nag(nag) => match moves.last_mut() {
+                Some(last) => last.nags.push(*nag),
+                None => return Err(format!("NAG ${} before any move", nag)),
+            },
+            Token::Comment(text) => {
+                let target = match moves.last_mut() {
+                    Some(last) => &mut last.comment,
+                    None if depth == 0 => &mut *comment,
+                    None => {
+                        *pos += 1;
+                        continue;
+                    }
+                };
+                *target = Some(match target.take() {
+                    Some(previous) => format!("{} {}", previous, text),
+                    None => text.clone(),
+                });
+            }
+            Token::Open => {
+                *pos += 1;
+                let variation = parse_line(tokens, pos, &before, depth + 1, comment, result)?;
+                match moves.last_mut() {
+                    Some(last) => last.variations.push(variation),
+                    None => return Err("variation before any move".to_string()),
+                }
+                continue;
+            }
+            Token::Close if depth > 0 => {
+                *pos += 1;
+                return Ok(moves);
+            }
+            Token::Close => return Err("unmatched ')'".to_string()),
+            Token::Result(text) => *result = Some(text.clone()),
+        }
+        *pos += 1;
+    }
+    if depth > 0 {
+        return Err("unterminated variation".to_string());
+    }
+    Ok(moves)
+}
+
+/// Movetext tokens of `moves` played from `start`, `number` being the
+/// number of the first move
+fn write_line(moves: &[PgnMove], start: &State, number: u32, tokens: &mut Vec<String>) {
+    let mut state = *start;
+    let mut number = number;
+    // black's moves are numbered at the start of a line and after comments
+    // and variations
+    let mut needs_number = true;
+    for pgn_move in moves {
+        let white = state.current_player == Color::White;
+        if white {
+            tokens.push(format!("{}.", number));
+        } else if needs_number {
+            tokens.push(format!("{}...", number));
+        }
+        tokens.push(pgn_move.san.clone());
+        tokens.extend(pgn_move.nags.iter().map(|nag| format!("${}", nag)));
+        if let Some(comment) = &pgn_move.comment {
+            tokens.push(format_comment(comment));
+        }
+        for variation in pgn_move.variations.iter() {
+            let mut variation_tokens: Vec<String> = vec![];
+            write_line(variation, &state, number, &mut variation_tokens);
+            if let Some(first) = variation_tokens.first_mut() {
+                first.insert(0, '(');
+            }
+            if let Some(last) = variation_tokens.last_mut() {
+                last.push(')');
+            }
+            tokens.extend(variation_tokens);
+        }
+        needs_number = pgn_move.comment.is_some() || !pgn_move.variations.is_empty();
+        state = next_state_promoting(
+            &state,
+            state.current_player,
+            pgn_move._move.clone(),
+            pgn_move.promotion,
+        )
+        .0;
+        if !white {
+            number += 1;
+        }
+    }
+}
+
+/// "{comment}", without the braces a comment cannot contain
+fn format_comment(comment: &str) -> String {
+    format!("{{{}}}", comment.replace('}', ""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GAME: &str = "[Event \"RAV\"]\n[Result \"1-0\"]\n\n\
+        1. e4 {best by test} e5 (1... c5 2. Nf3 (2. Nc3) d6) 2. Nf3!? $14 Nc6 1-0\n";
+
+    fn sans(line: &[PgnMove]) -> Vec<&str> {
+        line.iter().map(|pgn_move| pgn_move.san.as_str()).collect()
+    }
+
+    // variations replace the move they follow and nest
+    #[test]
+    fn parse_variations() {
+        let games = parse_pgn(GAME).unwrap();
+        assert_eq!(games.len(), 1);
+        let game = &games[0];
+        assert_eq!(game.result, "1-0");
+        assert_eq!(sans(&game.moves), ["e4", "e5", "Nf3", "Nc6"]);
+        assert_eq!(game.moves[0].comment.as_deref(), Some("best by test"));
+        assert_eq!(game.moves[2].nags, [5, 14]);
+
+        assert!(game.moves[0].variations.is_empty());
+        let variations = &game.moves[1].variations;
+        assert_eq!(variations.len(), 1);
+        assert_eq!(sans(&variations[0]), ["c5", "Nf3", "d6"]);
+        assert_eq!(variations[0][1].variations.len(), 1);
+        assert_eq!(sans(&variations[0][1].variations[0]), ["Nc3"]);
+    }
+
+    // black's moves are numbered again after comments and variations
+    #[test]
+    fn write_variations() {
+        let game = &parse_pgn(GAME).unwrap()[0];
+        let pgn = write_pgn(game);
+        assert_eq!(
+            pgn,
+            "[Event \"RAV\"]\n[Result \"1-0\"]\n\n\
+             1. e4 {best by test} 1... e5 (1... c5 2. Nf3 (2. Nc3) 2... d6) 2. Nf3 $5 $14 Nc6\n\
+             1-0\n"
+        );
+        assert_eq!(write_pgn(&parse_pgn(&pgn).unwrap()[0]), pgn);
+    }
+
+    #[test]
+    fn invalid_variations() {
+        assert!(parse_pgn("1. e4 (1. d4 e5").is_err());
+        assert!(parse_pgn("1. e4 e5) 2. Nf3").is_err());
+        assert!(parse_pgn("(1. d4) 1. e4").is_err());
+        assert!(parse_pgn("1. e4 (1. e5)").is_err());
+    }
+}
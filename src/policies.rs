@@ -0,0 +1,121 @@
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+
+use crate::eval::{Evaluator, Handcrafted};
+use crate::rng::Rng;
+use crate::selfplay::search_move;
+use crate::tt::TranspositionTable;
+use crate::{
+    convert_move_to_type, convert_move_union_to_string, legal_move_strings, next_state, State,
+    DEFAULT_EVAL_WEIGHTS,
+};
+
+//
+// Constants
+//
+pub const DEFAULT_POLICY_DEPTH: u32 = 2;
+/// the minimax policy only searches shallow trees
+const POLICY_HASH_MB: usize = 1;
+
+//
+// Structs
+//
+/// Built-in move choice of a scripted opponent, from the weakest to the
+/// strongest
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum OpponentPolicy {
+    /// a uniformly random legal move
+    Random,
+    /// the move winning the most material right away (captures and
+    /// promotions), random among equal ones
+    GreedyCapture,
+    /// the best move of a `depth` search, or with probability `noise` a
+    /// random legal move
+    Minimax { depth: u32, noise: f64 },
+}
+
+impl OpponentPolicy {
+    /// "random", "greedy" or "minimax" (with `depth` and `noise`)
+    pub fn from_name(name: &str, depth: u32, noise: f64) -> Result<Self, String> {
+        match name {
+            "random" => Ok(OpponentPolicy::Random),
+            "greedy" => Ok(OpponentPolicy::GreedyCapture),
+            "minimax" => {
+                if depth == 0 {
+                    return Err("the minimax policy must search at least 1 ply".to_string());
+                }
+                if !(0.0..=1.0).contains(&noise) {
+                    return Err(format!("invalid noise {}: must be in [0, 1]", noise));
+                }
+                Ok(OpponentPolicy::Minimax { depth, noise })
+            }
+            _ => Err(format!(
+                "Invalid opponent policy '{}': expected random, greedy or minimax",
+                name
+            )),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            OpponentPolicy::Random => "random",
+            OpponentPolicy::GreedyCapture => "greedy",
+            OpponentPolicy::Minimax { .. } => "minimax",
+        }
+    }
+
+    /// Move of the side to move of `state` (None without legal moves), its
+    /// random choices drawn from `rng`
+    pub fn choose_move(&self, state: &State, rng: &mut Rng) -> Option<String> {
+        let moves = legal_move_strings(state);
+        if moves.is_empty() {
+            return None;
+        }
+        match *self {
+            OpponentPolicy::Random => {
+                Some(moves[rng.below(moves.len())].clone())
+            }
+            OpponentPolicy::GreedyCapture => {
+                let gains: Vec<isize> = moves
+                    .iter()
+                    .map(|_move| {
+                        next_state(state, state.current_player, convert_move_to_type(_move)).1
+                    })
+                    .collect();
+                let best_gain = *gains.iter().max().unwrap();
+                let best_moves: Vec<&String> = moves
+                    .iter()
+                    .zip(gains.iter())
+                    .filter(|(_, &gain)| gain == best_gain)
+                    .map(|(_move, _)| _move)
+                    .collect();
+                Some(best_moves[rng.below(best_moves.len())].clone())
+            }
+            OpponentPolicy::Minimax { depth, noise } => {
+                if noise > 0.0 && rng.next_f64() < noise {
+                    return Some(moves[rng.below(moves.len())].clone());
+                }
+                let evaluator: Arc<dyn Evaluator> = Arc::new(Handcrafted {
+                    weights: DEFAULT_EVAL_WEIGHTS,
+                });
+                let mut tt = TranspositionTable::new(POLICY_HASH_MB);
+                let stop = Arc::new(AtomicBool::new(false));
+                let info = search_move(
+                    state,
+                    state.current_player,
+                    &evaluator,
+                    depth,
+                    None,
+                    &mut tt,
+                    &stop,
+                );
+                match info {
+                    Some(info) if !info.pv.is_empty() => {
+                        Some(convert_move_union_to_string(&info.pv[0]))
+                    }
+                    _ => Some(moves[rng.below(moves.len())].clone()),
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,53 @@
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+use crate::{from_fen, validate_position, State};
+
+//
+// Structs
+//
+/// A line of a position file that was not loaded
+#[derive(Debug, Clone)]
+pub struct RejectedLine {
+    /// 1-based line number
+    pub line: usize,
+    pub text: String,
+    pub reason: String,
+}
+
+/// Stream the positions of a FEN or EPD file (one position per line, EPD
+/// operations are ignored, blank lines and '#' comments skipped) to
+/// `on_position` with their line number, skipping the lines that do not parse
+/// or fail `validate_position`, which are returned with the reason.
+pub fn load_positions<F: FnMut(usize, State)>(
+    path: &Path,
+    mut on_position: F,
+) -> Result<Vec<RejectedLine>, String> {
+    let file = File::open(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let mut rejected: Vec<RejectedLine> = vec![];
+    for (i, line) in BufReader::new(file).lines().enumerate() {
+        let line = line.map_err(|e| format!("{}: {}", path.display(), e))?;
+        let text = line.trim();
+        if text.is_empty() || text.starts_with('#') {
+            continue;
+        }
+        let reason = match from_fen(text) {
+            Ok(state) => {
+                let problems = validate_position(&state);
+                if problems.is_empty() {
+                    on_position(i + 1, state);
+                    continue;
+                }
+                problems.join("; ")
+            }
+            Err(e) => e,
+        };
+        rejected.push(RejectedLine {
+            line: i + 1,
+            text: text.to_string(),
+            reason,
+        });
+    }
+    Ok(rejected)
+}
@@ -0,0 +1,19 @@
+//! Stable public API of the core library for Rust consumers:
+//! `use gym_chess::prelude::*;` (with the rlib manifest, Cargo.dev.toml).
+//!
+//! Removing or changing anything exported here is a breaking change and
+//! needs a new major version (minor while the crate is 0.x); the rest of the
+//! crate may change in any release.
+
+pub use crate::convert_move_union_to_string as move_to_string;
+pub use crate::eval::Evaluator;
+pub use crate::search::{SearchInfo, SearchLimits, Searcher};
+pub use crate::selfplay::{Game, GameResult};
+pub use crate::tt::{TranspositionTable, DEFAULT_HASH_MB};
+/// A move of a `State`, parsed from UCI notation by `parse_move` and printed
+/// back by `move_to_string`
+pub use crate::MoveStruct as ChessMove;
+pub use crate::{
+    from_fen, legal_move_strings, make_move_str, parse_move, try_next_state, try_to_fen,
+    validate_position, Board, Color, State, DEFAULT_BOARD, START_FEN,
+};
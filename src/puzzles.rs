@@ -0,0 +1,273 @@
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+use crate::positions::RejectedLine;
+use crate::rules::{Rules, Terminal};
+use crate::uci_client::uci_to_move;
+use crate::{
+    convert_move_union_to_string, from_fen, next_state_promoting, promotion_of, update_state,
+    validate_position, MoveStruct, State, QUEEN_ID,
+};
+
+//
+// Constants
+//
+/// first field of the header line of the Lichess puzzle CSV
+const HEADER_FIELD: &str = "PuzzleId";
+/// PuzzleId, FEN, Moves, Rating, RatingDeviation, Popularity, NbPlays and
+/// Themes; GameUrl and OpeningTags may be missing
+const MIN_FIELDS: usize = 8;
+/// width of the rating buckets of `PuzzleReport`
+pub const RATING_BUCKET: u32 = 200;
+
+//
+// Structs
+//
+/// A puzzle of the Lichess puzzle database. The CSV gives the position
+/// before the opponent's last move: that move is played so that the puzzle
+/// starts with the solver to move.
+#[derive(Clone)]
+pub struct Puzzle {
+    pub id: String,
+    /// position the solver plays from
+    pub state: State,
+    /// opponent's move leading to `state`, from the position of the CSV
+    pub setup_move: MoveStruct,
+    /// the solver's moves and the opponent's replies, alternately
+    pub solution: Vec<MoveStruct>,
+    pub rating: u32,
+    pub rating_deviation: u32,
+    /// -100 to 100
+    pub popularity: i32,
+    pub plays: u32,
+    pub themes: Vec<String>,
+    pub game_url: Option<String>,
+    pub opening_tags: Vec<String>,
+}
+
+/// How the engine played a puzzle
+#[derive(Clone)]
+pub struct PuzzleAttempt {
+    pub solved: bool,
+    /// the engine's moves, up to the first wrong one
+    pub moves: Vec<MoveStruct>,
+    /// index in the solution of the first wrong move (None if solved)
+    pub failed_at: Option<usize>,
+}
+
+/// Puzzles solved out of those tried
+#[derive(Debug, Copy, Clone, Default)]
+pub struct SolveRate {
+    pub solved: usize,
+    pub total: usize,
+}
+
+impl SolveRate {
+    fn add(&mut self, solved: bool) {
+        self.total += 1;
+        if solved {
+            self.solved += 1;
+        }
+    }
+
+    /// Share of the puzzles solved (0 without puzzles)
+    pub fn accuracy(&self) -> f64 {
+        if self.total == 0 {
+            return 0.0;
+        }
+        self.solved as f64 / self.total as f64
+    }
+}
+
+/// Solve rates over a puzzle set, overall, by theme and by rating bucket
+/// (keyed by the lowest rating of the bucket, RATING_BUCKET wide)
+#[derive(Debug, Clone, Default)]
+pub struct PuzzleReport {
+    pub overall: SolveRate,
+    pub by_theme: BTreeMap<String, SolveRate>,
+    pub by_rating: BTreeMap<u32, SolveRate>,
+}
+
+impl PuzzleReport {
+    /// Count a puzzle of `rating` and `themes`
+    pub fn add(&mut self, rating: u32, themes: &[String], solved: bool) {
+        self.overall.add(solved);
+        for theme in themes {
+            self.by_theme.entry(theme.clone()).or_default().add(solved);
+        }
+        let bucket = rating / RATING_BUCKET * RATING_BUCKET;
+        self.by_rating.entry(bucket).or_default().add(solved);
+    }
+}
+
+/// Stream the puzzles of a Lichess puzzle CSV file (the header line is
+/// skipped) to `on_puzzle` with their line number, stopping after
+/// `max_puzzles`. Lines whose position fails `validate_position` or whose
+/// moves are not legal are skipped and returned with the reason.
+pub fn load_puzzles<F: FnMut(usize, Puzzle)>(
+    path: &Path,
+    max_puzzles: Option<usize>,
+    mut on_puzzle: F,
+) -> Result<Vec<RejectedLine>, String> {
+    let file = File::open(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let mut rejected: Vec<RejectedLine> = vec![];
+    let mut loaded = 0;
+    for (i, line) in BufReader::new(file).lines().enumerate() {
+        if max_puzzles.map_or(false, |max_puzzles| loaded >= max_puzzles) {
+            break;
+        }
+        let line = line.map_err(|e| format!("{}: {}", path.display(), e))?;
+        let text = line.trim();
+        if text.is_empty() || text.starts_with(HEADER_FIELD) {
+            continue;
+        }
+        match parse_puzzle(text) {
+            Ok(puzzle) => {
+                on_puzzle(i + 1, puzzle);
+                loaded += 1;
+            }
+            Err(reason) => rejected.push(RejectedLine {
+                line: i + 1,
+                text: text.to_string(),
+                reason,
+            }),
+        }
+    }
+    Ok(rejected)
+}
+
+/// Puzzle of a line of the Lichess puzzle CSV
+pub fn parse_puzzle(line: &str) -> Result<Puzzle, String> {
+    let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+    if fields.len() < MIN_FIELDS {
+        return Err(format!(
+            "expected at least {} fields, found {}",
+            MIN_FIELDS,
+            fields.len()
+        ));
+    }
+    let number = |name: &str, value: &str| {
+        value
+            .parse::<i64>()
+            .map_err(|_| format!("invalid {} '{}'", name, value))
+    };
+    let rating = number("rating", fields[3])?;
+    let rating_deviation = number("rating deviation", fields[4])?;
+    let popularity = number("popularity", fields[5])?;
+    let plays = number("number of plays", fields[6])?;
+
+    let mut state = from_fen(fields[1])?;
+    let problems = validate_position(&state);
+    if !problems.is_empty() {
+        return Err(problems.join("; "));
+    }
+    // the solver starts after the setup move; every move must be legal
+    let mut moves: Vec<MoveStruct> = vec![];
+    let mut start = state;
+    for uci in fields[2].split_whitespace() {
+        let _move = puzzle_move(&state, uci)?;
+        let promotion = _move.promotion.unwrap_or(QUEEN_ID);
+        state = next_state_promoting(&state, state.current_player, _move.clone(), promotion).0;
+        if moves.is_empty() {
+            start = state;
+        }
+        moves.push(_move);
+    }
+    if moves.len() < 2 {
+        return Err("a puzzle needs a setup move and a solution".to_string());
+    }
+    let setup_move = moves.remove(0);
+    update_state(&mut start);
+    let list = |value: Option<&&str>| -> Vec<String> {
+        value.map_or(vec![], |value| {
+            value.split_whitespace().map(str::to_string).collect()
+        })
+    };
+    Ok(Puzzle {
+        id: fields[0].to_string(),
+        state: start,
+        setup_move,
+        solution: moves,
+        rating: rating.max(0) as u32,
+        rating_deviation: rating_deviation.max(0) as u32,
+        popularity: popularity as i32,
+        plays: plays.max(0) as u32,
+        themes: list(fields.get(7)),
+        game_url: fields
+            .get(8)
+            .filter(|url| !url.is_empty())
+            .map(|url| url.to_string()),
+        opening_tags: list(fields.get(9)),
+    })
+}
+
+/// Play the solver's side of a puzzle from `state`, the side to move,
+/// with the moves of `best_move` (None if it has none), the opponent
+/// replying with the moves of `solution`. A move other than the solution's
+/// still solves the puzzle if it mates (a transposed mate).
+pub fn attempt_puzzle<E, F>(
+    rules: &dyn Rules,
+    state: &State,
+    solution: &[MoveStruct],
+    mut best_move: F,
+) -> Result<PuzzleAttempt, E>
+where
+    F: FnMut(&State) -> Result<Option<MoveStruct>, E>,
+{
+    let mut state = *state;
+    let mut moves: Vec<MoveStruct> = vec![];
+    for (i, expected) in solution.iter().enumerate() {
+        let player = state.current_player;
+        if i % 2 == 1 {
+            state = rules.make_move(&state, player, expected.clone());
+            continue;
+        }
+        let _move = match best_move(&state)? {
+            Some(_move) => _move,
+            None => {
+                return Ok(PuzzleAttempt {
+                    solved: false,
+                    moves,
+                    failed_at: Some(i),
+                })
+            }
+        };
+        moves.push(_move.clone());
+        let new_state = rules.make_move(&state, player, _move.clone());
+        if convert_move_union_to_string(&_move) != convert_move_union_to_string(expected) {
+            let opponent = new_state.current_player;
+            let legal_moves = rules.legal_moves(&new_state, opponent);
+            let mates = rules.terminal(&new_state, opponent, &legal_moves) == Some(Terminal::Checkmate);
+            return Ok(PuzzleAttempt {
+                solved: mates,
+                moves,
+                failed_at: if mates { None } else { Some(i) },
+            });
+        }
+        state = new_state;
+    }
+    Ok(PuzzleAttempt {
+        solved: true,
+        moves,
+        failed_at: None,
+    })
+}
+
+// HELPER FUNCTIONS
+// ---------------------------------------------------------
+// ---------------------------------------------------------
+
+/// legal move of the side to move for a UCI move of the CSV, keeping its
+/// promotion piece
+fn puzzle_move(state: &State, uci: &str) -> Result<MoveStruct, String> {
+    let _move = uci_to_move(state, uci)?;
+    if _move.is_castle || uci.len() != 5 {
+        return Ok(_move);
+    }
+    Ok(MoveStruct {
+        promotion: Some(promotion_of(uci)),
+        .._move
+    })
+}
@@ -0,0 +1,173 @@
+use pyo3::exceptions::{PyException, PyKeyError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use crate::book::{Book, DEFAULT_BOOK_PLIES};
+use crate::openings::{self, SkippedMovetext};
+use crate::rng::Rng;
+use crate::selfplay::GameResult;
+use crate::{convert_move_union_to_string, from_fen, parse_move, try_to_fen, MoveStruct, State};
+
+use super::convert_py_state;
+
+/// Starting positions read from an EPD or PGN file, sampled by weight
+#[pyclass]
+pub struct OpeningSuite {
+    pub(super) suite: Arc<openings::OpeningSuite>,
+    skipped: Vec<SkippedMovetext>,
+    rng: Mutex<Rng>,
+}
+
+#[pymethods]
+impl OpeningSuite {
+    /// EPD files (".epd") take weights from a `weight` operation, PGN files
+    /// from a `Weight` tag; every opening weighs 1 by default. A `lenient`
+    /// PGN reader skips what it cannot parse instead of failing (see
+    /// `skipped`).
+    #[new]
+    #[args(seed = "None", lenient = "false")]
+    fn new(path: &str, seed: Option<u64>, lenient: bool) -> PyResult<Self> {
+        let (suite, skipped) = if lenient {
+            openings::OpeningSuite::load_lenient(Path::new(path))
+        } else {
+            openings::OpeningSuite::load(Path::new(path)).map(|suite| (suite, vec![]))
+        }
+        .map_err(PyValueError::new_err)?;
+        let rng = match seed {
+            Some(seed) => Rng::new(seed),
+            None => Rng::from_entropy(),
+        };
+        Ok(OpeningSuite {
+            suite: Arc::new(suite),
+            skipped,
+            rng: Mutex::new(rng),
+        })
+    }
+
+    /// What the lenient PGN reader skipped, as dicts {offset, game, text,
+    /// reason} with the byte offset of the text in the file
+    fn skipped<'a>(&self, _py: Python<'a>) -> PyResult<Vec<&'a PyDict>> {
+        let mut skipped_py: Vec<&PyDict> = vec![];
+        for skipped in self.skipped.iter() {
+            let dict = PyDict::new(_py);
+            dict.set_item("offset", skipped.offset)?;
+            dict.set_item("game", skipped.game)?;
+            dict.set_item("text", &skipped.text)?;
+            dict.set_item("reason", &skipped.reason)?;
+            skipped_py.push(dict);
+        }
+        Ok(skipped_py)
+    }
+
+    /// Name, FEN and state dict of a sampled opening
+    fn sample<'a>(&self, _py: Python<'a>) -> PyResult<&'a PyDict> {
+        let opening = self.suite.sample(&mut self.rng.lock().unwrap());
+        let state_py = PyDict::new(_py);
+        opening.state.to_py_object(state_py);
+        let opening_py = PyDict::new(_py);
+        opening_py.set_item("name", &opening.name)?;
+        opening_py.set_item("fen", try_to_fen(opening.state).map_err(PyValueError::new_err)?)?;
+        opening_py.set_item("state", state_py)?;
+        Ok(opening_py)
+    }
+
+    /// Names of the openings in file order
+    fn names(&self) -> Vec<String> {
+        self.suite.openings.iter().map(|opening| opening.name.clone()).collect()
+    }
+}
+
+/// Opening book learned from game results (see `start_self_play`'s `book`)
+#[pyclass]
+pub struct OpeningBook {
+    pub(super) book: Arc<Mutex<Book>>,
+}
+
+#[pymethods]
+impl OpeningBook {
+    /// An empty book learning the first `plies` plies of each game, or the
+    /// book saved at `path`
+    #[new]
+    #[args(path = "None", plies = "DEFAULT_BOOK_PLIES")]
+    fn new(path: Option<&str>, plies: u32) -> PyResult<Self> {
+        let book = match path {
+            Some(path) => Book::load(Path::new(path), plies).map_err(PyValueError::new_err)?,
+            None => Book::new(plies),
+        };
+        Ok(OpeningBook {
+            book: Arc::new(Mutex::new(book)),
+        })
+    }
+
+    /// Credit the book moves of `games` (game dicts with "fens", "moves" and
+    /// "result", as returned by `SelfPlayWorkers.get_games`) with their result
+    fn learn(&self, games: Vec<&PyDict>) -> PyResult<()> {
+        for (i, game_py) in games.iter().enumerate() {
+            let field = |key: &str| {
+                game_py
+                    .get_item(key)
+                    .ok_or_else(|| PyKeyError::new_err(format!("game {}: missing '{}'", i, key)))
+            };
+            let fens: Vec<String> = field("fens")?.extract()?;
+            let moves: Vec<String> = field("moves")?.extract()?;
+            let result = match field("result")?.extract::<&str>()? {
+                "1-0" => GameResult::WhiteWin,
+                "0-1" => GameResult::BlackWin,
+                "1/2-1/2" => GameResult::Draw,
+                result => return Err(PyValueError::new_err(format!("game {}: invalid result '{}'", i, result))),
+            };
+            let start = match fens.first() {
+                Some(fen) => from_fen(fen).map_err(|e| PyValueError::new_err(format!("game {}: {}", i, e)))?,
+                None => continue,
+            };
+            let moves = moves
+                .iter()
+                .map(|_move| parse_move(_move))
+                .collect::<Result<Vec<MoveStruct>, String>>()
+                .map_err(|e| PyValueError::new_err(format!("game {}: {}", i, e)))?;
+            self.book.lock().unwrap().learn(&start, &moves, result);
+        }
+        Ok(())
+    }
+
+    /// Book moves of `state` as dicts {move, weight, wins, draws, losses},
+    /// highest weight first (the weight is the expected score of the side to
+    /// move, starting at 0.5)
+    fn moves<'a>(&self, _py: Python<'a>, state_py: &'a PyDict) -> PyResult<Vec<&'a PyDict>> {
+        // parse state
+        let state: State = convert_py_state(_py, state_py)?;
+
+        let entries = self.book.lock().unwrap().moves(&state);
+        let mut entries_py: Vec<&PyDict> = vec![];
+        for entry in entries.iter() {
+            let entry_py = PyDict::new(_py);
+            entry_py.set_item("move", convert_move_union_to_string(&entry._move))?;
+            entry_py.set_item("weight", entry.weight())?;
+            entry_py.set_item("wins", entry.wins)?;
+            entry_py.set_item("draws", entry.draws)?;
+            entry_py.set_item("losses", entry.losses)?;
+            entries_py.push(entry_py);
+        }
+        Ok(entries_py)
+    }
+
+    fn num_positions(&self) -> usize {
+        return self.book.lock().unwrap().num_positions();
+    }
+
+    /// Memory held by the book, in bytes (estimated)
+    fn memory_usage(&self) -> usize {
+        return self.book.lock().unwrap().size_bytes();
+    }
+
+    fn save(&self, path: &str) -> PyResult<()> {
+        return self
+            .book
+            .lock()
+            .unwrap()
+            .save(Path::new(path))
+            .map_err(PyException::new_err);
+    }
+}
@@ -0,0 +1,2339 @@
+use pyo3::exceptions::{PyException, PyKeyError, PyRuntimeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyList, PyTuple};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::cell::RefCell;
+use std::ops::{Deref, DerefMut};
+use std::sync::{Arc, Mutex, MutexGuard, Weak};
+use std::thread;
+use std::time::Duration;
+
+use crate::ablation::{self, AblationConfig};
+use crate::arena::ArenaConfig;
+use crate::bench::{self, MovegenTiming};
+use crate::book::Book;
+use crate::config::EngineConfig;
+use crate::data_files::{self, DataStatus};
+use crate::difficulty::{estimate_difficulty, DEFAULT_DIFFICULTY_DEPTH};
+use crate::disagreement::{self, MinerConfig, DEFAULT_EVAL_THRESHOLD, DEFAULT_MOVE_THRESHOLD};
+use crate::env;
+use crate::draw;
+use crate::eval::{self, Evaluator};
+use crate::imbalance::{self, classify_imbalance, Imbalance};
+use crate::mate::{self, DEFAULT_MAX_MATE_DEPTH};
+use crate::memory::MemoryUsage;
+use crate::legality::why_illegal;
+use crate::move_cache::{move_cache_key, MoveCache};
+use crate::odds;
+use crate::pgn::{self, PgnGame};
+use crate::positions;
+use crate::puzzles::{self, Puzzle, PuzzleAttempt, PuzzleReport, SolveRate};
+use crate::report::{self, MoveAnalysis};
+use crate::rng::Rng;
+use crate::render;
+use crate::rules::{self, rules_for, Rules};
+use crate::san::annotate_move;
+use crate::search::{RootPolicy, SearchInfo, Searcher, TimeBudget, MAX_DEPTH};
+use crate::selfplay::{self, SelfPlayConfig, DEFAULT_SELFPLAY_DEPTH};
+use crate::svg;
+use crate::tactics::{self, SquareControl};
+use crate::telemetry;
+use crate::tt::{TranspositionTable, DEFAULT_HASH_MB};
+use crate::uci_client::UciLimit;
+use crate::{
+    board_to_diagram, check_turn, convert_castle_move_to_string, convert_move_to_string,
+    convert_move_to_type, convert_move_union_to_string, from_fen, get_all_possible_moves,
+    get_possible_castle_moves, legal_move_strings, move_leaves_king_checked, is_promotion,
+    make_move_str, null_move, player_enum_to_string, promotion_of, try_next_state, try_to_fen,
+    update_state, validate_position, Castle, Color, Move, MoveStruct, State, DEFAULT_BOARD,
+    START_FEN,
+};
+
+use super::{
+    convert_py_state, convert_py_player, convert_py_move, convert_py_move_or_san,
+    game_status_to_py_object, render_options, convert_eval_terms, convert_py_adjudication,
+    pgn_game_to_py, move_str_to_san, convert_py_pgn_moves, search_info_to_py_object,
+    tt_stats_to_py_object, king_safety_to_py_object, control_map_to_py_object, threat_to_py_object,
+    motif_to_py_object,
+};
+use super::search::{SearchLimits, SearchHandle};
+use super::selfplay::SelfPlayWorkers;
+use super::book::{OpeningSuite, OpeningBook};
+use super::uci::UciEngine;
+
+/// Stop flags of the calls running on an object, each call having its own
+/// so that a new call can't clear a `stop()` aimed at another one
+#[derive(Default)]
+pub(super) struct StopFlags {
+    flags: Mutex<Vec<Weak<AtomicBool>>>,
+}
+
+impl StopFlags {
+    /// A cleared flag for a new call, signaled by `stop_all` until the call
+    /// drops it
+    pub(super) fn new_flag(&self) -> Arc<AtomicBool> {
+        let flag = Arc::new(AtomicBool::new(false));
+        let mut flags = self.flags.lock().unwrap();
+        flags.retain(|flag| flag.strong_count() > 0);
+        flags.push(Arc::downgrade(&flag));
+        flag
+    }
+
+    /// Signal the calls running
+    pub(super) fn stop_all(&self) {
+        for flag in self.flags.lock().unwrap().iter().filter_map(Weak::upgrade) {
+            flag.store(true, Ordering::SeqCst);
+        }
+    }
+}
+
+/// Search settings resolved from the call arguments and the engine config
+#[derive(Clone)]
+struct SearchSettings {
+    max_depth: u32,
+    time: Option<TimeBudget>,
+    max_nodes: Option<u64>,
+    multipv: usize,
+    evaluator: Arc<dyn Evaluator>,
+    root_policy: Option<RootPolicy>,
+    verify_pv: bool,
+    deterministic: bool,
+    /// root split threads of a deterministic search
+    threads: usize,
+    telemetry_dir: Option<String>,
+}
+
+impl SearchSettings {
+    /// A deterministic search ignores the movetime, so it needs a depth or
+    /// node limit to end
+    fn check_deterministic(&self) -> PyResult<()> {
+        if self.deterministic && self.max_depth >= MAX_DEPTH && self.max_nodes.is_none() {
+            return Err(PyValueError::new_err(
+                "a deterministic search needs a depth or node limit",
+            ));
+        }
+        Ok(())
+    }
+}
+
+thread_local! {
+    /// Addresses of the transposition tables held by this thread
+    static HELD_TABLES: RefCell<Vec<usize>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Lock on a transposition table, recorded as held by this thread until
+/// dropped
+struct TableLock<'a> {
+    guard: MutexGuard<'a, TranspositionTable>,
+    address: usize,
+}
+
+/// Lock `tt`, or error if this thread already holds it: a search holds its
+/// table while an eval callback runs, and a callback calling back into the
+/// same engine would wait on the table forever
+fn lock_table(tt: &Mutex<TranspositionTable>) -> PyResult<TableLock<'_>> {
+    let address = tt as *const Mutex<TranspositionTable> as usize;
+    if HELD_TABLES.with(|held| held.borrow().contains(&address)) {
+        return Err(PyRuntimeError::new_err(
+            "the engine is searching: its eval_callback can't call it back",
+        ));
+    }
+    let guard = tt.lock().unwrap();
+    HELD_TABLES.with(|held| held.borrow_mut().push(address));
+    Ok(TableLock { guard, address })
+}
+
+impl Deref for TableLock<'_> {
+    type Target = TranspositionTable;
+
+    fn deref(&self) -> &TranspositionTable {
+        &self.guard
+    }
+}
+
+impl DerefMut for TableLock<'_> {
+    fn deref_mut(&mut self) -> &mut TranspositionTable {
+        &mut self.guard
+    }
+}
+
+impl Drop for TableLock<'_> {
+    fn drop(&mut self) {
+        HELD_TABLES.with(|held| held.borrow_mut().retain(|&address| address != self.address));
+    }
+}
+
+/// Run an iterative deepening search on the shared table (without the GIL)
+/// and write the telemetry report if enabled
+fn run_search<F: FnMut(&SearchInfo)>(
+    state: &State,
+    player: Color,
+    limits: &SearchSettings,
+    stop: Arc<AtomicBool>,
+    tt: &Mutex<TranspositionTable>,
+    mut on_iteration: F,
+) -> PyResult<Option<SearchInfo>> {
+    limits.check_deterministic()?;
+    let mut iterations: Vec<SearchInfo> = vec![];
+    let mut tt = lock_table(tt)?;
+    let mut searcher = Searcher::new(stop, &mut tt);
+    searcher.evaluator = limits.evaluator.clone();
+    searcher.root_policy = limits.root_policy.clone();
+    searcher.verify_pv = limits.verify_pv;
+    searcher.deterministic = limits.deterministic;
+    searcher.threads = limits.threads;
+    searcher.max_nodes = limits.max_nodes;
+    searcher.multipv = limits.multipv;
+    let info = searcher.iterative_deepening(state, player, limits.max_depth, limits.time, |info| {
+        iterations.push(info.clone());
+        on_iteration(info);
+    });
+    let stats = searcher.stats.clone();
+    let pv_error = searcher.pv_error.take();
+    let telemetry_result = limits.telemetry_dir.as_ref().map(|dir| {
+        let report = telemetry::search_report(&iterations, &stats, &tt.stats, tt.hashfull());
+        telemetry::write_report(Path::new(dir), "search", &report)
+    });
+    if let Some(e) = limits.evaluator.take_error() {
+        return Err(PyException::new_err(format!("evaluation failed: {}", e)));
+    }
+    if let Some(Err(e)) = telemetry_result {
+        return Err(e.into());
+    }
+    if let Some(e) = pv_error {
+        return Err(PyException::new_err(format!("PV verification failed: {}", e)));
+    }
+    Ok(info)
+}
+
+/// Evaluator calling the Python `callback(state, player) -> int`
+/// (score of the state dict for player "WHITE" / "BLACK")
+struct CallbackEvaluator {
+    callback: PyObject,
+    error: Mutex<Option<String>>,
+}
+
+impl Evaluator for CallbackEvaluator {
+    fn evaluate(&self, state: &State, player: Color) -> isize {
+        // after a failure, don't call back for every remaining leaf
+        if self.error.lock().unwrap().is_some() {
+            return 0;
+        }
+        Python::with_gil(|py| {
+            let state_py = PyDict::new(py);
+            state.to_py_object(state_py);
+            let player_py = player_enum_to_string(&player);
+            let score = self
+                .callback
+                .call1(py, (state_py, player_py))
+                .and_then(|score| score.extract::<isize>(py));
+            match score {
+                Ok(score) => score,
+                Err(e) => {
+                    *self.error.lock().unwrap() = Some(e.to_string());
+                    0
+                }
+            }
+        })
+    }
+
+    fn take_error(&self) -> Option<String> {
+        self.error.lock().unwrap().take()
+    }
+}
+
+/// One engine can be shared between Python threads: every method takes `&self`,
+/// searches release the GIL, and the transposition table is behind a mutex, so
+/// concurrent searches run one after the other while move generation calls
+/// never wait for them. `stop()` interrupts every search running.
+#[pyclass]
+pub struct ChessEngine {
+    config: Mutex<EngineConfig>,
+    evaluator: Arc<dyn Evaluator>,
+    stops: StopFlags,
+    tt: Arc<Mutex<TranspositionTable>>,
+    /// legal move lists of the positions queried with `get_possible_moves`
+    move_cache: Mutex<MoveCache>,
+    /// book of `book_path`, played by `best_move` and self-play while in book
+    book: Option<Arc<Mutex<Book>>>,
+    book_status: DataStatus,
+    tablebase_status: DataStatus,
+}
+
+impl ChessEngine {
+    fn with_config(config: EngineConfig, evaluator: Arc<dyn Evaluator>) -> Self {
+        let (book, book_status) = data_files::load_book(config.book_path.as_deref());
+        ChessEngine {
+            evaluator,
+            stops: StopFlags::default(),
+            tt: Arc::new(Mutex::new(TranspositionTable::new(config.hash_mb))),
+            move_cache: Mutex::new(MoveCache::new(config.move_cache_entries)),
+            book: book.map(|book| Arc::new(Mutex::new(book))),
+            book_status,
+            tablebase_status: data_files::tablebase_status(config.tablebase_path.as_deref()),
+            config: Mutex::new(config),
+        }
+    }
+
+    fn search_settings(&self, depth: Option<u32>) -> SearchSettings {
+        let config = self.config.lock().unwrap();
+        SearchSettings {
+            max_depth: depth.or(config.depth).unwrap_or(MAX_DEPTH),
+            time: config.movetime_ms.map(|ms| TimeBudget::fixed(Duration::from_millis(ms))),
+            max_nodes: None,
+            multipv: 1,
+            evaluator: self.evaluator.clone(),
+            root_policy: None,
+            verify_pv: config.verify_pv,
+            deterministic: config.deterministic,
+            threads: config.threads,
+            telemetry_dir: config.telemetry_dir.clone(),
+        }
+    }
+
+    /// Rules of the configured variant
+    fn rules(&self) -> Arc<dyn Rules> {
+        // the variant is checked when the config is loaded
+        return rules_for(&self.config.lock().unwrap().variant).unwrap();
+    }
+
+    /// Run `f` on the transposition table without holding the GIL: a search
+    /// holds the table while it runs and needs the GIL to call back into Python
+    /// (an error when called back from that search, see `lock_table`)
+    fn with_tt<R: Send, F: FnOnce(&mut TranspositionTable) -> R + Send>(
+        &self,
+        _py: Python,
+        f: F,
+    ) -> PyResult<R> {
+        _py.allow_threads(|| Ok(f(&mut *lock_table(&self.tt)?)))
+    }
+
+    /// Fit the tables of the config in its memory cap and resize the
+    /// transposition table and move cache to match
+    fn resize_tables(&self, _py: Python) -> PyResult<()> {
+        let (hash_mb, move_cache_entries) = {
+            let mut config = self.config.lock().unwrap();
+            config.fit_memory();
+            (config.hash_mb, config.move_cache_entries)
+        };
+        // the config lock is not held while waiting for a running search
+        self.with_tt(_py, |tt| tt.resize(hash_mb))?;
+        self.move_cache.lock().unwrap().set_capacity(move_cache_entries);
+        Ok(())
+    }
+}
+
+#[pymethods]
+impl ChessEngine {
+    /// The searches use the handcrafted evaluation unless `eval_callback`
+    /// (`callback(state, player) -> int`) or an NNUE network file is given.
+    /// The callback runs while the search holds the transposition table:
+    /// calling back into this engine from it raises RuntimeError.
+    #[new]
+    #[args(hash_mb = "DEFAULT_HASH_MB", eval_callback = "None", nnue_path = "None")]
+    fn new(
+        hash_mb: usize,
+        eval_callback: Option<PyObject>,
+        nnue_path: Option<String>,
+    ) -> PyResult<Self> {
+        let mut config = EngineConfig {
+            hash_mb,
+            ..EngineConfig::default()
+        };
+        let evaluator: Arc<dyn Evaluator> = match (eval_callback, nnue_path) {
+            (Some(_), Some(_)) => {
+                return Err(PyValueError::new_err(
+                    "eval_callback and nnue_path are mutually exclusive",
+                ))
+            }
+            (Some(callback), None) => Arc::new(CallbackEvaluator {
+                callback,
+                error: Mutex::new(None),
+            }),
+            (None, nnue_path) => {
+                if nnue_path.is_some() {
+                    config.evaluator = "nnue".to_string();
+                    config.nnue_path = nnue_path;
+                }
+                eval::from_config(&config).map_err(PyValueError::new_err)?
+            }
+        };
+        Ok(ChessEngine::with_config(config, evaluator))
+    }
+
+    /// Build an engine from a TOML file (search limits, evaluator, hash size, ...)
+    #[staticmethod]
+    fn from_config(path: &str) -> PyResult<Self> {
+        let config = EngineConfig::from_file(Path::new(path)).map_err(PyValueError::new_err)?;
+        let evaluator = eval::from_config(&config).map_err(PyValueError::new_err)?;
+        Ok(ChessEngine::with_config(config, evaluator))
+    }
+
+    /// Initial position with `giver` playing without the material of the
+    /// named odds ("pawn", "pawn_and_move", "knight", "queen", ...)
+    #[staticmethod]
+    #[args(giver = "\"WHITE\"")]
+    fn odds_position<'a>(_py: Python<'a>, name: &str, giver: &str) -> PyResult<&'a PyDict> {
+        // parse arguments
+        let giver: Color = convert_py_player(giver)?;
+
+        let state = odds::odds_position(name, giver).map_err(PyValueError::new_err)?;
+        let state_py = PyDict::new(_py);
+        state.to_py_object(state_py);
+        Ok(state_py)
+    }
+
+    /// Names accepted by `odds_position`
+    #[staticmethod]
+    fn odds_names() -> Vec<&'static str> {
+        odds::odds_names()
+    }
+
+    /// Shortest forced mate of the side to move in at most `max_moves` moves,
+    /// as {mate_in, line} (the defender playing the longest resistance), or None
+    #[staticmethod]
+    #[args(max_moves = "DEFAULT_MAX_MATE_DEPTH")]
+    fn solve_mate<'a>(
+        _py: Python<'a>,
+        state_py: &'a PyDict,
+        max_moves: u32,
+    ) -> PyResult<Option<&'a PyDict>> {
+        // parse state
+        let state: State = convert_py_state(_py, state_py)?;
+        check_turn(&state, state.current_player).map_err(PyValueError::new_err)?;
+
+        let solution = _py.allow_threads(|| mate::solve_mate(&state, max_moves));
+        let (mate_in, line) = match solution {
+            Some(solution) => solution,
+            None => return Ok(None),
+        };
+        let mate_py = PyDict::new(_py);
+        mate_py.set_item("mate_in", mate_in)?;
+        let line: Vec<String> = line.iter().map(convert_move_union_to_string).collect();
+        mate_py.set_item("line", line)?;
+        Ok(Some(mate_py))
+    }
+
+    /// Up to `n` positions of random games where the side to move mates in at
+    /// most `max_mate_depth` moves with a unique first move, as dicts
+    /// {fen, state, mate_in, solution}
+    #[staticmethod]
+    #[args(max_mate_depth = "DEFAULT_MAX_MATE_DEPTH", seed = "None")]
+    fn generate_mate_puzzles<'a>(
+        _py: Python<'a>,
+        n: usize,
+        max_mate_depth: u32,
+        seed: Option<u64>,
+    ) -> PyResult<Vec<&'a PyDict>> {
+        let mut rng = match seed {
+            Some(seed) => Rng::new(seed),
+            None => Rng::from_entropy(),
+        };
+        let puzzles = _py.allow_threads(|| mate::generate_mate_puzzles(n, max_mate_depth, &mut rng));
+        let mut puzzles_py: Vec<&PyDict> = vec![];
+        for puzzle in puzzles.iter() {
+            let state_py = PyDict::new(_py);
+            puzzle.state.to_py_object(state_py);
+            let solution: Vec<String> = puzzle.solution.iter().map(convert_move_union_to_string).collect();
+            let puzzle_py = PyDict::new(_py);
+            puzzle_py.set_item("fen", try_to_fen(puzzle.state).map_err(PyValueError::new_err)?)?;
+            puzzle_py.set_item("state", state_py)?;
+            puzzle_py.set_item("mate_in", puzzle.mate_in)?;
+            puzzle_py.set_item("solution", solution)?;
+            puzzles_py.push(puzzle_py);
+        }
+        Ok(puzzles_py)
+    }
+
+    /// Material imbalance of `state` as (class, side ahead), the class one of
+    /// "equal", "pawn_up", "minor_up", "exchange_up", "rook_up", "queen_up"
+    /// or "other" and the side None when equal
+    #[staticmethod]
+    fn material_imbalance<'a>(
+        _py: Python<'a>,
+        state_py: &'a PyDict,
+    ) -> PyResult<(&'static str, Option<&'static str>)> {
+        // parse state
+        let state: State = convert_py_state(_py, state_py)?;
+
+        let (imbalance, ahead) = classify_imbalance(&state.board);
+        Ok((imbalance.name(), ahead.as_ref().map(player_enum_to_string)))
+    }
+
+    /// Whether `state` is likely a dead draw from its material signature
+    /// (pawnless with at most a minor piece of difference, bishops of
+    /// opposite colours alone) or locked pawns, unless the recent search
+    /// `scores` (the last one most recent) left 0
+    #[staticmethod]
+    #[args(scores = "None")]
+    fn likely_draw<'a>(_py: Python<'a>, state_py: &'a PyDict, scores: Option<Vec<isize>>) -> PyResult<bool> {
+        // parse state
+        let state: State = convert_py_state(_py, state_py)?;
+
+        Ok(draw::likely_draw(&state, &scores.unwrap_or_default()))
+    }
+
+    /// The signals `likely_draw` combines: "drawish_material", "locked_pawns"
+    /// and "stable_eval" (None with too few `scores`)
+    #[staticmethod]
+    #[args(scores = "None")]
+    fn draw_signals<'a>(
+        _py: Python<'a>,
+        state_py: &'a PyDict,
+        scores: Option<Vec<isize>>,
+    ) -> PyResult<&'a PyDict> {
+        // parse state
+        let state: State = convert_py_state(_py, state_py)?;
+
+        let signals = draw::draw_signals(&state, &scores.unwrap_or_default());
+        let signals_py = PyDict::new(_py);
+        signals_py.set_item("drawish_material", signals.drawish_material)?;
+        signals_py.set_item("locked_pawns", signals.locked_pawns)?;
+        signals_py.set_item("stable_eval", signals.stable_eval)?;
+        Ok(signals_py)
+    }
+
+    /// Time the move generation of the side to move of each of `fens` over
+    /// `iterations` runs, as dicts {fen, attack_map_ns, pseudo_legal_ns,
+    /// legal_filter_ns, pseudo_legal_moves, legal_moves} with the average
+    /// nanoseconds per call of each stage
+    #[staticmethod]
+    #[args(iterations = "1000")]
+    fn benchmark_movegen<'a>(
+        _py: Python<'a>,
+        fens: Vec<String>,
+        iterations: u32,
+    ) -> PyResult<Vec<&'a PyDict>> {
+        // parse arguments
+        let states = fens
+            .iter()
+            .enumerate()
+            .map(|(i, fen)| from_fen(fen).map_err(|e| format!("FEN {}: {}", i, e)))
+            .collect::<Result<Vec<State>, String>>()
+            .map_err(PyValueError::new_err)?;
+
+        let timings: Vec<MovegenTiming> = _py.allow_threads(|| {
+            states
+                .iter()
+                .map(|state| bench::benchmark_movegen(state, iterations))
+                .collect()
+        });
+        let mut timings_py: Vec<&PyDict> = vec![];
+        for (fen, timing) in fens.iter().zip(timings.iter()) {
+            let timing_py = PyDict::new(_py);
+            timing_py.set_item("fen", fen)?;
+            timing_py.set_item("attack_map_ns", timing.attack_map_ns)?;
+            timing_py.set_item("pseudo_legal_ns", timing.pseudo_legal_ns)?;
+            timing_py.set_item("legal_filter_ns", timing.legal_filter_ns)?;
+            timing_py.set_item("pseudo_legal_moves", timing.pseudo_legal_moves)?;
+            timing_py.set_item("legal_moves", timing.legal_moves)?;
+            timings_py.push(timing_py);
+        }
+        Ok(timings_py)
+    }
+
+    /// Up to `n_per_class` positions of each imbalance class in `classes`
+    /// (see `material_imbalance`), taken from `fens` in their order or, without
+    /// them, from random games, as dicts {fen, state, imbalance, ahead}
+    #[staticmethod]
+    #[args(fens = "None", seed = "None")]
+    fn sample_imbalanced_positions<'a>(
+        _py: Python<'a>,
+        classes: Vec<&str>,
+        n_per_class: usize,
+        fens: Option<Vec<String>>,
+        seed: Option<u64>,
+    ) -> PyResult<Vec<&'a PyDict>> {
+        // parse arguments
+        let classes = classes
+            .iter()
+            .map(|name| Imbalance::from_name(name))
+            .collect::<Result<Vec<Imbalance>, String>>()
+            .map_err(PyValueError::new_err)?;
+        let samples = match fens {
+            Some(fens) => {
+                let states = fens
+                    .iter()
+                    .enumerate()
+                    .map(|(i, fen)| from_fen(fen).map_err(|e| format!("FEN {}: {}", i, e)))
+                    .collect::<Result<Vec<State>, String>>()
+                    .map_err(PyValueError::new_err)?;
+                imbalance::filter_by_imbalance(states, &classes, n_per_class)
+            }
+            None => {
+                let mut rng = match seed {
+                    Some(seed) => Rng::new(seed),
+                    None => Rng::from_entropy(),
+                };
+                _py.allow_threads(|| {
+                    imbalance::sample_imbalanced_positions(&classes, n_per_class, &mut rng)
+                })
+            }
+        };
+        let mut samples_py: Vec<&PyDict> = vec![];
+        for (state, imbalance, ahead) in samples.iter() {
+            let state_py = PyDict::new(_py);
+            state.to_py_object(state_py);
+            let sample_py = PyDict::new(_py);
+            sample_py.set_item("fen", try_to_fen(*state).map_err(PyValueError::new_err)?)?;
+            sample_py.set_item("state", state_py)?;
+            sample_py.set_item("imbalance", imbalance.name())?;
+            sample_py.set_item("ahead", ahead.as_ref().map(player_enum_to_string))?;
+            samples_py.push(sample_py);
+        }
+        Ok(samples_py)
+    }
+
+    /// Sample a legal action of the side to move from `policy_logits` (one
+    /// per action of `ChessEnv.action_space`, `auto_queen` choosing the
+    /// space): illegal actions are masked out and the others renormalized
+    /// with a softmax at `temperature` (0 takes the most likely one).
+    /// Returns (action, move string); `seed` makes the draw reproducible.
+    #[staticmethod]
+    #[args(temperature = "1.0", seed = "None", auto_queen = "true")]
+    fn sample_action<'a>(
+        _py: Python<'a>,
+        policy_logits: Vec<f64>,
+        state_py: &'a PyDict,
+        temperature: f64,
+        seed: Option<u64>,
+        auto_queen: bool,
+    ) -> PyResult<(usize, String)> {
+        // parse state
+        let state: State = convert_py_state(_py, state_py)?;
+
+        // parse arguments
+        let mut rng = match seed {
+            Some(seed) => Rng::new(seed),
+            None => Rng::from_entropy(),
+        };
+
+        env::sample_action(&policy_logits, &state, auto_queen, temperature, &mut rng)
+            .map_err(PyValueError::new_err)
+    }
+
+    /// State of a FEN position: board, side to move, castling rights, en
+    /// passant square and clocks (the last fields may be left out). Raises
+    /// ValueError if it does not parse; see `validate_position` for legality.
+    #[staticmethod]
+    fn state_from_fen<'a>(_py: Python<'a>, fen: &str) -> PyResult<&'a PyDict> {
+        let mut state = from_fen(fen).map_err(PyValueError::new_err)?;
+        update_state(&mut state);
+        let state_py = PyDict::new(_py);
+        state.to_py_object(state_py);
+        Ok(state_py)
+    }
+
+    /// FEN of `state`, as also returned under "fen" by `next_state`
+    #[staticmethod]
+    fn to_fen<'a>(_py: Python<'a>, state_py: &'a PyDict) -> PyResult<String> {
+        // parse state
+        let state: State = convert_py_state(_py, state_py)?;
+
+        try_to_fen(state).map_err(PyValueError::new_err)
+    }
+
+    /// ASCII diagram of the board of `state` with rank and file labels,
+    /// which state dicts also accept as "board"
+    #[staticmethod]
+    fn state_to_diagram<'a>(_py: Python<'a>, state_py: &'a PyDict) -> PyResult<String> {
+        // parse state
+        let state: State = convert_py_state(_py, state_py)?;
+
+        Ok(board_to_diagram(&state.board))
+    }
+
+    /// Problems making `state` impossible in a legal game (empty list if valid)
+    #[staticmethod]
+    fn validate_position<'a>(_py: Python<'a>, state_py: &'a PyDict) -> PyResult<Vec<String>> {
+        // parse state
+        let state: State = convert_py_state(_py, state_py)?;
+
+        Ok(validate_position(&state))
+    }
+
+    /// Why `move` can't be played by the side to move, in words (None if it
+    /// is legal): for teaching UIs
+    #[staticmethod]
+    fn why_illegal<'a>(
+        _py: Python<'a>,
+        state_py: &'a PyDict,
+        _move: &str,
+    ) -> PyResult<Option<String>> {
+        // parse state
+        let state: State = convert_py_state(_py, state_py)?;
+
+        Ok(why_illegal(&state, _move))
+    }
+
+    /// Standard algebraic notation of `move` ("e2e4", "e7e8n" or a castle
+    /// move) played by the side to move: "Nbd7", "exd5", "O-O", "e8=Q+".
+    /// Raises ValueError if the move is illegal.
+    #[staticmethod]
+    fn move_to_san<'a>(_py: Python<'a>, state_py: &'a PyDict, _move: &str) -> PyResult<String> {
+        // parse state
+        let state: State = convert_py_state(_py, state_py)?;
+
+        move_str_to_san(&state, _move).map_err(PyValueError::new_err)
+    }
+
+    /// SAN of each of `moves`, played in turn from `state`, e.g. to log a game
+    #[staticmethod]
+    fn moves_to_san<'a>(
+        _py: Python<'a>,
+        state_py: &'a PyDict,
+        moves: Vec<String>,
+    ) -> PyResult<Vec<String>> {
+        // parse state
+        let mut state: State = convert_py_state(_py, state_py)?;
+
+        let mut sans: Vec<String> = vec![];
+        for (i, _move) in moves.iter().enumerate() {
+            let san = move_str_to_san(&state, _move)
+                .map_err(|e| PyValueError::new_err(format!("move {}: {}", i + 1, e)))?;
+            sans.push(san);
+            state = make_move_str(&state, _move).map_err(PyValueError::new_err)?;
+        }
+        Ok(sans)
+    }
+
+    /// Load the valid positions of a FEN or EPD file as
+    /// {"positions": [{line, fen, state}], "rejected": [{line, text, reason}]}
+    #[staticmethod]
+    fn load_positions<'a>(_py: Python<'a>, path: &str) -> PyResult<&'a PyDict> {
+        let (states, rejected) = _py.allow_threads(|| {
+            let mut states: Vec<(usize, State)> = vec![];
+            let rejected =
+                positions::load_positions(Path::new(path), |line, state| states.push((line, state)));
+            rejected.map(|rejected| (states, rejected))
+        })
+        .map_err(PyValueError::new_err)?;
+
+        let positions_py: Vec<&PyDict> = states
+            .iter()
+            .map(|(line, state)| -> PyResult<&PyDict> {
+                let state_py = PyDict::new(_py);
+                state.to_py_object(state_py);
+                let position_py = PyDict::new(_py);
+                position_py.set_item("line", line)?;
+                position_py.set_item("fen", try_to_fen(*state).map_err(PyValueError::new_err)?)?;
+                position_py.set_item("state", state_py)?;
+                Ok(position_py)
+            })
+            .collect::<PyResult<_>>()?;
+        let rejected_py: Vec<&PyDict> = rejected
+            .iter()
+            .map(|rejected_line| {
+                let rejected_py = PyDict::new(_py);
+                rejected_py.set_item("line", rejected_line.line).unwrap();
+                rejected_py.set_item("text", &rejected_line.text).unwrap();
+                rejected_py.set_item("reason", &rejected_line.reason).unwrap();
+                rejected_py
+            })
+            .collect();
+        let report_py = PyDict::new(_py);
+        report_py.set_item("positions", positions_py)?;
+        report_py.set_item("rejected", rejected_py)?;
+        Ok(report_py)
+    }
+
+    /// Load the puzzles of a Lichess puzzle CSV file (at most `max_puzzles`)
+    /// as {"puzzles": [{line, id, fen, state, setup_move, solution, rating,
+    /// rating_deviation, popularity, plays, themes, game_url, opening_tags}],
+    /// "rejected": [{line, text, reason}]}. The puzzle positions are those
+    /// after the CSV's setup move, with the solver to move.
+    #[staticmethod]
+    #[args(max_puzzles = "None")]
+    fn load_lichess_puzzles<'a>(
+        _py: Python<'a>,
+        path: &str,
+        max_puzzles: Option<usize>,
+    ) -> PyResult<&'a PyDict> {
+        let (puzzles, rejected) = _py
+            .allow_threads(|| {
+                let mut puzzles: Vec<(usize, Puzzle)> = vec![];
+                let rejected = puzzles::load_puzzles(Path::new(path), max_puzzles, |line, puzzle| {
+                    puzzles.push((line, puzzle))
+                });
+                rejected.map(|rejected| (puzzles, rejected))
+            })
+            .map_err(PyValueError::new_err)?;
+
+        let mut puzzles_py: Vec<&PyDict> = vec![];
+        for (line, puzzle) in puzzles.iter() {
+            let state_py = PyDict::new(_py);
+            puzzle.state.to_py_object(state_py);
+            let solution: Vec<String> = puzzle.solution.iter().map(convert_move_union_to_string).collect();
+            let puzzle_py = PyDict::new(_py);
+            puzzle_py.set_item("line", line)?;
+            puzzle_py.set_item("id", &puzzle.id)?;
+            puzzle_py.set_item("fen", try_to_fen(puzzle.state).map_err(PyValueError::new_err)?)?;
+            puzzle_py.set_item("state", state_py)?;
+            puzzle_py.set_item("setup_move", convert_move_union_to_string(&puzzle.setup_move))?;
+            puzzle_py.set_item("solution", solution)?;
+            puzzle_py.set_item("rating", puzzle.rating)?;
+            puzzle_py.set_item("rating_deviation", puzzle.rating_deviation)?;
+            puzzle_py.set_item("popularity", puzzle.popularity)?;
+            puzzle_py.set_item("plays", puzzle.plays)?;
+            puzzle_py.set_item("themes", &puzzle.themes)?;
+            puzzle_py.set_item("game_url", &puzzle.game_url)?;
+            puzzle_py.set_item("opening_tags", &puzzle.opening_tags)?;
+            puzzles_py.push(puzzle_py);
+        }
+        let rejected_py: Vec<&PyDict> = rejected
+            .iter()
+            .map(|rejected_line| {
+                let rejected_py = PyDict::new(_py);
+                rejected_py.set_item("line", rejected_line.line).unwrap();
+                rejected_py.set_item("text", &rejected_line.text).unwrap();
+                rejected_py.set_item("reason", &rejected_line.reason).unwrap();
+                rejected_py
+            })
+            .collect();
+        let report_py = PyDict::new(_py);
+        report_py.set_item("puzzles", puzzles_py)?;
+        report_py.set_item("rejected", rejected_py)?;
+        Ok(report_py)
+    }
+
+    /// Search the valid positions of a FEN or EPD file with this engine (to
+    /// `depth`) and the reference `engine` (a `UciEngine`, to
+    /// `reference_depth` or for `reference_movetime_ms`) and return those
+    /// where the scores are `eval_threshold` centipawns apart or this
+    /// engine's move loses `move_threshold` according to the reference,
+    /// largest disagreements first, as {line, fen, own_move, own_score,
+    /// reference_move, reference_score, own_move_reference_score, eval_gap,
+    /// move_loss}. `output` also writes them as an EPD suite. `stop()`
+    /// ends the scan early.
+    #[args(
+        output = "None",
+        depth = "None",
+        reference_depth = "None",
+        reference_movetime_ms = "None",
+        eval_threshold = "DEFAULT_EVAL_THRESHOLD",
+        move_threshold = "DEFAULT_MOVE_THRESHOLD"
+    )]
+    fn mine_disagreements<'a>(
+        &self,
+        _py: Python<'a>,
+        engine: PyRef<UciEngine>,
+        path: &str,
+        output: Option<&str>,
+        depth: Option<u32>,
+        reference_depth: Option<u32>,
+        reference_movetime_ms: Option<u64>,
+        eval_threshold: isize,
+        move_threshold: isize,
+    ) -> PyResult<Vec<&'a PyDict>> {
+        // parse arguments
+        let config = self.config.lock().unwrap().clone();
+        let depth = depth.or(config.depth).unwrap_or(DEFAULT_SELFPLAY_DEPTH);
+        let reference_limit = match (reference_depth, reference_movetime_ms) {
+            (_, None) => UciLimit::Depth(reference_depth.unwrap_or(depth)),
+            (None, Some(movetime_ms)) => UciLimit::MoveTime(Duration::from_millis(movetime_ms)),
+            _ => {
+                return Err(PyValueError::new_err(
+                    "only one of reference_depth and reference_movetime_ms can be given",
+                ))
+            }
+        };
+        let miner_config = MinerConfig {
+            depth,
+            hash_mb: config.hash_mb,
+            reference_limit,
+            eval_threshold,
+            move_threshold,
+        };
+
+        let stop = self.stops.new_flag();
+        let evaluator = self.evaluator.clone();
+        let engine = engine.engine.clone();
+        let disagreements = _py
+            .allow_threads(|| -> Result<Vec<disagreement::Disagreement>, String> {
+                let mut states: Vec<(usize, State)> = vec![];
+                positions::load_positions(Path::new(path), |line, state| states.push((line, state)))?;
+                let disagreements = disagreement::mine_disagreements(
+                    &states,
+                    &evaluator,
+                    &engine,
+                    &miner_config,
+                    &stop,
+                    |_, _| {},
+                )?;
+                if let Some(output) = output {
+                    disagreement::write_epd(Path::new(output), &disagreements)?;
+                }
+                Ok(disagreements)
+            })
+            .map_err(PyRuntimeError::new_err)?;
+        if let Some(e) = self.evaluator.take_error() {
+            return Err(PyException::new_err(format!("evaluation failed: {}", e)));
+        }
+
+        let disagreements_py = disagreements
+            .iter()
+            .map(|d| -> PyResult<&PyDict> {
+                let disagreement_py = PyDict::new(_py);
+                disagreement_py.set_item("line", d.line).unwrap();
+                disagreement_py.set_item("fen", try_to_fen(d.state).map_err(PyValueError::new_err)?)?;
+                disagreement_py
+                    .set_item("own_move", convert_move_union_to_string(&d.own_move))
+                    .unwrap();
+                disagreement_py.set_item("own_score", d.own_score).unwrap();
+                disagreement_py
+                    .set_item("reference_move", convert_move_union_to_string(&d.reference_move))
+                    .unwrap();
+                disagreement_py.set_item("reference_score", d.reference_score).unwrap();
+                disagreement_py
+                    .set_item("own_move_reference_score", d.own_move_reference_score)
+                    .unwrap();
+                disagreement_py.set_item("eval_gap", d.eval_gap()).unwrap();
+                disagreement_py.set_item("move_loss", d.move_loss()).unwrap();
+                Ok(disagreement_py)
+            })
+            .collect::<PyResult<_>>()?;
+        Ok(disagreements_py)
+    }
+
+    /// State after `player` plays `move`, given as a move string ("e2e4",
+    /// "e7e8n", "CASTLE_KING_SIDE_WHITE") or in SAN ("Nbd7", "O-O-O",
+    /// "exd8=N#"), and the reward of the move
+    fn next_state<'a>(
+        &self,
+        _py: Python<'a>,
+        state_py: &'a PyDict,
+        _player: &str,
+        _move: &str,
+    ) -> PyResult<(&'a PyDict, isize)> {
+        // parse state
+        let state: State = convert_py_state(_py, state_py)?;
+
+        // parse arguments
+        let player: Color = convert_py_player(_player)?;
+        check_turn(&state, player).map_err(PyValueError::new_err)?;
+
+        // next state
+        let (move_union, promotion) = convert_py_move_or_san(&state, player, _move)?;
+        let (mut new_state, reward) =
+            try_next_state(&state, player, move_union, promotion).map_err(PyValueError::new_err)?;
+
+        // update kings under attack
+        update_state(&mut new_state);
+        // if both kings are checked, this position is impossible => raise exception
+        if new_state.white_king_is_checked && new_state.black_king_is_checked {
+            return Err(PyValueError::new_err(
+                "Both Kings are in check: this position is impossible",
+            ));
+        }
+
+        // return new state
+        let new_state_py = PyDict::new(_py);
+        new_state.to_py_object(new_state_py);
+        new_state_py.set_item("fen", try_to_fen(new_state).map_err(PyValueError::new_err)?)?;
+        game_status_to_py_object(&*self.rules(), &new_state, new_state_py)?;
+        Ok((new_state_py, reward))
+    }
+
+    /// Whether the game is over in `state` with its side to move to play, as
+    /// {terminal, status, winner, result}: "status" is "checkmate",
+    /// "stalemate", "fifty_moves" (the state's "halfmove_clock" reached 100)
+    /// or "insufficient_material" (None while the game goes on),
+    /// "winner" the mating side ("WHITE" or "BLACK", None otherwise) and
+    /// "result" the score ("1-0", "0-1" or "1/2-1/2" for draws, stalemate
+    /// included). The states returned by `next_state` and `update_state`
+    /// carry the same keys.
+    fn get_game_status<'a>(&self, _py: Python<'a>, state_py: &'a PyDict) -> PyResult<&'a PyDict> {
+        // parse state
+        let state: State = convert_py_state(_py, state_py)?;
+
+        let status_py = PyDict::new(_py);
+        game_status_to_py_object(&*self.rules(), &state, status_py)?;
+        Ok(status_py)
+    }
+
+    /// Whether the side to move has no legal move but is not in check: the
+    /// game is drawn (`get_possible_moves` just returns no moves)
+    fn is_stalemate<'a>(&self, _py: Python<'a>, state_py: &'a PyDict) -> PyResult<bool> {
+        // parse state
+        let state: State = convert_py_state(_py, state_py)?;
+
+        return Ok(rules::is_stalemate(&*self.rules(), &state));
+    }
+
+    /// The position with the other side to move and nothing moved, to see
+    /// what the opponent threatens. Raises ValueError in check.
+    fn null_move<'a>(&self, _py: Python<'a>, state_py: &'a PyDict) -> PyResult<&'a PyDict> {
+        // parse state
+        let state: State = convert_py_state(_py, state_py)?;
+
+        // null move
+        let mut new_state = null_move(&state).map_err(PyValueError::new_err)?;
+        update_state(&mut new_state);
+
+        // return new state
+        let new_state_py = PyDict::new(_py);
+        new_state.to_py_object(new_state_py);
+        Ok(new_state_py)
+    }
+
+    /// Moves of `player` ("e2e4", "CASTLE_KING_SIDE_WHITE"), or the squares
+    /// its pieces attack with `attack`. With `annotate`, the moves are marked
+    /// with captures, promotions, checks and mates ("e4xd5", "e7e8=Q", "d1h5+").
+    /// With `promotions`, each promotion is listed once per piece ("e7e8q",
+    /// "e7e8r", "e7e8b", "e7e8n") instead of once for a queen ("e7e8").
+    #[args(attack = false, annotate = false, promotions = false)]
+    fn get_possible_moves<'a>(
+        &self,
+        _py: Python<'a>,
+        state_py: &'a PyDict,
+        _player: &str,
+        attack: bool,
+        annotate: bool,
+        promotions: bool,
+    ) -> PyResult<Vec<String>> {
+        // parse state
+        let state: State = convert_py_state(_py, state_py)?;
+
+        // parse arguments
+        let player: Color = convert_py_player(_player)?;
+        if attack && annotate {
+            return Err(PyValueError::new_err("attack moves can't be annotated"));
+        }
+
+        let key = move_cache_key(&state, player, attack);
+        let moves_str = self.move_cache.lock().unwrap().get_or_insert_with(key, || {
+            let (mut moves, castle_moves): (Vec<Move>, Vec<Castle>) =
+                get_all_possible_moves(&state, player, attack);
+            // let moves: Vec<Move>le_moves(&st = get_possibate, player, attack);
+            // let castle_moves: Vec<Castle> = get_possible_castle_moves(&state, player, attack);
+
+            // attacked squares include those of pinned pieces, as in the
+            // engine's own attack map
+            if !attack {
+                moves.retain(|_move: &Move| !move_leaves_king_checked(&state, player, *_move));
+            }
+
+            let mut moves_str: Vec<String> = moves.iter().map(|&x| convert_move_to_string(x)).collect();
+            let castle_moves_str: Vec<String> = castle_moves
+                .iter()
+                .map(|&x| convert_castle_move_to_string(x))
+                .collect();
+            moves_str.extend(castle_moves_str);
+            moves_str
+        });
+        let moves_str: Vec<String> = if promotions && !attack {
+            moves_str
+                .into_iter()
+                .flat_map(|_move| {
+                    if is_promotion(&state, &convert_move_to_type(&_move)) {
+                        ["q", "r", "b", "n"]
+                            .iter()
+                            .map(|piece| format!("{}{}", _move, piece))
+                            .collect()
+                    } else {
+                        vec![_move]
+                    }
+                })
+                .collect()
+        } else {
+            moves_str
+        };
+        if annotate {
+            let moves_str = moves_str
+                .iter()
+                .map(|_move| {
+                    annotate_move(&state, player, convert_move_to_type(_move), promotion_of(_move))
+                })
+                .collect();
+            return Ok(moves_str);
+        }
+        Ok(moves_str)
+    }
+
+    fn get_castle_moves<'a>(
+        &self,
+        _py: Python<'a>,
+        state_py: &'a PyDict,
+        _player: &str,
+    ) -> PyResult<Vec<String>> {
+        // parse state
+        let state: State = convert_py_state(_py, state_py)?;
+
+        // parse arguments
+        let player: Color = convert_py_player(_player)?;
+
+        let castle_moves: Vec<Castle> = get_possible_castle_moves(&state, player, false);
+        let castle_moves_str: Vec<String> = castle_moves
+            .iter()
+            .map(|&x| convert_castle_move_to_string(x))
+            .collect();
+        Ok(castle_moves_str)
+    }
+
+    /// Pieces of `player` that the opponent wins material against (attacked and
+    /// insufficiently defended according to static exchange evaluation), as
+    /// dicts {square, piece, attackers, defenders, loss} sorted by decreasing loss
+    fn get_threats<'a>(
+        &self,
+        _py: Python<'a>,
+        state_py: &'a PyDict,
+        _player: &str,
+    ) -> PyResult<Vec<&'a PyDict>> {
+        // parse state
+        let state: State = convert_py_state(_py, state_py)?;
+
+        // parse arguments
+        let player: Color = convert_py_player(_player)?;
+
+        let threats_py = tactics::get_threats(&state.board, player)
+            .iter()
+            .map(|threat| {
+                let threat_py = PyDict::new(_py);
+                threat_to_py_object(threat, threat_py);
+                threat_py
+            })
+            .collect();
+        Ok(threats_py)
+    }
+
+    /// Static exchange evaluation of `move` in centipawns: material won by the
+    /// moving side after the exchange on the target square (negative for a
+    /// losing capture). Castling moves score 0.
+    fn see<'a>(&self, _py: Python<'a>, state_py: &'a PyDict, _move: &str) -> PyResult<i32> {
+        // parse state
+        let state: State = convert_py_state(_py, state_py)?;
+
+        // parse arguments
+        let move_union = convert_py_move(_move)?;
+
+        if move_union.is_castle {
+            return Ok(0);
+        }
+        let normal_move = unsafe { move_union.data.normal_move };
+        let (row, col) = normal_move.0;
+        if state.board[row as usize][col as usize] == 0 {
+            return Err(PyValueError::new_err(format!("No piece to move in '{}'", _move)));
+        }
+        Ok(tactics::see_move(&state.board, normal_move))
+    }
+
+    /// Forks, skewers and discovered attacks in favour of `player`, as dicts
+    /// {kind, attacker, targets, blocker}. With `last_move` (the move `player`
+    /// just played to reach `state`) only the motifs created by that move.
+    #[args(last_move = "None")]
+    fn get_motifs<'a>(
+        &self,
+        _py: Python<'a>,
+        state_py: &'a PyDict,
+        _player: &str,
+        last_move: Option<&str>,
+    ) -> PyResult<Vec<&'a PyDict>> {
+        // parse state
+        let state: State = convert_py_state(_py, state_py)?;
+
+        // parse arguments
+        let player: Color = convert_py_player(_player)?;
+        let last_move: Option<Move> = match last_move {
+            Some(_move) => {
+                let move_struct = convert_py_move(_move)?;
+                if move_struct.is_castle {
+                    return Err(PyValueError::new_err("last_move must not be a castle move"));
+                }
+                Some(unsafe { move_struct.data.normal_move })
+            }
+            None => None,
+        };
+
+        let motifs_py = tactics::find_motifs(&state.board, player, last_move)
+            .iter()
+            .map(|motif| {
+                let motif_py = PyDict::new(_py);
+                motif_to_py_object(motif, motif_py);
+                motif_py
+            })
+            .collect();
+        Ok(motifs_py)
+    }
+
+    /// Board control: for each square, the number and the piece ids of the
+    /// white and black pieces attacking it, as 8x8 grids (same layout as
+    /// `board`) under the keys "white", "black", "white_pieces", "black_pieces"
+    fn control_map<'a>(&self, _py: Python<'a>, state_py: &'a PyDict) -> PyResult<&'a PyDict> {
+        // parse state
+        let state: State = convert_py_state(_py, state_py)?;
+
+        let map = tactics::control_map(&state.board);
+        let control_py = PyDict::new(_py);
+        control_map_to_py_object(&map, control_py)?;
+        Ok(control_py)
+    }
+
+    /// Net control of each square as an 8x8 grid (same layout as `board`):
+    /// white attackers minus black attackers
+    fn control_heatmap<'a>(&self, _py: Python<'a>, state_py: &'a PyDict) -> PyResult<Vec<Vec<i32>>> {
+        // parse state
+        let state: State = convert_py_state(_py, state_py)?;
+
+        let heatmap = tactics::control_heatmap(&state.board);
+        Ok(heatmap.iter().map(|row| row.to_vec()).collect())
+    }
+
+    /// King safety features of both sides under "white" and "black": the
+    /// "pawn_shield", the "attacker_weight" of the pieces attacking the king
+    /// zone and the "open_files" around the king (None for a missing king)
+    fn king_safety<'a>(&self, _py: Python<'a>, state_py: &'a PyDict) -> PyResult<&'a PyDict> {
+        // parse state
+        let state: State = convert_py_state(_py, state_py)?;
+
+        let safety_py = PyDict::new(_py);
+        king_safety_to_py_object(_py, &state.board, safety_py)?;
+        Ok(safety_py)
+    }
+
+    /// SVG image of the board, with the `control_heatmap` as an overlay if
+    /// `heatmap` is True. `labels` draws the rank and file labels on all four
+    /// sides, `flipped` puts black at the bottom, and `move_number` and
+    /// `side_to_move` (True) are written under the board.
+    #[args(
+        heatmap = false,
+        labels = false,
+        flipped = false,
+        move_number = "None",
+        side_to_move = false
+    )]
+    fn render_svg<'a>(
+        &self,
+        _py: Python<'a>,
+        state_py: &'a PyDict,
+        heatmap: bool,
+        labels: bool,
+        flipped: bool,
+        move_number: Option<u32>,
+        side_to_move: bool,
+    ) -> PyResult<String> {
+        // parse state
+        let state: State = convert_py_state(_py, state_py)?;
+
+        // parse arguments
+        let options = render_options(&state, labels, flipped, move_number, side_to_move);
+
+        let control = if heatmap {
+            Some(tactics::control_heatmap(&state.board))
+        } else {
+            None
+        };
+        Ok(svg::board_to_svg(&state.board, control.as_ref(), &options))
+    }
+
+    /// Text diagram of the board, with the options of `render_svg`
+    #[args(labels = false, flipped = false, move_number = "None", side_to_move = false)]
+    fn render_text<'a>(
+        &self,
+        _py: Python<'a>,
+        state_py: &'a PyDict,
+        labels: bool,
+        flipped: bool,
+        move_number: Option<u32>,
+        side_to_move: bool,
+    ) -> PyResult<String> {
+        // parse state
+        let state: State = convert_py_state(_py, state_py)?;
+
+        // parse arguments
+        let options = render_options(&state, labels, flipped, move_number, side_to_move);
+
+        Ok(render::board_to_text(&state.board, &options))
+    }
+
+    /// Standalone HTML review of a game (a dict with "fens", "moves" and
+    /// optionally "result" and "opening", like the self-play games): an eval
+    /// graph, the board after each move and the inaccuracies, mistakes and
+    /// blunders. `analysis` has one entry per move: an `analyze` result
+    /// (score from the side to move's point of view, best_move) or a score
+    /// from white's point of view; the game's "scores" by default. `path`
+    /// also writes the page to a file.
+    #[staticmethod]
+    #[args(analysis = "None", path = "None", title = "None")]
+    fn export_analysis_html(
+        game: &PyDict,
+        analysis: Option<&PyList>,
+        path: Option<&str>,
+        title: Option<String>,
+    ) -> PyResult<String> {
+        // parse arguments
+        let item = |key: &str| {
+            game.get_item(key)
+                .ok_or_else(|| PyKeyError::new_err(format!("game has no '{}'", key)))
+        };
+        let fens: Vec<String> = item("fens")?.extract()?;
+        let moves: Vec<String> = item("moves")?.extract()?;
+        let result: String = match game.get_item("result") {
+            Some(result) => result.extract()?,
+            None => "*".to_string(),
+        };
+        let title = match title {
+            Some(title) => title,
+            None => match game.get_item("opening").map(|opening| opening.extract::<Option<String>>()) {
+                Some(Ok(Some(opening))) => format!("Game review: {}", opening),
+                _ => "Game review".to_string(),
+            },
+        };
+        let analysis_py: &PyList = match analysis {
+            Some(analysis) => analysis,
+            None => item("scores")?.downcast()?,
+        };
+        let mut analysis: Vec<MoveAnalysis> = vec![];
+        for (ply, entry) in analysis_py.iter().enumerate() {
+            let move_analysis = match entry.downcast::<PyDict>() {
+                Ok(info) => {
+                    let score: isize = info
+                        .get_item("score")
+                        .ok_or_else(|| PyKeyError::new_err(format!("analysis {} has no 'score'", ply)))?
+                        .extract()?;
+                    let black_to_move = fens
+                        .get(ply)
+                        .and_then(|fen| from_fen(fen).ok())
+                        .map_or(false, |state| state.current_player == Color::Black);
+                    let best_move = match info.get_item("best_move") {
+                        Some(best_move) => Some(best_move.extract::<String>()?),
+                        None => None,
+                    };
+                    MoveAnalysis {
+                        score: if black_to_move { -score } else { score },
+                        best_move: best_move.filter(|best_move| !best_move.is_empty()),
+                    }
+                }
+                Err(_) => MoveAnalysis {
+                    score: entry.extract()?,
+                    best_move: None,
+                },
+            };
+            analysis.push(move_analysis);
+        }
+
+        let html = report::export_analysis_html(&title, &fens, &moves, &analysis, &result)
+            .map_err(PyValueError::new_err)?;
+        if let Some(path) = path {
+            std::fs::write(path, &html)?;
+        }
+        Ok(html)
+    }
+
+    /// The games of a PGN file as dicts {tags, fen, move_number, comment,
+    /// moves, result}, each move a dict {san, move (UCI), nags, comment,
+    /// variations}, the variations being lists of moves replacing it. See
+    /// `PgnReader` for files too large to load.
+    #[staticmethod]
+    fn parse_pgn<'a>(_py: Python<'a>, text: &str) -> PyResult<Vec<&'a PyDict>> {
+        let games = pgn::parse_pgn(text).map_err(PyValueError::new_err)?;
+        let mut games_py: Vec<&PyDict> = vec![];
+        for game in games.iter() {
+            games_py.push(pgn_game_to_py(_py, game)?);
+        }
+        Ok(games_py)
+    }
+
+    /// PGN text of a game dict like those of `parse_pgn`, whose moves give
+    /// their "move" (UCI) or "san"; every key but "moves" is optional
+    #[staticmethod]
+    fn write_pgn(game: &PyDict) -> PyResult<String> {
+        // parse arguments
+        let moves_py: &PyList = game
+            .get_item("moves")
+            .ok_or_else(|| PyKeyError::new_err("game has no 'moves'"))?
+            .downcast()?;
+        let start = match game.get_item("fen") {
+            Some(fen) => from_fen(fen.extract()?).map_err(PyValueError::new_err)?,
+            None => State::new(DEFAULT_BOARD, Color::White, true, true, true, true),
+        };
+        let mut tags: Vec<(String, String)> = vec![];
+        if let Some(tags_py) = game.get_item("tags") {
+            let tags_py: &PyDict = tags_py.downcast()?;
+            for (name, value) in tags_py.iter() {
+                tags.push((name.extract()?, value.str()?.to_string()));
+            }
+        }
+        let pgn_game = PgnGame {
+            tags,
+            start,
+            move_number: match game.get_item("move_number") {
+                Some(number) => number.extract()?,
+                None => 1,
+            },
+            comment: match game.get_item("comment") {
+                Some(comment) => comment.extract()?,
+                None => None,
+            },
+            moves: convert_py_pgn_moves(&start, moves_py)?,
+            result: match game.get_item("result") {
+                Some(result) => result.extract()?,
+                None => "*".to_string(),
+            },
+        };
+        Ok(pgn::write_pgn(&pgn_game))
+    }
+
+    /// PGN text of search `lines` ((score, pv) like the "lines" of `search`)
+    /// from `state`: the first line is the main line and the others are
+    /// variations of its first move, each score commented after the line's
+    /// first move
+    #[staticmethod]
+    #[args(tags = "None")]
+    fn lines_to_pgn<'a>(
+        _py: Python<'a>,
+        state_py: &'a PyDict,
+        lines: Vec<(isize, Vec<String>)>,
+        tags: Option<&PyDict>,
+    ) -> PyResult<String> {
+        // parse state
+        let state: State = convert_py_state(_py, state_py)?;
+
+        // parse arguments
+        let mut lines_vec: Vec<(isize, Vec<MoveStruct>)> = vec![];
+        for (score, pv) in lines.iter() {
+            let mut line_state = state;
+            let mut moves: Vec<MoveStruct> = vec![];
+            for _move in pv {
+                line_state = make_move_str(&line_state, _move).map_err(PyValueError::new_err)?;
+                moves.push(convert_py_move(_move)?);
+            }
+            lines_vec.push((*score, moves));
+        }
+        let mut tags_vec: Vec<(String, String)> = vec![];
+        for (name, value) in tags.into_iter().flat_map(|tags| tags.iter()) {
+            tags_vec.push((name.extract()?, value.str()?.to_string()));
+        }
+        let fen = try_to_fen(state).map_err(PyValueError::new_err)?;
+        if fen != START_FEN {
+            tags_vec.push(("SetUp".to_string(), "1".to_string()));
+            tags_vec.push(("FEN".to_string(), fen));
+        }
+        Ok(pgn::write_pgn(&pgn::lines_to_game(&state, &lines_vec, tags_vec)))
+    }
+
+    /// `control` (the `control_map` of `state`) updated after `player` plays
+    /// `move`, recomputing only the squares the move can affect, which are
+    /// listed under the extra key "squares"
+    fn update_control_map<'a>(
+        &self,
+        _py: Python<'a>,
+        state_py: &'a PyDict,
+        _player: &str,
+        _move: &str,
+        control_py: &'a PyDict,
+    ) -> PyResult<&'a PyDict> {
+        // parse state
+        let state: State = convert_py_state(_py, state_py)?;
+
+        // parse arguments
+        let player: Color = convert_py_player(_player)?;
+        let move_union = convert_py_move(_move)?;
+        let mut map: [[SquareControl; 8]; 8] = Default::default();
+        for (key, white) in [("white_pieces", true), ("black_pieces", false)].iter() {
+            let pieces: Vec<Vec<Vec<isize>>> = control_py
+                .get_item(key)
+                .ok_or_else(|| PyValueError::new_err(format!("control map without '{}'", key)))?
+                .extract()?;
+            if pieces.len() != 8 || pieces.iter().any(|row| row.len() != 8) {
+                return Err(PyValueError::new_err(format!("'{}' is not 8x8", key)));
+            }
+            for (row, pieces_row) in pieces.into_iter().enumerate() {
+                for (col, square_pieces) in pieces_row.into_iter().enumerate() {
+                    if *white {
+                        map[row][col].white = square_pieces;
+                    } else {
+                        map[row][col].black = square_pieces;
+                    }
+                }
+            }
+        }
+
+        let (new_state, _) = try_next_state(&state, player, move_union, promotion_of(_move)).map_err(PyValueError::new_err)?;
+        let squares = tactics::update_control_map(&mut map, &state.board, &new_state.board);
+        let control_py = PyDict::new(_py);
+        control_map_to_py_object(&map, control_py)?;
+        control_py.set_item("squares", squares)?;
+        Ok(control_py)
+    }
+
+    fn update_state<'a>(&self, _py: Python<'a>, state_py: &'a PyDict) -> PyResult<&'a PyDict> {
+        // parse state
+        let mut state: State = convert_py_state(_py, state_py)?;
+        // update kings under attack
+        update_state(&mut state);
+        // Python state
+        let state_py = PyDict::new(_py);
+        state.to_py_object(state_py);
+        game_status_to_py_object(&*self.rules(), &state, state_py)?;
+        Ok(state_py)
+    }
+
+    /// `search` to a fixed `depth` (at least 1) without time limit, returning
+    /// (score, move) with an empty move when `player` has none; kept for the
+    /// Python environments, `search` takes every other limit
+    fn minimax<'a>(
+        &self,
+        _py: Python<'a>,
+        state_py: &'a PyDict,
+        depth: usize,
+        player: &str,
+    ) -> PyResult<Py<PyTuple>> {
+        // parse state
+        let state: State = convert_py_state(_py, state_py)?;
+
+        // parse arguments
+        let player: Color = convert_py_player(player)?;
+        check_turn(&state, player).map_err(PyValueError::new_err)?;
+
+        let mut settings = self.search_settings(Some(depth.clamp(1, MAX_DEPTH as usize) as u32));
+        settings.time = None;
+        let stop = self.stops.new_flag();
+        let tt = self.tt.clone();
+        // the search only touches Rust data: let other Python threads run meanwhile
+        let info = _py.allow_threads(move || run_search(&state, player, &settings, stop, &tt, |_| {}))?;
+        let (best_score, best_move_str) = match info {
+            Some(info) => (
+                info.score,
+                info.pv.first().map(convert_move_union_to_string).unwrap_or_default(),
+            ),
+            None => (0, "".to_string()),
+        };
+        let tuple = PyTuple::new(_py, vec![best_score.to_object(_py), best_move_str.to_object(_py)]);
+        Ok(tuple.into())
+    }
+
+    /// Search the side to move of the position within `limits` (a
+    /// `SearchLimits`, the engine config filling in what is not set) and
+    /// return the info of the last completed iteration, with the best
+    /// `multipv` lines under "lines" as (score, pv). `movetime_ms` is a
+    /// shorthand for the limit of the same name: the search deepens until
+    /// the budget expires and the best move is "best_move".
+    #[args(limits = "None", movetime_ms = "None")]
+    fn search<'a>(
+        &self,
+        _py: Python<'a>,
+        state_py: &'a PyDict,
+        limits: Option<SearchLimits>,
+        movetime_ms: Option<u64>,
+    ) -> PyResult<&'a PyDict> {
+        // parse state
+        let state: State = convert_py_state(_py, state_py)?;
+        let player = state.current_player;
+        check_turn(&state, player).map_err(PyValueError::new_err)?;
+
+        // parse arguments
+        let mut limits = limits.unwrap_or_default();
+        if movetime_ms.is_some() {
+            limits.movetime_ms = movetime_ms;
+        }
+        let limits = limits.to_limits(&self.config.lock().unwrap());
+        if limits.multipv == 0 {
+            return Err(PyValueError::new_err("multipv must be at least 1"));
+        }
+        let mut settings = self.search_settings(limits.depth);
+        if let Some(time) = limits.time_budget(player) {
+            settings.time = Some(time);
+        }
+        settings.max_nodes = limits.nodes;
+        settings.multipv = limits.multipv;
+        if let Some(root_policy) = limits.root_policy(&state).map_err(PyValueError::new_err)? {
+            settings.root_policy = Some(root_policy);
+        }
+
+        let stop = self.stops.new_flag();
+        let tt = self.tt.clone();
+        let info = _py.allow_threads(move || run_search(&state, player, &settings, stop, &tt, |_| {}))?;
+
+        let info_py = PyDict::new(_py);
+        if let Some(info) = info {
+            search_info_to_py_object(&info, info_py);
+        }
+        Ok(info_py)
+    }
+
+    /// Run the engine on `puzzles` (dicts like those of
+    /// `load_lichess_puzzles`, with a "state" or "fen" and the "solution";
+    /// "id", "rating" and "themes" are optional), searching each of the
+    /// solver's positions within `limits` (a `SearchLimits`) while the
+    /// opponent replies from the solution. A mating move solves a puzzle
+    /// even when the solution mates differently. Returns {solved, total,
+    /// accuracy, by_theme, by_rating, puzzles}: the rates by theme and by
+    /// rating bucket ("1200-1399") are {solved, total, accuracy}, and each
+    /// puzzle gives {id, solved, moves, failed_at} (the index in the
+    /// solution of the first wrong move).
+    #[args(limits = "None")]
+    fn solve_puzzles<'a>(
+        &self,
+        _py: Python<'a>,
+        puzzles: Vec<&'a PyDict>,
+        limits: Option<SearchLimits>,
+    ) -> PyResult<&'a PyDict> {
+        // parse arguments
+        let mut puzzles_rs: Vec<(String, State, Vec<MoveStruct>, u32, Vec<String>)> = vec![];
+        for puzzle_py in puzzles.iter() {
+            let item = |key: &str| puzzle_py.get_item(key);
+            let state = match (item("state"), item("fen")) {
+                (Some(state_py), _) => convert_py_state(_py, state_py.downcast()?)?,
+                (None, Some(fen)) => from_fen(fen.extract()?).map_err(PyValueError::new_err)?,
+                (None, None) => return Err(PyKeyError::new_err("puzzle without 'state' or 'fen'")),
+            };
+            let solution: Vec<String> = item("solution")
+                .ok_or_else(|| PyKeyError::new_err("puzzle without 'solution'"))?
+                .extract()?;
+            let solution = solution
+                .iter()
+                .map(|_move| {
+                    let move_struct = convert_py_move(_move)?;
+                    Ok(MoveStruct {
+                        promotion: Some(promotion_of(_move)),
+                        ..move_struct
+                    })
+                })
+                .collect::<PyResult<Vec<MoveStruct>>>()?;
+            let id: String = match item("id") {
+                Some(id) => id.str()?.to_string(),
+                None => puzzles_rs.len().to_string(),
+            };
+            let rating: u32 = match item("rating") {
+                Some(rating) => rating.extract()?,
+                None => 0,
+            };
+            let themes: Vec<String> = match item("themes") {
+                Some(themes) => themes.extract()?,
+                None => vec![],
+            };
+            puzzles_rs.push((id, state, solution, rating, themes));
+        }
+        let limits = limits.unwrap_or_default().to_limits(&self.config.lock().unwrap());
+        let mut settings = self.search_settings(limits.depth);
+        settings.max_nodes = limits.nodes;
+        let rules = self.rules();
+
+        let stop = self.stops.new_flag();
+        let tt = self.tt.clone();
+        let (report, attempts) = _py.allow_threads(move || {
+            let mut report = PuzzleReport::default();
+            let mut attempts: Vec<(String, PuzzleAttempt)> = vec![];
+            for (id, state, solution, rating, themes) in puzzles_rs {
+                if let Some(time) = limits.time_budget(state.current_player) {
+                    settings.time = Some(time);
+                }
+                let attempt = puzzles::attempt_puzzle(&*rules, &state, &solution, |state| {
+                    let info = run_search(state, state.current_player, &settings, stop.clone(), &tt, |_| {})?;
+                    Ok::<_, PyErr>(info.and_then(|info| info.pv.first().cloned()))
+                })?;
+                report.add(rating, &themes, attempt.solved);
+                attempts.push((id, attempt));
+            }
+            Ok::<_, PyErr>((report, attempts))
+        })?;
+
+        let rate_to_py = |rate: &SolveRate| {
+            let rate_py = PyDict::new(_py);
+            rate_py.set_item("solved", rate.solved).unwrap();
+            rate_py.set_item("total", rate.total).unwrap();
+            rate_py.set_item("accuracy", rate.accuracy()).unwrap();
+            rate_py
+        };
+        let report_py = rate_to_py(&report.overall);
+        let by_theme_py = PyDict::new(_py);
+        for (theme, rate) in report.by_theme.iter() {
+            by_theme_py.set_item(theme, rate_to_py(rate))?;
+        }
+        let by_rating_py = PyDict::new(_py);
+        for (bucket, rate) in report.by_rating.iter() {
+            let name = format!("{}-{}", bucket, bucket + puzzles::RATING_BUCKET - 1);
+            by_rating_py.set_item(name, rate_to_py(rate))?;
+        }
+        let mut attempts_py: Vec<&PyDict> = vec![];
+        for (id, attempt) in attempts.iter() {
+            let moves: Vec<String> = attempt.moves.iter().map(convert_move_union_to_string).collect();
+            let attempt_py = PyDict::new(_py);
+            attempt_py.set_item("id", id)?;
+            attempt_py.set_item("solved", attempt.solved)?;
+            attempt_py.set_item("moves", moves)?;
+            attempt_py.set_item("failed_at", attempt.failed_at)?;
+            attempts_py.push(attempt_py);
+        }
+        report_py.set_item("by_theme", by_theme_py)?;
+        report_py.set_item("by_rating", by_rating_py)?;
+        report_py.set_item("puzzles", attempts_py)?;
+        Ok(report_py)
+    }
+
+    /// Move to play for the side to move as {move, source}: the best book
+    /// move while in the config's book ("source" "book"), else the best
+    /// move of a search to `depth` ("search", None without legal moves)
+    #[args(depth = "None")]
+    fn best_move<'a>(
+        &self,
+        _py: Python<'a>,
+        state_py: &'a PyDict,
+        depth: Option<u32>,
+    ) -> PyResult<&'a PyDict> {
+        // parse state
+        let state: State = convert_py_state(_py, state_py)?;
+        let player = state.current_player;
+        check_turn(&state, player).map_err(PyValueError::new_err)?;
+
+        let move_py = PyDict::new(_py);
+        let book_move = self
+            .book
+            .as_ref()
+            .and_then(|book| book.lock().unwrap().moves(&state).into_iter().next());
+        if let Some(entry) = book_move {
+            move_py.set_item("move", convert_move_union_to_string(&entry._move))?;
+            move_py.set_item("source", "book")?;
+            return Ok(move_py);
+        }
+
+        let settings = self.search_settings(depth);
+        let stop = self.stops.new_flag();
+        let tt = self.tt.clone();
+        let info = _py.allow_threads(move || run_search(&state, player, &settings, stop, &tt, |_| {}))?;
+        let best_move = info.and_then(|info| info.pv.first().map(convert_move_union_to_string));
+        move_py.set_item("move", best_move)?;
+        move_py.set_item("source", "search")?;
+        Ok(move_py)
+    }
+
+    /// Grade `move` of the side to move against the engine's best move at
+    /// `depth`: returns the "best_move", its "best_score", the "score" of
+    /// `move` and the centipawns it loses ("delta", 0 for the best move, a
+    /// mate counting as 1000) with its "annotation" ("inaccuracy", "mistake",
+    /// "blunder" or None)
+    #[args(depth = "None")]
+    fn grade_move<'a>(
+        &self,
+        _py: Python<'a>,
+        state_py: &'a PyDict,
+        _move: &str,
+        depth: Option<u32>,
+    ) -> PyResult<&'a PyDict> {
+        // parse state
+        let state: State = convert_py_state(_py, state_py)?;
+        let player = state.current_player;
+        check_turn(&state, player).map_err(PyValueError::new_err)?;
+
+        // parse arguments
+        if !legal_move_strings(&state).iter().any(|m| m == _move) {
+            return Err(PyValueError::new_err(format!("'{}' is not a legal move", _move)));
+        }
+        let move_struct = convert_py_move(_move)?;
+        let best_settings = self.search_settings(depth);
+        // the same search with `move` as the only root move
+        let mut move_settings = best_settings.clone();
+        move_settings.root_policy = Some(RootPolicy {
+            priors: vec![(move_struct, 1.0)],
+            min_prior: Some(1.0),
+            top_k: None,
+        });
+
+        let stop = self.stops.new_flag();
+        let tt = self.tt.clone();
+        let (best, graded) = _py.allow_threads(move || -> PyResult<_> {
+            let best = run_search(&state, player, &best_settings, stop.clone(), &tt, |_| {})?;
+            let graded = run_search(&state, player, &move_settings, stop, &tt, |_| {})?;
+            Ok((best, graded))
+        })?;
+        let (best, graded) = match (best, graded) {
+            (Some(best), Some(graded)) => (best, graded),
+            _ => return Err(PyException::new_err("the search was stopped")),
+        };
+
+        let score = graded.score;
+        // searched alone, the move can score above the best one
+        let (best_move, best_score) = if score >= best.score {
+            (_move.to_string(), score)
+        } else {
+            (convert_move_union_to_string(&best.pv[0]), best.score)
+        };
+        let delta = report::clamp_score(best_score) - report::clamp_score(score);
+        let grade_py = PyDict::new(_py);
+        grade_py.set_item("move", _move)?;
+        grade_py.set_item("score", score)?;
+        grade_py.set_item("best_move", best_move)?;
+        grade_py.set_item("best_score", best_score)?;
+        grade_py.set_item("delta", delta)?;
+        grade_py.set_item("annotation", report::annotate_loss(delta).map(|a| a.to_str()))?;
+        Ok(grade_py)
+    }
+
+    /// Difficulty of the position for its side to move, for curriculum
+    /// sampling and puzzle rating: a dict with the "difficulty" (0 to 100)
+    /// and what it mixes, the "branching" factor, the score "volatility"
+    /// and "best_move_changes" of searches at depths 1 to `depth`, and the
+    /// number of "tactics" (motifs and pieces en prise of both sides)
+    #[args(depth = "None")]
+    fn estimate_difficulty<'a>(
+        &self,
+        _py: Python<'a>,
+        state_py: &'a PyDict,
+        depth: Option<u32>,
+    ) -> PyResult<&'a PyDict> {
+        // parse state
+        let state: State = convert_py_state(_py, state_py)?;
+        check_turn(&state, state.current_player).map_err(PyValueError::new_err)?;
+
+        // parse arguments
+        let depth = depth.unwrap_or(DEFAULT_DIFFICULTY_DEPTH);
+
+        let stop = self.stops.new_flag();
+        let evaluator = self.evaluator.clone();
+        let difficulty = _py.allow_threads(|| estimate_difficulty(&state, &evaluator, depth, stop));
+        if let Some(e) = evaluator.take_error() {
+            return Err(PyException::new_err(format!("evaluation failed: {}", e)));
+        }
+
+        let difficulty_py = PyDict::new(_py);
+        difficulty_py.set_item("difficulty", difficulty.score)?;
+        difficulty_py.set_item("branching", difficulty.branching)?;
+        difficulty_py.set_item("volatility", difficulty.volatility)?;
+        difficulty_py.set_item("best_move_changes", difficulty.best_move_changes)?;
+        difficulty_py.set_item("tactics", difficulty.tactics)?;
+        difficulty_py.set_item("depth", difficulty.depth)?;
+        Ok(difficulty_py)
+    }
+
+    /// Search the position with increasing depth until `stop()` is called
+    /// (or `depth` / the configured search limits are reached), calling
+    /// `callback(info)` after every completed iteration.
+    /// Returns the info of the last completed iteration.
+    #[args(callback = "None", depth = "None")]
+    fn analyze<'a>(
+        &self,
+        _py: Python<'a>,
+        state_py: &'a PyDict,
+        player: &str,
+        callback: Option<PyObject>,
+        depth: Option<u32>,
+    ) -> PyResult<&'a PyDict> {
+        // parse state
+        let state: State = convert_py_state(_py, state_py)?;
+
+        // parse arguments
+        let player: Color = convert_py_player(player)?;
+        check_turn(&state, player).map_err(PyValueError::new_err)?;
+        let limits = self.search_settings(depth);
+
+        let stop = self.stops.new_flag();
+        let tt = self.tt.clone();
+        let (result, callback_err) = _py.allow_threads(move || {
+            let mut callback_err: Option<PyErr> = None;
+            let result = run_search(&state, player, &limits, stop.clone(), &tt, |info| {
+                if let Some(callback) = &callback {
+                    Python::with_gil(|py| {
+                        let info_py = PyDict::new(py);
+                        search_info_to_py_object(info, info_py);
+                        if let Err(e) = callback.call1(py, (info_py,)) {
+                            callback_err = Some(e);
+                            stop.store(true, Ordering::SeqCst);
+                        }
+                    });
+                }
+            });
+            (result, callback_err)
+        });
+        if let Some(e) = callback_err {
+            return Err(e);
+        }
+        let info = result?;
+
+        let info_py = PyDict::new(_py);
+        if let Some(info) = info {
+            search_info_to_py_object(&info, info_py);
+        }
+        Ok(info_py)
+    }
+
+    /// Search like `analyze` with a prior over the root moves (`policy`: move
+    /// -> prior, e.g. from a policy network; unlisted moves have prior 0).
+    /// The root moves are searched by decreasing prior, and only those with a
+    /// prior >= `min_prior` and among the `top_k` highest when given (every
+    /// move if that leaves none). A 0/1 `policy` with `min_prior=1` is an
+    /// action mask.
+    #[args(depth = "None", min_prior = "None", top_k = "None")]
+    fn search_with_policy<'a>(
+        &self,
+        _py: Python<'a>,
+        state_py: &'a PyDict,
+        player: &str,
+        policy: &'a PyDict,
+        depth: Option<u32>,
+        min_prior: Option<f64>,
+        top_k: Option<usize>,
+    ) -> PyResult<&'a PyDict> {
+        // parse state
+        let mut state: State = convert_py_state(_py, state_py)?;
+
+        // parse arguments
+        let player: Color = convert_py_player(player)?;
+        check_turn(&state, player).map_err(PyValueError::new_err)?;
+        state.current_player = player;
+        let legal_moves = legal_move_strings(&state);
+        let mut priors: Vec<(MoveStruct, f64)> = vec![];
+        for (_move, prior) in policy.iter() {
+            let _move: &str = _move.extract()?;
+            if !legal_moves.iter().any(|m| m == _move) {
+                return Err(PyValueError::new_err(format!("'{}' is not a legal move", _move)));
+            }
+            priors.push((convert_py_move(_move)?, prior.extract()?));
+        }
+        let mut limits = self.search_settings(depth);
+        limits.root_policy = Some(RootPolicy {
+            priors,
+            min_prior,
+            top_k,
+        });
+
+        let stop = self.stops.new_flag();
+        let tt = self.tt.clone();
+        let info = _py.allow_threads(move || run_search(&state, player, &limits, stop, &tt, |_| {}))?;
+
+        let info_py = PyDict::new(_py);
+        if let Some(info) = info {
+            search_info_to_py_object(&info, info_py);
+        }
+        Ok(info_py)
+    }
+
+    /// Interrupt the searches running on this engine (`search`, `analyze`,
+    /// `best_move`, `ablation_match`, ...) from another Python thread; each
+    /// returns what it completed. Searches started afterwards are not affected.
+    fn stop(&self) {
+        self.stops.stop_all();
+    }
+
+    /// Search many independent positions (FEN strings or state dicts, each
+    /// searched for its side to move) on `threads` Rust threads, each with its
+    /// own transposition table. Returns one info dict per position, in order.
+    /// `stop()` interrupts the batch (finished positions keep their result).
+    /// With `deterministic` (default from the config) thread t searches
+    /// positions t, t + threads, ... from an empty table without time
+    /// cutoffs, so the same batch, threads and hash size always give the
+    /// same results.
+    #[args(depth = "None", movetime_ms = "None", threads = "None", deterministic = "None")]
+    fn analyze_batch<'a>(
+        &self,
+        _py: Python<'a>,
+        positions: &'a PyList,
+        depth: Option<u32>,
+        movetime_ms: Option<u64>,
+        threads: Option<usize>,
+        deterministic: Option<bool>,
+    ) -> PyResult<Vec<&'a PyDict>> {
+        // parse positions
+        let mut states: Vec<State> = vec![];
+        for (i, position) in positions.iter().enumerate() {
+            let state = match position.extract::<&str>() {
+                Ok(fen) => from_fen(fen).map_err(|e| PyValueError::new_err(format!("position {}: {}", i, e)))?,
+                Err(_) => convert_py_state(_py, position.downcast::<PyDict>()?)?,
+            };
+            check_turn(&state, state.current_player)
+                .map_err(|e| PyValueError::new_err(format!("position {}: {}", i, e)))?;
+            states.push(state);
+        }
+
+        // parse arguments
+        let mut limits = self.search_settings(depth);
+        if movetime_ms.is_some() {
+            limits.time = movetime_ms.map(|ms| TimeBudget::fixed(Duration::from_millis(ms)));
+        }
+        if let Some(deterministic) = deterministic {
+            limits.deterministic = deterministic;
+        }
+        limits.check_deterministic()?;
+        let (hash_mb, config_threads) = {
+            let config = self.config.lock().unwrap();
+            (config.hash_mb, config.threads)
+        };
+        let threads = threads.unwrap_or(config_threads).max(1).min(states.len().max(1));
+
+        let stop = self.stops.new_flag();
+        let results: Vec<Mutex<Option<SearchInfo>>> = states.iter().map(|_| Mutex::new(None)).collect();
+        let next_index = AtomicUsize::new(0);
+        let pv_error: Mutex<Option<String>> = Mutex::new(None);
+        _py.allow_threads(|| {
+            thread::scope(|scope| {
+                for t in 0..threads {
+                    let (limits, states, stop) = (&limits, &states, &stop);
+                    let (next_index, results, pv_error) = (&next_index, &results, &pv_error);
+                    scope.spawn(move || {
+                        let mut tt = TranspositionTable::new((hash_mb / threads).max(1));
+                        let mut n = 0;
+                        loop {
+                            // fixed split in deterministic mode, else the next free position
+                            let i = match limits.deterministic {
+                                true => t + n * threads,
+                                false => next_index.fetch_add(1, Ordering::SeqCst),
+                            };
+                            n += 1;
+                            if i >= states.len() || stop.load(Ordering::SeqCst) {
+                                break;
+                            }
+                            let state = &states[i];
+                            let mut searcher = Searcher::new(stop.clone(), &mut tt);
+                            searcher.evaluator = limits.evaluator.clone();
+                            searcher.verify_pv = limits.verify_pv;
+                            searcher.deterministic = limits.deterministic;
+                            let info = searcher.iterative_deepening(
+                                state,
+                                state.current_player,
+                                limits.max_depth,
+                                limits.time,
+                                |_| {},
+                            );
+                            if let Some(e) = searcher.pv_error.take() {
+                                pv_error.lock().unwrap().get_or_insert(format!("state {}, {}", i, e));
+                            }
+                            *results[i].lock().unwrap() = info;
+                        }
+                    });
+                }
+            });
+        });
+        if let Some(e) = limits.evaluator.take_error() {
+            return Err(PyException::new_err(format!("evaluation failed: {}", e)));
+        }
+        if let Some(e) = pv_error.into_inner().unwrap() {
+            return Err(PyException::new_err(format!("PV verification failed: {}", e)));
+        }
+
+        let infos_py = results
+            .into_iter()
+            .map(|result| {
+                let info_py = PyDict::new(_py);
+                if let Some(info) = result.into_inner().unwrap() {
+                    search_info_to_py_object(&info, info_py);
+                }
+                info_py
+            })
+            .collect();
+        Ok(infos_py)
+    }
+
+    /// Start a search in a background thread and return immediately with a
+    /// `SearchHandle` to poll, wait for or cancel it.
+    /// Other searches on this engine wait until it is done.
+    #[args(depth = "None")]
+    fn start_search<'a>(
+        &self,
+        _py: Python<'a>,
+        state_py: &'a PyDict,
+        player: &str,
+        depth: Option<u32>,
+    ) -> PyResult<SearchHandle> {
+        // parse state
+        let state: State = convert_py_state(_py, state_py)?;
+
+        // parse arguments
+        let player: Color = convert_py_player(player)?;
+        check_turn(&state, player).map_err(PyValueError::new_err)?;
+        let limits = self.search_settings(depth);
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let latest: Arc<Mutex<Option<SearchInfo>>> = Arc::new(Mutex::new(None));
+        let tt = self.tt.clone();
+        let thread_stop = stop.clone();
+        let thread_latest = latest.clone();
+        let thread = thread::spawn(move || {
+            run_search(&state, player, &limits, thread_stop, &tt, |info| {
+                *thread_latest.lock().unwrap() = Some(info.clone());
+            })
+        });
+        Ok(SearchHandle {
+            stop,
+            latest,
+            thread: Mutex::new(Some(thread)),
+            result: Mutex::new(None),
+        })
+    }
+
+    /// Start `workers` threads playing games against themselves and return a
+    /// `SelfPlayWorkers` to collect the finished games from. Each worker has
+    /// its own transposition table; the first `random_plies` moves of each
+    /// game are random. `adjudication` overrides the config's resign/draw
+    /// rules (keys of the `[adjudication]` config table); games start from
+    /// positions sampled from `openings` (an `OpeningSuite`) if given.
+    /// With a `book` (an `OpeningBook`, best with `random_plies=0`) the moves
+    /// are drawn from the book while in book and each game updates it (the
+    /// config's `book_path` book by default, if available). `eval_noise`
+    /// adds Gaussian noise of that standard deviation (centipawns) to the
+    /// evaluations, seeded per game from `seed`, to diversify the games.
+    #[args(
+        workers = "None",
+        depth = "None",
+        random_plies = "8",
+        max_plies = "None",
+        seed = "None",
+        adjudication = "None",
+        openings = "None",
+        book = "None",
+        eval_noise = "0.0"
+    )]
+    #[allow(clippy::too_many_arguments)]
+    fn start_self_play(
+        &self,
+        workers: Option<usize>,
+        depth: Option<u32>,
+        random_plies: u32,
+        max_plies: Option<u32>,
+        seed: Option<u64>,
+        adjudication: Option<&PyDict>,
+        openings: Option<PyRef<OpeningSuite>>,
+        book: Option<PyRef<OpeningBook>>,
+        eval_noise: f64,
+    ) -> PyResult<SelfPlayWorkers> {
+        // parse arguments
+        if !(eval_noise >= 0.0 && eval_noise.is_finite()) {
+            return Err(PyValueError::new_err("eval_noise must be a non-negative number of centipawns"));
+        }
+        let config = self.config.lock().unwrap();
+        let mut adjudication = convert_py_adjudication(config.adjudication, adjudication)?;
+        if let Some(max_plies) = max_plies {
+            adjudication.max_plies = max_plies;
+        }
+        let selfplay_config = SelfPlayConfig {
+            depth: depth.or(config.depth).unwrap_or(DEFAULT_SELFPLAY_DEPTH),
+            movetime: config.movetime_ms.map(Duration::from_millis),
+            openings: openings.map(|openings| openings.suite.clone()),
+            random_plies,
+            adjudication,
+            hash_mb: config.hash_mb,
+            book: book.map(|book| book.book.clone()).or_else(|| self.book.clone()),
+            rules: rules_for(&config.variant).map_err(PyValueError::new_err)?,
+            eval_noise,
+        };
+        let workers = workers.unwrap_or(config.threads);
+        let pool = selfplay::SelfPlayPool::start(workers, selfplay_config, self.evaluator.clone(), seed);
+        Ok(SelfPlayWorkers {
+            pool,
+            evaluator: self.evaluator.clone(),
+            telemetry_dir: config.telemetry_dir.clone(),
+        })
+    }
+
+    /// Handcrafted evaluation of `state` for the side to move with the
+    /// configured weights ("score") and the contribution of each of `terms`
+    /// ("deltas": the score minus the score without the term). `terms`
+    /// defaults to all of "material", "pawn_advance", "center", "mobility" and
+    /// "attack_defense".
+    #[args(terms = "None")]
+    fn eval_ablation<'a>(
+        &self,
+        _py: Python<'a>,
+        state_py: &'a PyDict,
+        terms: Option<Vec<String>>,
+    ) -> PyResult<&'a PyDict> {
+        // parse state
+        let state: State = convert_py_state(_py, state_py)?;
+
+        // parse arguments
+        let terms = convert_eval_terms(terms)?;
+
+        let weights = self.config.lock().unwrap().eval;
+        let (score, deltas) = ablation::term_deltas(&state, state.current_player, &weights, &terms);
+        let deltas_py = PyDict::new(_py);
+        for (term, delta) in deltas.iter() {
+            deltas_py.set_item(term.name(), delta)?;
+        }
+        let result_py = PyDict::new(_py);
+        result_py.set_item("score", score)?;
+        result_py.set_item("deltas", deltas_py)?;
+        Ok(result_py)
+    }
+
+    /// Play `games` games (alternating colors, on `threads` threads) of the
+    /// handcrafted engine without each of `terms` against the full engine
+    /// and return, per term, the ablated engine's "wins", "draws", "losses",
+    /// "score" and the "elo_loss" of dropping the term (None when a side won
+    /// every game). `stop()` interrupts the match.
+    #[args(
+        terms = "None",
+        games = "10",
+        depth = "None",
+        random_plies = "8",
+        threads = "None",
+        seed = "None",
+        adjudication = "None",
+        openings = "None"
+    )]
+    fn ablation_match<'a>(
+        &self,
+        _py: Python<'a>,
+        terms: Option<Vec<String>>,
+        games: usize,
+        depth: Option<u32>,
+        random_plies: u32,
+        threads: Option<usize>,
+        seed: Option<u64>,
+        adjudication: Option<&PyDict>,
+        openings: Option<PyRef<OpeningSuite>>,
+    ) -> PyResult<Vec<&'a PyDict>> {
+        // parse arguments
+        let terms = convert_eval_terms(terms)?;
+        let config = self.config.lock().unwrap().clone();
+        let ablation_config = AblationConfig {
+            arena: ArenaConfig {
+                games,
+                openings: openings.map(|openings| openings.suite.clone()),
+                random_plies,
+                adjudication: convert_py_adjudication(config.adjudication, adjudication)?,
+                threads: threads.unwrap_or(config.threads),
+            },
+            depth: depth.or(config.depth).unwrap_or(DEFAULT_SELFPLAY_DEPTH),
+            hash_mb: config.hash_mb,
+            seed,
+        };
+
+        let stop = self.stops.new_flag();
+        let results = _py
+            .allow_threads(|| ablation::ablation_match(&config.eval, &terms, &ablation_config, &stop))
+            .map_err(PyValueError::new_err)?;
+
+        let results_py = results
+            .iter()
+            .map(|result| {
+                let result_py = PyDict::new(_py);
+                result_py.set_item("term", result.term.name()).unwrap();
+                result_py.set_item("wins", result.wins).unwrap();
+                result_py.set_item("draws", result.draws).unwrap();
+                result_py.set_item("losses", result.losses).unwrap();
+                result_py.set_item("score", result.score()).unwrap();
+                result_py.set_item("elo_loss", result.elo_loss()).unwrap();
+                result_py
+            })
+            .collect();
+        Ok(results_py)
+    }
+
+    /// Transposition table occupancy and counters of the last search
+    fn tt_stats<'a>(&self, _py: Python<'a>) -> PyResult<&'a PyDict> {
+        let (size_mb, hashfull, stats) = self.with_tt(_py, |tt| (tt.size_mb(), tt.hashfull(), tt.stats))?;
+        let stats_py = PyDict::new(_py);
+        stats_py.set_item("size_mb", size_mb)?;
+        stats_py.set_item("hashfull", hashfull)?;
+        tt_stats_to_py_object(&stats, stats_py);
+        Ok(stats_py)
+    }
+
+    /// Legal move cache size, occupancy and counters
+    fn move_cache_stats<'a>(&self, _py: Python<'a>) -> PyResult<&'a PyDict> {
+        let cache = self.move_cache.lock().unwrap();
+        let stats_py = PyDict::new(_py);
+        stats_py.set_item("capacity", cache.capacity())?;
+        stats_py.set_item("entries", cache.num_entries())?;
+        stats_py.set_item("hits", cache.stats.hits)?;
+        stats_py.set_item("misses", cache.stats.misses)?;
+        stats_py.set_item("evictions", cache.stats.evictions)?;
+        Ok(stats_py)
+    }
+
+    /// Number of positions whose moves `get_possible_moves` keeps (0 disables
+    /// the cache), the tables being scaled down if that exceeds `set_max_memory`
+    fn set_move_cache_size(&self, _py: Python, entries: usize) -> PyResult<()> {
+        self.config.lock().unwrap().move_cache_entries = entries;
+        self.resize_tables(_py)
+    }
+
+    fn clear_move_cache(&self) {
+        self.move_cache.lock().unwrap().clear();
+    }
+
+    /// Memory held by the engine's tables in bytes: {"tt", "move_cache",
+    /// "book" (only with a book), "total"}, and the cap set with
+    /// `set_max_memory` ("max_memory_mb")
+    fn memory_usage<'a>(&self, _py: Python<'a>) -> PyResult<&'a PyDict> {
+        let usage = MemoryUsage {
+            tt: self.with_tt(_py, |tt| tt.size_bytes())?,
+            move_cache: self.move_cache.lock().unwrap().size_bytes(),
+            book: self.book.as_ref().map(|book| book.lock().unwrap().size_bytes()),
+        };
+        let usage_py = PyDict::new(_py);
+        usage_py.set_item("tt", usage.tt)?;
+        usage_py.set_item("move_cache", usage.move_cache)?;
+        if let Some(book) = usage.book {
+            usage_py.set_item("book", book)?;
+        }
+        usage_py.set_item("total", usage.total())?;
+        usage_py.set_item("max_memory_mb", self.config.lock().unwrap().max_memory_mb)?;
+        Ok(usage_py)
+    }
+
+    /// Cap the transposition table and move cache together at `max_mb`,
+    /// scaling both down to fit (None lifts the cap without growing them
+    /// back). A resized transposition table starts empty.
+    #[args(max_mb = "None")]
+    fn set_max_memory(&self, _py: Python, max_mb: Option<usize>) -> PyResult<()> {
+        self.config.lock().unwrap().max_memory_mb = max_mb;
+        self.resize_tables(_py)
+    }
+
+    /// Whether the config's `book_path` book was loaded (`best_move` and
+    /// self-play search without it)
+    fn book_available(&self) -> bool {
+        self.book_status.is_available()
+    }
+
+    /// Whether the config's `tablebase_path` holds Syzygy tables (reported
+    /// only: the search does not probe them)
+    fn tb_available(&self) -> bool {
+        self.tablebase_status.is_available()
+    }
+
+    /// {"book": ..., "tablebase": ...} with the "path" of each optional data
+    /// file of the config, its "status" ("available", "not_configured" or
+    /// "unavailable") and why it is unavailable ("error", None otherwise)
+    fn data_status<'a>(&self, _py: Python<'a>) -> PyResult<&'a PyDict> {
+        let config = self.config.lock().unwrap();
+        let status_py = PyDict::new(_py);
+        let files = [
+            ("book", &config.book_path, &self.book_status),
+            ("tablebase", &config.tablebase_path, &self.tablebase_status),
+        ];
+        for (name, path, status) in files.iter() {
+            let file_py = PyDict::new(_py);
+            file_py.set_item("path", path.as_deref())?;
+            file_py.set_item("status", status.name())?;
+            file_py.set_item("error", status.error())?;
+            status_py.set_item(name, file_py)?;
+        }
+        Ok(status_py)
+    }
+
+    /// Replay the PV of every search iteration and raise if a move is illegal,
+    /// to catch transposition table or PV corruption in tests
+    fn set_verify_pv(&self, enabled: bool) {
+        self.config.lock().unwrap().verify_pv = enabled;
+    }
+
+    /// Reproducible searches: movetimes are ignored and each search uses an
+    /// empty private transposition table, leaving the engine's one untouched
+    /// (a depth or node limit is required). With `threads` above 1 in the
+    /// config, the root moves are split among that many threads in a fixed
+    /// order, each with its own table, giving the same result on every run.
+    fn set_deterministic(&self, enabled: bool) {
+        self.config.lock().unwrap().deterministic = enabled;
+    }
+
+    /// Write a JSON report to `path` after each search (None disables reports)
+    #[args(path = "None")]
+    fn set_telemetry_dir(&self, path: Option<String>) {
+        self.config.lock().unwrap().telemetry_dir = path;
+    }
+
+    fn clear_hash(&self, _py: Python) -> PyResult<()> {
+        self.with_tt(_py, |tt| tt.clear())
+    }
+
+    /// Save the transposition table to disk so a later process can reuse it
+    fn save_hash(&self, _py: Python, path: &str) -> PyResult<usize> {
+        Ok(self.with_tt(_py, |tt| tt.save(Path::new(path)))??)
+    }
+
+    /// Merge a transposition table saved with `save_hash` into the current one
+    fn load_hash(&self, _py: Python, path: &str) -> PyResult<usize> {
+        Ok(self.with_tt(_py, |tt| tt.load(Path::new(path)))??)
+    }
+}
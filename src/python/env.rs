@@ -0,0 +1,559 @@
+use pyo3::exceptions::{PyRuntimeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use std::thread;
+
+use crate::env::{self, Action, Env, StepError, VecEnv};
+use crate::policies::DEFAULT_POLICY_DEPTH;
+use crate::rng::Rng;
+use crate::{
+    is_promotion, parse_move, player_enum_to_string, promotion_of, try_to_fen, Color,
+    State, DEFAULT_BOARD, QUEEN_ID,
+};
+
+use super::{
+    convert_py_player, episode_stats_to_py_object, convert_py_action, planes_to_vec3d, make_space,
+    convert_py_opponent, convert_py_rewards, king_safety_to_py_object, control_map_to_py_object,
+};
+
+/// RL environment playing both sides of a game from the initial position.
+/// Observations are planes of 8x8 squares stacking the last `history`
+/// positions (8 in AlphaZero) with repetition planes, kept by the environment.
+/// `rewards` overrides the rewards of the mover: "win", "material" (factor
+/// of the capture and promotion reward) and the shaping bonuses "check",
+/// "castle" and "passed_pawn" (per passed pawn created), and the penalty
+/// "unconverted_win" of the side ahead by "winning_material" (pawn = 1) when
+/// the game ends in stalemate or by the fifty-move rule, which are off by default.
+/// The intrinsic rewards "exploration_branching" (per unit of log number of
+/// legal moves) and "exploration_uncertainty" (per pawn of standard deviation
+/// of the search scores up to "exploration_depth", 3 by default) of the
+/// position a step reaches are added to its reward, also reported as
+/// info["intrinsic_reward"]; they are off by default.
+/// With an "illegal_move" reward, `step` returns it for an illegal action
+/// instead of raising.
+/// With `auto_queen` (the default) pawns always promote to a queen, for action
+/// spaces without underpromotions; otherwise promotion moves name their piece
+/// ("e7e8q", "e7e8n") and `action_space` has underpromotion actions.
+/// With an `opponent` (see `set_opponent`) the agent only plays `agent_color`.
+#[pyclass]
+pub struct ChessEnv {
+    env: Env,
+}
+
+#[pymethods]
+impl ChessEnv {
+    #[new]
+    #[args(
+        history = "1",
+        max_plies = "None",
+        rewards = "None",
+        auto_queen = "true",
+        opponent = "None",
+        opponent_depth = "DEFAULT_POLICY_DEPTH",
+        opponent_noise = "0.0",
+        agent_color = "\"WHITE\""
+    )]
+    fn new(
+        history: usize,
+        max_plies: Option<u32>,
+        rewards: Option<&PyDict>,
+        auto_queen: bool,
+        opponent: Option<&str>,
+        opponent_depth: u32,
+        opponent_noise: f64,
+        agent_color: &str,
+    ) -> PyResult<Self> {
+        let mut env = Env::new(history, max_plies).map_err(PyValueError::new_err)?;
+        env.rewards = convert_py_rewards(rewards)?;
+        env.auto_queen = auto_queen;
+        let mut chess_env = ChessEnv { env };
+        chess_env.set_opponent(opponent, opponent_depth, opponent_noise, agent_color)?;
+        Ok(chess_env)
+    }
+
+    /// Play against a scripted `opponent` answering every move: "random"
+    /// (random legal moves), "greedy" (the move winning the most material,
+    /// random among equal ones) or "minimax" (a `depth` search, with
+    /// probability `noise` a random move instead), the agent playing
+    /// `agent_color`. None plays both sides. Takes effect from the next
+    /// `reset`, where the opponent moves first if it has white.
+    #[args(
+        opponent = "None",
+        depth = "DEFAULT_POLICY_DEPTH",
+        noise = "0.0",
+        agent_color = "\"WHITE\""
+    )]
+    fn set_opponent(
+        &mut self,
+        opponent: Option<&str>,
+        depth: u32,
+        noise: f64,
+        agent_color: &str,
+    ) -> PyResult<()> {
+        // parse arguments
+        let opponent = convert_py_opponent(opponent, depth, noise)?;
+        let agent_color: Color = convert_py_player(agent_color)?;
+
+        self.env.opponent = opponent;
+        self.env.agent_color = agent_color;
+        Ok(())
+    }
+
+    /// Name of the scripted opponent (None without one)
+    #[getter]
+    fn opponent(&self) -> Option<&'static str> {
+        self.env.opponent.map(|opponent| opponent.name())
+    }
+
+    #[getter]
+    fn agent_color(&self) -> &'static str {
+        player_enum_to_string(&self.env.agent_color)
+    }
+
+    /// Start a new episode from the initial position or from `fen` (rejected
+    /// with ValueError if it is not a valid position), returning
+    /// (observation, info). `seed` reseeds the environment's random moves.
+    #[args(fen = "None", seed = "None")]
+    fn reset<'a>(
+        &mut self,
+        _py: Python<'a>,
+        fen: Option<&str>,
+        seed: Option<u64>,
+    ) -> PyResult<(Vec<Vec<Vec<f32>>>, &'a PyDict)> {
+        match fen {
+            Some(fen) => self.env.reset_from_fen(fen).map_err(PyValueError::new_err)?,
+            None => self
+                .env
+                .reset(State::new(DEFAULT_BOARD, Color::White, true, true, true, true)),
+        }
+        if let Some(seed) = seed {
+            self.env.rng = Rng::new(seed);
+        }
+        return Ok((self.observation(), self.info(_py)?));
+    }
+
+    /// A random legal move of the side to move (None once done), reproducible
+    /// after `reset(seed=...)`
+    fn random_move(&mut self) -> Option<String> {
+        self.env.random_move()
+    }
+
+    /// Play a move of `legal_moves`, or an action of `legal_actions`, for the
+    /// side to move, returning (observation, reward of the mover, done, info).
+    /// A failed step leaves the environment unchanged: an illegal action
+    /// raises ValueError and an engine error RuntimeError, or with an
+    /// "illegal_move" reward both return it with info["error"] set to
+    /// {kind ("illegal_move" or "internal_error"), message}.
+    fn step<'a>(
+        &mut self,
+        _py: Python<'a>,
+        action: &PyAny,
+    ) -> PyResult<(Vec<Vec<Vec<f32>>>, f64, bool, &'a PyDict)> {
+        // parse arguments
+        let _move = convert_py_action(action)?
+            .to_move(self.env.state(), self.env.auto_queen)
+            .map_err(StepError::IllegalMove);
+
+        let result = _move.and_then(|_move| self.env.step(&_move));
+        let reward = match (result, self.env.rewards.illegal_move) {
+            (Ok(reward), _) => reward,
+            (Err(error), Some(penalty)) => {
+                let info_py = self.info(_py)?;
+                let error_py = PyDict::new(_py);
+                error_py.set_item("kind", error.kind())?;
+                error_py.set_item("message", error.message())?;
+                info_py.set_item("error", error_py)?;
+                return Ok((self.observation(), penalty, self.env.outcome.is_some(), info_py));
+            }
+            (Err(StepError::IllegalMove(message)), None) => {
+                return Err(PyValueError::new_err(message))
+            }
+            (Err(StepError::Internal(message)), None) => {
+                return Err(PyRuntimeError::new_err(message))
+            }
+        };
+        return Ok((
+            self.observation(),
+            reward,
+            self.env.outcome.is_some(),
+            self.info(_py)?,
+        ));
+    }
+
+    /// Replay `actions` (moves or actions, as for `step`) from the initial
+    /// position or `from_fen` in a new game with the same settings, leaving
+    /// the current episode untouched. Returns a dict with the "observations"
+    /// of every position (the start included), the "rewards" and "fens" after
+    /// each action, and the final "result" and "termination". Raises
+    /// ValueError at the first illegal action.
+    #[args(from_fen = "None")]
+    fn replay<'a>(
+        &self,
+        _py: Python<'a>,
+        actions: Vec<&PyAny>,
+        from_fen: Option<&str>,
+    ) -> PyResult<&'a PyDict> {
+        // parse arguments
+        let actions = actions
+            .into_iter()
+            .map(convert_py_action)
+            .collect::<PyResult<Vec<Action>>>()?;
+
+        let env = &self.env;
+        let (env, observations, rewards) = _py
+            .allow_threads(|| env.replay(from_fen, &actions))
+            .map_err(PyValueError::new_err)?;
+        let fens: Vec<String> = env
+            .positions()
+            .iter()
+            .skip(1)
+            .map(|state| try_to_fen(*state))
+            .collect::<Result<_, _>>()
+            .map_err(PyValueError::new_err)?;
+        let observations: Vec<Vec<Vec<Vec<f32>>>> = observations
+            .iter()
+            .map(|planes| planes_to_vec3d(planes))
+            .collect();
+        let (result, termination) = match env.outcome {
+            Some((result, termination)) => (Some(result.to_str()), Some(termination)),
+            None => (None, None),
+        };
+        let replay_py = PyDict::new(_py);
+        replay_py.set_item("observations", observations)?;
+        replay_py.set_item("rewards", rewards)?;
+        replay_py.set_item("fens", fens)?;
+        replay_py.set_item("result", result)?;
+        replay_py.set_item("termination", termination)?;
+        Ok(replay_py)
+    }
+
+    /// `num_planes` planes of 8x8 squares from the point of view of the side
+    /// to move: for each stacked position (most recent first) 6 planes for
+    /// its pieces, 6 for the opponent's and 2 for repetitions, then the side
+    /// to move, the 4 castling rights, and the repetition count and halfmove
+    /// clock scaled to [0, 1]
+    fn observation(&self) -> Vec<Vec<Vec<f32>>> {
+        planes_to_vec3d(&self.env.observation())
+    }
+
+    /// ply, repetitions, halfmove_clock, legal_moves, result and termination
+    /// once done, the exploration bonus included in the last reward
+    /// ("intrinsic_reward") and the episode "stats" of each side (captures by
+    /// piece, checks, promotions, castles)
+    fn info<'a>(&self, _py: Python<'a>) -> PyResult<&'a PyDict> {
+        let info_py = PyDict::new(_py);
+        info_py.set_item("ply", self.env.ply())?;
+        info_py.set_item("repetitions", self.env.repetitions())?;
+        info_py.set_item("halfmove_clock", self.env.halfmove_clock())?;
+        info_py.set_item("legal_moves", self.legal_moves())?;
+        let (result, termination) = match self.env.outcome {
+            Some((result, termination)) => (Some(result.to_str()), Some(termination)),
+            None => (None, None),
+        };
+        info_py.set_item("result", result)?;
+        info_py.set_item("termination", termination)?;
+        info_py.set_item("intrinsic_reward", self.env.intrinsic_reward)?;
+        let stats_py = PyDict::new(_py);
+        episode_stats_to_py_object(_py, &self.env.stats, stats_py)?;
+        info_py.set_item("stats", stats_py)?;
+        Ok(info_py)
+    }
+
+    /// `ChessEngine.control_map` of the current position, kept up to date
+    /// move by move once it has been requested
+    fn control_map<'a>(&mut self, _py: Python<'a>) -> PyResult<&'a PyDict> {
+        let control_py = PyDict::new(_py);
+        control_map_to_py_object(self.env.control_map(), control_py)?;
+        Ok(control_py)
+    }
+
+    /// `ChessEngine.king_safety` of the current position
+    fn king_safety<'a>(&self, _py: Python<'a>) -> PyResult<&'a PyDict> {
+        let safety_py = PyDict::new(_py);
+        king_safety_to_py_object(_py, &self.env.state().board, safety_py)?;
+        Ok(safety_py)
+    }
+
+    /// Observations of the positions of `fens` (num_fens x num_planes x 8
+    /// x 8), each as at the start of an episode with `history` stacked
+    /// positions, converted on `threads` Rust threads (all the cores by
+    /// default) for preprocessing datasets. Raises ValueError at the first
+    /// invalid FEN.
+    #[staticmethod]
+    #[args(history = "1", threads = "None")]
+    fn fens_to_planes(
+        _py: Python,
+        fens: Vec<String>,
+        history: usize,
+        threads: Option<usize>,
+    ) -> PyResult<Vec<Vec<Vec<Vec<f32>>>>> {
+        // parse arguments
+        let threads = threads
+            .unwrap_or_else(|| thread::available_parallelism().map_or(1, |n| n.get()));
+
+        let planes = _py
+            .allow_threads(|| env::fens_to_planes(&fens, history, threads))
+            .map_err(PyValueError::new_err)?;
+        let size = (history * env::PLANES_PER_POSITION + env::CONSTANT_PLANES) * 64;
+        Ok(planes.chunks(size).map(planes_to_vec3d).collect())
+    }
+
+    #[getter]
+    fn history(&self) -> usize {
+        self.env.history
+    }
+
+    #[getter]
+    fn auto_queen(&self) -> bool {
+        self.env.auto_queen
+    }
+
+    #[getter]
+    fn num_planes(&self) -> usize {
+        self.env.num_planes()
+    }
+
+    #[getter]
+    fn state<'a>(&self, _py: Python<'a>) -> &'a PyDict {
+        let state_py = PyDict::new(_py);
+        self.env.state().to_py_object(state_py);
+        state_py
+    }
+
+    #[getter]
+    fn fen(&self) -> PyResult<String> {
+        try_to_fen(*self.env.state()).map_err(PyValueError::new_err)
+    }
+
+    #[getter]
+    fn done(&self) -> bool {
+        self.env.outcome.is_some()
+    }
+
+    fn legal_moves(&self) -> Vec<String> {
+        if self.env.outcome.is_some() {
+            return vec![];
+        }
+        self.env.legal_moves()
+    }
+
+    /// `legal_moves` as actions of `action_space`: from_square * 64 +
+    /// to_square, squares numbered row by row from a8 (castling is the
+    /// king's move), then without `auto_queen` the underpromotions by file of
+    /// the pawn, direction (capture left, push, capture right) and piece
+    /// (rook, bishop, knight)
+    fn legal_actions(&self) -> Vec<usize> {
+        self.legal_moves()
+            .iter()
+            .map(|_move| env::move_to_action(_move))
+            .collect()
+    }
+
+    /// Box of shape (num_planes, 8, 8) with values in [0, 1], a
+    /// `gymnasium.spaces.Box` (or `gym.spaces.Box`) if installed, otherwise
+    /// a dict describing it
+    #[getter]
+    fn observation_space(&self, _py: Python) -> PyResult<PyObject> {
+        let kwargs = PyDict::new(_py);
+        kwargs.set_item("low", 0.0)?;
+        kwargs.set_item("high", 1.0)?;
+        kwargs.set_item("shape", (self.env.num_planes(), 8, 8))?;
+        kwargs.set_item("dtype", "float32")?;
+        make_space(_py, "Box", kwargs)
+    }
+
+    /// Discrete space of the actions (see `legal_actions`), a
+    /// `gymnasium.spaces.Discrete` (or `gym.spaces.Discrete`) if installed,
+    /// otherwise a dict describing it
+    #[getter]
+    fn action_space(&self, _py: Python) -> PyResult<PyObject> {
+        let kwargs = PyDict::new(_py);
+        kwargs.set_item("n", self.env.num_actions())?;
+        make_space(_py, "Discrete", kwargs)
+    }
+
+    /// `ChessEngine.sample_action` in the current position with the
+    /// environment's random moves (reproducible after `reset(seed=...)`)
+    #[args(temperature = "1.0")]
+    fn sample_action(&mut self, policy_logits: Vec<f64>, temperature: f64) -> PyResult<(usize, String)> {
+        let env = &mut self.env;
+        let state = *env.state();
+        env::sample_action(&policy_logits, &state, env.auto_queen, temperature, &mut env.rng)
+            .map_err(PyValueError::new_err)
+    }
+
+    /// `legal_actions` as a mask over `action_space` (all False once done),
+    /// for masked policies
+    fn action_mask(&self) -> Vec<bool> {
+        self.env.action_mask()
+    }
+
+    /// Action of `move` (a move string, legal or not) in `action_space`
+    fn encode_move(&self, _move: &str) -> PyResult<usize> {
+        // parse arguments
+        let move_struct = parse_move(_move).map_err(PyValueError::new_err)?;
+        if self.env.auto_queen && promotion_of(_move) != QUEEN_ID {
+            return Err(PyValueError::new_err(format!(
+                "'{}' underpromotes but pawns always promote to a queen (auto_queen)",
+                _move
+            )));
+        }
+        if move_struct.promotion.is_some() && !is_promotion(self.env.state(), &move_struct) {
+            return Err(PyValueError::new_err(format!("'{}' is not a promotion", _move)));
+        }
+
+        Ok(env::move_to_action(_move))
+    }
+
+    /// Move string of `action` for the side to move, legal or not (see
+    /// `legal_actions`)
+    fn decode_action(&self, action: usize) -> PyResult<String> {
+        env::action_to_move(self.env.state(), action, self.env.auto_queen)
+            .map_err(PyValueError::new_err)
+    }
+}
+
+/// `num_envs` independent `ChessEnv` games stepped together in one call, the
+/// environments being split among `threads` threads (GIL released). Finished
+/// games stay done until `reset_done()`.
+#[pyclass]
+pub struct VecChessEnv {
+    envs: VecEnv,
+}
+
+impl VecChessEnv {
+    fn observations(&self) -> Vec<Vec<Vec<Vec<f32>>>> {
+        self.envs
+            .observations()
+            .chunks(self.envs.envs[0].num_planes() * 64)
+            .map(planes_to_vec3d)
+            .collect()
+    }
+}
+
+#[pymethods]
+impl VecChessEnv {
+    #[new]
+    #[args(
+        history = "1",
+        max_plies = "None",
+        rewards = "None",
+        threads = "1",
+        auto_queen = "true",
+        opponent = "None",
+        opponent_depth = "DEFAULT_POLICY_DEPTH",
+        opponent_noise = "0.0",
+        agent_color = "\"WHITE\""
+    )]
+    fn new(
+        num_envs: usize,
+        history: usize,
+        max_plies: Option<u32>,
+        rewards: Option<&PyDict>,
+        threads: usize,
+        auto_queen: bool,
+        opponent: Option<&str>,
+        opponent_depth: u32,
+        opponent_noise: f64,
+        agent_color: &str,
+    ) -> PyResult<Self> {
+        let mut envs = VecEnv::new(num_envs, history, max_plies, threads).map_err(PyValueError::new_err)?;
+        let rewards = convert_py_rewards(rewards)?;
+        let opponent = convert_py_opponent(opponent, opponent_depth, opponent_noise)?;
+        let agent_color: Color = convert_py_player(agent_color)?;
+        for env in envs.envs.iter_mut() {
+            env.rewards = rewards;
+            env.auto_queen = auto_queen;
+            env.opponent = opponent;
+            env.agent_color = agent_color;
+        }
+        Ok(VecChessEnv { envs })
+    }
+
+    /// Start a new episode in every game, returning the stacked observations
+    /// (num_envs x num_planes x 8 x 8). `seed` reseeds game i with seed + i.
+    #[args(seed = "None")]
+    fn reset(&mut self, seed: Option<u64>) -> Vec<Vec<Vec<Vec<f32>>>> {
+        self.envs.reset(seed);
+        self.observations()
+    }
+
+    /// Play one action (see `ChessEnv.legal_actions`) in each game, returning
+    /// (observations, rewards of the movers, dones). Nothing is played if an
+    /// action is illegal or its game is done (ValueError).
+    fn step_batch(
+        &mut self,
+        _py: Python,
+        actions: Vec<usize>,
+    ) -> PyResult<(Vec<Vec<Vec<Vec<f32>>>>, Vec<f64>, Vec<bool>)> {
+        let envs = &mut self.envs;
+        let rewards = _py
+            .allow_threads(|| envs.step(&actions))
+            .map_err(PyValueError::new_err)?;
+        Ok((self.observations(), rewards, self.dones()))
+    }
+
+    /// Reset the finished games, returning their indices
+    fn reset_done(&mut self) -> Vec<usize> {
+        self.envs.reset_done()
+    }
+
+    /// Whether the episode of each game is over
+    fn dones(&self) -> Vec<bool> {
+        self.envs.envs.iter().map(|env| env.outcome.is_some()).collect()
+    }
+
+    /// Legal actions of each game (empty once done)
+    fn legal_actions(&self) -> Vec<Vec<usize>> {
+        self.envs
+            .envs
+            .iter()
+            .map(|env| match env.outcome {
+                Some(_) => vec![],
+                None => env
+                    .legal_moves()
+                    .iter()
+                    .map(|_move| env::move_to_action(_move))
+                    .collect(),
+            })
+            .collect()
+    }
+
+    /// `ChessEnv.action_mask` of each game
+    fn action_masks(&self) -> Vec<Vec<bool>> {
+        self.envs.envs.iter().map(|env| env.action_mask()).collect()
+    }
+
+    /// ply, repetitions, halfmove_clock, result, termination and episode
+    /// stats of each game (see `ChessEnv.info`)
+    fn infos<'a>(&self, _py: Python<'a>) -> PyResult<Vec<&'a PyDict>> {
+        let mut infos_py = vec![];
+        for env in self.envs.envs.iter() {
+            let info_py = PyDict::new(_py);
+            info_py.set_item("ply", env.ply())?;
+            info_py.set_item("repetitions", env.repetitions())?;
+            info_py.set_item("halfmove_clock", env.halfmove_clock())?;
+            let (result, termination) = match env.outcome {
+                Some((result, termination)) => (Some(result.to_str()), Some(termination)),
+                None => (None, None),
+            };
+            info_py.set_item("result", result)?;
+            info_py.set_item("termination", termination)?;
+            let stats_py = PyDict::new(_py);
+            episode_stats_to_py_object(_py, &env.stats, stats_py)?;
+            info_py.set_item("stats", stats_py)?;
+            infos_py.push(info_py);
+        }
+        Ok(infos_py)
+    }
+
+    #[getter]
+    fn num_envs(&self) -> usize {
+        self.envs.envs.len()
+    }
+
+    #[getter]
+    fn num_planes(&self) -> usize {
+        self.envs.envs[0].num_planes()
+    }
+}
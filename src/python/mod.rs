@@ -0,0 +1,677 @@
+//! Python bindings (pyo3), built as the `gym_chess` extension module by
+//! default. The conversions between Python objects and engine types are
+//! here, the classes in a module per area: `engine` (ChessEngine), `search`
+//! (SearchLimits, SearchHandle), `selfplay` (SelfPlayWorkers,
+//! OpponentPool), `env` (ChessEnv, VecChessEnv), `pgn`, `book`
+//! (OpeningBook, OpeningSuite), `uci` (UciEngine) and `small_board`.
+
+mod book;
+mod engine;
+mod env;
+mod pgn;
+mod search;
+mod selfplay;
+mod small_board;
+mod uci;
+
+use pyo3::exceptions::{PyKeyError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::PyNativeType;
+use pyo3::types::{PyDict, PyList};
+
+use crate::ablation::EvalTerm;
+use crate::env::{Action, EpisodeStats, RewardConfig};
+use crate::king_safety::king_safety;
+use crate::legality::why_illegal;
+use crate::pgn::{PgnGame, PgnMove};
+use crate::policies::OpponentPolicy;
+use crate::render::RenderOptions;
+use crate::rules::{self, Rules};
+use crate::san::{parse_san, san_promotion, to_san};
+use crate::search::{RootMoveIteration, RootMoveStats, SearchInfo, SearchStats, TimeReport};
+use crate::selfplay::{Adjudication, Game};
+use crate::tactics::{Motif, SquareControl, Threat};
+use crate::tt::TTStats;
+use crate::{
+    board_from_diagram, board_from_diagram_text, board_to_diagram, convert_move_union_to_string,
+    convert_square_to_string, is_legal_move_str, parse_move, parse_player, player_enum_to_string,
+    promotion_of, try_next_state, try_to_fen, Board, Color, MoveStruct, Square, State,
+    PAWN_ID,
+};
+
+use self::book::{OpeningBook, OpeningSuite};
+use self::engine::ChessEngine;
+use self::env::{ChessEnv, VecChessEnv};
+use self::pgn::{PgnReader, PgnWriter};
+use self::search::{SearchHandle, SearchLimits};
+use self::selfplay::{OpponentPool, SelfPlayWorkers};
+use self::small_board::SmallBoard;
+use self::uci::UciEngine;
+
+impl State {
+    pub fn to_py_object(&self, dict: &PyDict) {
+        dict.set_item(
+            "white_king_castle_is_possible",
+            self.white_king_castle_is_possible,
+        )
+        .unwrap();
+        dict.set_item(
+            "white_queen_castle_is_possible",
+            self.white_queen_castle_is_possible,
+        )
+        .unwrap();
+        dict.set_item(
+            "black_king_castle_is_possible",
+            self.black_king_castle_is_possible,
+        )
+        .unwrap();
+        dict.set_item(
+            "black_queen_castle_is_possible",
+            self.black_queen_castle_is_possible,
+        )
+        .unwrap();
+        dict.set_item("white_king_is_checked", self.white_king_is_checked)
+            .unwrap();
+        dict.set_item("black_king_is_checked", self.black_king_is_checked)
+            .unwrap();
+        dict.set_item("en_passant_square", self.en_passant_square)
+            .unwrap();
+        dict.set_item("halfmove_clock", self.halfmove_clock).unwrap();
+        dict.set_item("fullmove_number", self.fullmove_number).unwrap();
+
+        let board: &[&[isize]] = &[
+            &self.board[0],
+            &self.board[1],
+            &self.board[2],
+            &self.board[3],
+            &self.board[4],
+            &self.board[5],
+            &self.board[6],
+            &self.board[7],
+        ];
+
+        dict.set_item("board", array2d_to_vec2d(board)).unwrap();
+        let current_player: &str = player_enum_to_string(&self.current_player);
+        dict.set_item("current_player", current_player).unwrap();
+    }
+}
+
+fn array2d_to_vec2d(arr: &[&[isize]]) -> Vec<Vec<isize>> {
+    let mut vec: Vec<Vec<isize>> = Vec::new();
+    for &row in arr.iter() {
+        vec.push(row.to_vec());
+    }
+    vec
+}
+
+fn convert_py_state<'a>(_py: Python<'a>, state_py: &'a PyDict) -> PyResult<State> {
+    let get_item = |key: &str| {
+        state_py
+            .get_item(key)
+            .ok_or_else(|| PyKeyError::new_err(format!("state without '{}'", key)))
+    };
+    let board = convert_py_board(get_item("board")?)?;
+    let current_player: &str = get_item("current_player")?.extract()?;
+    let white_king_castle_is_possible: bool = get_item("white_king_castle_is_possible")?.extract()?;
+    let white_queen_castle_is_possible: bool = get_item("white_queen_castle_is_possible")?.extract()?;
+    let black_king_castle_is_possible: bool = get_item("black_king_castle_is_possible")?.extract()?;
+    let black_queen_castle_is_possible: bool = get_item("black_queen_castle_is_possible")?.extract()?;
+    // optional, states saved before en passant was tracked have none
+    let en_passant_square: Option<Square> = match state_py.get_item("en_passant_square") {
+        Some(square) => square.extract()?,
+        None => None,
+    };
+    // optional too, 0 and 1 without
+    let halfmove_clock: u32 = match state_py.get_item("halfmove_clock") {
+        Some(clock) => clock.extract()?,
+        None => 0,
+    };
+    let fullmove_number: u32 = match state_py.get_item("fullmove_number") {
+        Some(number) => number.extract()?,
+        None => 1,
+    };
+
+    // the move generator panics on unknown pieces
+    let current_player = convert_py_player(current_player)?;
+    for (row, pieces) in board.iter().enumerate() {
+        for (col, &piece_id) in pieces.iter().enumerate() {
+            if piece_id.abs() > PAWN_ID {
+                return Err(PyValueError::new_err(format!(
+                    "Invalid piece id {} at board[{}][{}]",
+                    piece_id, row, col
+                )));
+            }
+        }
+    }
+    if let Some(square) = en_passant_square {
+        if !(square.0 == 2 || square.0 == 5) || !(0..8).contains(&square.1) {
+            return Err(PyValueError::new_err(format!(
+                "Invalid en passant square {:?}",
+                square
+            )));
+        }
+    }
+
+    // create state
+    let mut state = State::new(
+        board,
+        current_player,
+        white_king_castle_is_possible,
+        white_queen_castle_is_possible,
+        black_king_castle_is_possible,
+        black_queen_castle_is_possible,
+    );
+    state.en_passant_square = en_passant_square;
+    state.halfmove_clock = halfmove_clock;
+    state.fullmove_number = fullmove_number.max(1);
+    Ok(state)
+}
+
+/// Board of 8x8 piece ids, 8x8 piece letters ("K", "q", "." or "" for an
+/// empty square), 8 rows of letters ("rnbqkbnr") or a multiline diagram
+fn convert_py_board(board_py: &PyAny) -> PyResult<Board> {
+    if let Ok(board) = board_py.extract::<Board>() {
+        return Ok(board);
+    }
+    let board = if let Ok(text) = board_py.extract::<&str>() {
+        board_from_diagram_text(text)
+    } else if let Ok(rows) = board_py.extract::<Vec<&str>>() {
+        board_from_diagram_text(&rows.join("\n"))
+    } else if let Ok(rows) = board_py.extract::<Vec<Vec<&str>>>() {
+        let mut squares: Vec<Vec<char>> = vec![];
+        for row in rows {
+            let mut chars: Vec<char> = vec![];
+            for square in row {
+                let mut letters = square.trim().chars();
+                match (letters.next(), letters.next()) {
+                    (None, _) => chars.push('.'),
+                    (Some(c), None) => chars.push(c),
+                    _ => {
+                        return Err(PyValueError::new_err(format!(
+                            "Invalid diagram: bad square '{}'",
+                            square
+                        )))
+                    }
+                }
+            }
+            squares.push(chars);
+        }
+        board_from_diagram(&squares)
+    } else {
+        return Err(PyValueError::new_err(
+            "board must be 8x8 piece ids, 8x8 piece letters or a diagram string",
+        ));
+    };
+    board.map_err(PyValueError::new_err)
+}
+
+fn convert_py_player(player: &str) -> PyResult<Color> {
+    parse_player(player).map_err(PyValueError::new_err)
+}
+
+fn convert_py_move(_move: &str) -> PyResult<MoveStruct> {
+    parse_move(_move).map_err(PyValueError::new_err)
+}
+
+/// Move of `player` in `state` and its promotion piece for a move string
+/// ("e2e4", "e7e8n", castle moves) or a legal move in SAN ("Nbd7", "O-O-O",
+/// "exd8=N#")
+fn convert_py_move_or_san(state: &State, player: Color, _move: &str) -> PyResult<(MoveStruct, isize)> {
+    if let Ok(move_struct) = parse_move(_move) {
+        return Ok((move_struct, promotion_of(_move)));
+    }
+    let mut state = *state;
+    state.current_player = player;
+    let move_struct = parse_san(&state, _move).map_err(|message| {
+        PyValueError::new_err(format!("{}\n{}", message, board_to_diagram(&state.board)))
+    })?;
+    Ok((move_struct, san_promotion(_move)))
+}
+
+/// {"white": {...}, "black": {...}} with the "captures" by piece name, and the
+/// "checks", "promotions" and "castles" of each side
+fn episode_stats_to_py_object(
+    _py: Python,
+    stats: &[EpisodeStats; 2],
+    dict: &PyDict,
+) -> PyResult<()> {
+    let piece_names = ["king", "queen", "rook", "bishop", "knight", "pawn"];
+    for (side, side_stats) in ["white", "black"].iter().zip(stats.iter()) {
+        let captures_py = PyDict::new(_py);
+        for (name, &count) in piece_names.iter().zip(side_stats.captures.iter()).skip(1) {
+            captures_py.set_item(name, count)?;
+        }
+        let side_py = PyDict::new(_py);
+        side_py.set_item("captures", captures_py)?;
+        side_py.set_item("checks", side_stats.checks)?;
+        side_py.set_item("promotions", side_stats.promotions)?;
+        side_py.set_item("castles", side_stats.castles)?;
+        dict.set_item(side, side_py)?;
+    }
+    Ok(())
+}
+
+/// "terminal", "status" ("checkmate", "stalemate", "fifty_moves",
+/// "insufficient_material" or None while the game goes on), "winner" (None
+/// unless a side is mated) and "result" ("1-0", "0-1", "1/2-1/2" or None) of
+/// `state` with its side to move to play
+fn game_status_to_py_object(rules: &dyn Rules, state: &State, dict: &PyDict) -> PyResult<()> {
+    let terminal = rules::game_status(rules, state);
+    dict.set_item("terminal", terminal.is_some())?;
+    dict.set_item("status", terminal.map(|terminal| terminal.name()))?;
+    let winner = terminal.and_then(|terminal| terminal.winner(state.current_player));
+    dict.set_item("winner", winner.as_ref().map(player_enum_to_string))?;
+    let result = terminal.map(|terminal| terminal.result(state.current_player));
+    dict.set_item("result", result.map(|result| result.to_str()))?;
+    Ok(())
+}
+
+/// Render options of the `render_svg` and `render_text` arguments
+fn render_options(
+    state: &State,
+    labels: bool,
+    flipped: bool,
+    move_number: Option<u32>,
+    side_to_move: bool,
+) -> RenderOptions {
+    RenderOptions {
+        labels_all_sides: labels,
+        flipped,
+        move_number,
+        side_to_move: if side_to_move { Some(state.current_player) } else { None },
+    }
+}
+
+/// A move string or an action (int) of `ChessEnv.step`
+fn convert_py_action(action: &PyAny) -> PyResult<Action> {
+    match action.extract::<usize>() {
+        Ok(action) => Ok(Action::Index(action)),
+        Err(_) => Ok(Action::Move(action.extract()?)),
+    }
+}
+
+/// Planes of 8x8 squares, flattened, as nested lists
+fn planes_to_vec3d(planes: &[f32]) -> Vec<Vec<Vec<f32>>> {
+    planes
+        .chunks(64)
+        .map(|plane| plane.chunks(8).map(|row| row.to_vec()).collect())
+        .collect()
+}
+
+/// `kind` space of gymnasium (or gym) built from `kwargs`, or without either
+/// installed, `kwargs` with the space kind under "type"
+fn make_space(_py: Python, kind: &str, kwargs: &PyDict) -> PyResult<PyObject> {
+    let spaces = _py
+        .import("gymnasium.spaces")
+        .or_else(|_| _py.import("gym.spaces"));
+    match spaces {
+        Ok(spaces) => {
+            let space = spaces.getattr(kind)?.call((), Some(kwargs))?;
+            Ok(space.into())
+        }
+        Err(_) => {
+            kwargs.set_item("type", kind)?;
+            Ok(kwargs.into())
+        }
+    }
+}
+
+/// Evaluation terms by name, all of them when not given
+fn convert_eval_terms(terms: Option<Vec<String>>) -> PyResult<Vec<EvalTerm>> {
+    match terms {
+        Some(names) => names
+            .iter()
+            .map(|name| EvalTerm::from_name(name).map_err(PyValueError::new_err))
+            .collect(),
+        None => Ok(EvalTerm::ALL.to_vec()),
+    }
+}
+
+/// `base` with the rules set in `adjudication_py` (None keeps `base`)
+fn convert_py_adjudication(base: Adjudication, adjudication_py: Option<&PyDict>) -> PyResult<Adjudication> {
+    let mut adjudication = base;
+    for (key, value) in adjudication_py.into_iter().flat_map(|dict| dict.iter()) {
+        let key: &str = key.extract()?;
+        match key {
+            "resign_score" => adjudication.resign_score = value.extract()?,
+            "resign_moves" => adjudication.resign_moves = value.extract::<u32>()?.max(1),
+            "draw_score" => adjudication.draw_score = value.extract()?,
+            "draw_moves" => adjudication.draw_moves = value.extract::<u32>()?.max(1),
+            "draw_min_ply" => adjudication.draw_min_ply = value.extract()?,
+            "likely_draw" => adjudication.likely_draw = value.extract()?,
+            "max_plies" => adjudication.max_plies = value.extract()?,
+            _ => return Err(PyValueError::new_err(format!("unknown adjudication rule '{}'", key))),
+        }
+    }
+    Ok(adjudication)
+}
+
+/// Scripted opponent named `opponent` ("random", "greedy" or "minimax"
+/// searching `depth` plies with `noise`), None without a name
+fn convert_py_opponent(
+    opponent: Option<&str>,
+    depth: u32,
+    noise: f64,
+) -> PyResult<Option<OpponentPolicy>> {
+    opponent
+        .map(|name| OpponentPolicy::from_name(name, depth, noise))
+        .transpose()
+        .map_err(PyValueError::new_err)
+}
+
+/// Move dicts {san, move, nags, comment, variations} of PGN `moves`
+/// {tags, fen, move_number, comment, moves, result} of `game`
+fn pgn_game_to_py<'a>(_py: Python<'a>, game: &PgnGame) -> PyResult<&'a PyDict> {
+    let tags_py = PyDict::new(_py);
+    for (name, value) in game.tags.iter() {
+        tags_py.set_item(name, value)?;
+    }
+    let game_py = PyDict::new(_py);
+    game_py.set_item("tags", tags_py)?;
+    game_py.set_item("fen", try_to_fen(game.start).map_err(PyValueError::new_err)?)?;
+    game_py.set_item("move_number", game.move_number)?;
+    game_py.set_item("comment", &game.comment)?;
+    game_py.set_item("moves", pgn_moves_to_py(_py, &game.moves)?)?;
+    game_py.set_item("result", &game.result)?;
+    Ok(game_py)
+}
+
+fn pgn_moves_to_py<'a>(_py: Python<'a>, moves: &[PgnMove]) -> PyResult<Vec<&'a PyDict>> {
+    let mut moves_py: Vec<&PyDict> = vec![];
+    for pgn_move in moves {
+        let variations: Vec<Vec<&PyDict>> = pgn_move
+            .variations
+            .iter()
+            .map(|variation| pgn_moves_to_py(_py, variation))
+            .collect::<PyResult<_>>()?;
+        let move_py = PyDict::new(_py);
+        move_py.set_item("san", &pgn_move.san)?;
+        move_py.set_item("move", pgn_move.to_uci())?;
+        move_py.set_item("nags", &pgn_move.nags)?;
+        move_py.set_item("comment", &pgn_move.comment)?;
+        move_py.set_item("variations", variations)?;
+        moves_py.push(move_py);
+    }
+    Ok(moves_py)
+}
+
+/// SAN of `_move` (a move string) played by the side to move of `state`
+fn move_str_to_san(state: &State, _move: &str) -> Result<String, String> {
+    if let Some(reason) = why_illegal(state, _move) {
+        return Err(format!(
+            "Illegal move '{}': {}\n{}",
+            _move,
+            reason,
+            board_to_diagram(&state.board)
+        ));
+    }
+    Ok(to_san(state, &parse_move(_move)?, promotion_of(_move)))
+}
+
+/// PGN moves of the move dicts of `moves_py` played from `state`, each with
+/// a "move" (UCI) or a "san" and optionally "nags", "comment" and
+/// "variations"
+fn convert_py_pgn_moves(state: &State, moves_py: &PyList) -> PyResult<Vec<PgnMove>> {
+    let mut state = *state;
+    let mut moves: Vec<PgnMove> = vec![];
+    for move_py in moves_py.iter() {
+        let move_py: &PyDict = move_py.downcast()?;
+        let (_move, promotion) = match (move_py.get_item("move"), move_py.get_item("san")) {
+            (Some(uci), _) => {
+                let uci: &str = uci.extract()?;
+                if !is_legal_move_str(&state, uci) {
+                    return Err(PyValueError::new_err(format!("'{}' is not a legal move", uci)));
+                }
+                (convert_py_move(uci)?, promotion_of(uci))
+            }
+            (None, Some(san)) => {
+                let san: &str = san.extract()?;
+                (parse_san(&state, san).map_err(PyValueError::new_err)?, san_promotion(san))
+            }
+            (None, None) => return Err(PyKeyError::new_err("move without 'move' or 'san'")),
+        };
+        let mut pgn_move = PgnMove::new(&state, _move.clone(), promotion);
+        if let Some(nags) = move_py.get_item("nags") {
+            pgn_move.nags = nags.extract()?;
+        }
+        if let Some(comment) = move_py.get_item("comment") {
+            pgn_move.comment = comment.extract()?;
+        }
+        if let Some(variations) = move_py.get_item("variations") {
+            let variations: &PyList = variations.downcast()?;
+            for variation in variations.iter() {
+                pgn_move.variations.push(convert_py_pgn_moves(&state, variation.downcast()?)?);
+            }
+        }
+        state = try_next_state(&state, state.current_player, _move, promotion).map_err(PyValueError::new_err)?.0;
+        moves.push(pgn_move);
+    }
+    Ok(moves)
+}
+
+fn convert_py_rewards(rewards_py: Option<&PyDict>) -> PyResult<RewardConfig> {
+    let mut rewards = RewardConfig::default();
+    for (key, value) in rewards_py.into_iter().flat_map(|dict| dict.iter()) {
+        let key: &str = key.extract()?;
+        match key {
+            "win" => rewards.win = value.extract()?,
+            "material" => rewards.material = value.extract()?,
+            "check" => rewards.check = value.extract()?,
+            "castle" => rewards.castle = value.extract()?,
+            "passed_pawn" => rewards.passed_pawn = value.extract()?,
+            "unconverted_win" => rewards.unconverted_win = value.extract()?,
+            "winning_material" => rewards.winning_material = value.extract()?,
+            "illegal_move" => rewards.illegal_move = Some(value.extract()?),
+            "exploration_branching" => rewards.exploration_branching = value.extract()?,
+            "exploration_uncertainty" => rewards.exploration_uncertainty = value.extract()?,
+            "exploration_depth" => rewards.exploration_depth = value.extract()?,
+            _ => return Err(PyValueError::new_err(format!("unknown reward '{}'", key))),
+        }
+    }
+    Ok(rewards)
+}
+
+fn search_info_to_py_object(info: &SearchInfo, dict: &PyDict) {
+    dict.set_item("depth", info.depth).unwrap();
+    dict.set_item("seldepth", info.seldepth).unwrap();
+    dict.set_item("score", info.score).unwrap();
+    dict.set_item("nodes", info.nodes).unwrap();
+    dict.set_item("time_ms", info.time_ms as u64).unwrap();
+    dict.set_item("hashfull", info.hashfull).unwrap();
+    let pv: Vec<String> = info.pv.iter().map(convert_move_union_to_string).collect();
+    let best_move = pv.first().cloned().unwrap_or_default();
+    dict.set_item("pv", pv).unwrap();
+    dict.set_item("best_move", best_move).unwrap();
+    let lines: Vec<(isize, Vec<String>)> = info
+        .lines
+        .iter()
+        .map(|(score, pv)| (*score, pv.iter().map(convert_move_union_to_string).collect()))
+        .collect();
+    dict.set_item("lines", lines).unwrap();
+    let stats_py = PyDict::new(dict.py());
+    search_stats_to_py_object(&info.stats, stats_py);
+    dict.set_item("stats", stats_py).unwrap();
+    let root_moves: Vec<&PyDict> = info
+        .root_moves
+        .iter()
+        .map(|stats| root_move_stats_to_py_object(dict.py(), stats))
+        .collect();
+    dict.set_item("root_moves", root_moves).unwrap();
+    let time = info.time.as_ref().map(|time| {
+        let time_py = PyDict::new(dict.py());
+        time_report_to_py_object(time, time_py);
+        time_py
+    });
+    dict.set_item("time", time).unwrap();
+}
+
+/// {move, score, rank} of the last iteration searching the root move, its
+/// "nodes" over the whole search and the "iterations" [{depth, score, nodes,
+/// rank}]
+fn root_move_stats_to_py_object<'a>(_py: Python<'a>, stats: &RootMoveStats) -> &'a PyDict {
+    let iteration_to_py = |iteration: &RootMoveIteration| {
+        let iteration_py = PyDict::new(_py);
+        iteration_py.set_item("depth", iteration.depth).unwrap();
+        iteration_py.set_item("score", iteration.score).unwrap();
+        iteration_py.set_item("nodes", iteration.nodes).unwrap();
+        iteration_py.set_item("rank", iteration.rank).unwrap();
+        iteration_py
+    };
+    let last = stats.iterations.last().unwrap();
+    let nodes: u64 = stats.iterations.iter().map(|iteration| iteration.nodes).sum();
+    let iterations: Vec<&PyDict> = stats.iterations.iter().map(iteration_to_py).collect();
+    let stats_py = PyDict::new(_py);
+    stats_py.set_item("move", convert_move_union_to_string(&stats._move)).unwrap();
+    stats_py.set_item("score", last.score).unwrap();
+    stats_py.set_item("nodes", nodes).unwrap();
+    stats_py.set_item("rank", last.rank).unwrap();
+    stats_py.set_item("iterations", iterations).unwrap();
+    stats_py
+}
+
+/// Node types and the cutoffs of each pruning heuristic, to tune them
+fn search_stats_to_py_object(stats: &SearchStats, dict: &PyDict) {
+    dict.set_item("pv_nodes", stats.pv_nodes).unwrap();
+    dict.set_item("cut_nodes", stats.cut_nodes).unwrap();
+    dict.set_item("all_nodes", stats.all_nodes).unwrap();
+    dict.set_item("leaf_nodes", stats.leaf_nodes).unwrap();
+    dict.set_item("beta_cutoffs", stats.beta_cutoffs).unwrap();
+    dict.set_item("tt_move_cutoffs", stats.tt_move_cutoffs).unwrap();
+    dict.set_item("capture_cutoffs", stats.capture_cutoffs).unwrap();
+    dict.set_item("quiet_cutoffs", stats.quiet_cutoffs).unwrap();
+    dict.set_item("first_move_cutoffs", stats.first_move_cutoffs).unwrap();
+    dict.set_item("tt_cutoffs", stats.tt_cutoffs).unwrap();
+    dict.set_item("null_move_cutoffs", stats.null_move_cutoffs).unwrap();
+    dict.set_item("singular_extensions", stats.singular_extensions).unwrap();
+    dict.set_item("eval_histogram", stats.eval_histogram.clone()).unwrap();
+}
+
+fn tt_stats_to_py_object(stats: &TTStats, dict: &PyDict) {
+    dict.set_item("probes", stats.probes).unwrap();
+    dict.set_item("hits", stats.hits).unwrap();
+    dict.set_item("cutoffs", stats.cutoffs).unwrap();
+    dict.set_item("stores", stats.stores).unwrap();
+    dict.set_item("replacements", stats.replacements).unwrap();
+    dict.set_item("updates", stats.updates).unwrap();
+    dict.set_item("rejections", stats.rejections).unwrap();
+}
+
+fn king_safety_to_py_object(_py: Python, board: &Board, dict: &PyDict) -> PyResult<()> {
+    for (name, player) in [("white", Color::White), ("black", Color::Black)].iter() {
+        match king_safety(board, *player) {
+            Some(safety) => {
+                let side_py = PyDict::new(_py);
+                side_py.set_item("pawn_shield", safety.pawn_shield)?;
+                side_py.set_item("attacker_weight", safety.attacker_weight)?;
+                side_py.set_item("open_files", safety.open_files)?;
+                dict.set_item(name, side_py)?;
+            }
+            None => dict.set_item(name, _py.None())?,
+        }
+    }
+    Ok(())
+}
+
+fn control_map_to_py_object(map: &[[SquareControl; 8]; 8], dict: &PyDict) -> PyResult<()> {
+    let pieces = |side: fn(&SquareControl) -> &Vec<isize>| -> Vec<Vec<Vec<isize>>> {
+        map.iter()
+            .map(|row| row.iter().map(|control| side(control).clone()).collect())
+            .collect()
+    };
+    let white_pieces = pieces(|control| &control.white);
+    let black_pieces = pieces(|control| &control.black);
+    let counts = |pieces: &Vec<Vec<Vec<isize>>>| -> Vec<Vec<usize>> {
+        pieces.iter().map(|row| row.iter().map(Vec::len).collect()).collect()
+    };
+    dict.set_item("white", counts(&white_pieces))?;
+    dict.set_item("black", counts(&black_pieces))?;
+    dict.set_item("white_pieces", white_pieces)?;
+    dict.set_item("black_pieces", black_pieces)?;
+    Ok(())
+}
+
+fn threat_to_py_object(threat: &Threat, dict: &PyDict) {
+    let squares_to_strings =
+        |squares: &[Square]| -> Vec<String> { squares.iter().map(|&x| convert_square_to_string(x)).collect() };
+    dict.set_item("square", convert_square_to_string(threat.square)).unwrap();
+    dict.set_item("piece", threat.piece).unwrap();
+    dict.set_item("attackers", squares_to_strings(&threat.attackers)).unwrap();
+    dict.set_item("defenders", squares_to_strings(&threat.defenders)).unwrap();
+    dict.set_item("loss", threat.loss).unwrap();
+}
+
+fn motif_to_py_object(motif: &Motif, dict: &PyDict) {
+    let targets: Vec<String> = motif.targets.iter().map(|&x| convert_square_to_string(x)).collect();
+    dict.set_item("kind", motif.kind.to_str()).unwrap();
+    dict.set_item("attacker", convert_square_to_string(motif.attacker)).unwrap();
+    dict.set_item("targets", targets).unwrap();
+    dict.set_item("blocker", motif.blocker.map(convert_square_to_string)).unwrap();
+}
+
+/// {soft_ms, hard_ms, used_ms, stop, extensions}, each extension as {depth,
+/// reason, elapsed_ms}
+fn time_report_to_py_object(time: &TimeReport, dict: &PyDict) {
+    dict.set_item("soft_ms", time.soft_ms.map(|ms| ms as u64)).unwrap();
+    dict.set_item("hard_ms", time.hard_ms.map(|ms| ms as u64)).unwrap();
+    dict.set_item("used_ms", time.used_ms as u64).unwrap();
+    dict.set_item("stop", time.stop).unwrap();
+    let extensions: Vec<&PyDict> = time
+        .extensions
+        .iter()
+        .map(|extension| {
+            let extension_py = PyDict::new(dict.py());
+            extension_py.set_item("depth", extension.depth).unwrap();
+            extension_py.set_item("reason", extension.reason).unwrap();
+            extension_py.set_item("elapsed_ms", extension.elapsed_ms as u64).unwrap();
+            extension_py
+        })
+        .collect();
+    dict.set_item("extensions", extensions).unwrap();
+}
+
+fn game_to_py_object(game: &Game, dict: &PyDict) {
+    let moves: Vec<String> = game.moves.iter().map(convert_move_union_to_string).collect();
+    dict.set_item("opening", &game.opening).unwrap();
+    dict.set_item("fens", &game.fens).unwrap();
+    dict.set_item("moves", moves).unwrap();
+    dict.set_item("scores", &game.scores).unwrap();
+    let times: Vec<Option<&PyDict>> = game
+        .times
+        .iter()
+        .map(|time| {
+            time.as_ref().map(|time| {
+                let time_py = PyDict::new(dict.py());
+                time_report_to_py_object(time, time_py);
+                time_py
+            })
+        })
+        .collect();
+    dict.set_item("times", times).unwrap();
+    dict.set_item("result", game.result.to_str()).unwrap();
+    dict.set_item("termination", game.termination).unwrap();
+    dict.set_item("plies", game.moves.len()).unwrap();
+    dict.set_item("nodes", game.nodes).unwrap();
+    dict.set_item("time_ms", game.time_ms as u64).unwrap();
+}
+
+// PYTHON MODULE
+// ---------------------------------------------------------
+// ---------------------------------------------------------
+#[pymodule]
+fn gym_chess(_py: Python<'_>, m: &PyModule) -> PyResult<()> {
+    m.add_class::<ChessEngine>()?;
+    m.add_class::<ChessEnv>()?;
+    m.add_class::<OpeningBook>()?;
+    m.add_class::<OpeningSuite>()?;
+    m.add_class::<OpponentPool>()?;
+    m.add_class::<PgnReader>()?;
+    m.add_class::<PgnWriter>()?;
+    m.add_class::<SearchHandle>()?;
+    m.add_class::<SearchLimits>()?;
+    m.add_class::<SelfPlayWorkers>()?;
+    m.add_class::<SmallBoard>()?;
+    m.add_class::<UciEngine>()?;
+    m.add_class::<VecChessEnv>()?;
+
+    Ok(())
+}
@@ -0,0 +1,149 @@
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::PyIterProtocol;
+use pyo3::PyNativeType;
+use pyo3::types::PyDict;
+use std::fs::File;
+use std::io::{BufReader, Write};
+use std::path::Path;
+
+use crate::pgn;
+use crate::{from_fen, try_to_fen, Color, State, DEFAULT_BOARD};
+
+use super::{convert_py_move_or_san, pgn_game_to_py};
+
+/// Games of a PGN file read lazily, one game dict (see
+/// `ChessEngine.parse_pgn`) per iteration, so that multi-gigabyte database
+/// dumps never need to fit in memory. A game that does not parse raises
+/// ValueError ("game 3: ...") and iterating again resumes after it.
+#[pyclass]
+pub struct PgnReader {
+    reader: pgn::PgnReader<BufReader<File>>,
+}
+
+#[pymethods]
+impl PgnReader {
+    #[new]
+    fn new(path: &str) -> PyResult<Self> {
+        let reader = pgn::PgnReader::open(Path::new(path)).map_err(PyValueError::new_err)?;
+        Ok(PgnReader { reader })
+    }
+
+    /// Number of games read so far, those that failed to parse included
+    fn games_read(&self) -> usize {
+        self.reader.games_read()
+    }
+}
+
+#[pyproto]
+impl PyIterProtocol for PgnReader {
+    fn __iter__(slf: PyRef<Self>) -> PyRef<Self> {
+        slf
+    }
+
+    fn __next__(mut slf: PyRefMut<Self>) -> PyResult<Option<PyObject>> {
+        let next = slf.reader.next();
+        let _py = slf.py();
+        match next {
+            Some(Ok(game)) => Ok(Some(pgn_game_to_py(_py, &game)?.into())),
+            Some(Err(e)) => Err(PyValueError::new_err(e)),
+            None => Ok(None),
+        }
+    }
+}
+
+/// A game recorded move by move for PGN export: the seven tag roster
+/// (Event, Site, Date, Round, White, Black, Result), the SetUp and FEN tags
+/// when starting from `fen`, and the moves in SAN. The result is set when a
+/// move ends the game, or with `set_result` (e.g. after a resignation).
+#[pyclass]
+pub struct PgnWriter {
+    writer: pgn::PgnWriter,
+}
+
+#[pymethods]
+impl PgnWriter {
+    /// Game from `fen` (the initial position by default) with `tags`
+    #[new]
+    #[args(fen = "None", tags = "None")]
+    fn new(fen: Option<&str>, tags: Option<&PyDict>) -> PyResult<Self> {
+        let start = match fen {
+            Some(fen) => from_fen(fen).map_err(PyValueError::new_err)?,
+            None => State::new(DEFAULT_BOARD, Color::White, true, true, true, true),
+        };
+        let mut writer = pgn::PgnWriter::new(start);
+        for (name, value) in tags.into_iter().flat_map(|tags| tags.iter()) {
+            writer
+                .set_tag(name.extract()?, &value.str()?.to_string())
+                .map_err(PyValueError::new_err)?;
+        }
+        Ok(PgnWriter { writer })
+    }
+
+    /// Play `move` (a move string or SAN, like `ChessEngine.next_state`)
+    /// with an optional `comment` and return its SAN. Raises ValueError if
+    /// it is illegal or the game is over.
+    #[args(comment = "None")]
+    fn push(&mut self, _move: &str, comment: Option<String>) -> PyResult<String> {
+        let state = self.writer.state;
+        let (move_struct, promotion) = convert_py_move_or_san(&state, state.current_player, _move)?;
+        self
+            .writer
+            .push(move_struct, promotion, comment)
+            .map_err(PyValueError::new_err)
+    }
+
+    fn set_tag(&mut self, name: &str, value: &str) -> PyResult<()> {
+        self.writer.set_tag(name, value).map_err(PyValueError::new_err)
+    }
+
+    /// "1-0", "0-1", "1/2-1/2" or "*"
+    fn set_result(&mut self, result: &str) -> PyResult<()> {
+        self.writer.set_result(result).map_err(PyValueError::new_err)
+    }
+
+    #[getter]
+    fn result(&self) -> String {
+        self.writer.game.result.clone()
+    }
+
+    /// FEN of the position after the last move
+    #[getter]
+    fn fen(&self) -> PyResult<String> {
+        try_to_fen(self.writer.state).map_err(PyValueError::new_err)
+    }
+
+    /// State dict of the position after the last move
+    fn state<'a>(&self, _py: Python<'a>) -> &'a PyDict {
+        let state_py = PyDict::new(_py);
+        self.writer.state.to_py_object(state_py);
+        state_py
+    }
+
+    /// SAN of the moves played
+    fn moves(&self) -> Vec<String> {
+        self.writer.game.moves.iter().map(|pgn_move| pgn_move.san.clone()).collect()
+    }
+
+    /// PGN text of the game
+    fn pgn(&self) -> String {
+        self.writer.to_pgn()
+    }
+
+    /// Write the PGN text of the game to `path`, appending to the file with
+    /// `append` (games separated by a blank line)
+    #[args(append = "false")]
+    fn save(&self, path: &str, append: bool) -> PyResult<()> {
+        let pgn = self.writer.to_pgn();
+        if !append {
+            std::fs::write(path, pgn)?;
+            return Ok(());
+        }
+        let mut file = std::fs::OpenOptions::new().create(true).append(true).open(path)?;
+        if file.metadata()?.len() > 0 {
+            file.write_all(b"\n")?;
+        }
+        file.write_all(pgn.as_bytes())?;
+        Ok(())
+    }
+}
@@ -0,0 +1,191 @@
+use pyo3::exceptions::PyException;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+
+use crate::config::EngineConfig;
+use crate::search::{self, SearchInfo};
+
+use super::search_info_to_py_object;
+
+/// Limits of `ChessEngine.search`, each optional: the `depth`, `nodes` and
+/// `movetime_ms` of the search, the clocks `wtime_ms` / `btime_ms` and
+/// increments `winc_ms` / `binc_ms` (the side to move's clock sets the
+/// movetime when it is not given), the `multipv` best lines to find and the
+/// `searchmoves` to search at the root (every legal move by default).
+/// `move_overhead_ms` and `latency_ms`, the time each move loses outside the
+/// search (GUI and bridge processing, network round trip), are taken off the
+/// movetime; the engine config sets them by default.
+#[pyclass]
+#[derive(Clone)]
+pub struct SearchLimits {
+    #[pyo3(get, set)]
+    depth: Option<u32>,
+    #[pyo3(get, set)]
+    nodes: Option<u64>,
+    #[pyo3(get, set)]
+    pub(super) movetime_ms: Option<u64>,
+    #[pyo3(get, set)]
+    wtime_ms: Option<u64>,
+    #[pyo3(get, set)]
+    btime_ms: Option<u64>,
+    #[pyo3(get, set)]
+    winc_ms: Option<u64>,
+    #[pyo3(get, set)]
+    binc_ms: Option<u64>,
+    #[pyo3(get, set)]
+    multipv: usize,
+    #[pyo3(get, set)]
+    searchmoves: Option<Vec<String>>,
+    #[pyo3(get, set)]
+    move_overhead_ms: Option<u64>,
+    #[pyo3(get, set)]
+    latency_ms: Option<u64>,
+}
+
+impl Default for SearchLimits {
+    fn default() -> Self {
+        SearchLimits::new(None, None, None, None, None, None, None, 1, None, None, None)
+    }
+}
+
+#[pymethods]
+impl SearchLimits {
+    #[new]
+    #[args(
+        depth = "None",
+        nodes = "None",
+        movetime_ms = "None",
+        wtime_ms = "None",
+        btime_ms = "None",
+        winc_ms = "None",
+        binc_ms = "None",
+        multipv = "1",
+        searchmoves = "None",
+        move_overhead_ms = "None",
+        latency_ms = "None"
+    )]
+    fn new(
+        depth: Option<u32>,
+        nodes: Option<u64>,
+        movetime_ms: Option<u64>,
+        wtime_ms: Option<u64>,
+        btime_ms: Option<u64>,
+        winc_ms: Option<u64>,
+        binc_ms: Option<u64>,
+        multipv: usize,
+        searchmoves: Option<Vec<String>>,
+        move_overhead_ms: Option<u64>,
+        latency_ms: Option<u64>,
+    ) -> Self {
+        SearchLimits {
+            depth,
+            nodes,
+            movetime_ms,
+            wtime_ms,
+            btime_ms,
+            winc_ms,
+            binc_ms,
+            multipv,
+            searchmoves,
+            move_overhead_ms,
+            latency_ms,
+        }
+    }
+}
+
+impl SearchLimits {
+    /// the limits with the overheads of `config` when not set
+    pub(super) fn to_limits(&self, config: &EngineConfig) -> search::SearchLimits {
+        search::SearchLimits {
+            depth: self.depth,
+            nodes: self.nodes,
+            movetime_ms: self.movetime_ms,
+            wtime_ms: self.wtime_ms,
+            btime_ms: self.btime_ms,
+            winc_ms: self.winc_ms,
+            binc_ms: self.binc_ms,
+            multipv: self.multipv,
+            searchmoves: self.searchmoves.clone(),
+            move_overhead_ms: self.move_overhead_ms.unwrap_or(config.move_overhead_ms),
+            latency_ms: self.latency_ms.unwrap_or(config.latency_ms),
+        }
+    }
+}
+
+type SearchThread = JoinHandle<PyResult<Option<SearchInfo>>>;
+
+/// A search running in a background thread, returned by `ChessEngine.start_search`
+#[pyclass]
+pub struct SearchHandle {
+    stop: Arc<AtomicBool>,
+    latest: Arc<Mutex<Option<SearchInfo>>>,
+    thread: Mutex<Option<SearchThread>>,
+    result: Mutex<Option<PyResult<Option<SearchInfo>>>>,
+}
+
+impl SearchHandle {
+    /// wait for the search thread (only the first caller joins it, the others
+    /// wait on the lock) and keep its result
+    fn join(&self, _py: Python) {
+        _py.allow_threads(|| {
+            let mut thread = self.thread.lock().unwrap();
+            if let Some(thread) = thread.take() {
+                let result = match thread.join() {
+                    Ok(result) => result,
+                    Err(_) => Err(PyException::new_err("search thread panicked")),
+                };
+                *self.result.lock().unwrap() = Some(result);
+            }
+        });
+    }
+}
+
+#[pymethods]
+impl SearchHandle {
+    /// True once the search has finished (or was cancelled)
+    fn is_done(&self) -> bool {
+        match &*self.thread.lock().unwrap() {
+            Some(thread) => thread.is_finished(),
+            None => true,
+        }
+    }
+
+    /// Info of the last completed iteration so far (None before the first one),
+    /// never blocks
+    fn poll<'a>(&self, _py: Python<'a>) -> PyResult<Option<&'a PyDict>> {
+        let latest = self.latest.lock().unwrap();
+        Ok(latest.as_ref().map(|info| {
+            let info_py = PyDict::new(_py);
+            search_info_to_py_object(info, info_py);
+            info_py
+        }))
+    }
+
+    /// Wait for the search to finish (without holding the GIL) and return the
+    /// info of the last completed iteration, like `ChessEngine.analyze`
+    fn result<'a>(&self, _py: Python<'a>) -> PyResult<&'a PyDict> {
+        self.join(_py);
+        let info_py = PyDict::new(_py);
+        match &*self.result.lock().unwrap() {
+            Some(Ok(Some(info))) => search_info_to_py_object(info, info_py),
+            Some(Err(e)) => return Err(e.clone_ref(_py)),
+            _ => {}
+        }
+        Ok(info_py)
+    }
+
+    /// Ask the search to stop; `result()` then returns the last completed iteration
+    fn cancel(&self) {
+        self.stop.store(true, Ordering::SeqCst);
+    }
+}
+
+impl Drop for SearchHandle {
+    fn drop(&mut self) {
+        // don't leave an orphan thread searching (and holding the table) forever
+        self.stop.store(true, Ordering::SeqCst);
+    }
+}
@@ -0,0 +1,311 @@
+use pyo3::exceptions::{PyException, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::arena::{self, ArenaConfig, Opponent};
+use crate::config::EngineConfig;
+use crate::eval::{self, Evaluator};
+use crate::rating::{Rating, RatingPoint, DEFAULT_ELO, DEFAULT_K_FACTOR};
+use crate::selfplay::{self, Adjudication, DEFAULT_SELFPLAY_DEPTH};
+use crate::telemetry;
+use crate::player_enum_to_string;
+
+use super::{convert_py_adjudication, game_to_py_object};
+use super::engine::StopFlags;
+use super::book::OpeningSuite;
+use super::uci::UciEngine;
+
+/// Self-play games running in background threads, returned by
+/// `ChessEngine.start_self_play`
+#[pyclass]
+pub struct SelfPlayWorkers {
+    pool: selfplay::SelfPlayPool,
+    evaluator: Arc<dyn Evaluator>,
+    telemetry_dir: Option<String>,
+}
+
+#[pymethods]
+impl SelfPlayWorkers {
+    /// Wait (without holding the GIL) up to `timeout` seconds, or until a game
+    /// finishes if None, and return the finished games as dicts. The list is
+    /// empty if none finished in time or the workers were stopped.
+    #[args(timeout = "None", max_games = "None")]
+    fn get_games<'a>(
+        &self,
+        _py: Python<'a>,
+        timeout: Option<f64>,
+        max_games: Option<usize>,
+    ) -> PyResult<Vec<&'a PyDict>> {
+        // parse arguments
+        let timeout = match timeout {
+            Some(timeout) if timeout < 0.0 || !timeout.is_finite() => {
+                return Err(PyValueError::new_err("timeout must be a positive number of seconds"));
+            }
+            timeout => timeout.map(Duration::from_secs_f64),
+        };
+        let max_games = max_games.unwrap_or(usize::MAX);
+
+        let pool = &self.pool;
+        let games = _py.allow_threads(|| pool.get_games(timeout, max_games));
+        if let Some(e) = self.evaluator.take_error() {
+            return Err(PyException::new_err(format!("evaluation failed: {}", e)));
+        }
+        if let (Some(dir), false) = (&self.telemetry_dir, games.is_empty()) {
+            telemetry::write_report(Path::new(dir), "selfplay", &telemetry::selfplay_report(&games))?;
+        }
+
+        let games_py = games
+            .iter()
+            .map(|game| {
+                let game_py = PyDict::new(_py);
+                game_to_py_object(game, game_py);
+                game_py
+            })
+            .collect();
+        Ok(games_py)
+    }
+
+    fn is_running(&self) -> bool {
+        self.pool.is_running()
+    }
+
+    /// Stop the workers and wait for them; games not collected yet are lost
+    fn stop(&self, _py: Python) {
+        let pool = &self.pool;
+        _py.allow_threads(|| pool.stop());
+    }
+}
+
+/// Engine configurations (or NNUE checkpoints) for league-style training,
+/// with per-opponent Elo ratings
+#[pyclass]
+pub struct OpponentPool {
+    pool: Mutex<arena::OpponentPool>,
+    stops: StopFlags,
+}
+
+impl OpponentPool {
+    /// lock the pool without holding the GIL, `play` holds it while games run
+    fn with_pool<R: Send, F: FnOnce(&mut arena::OpponentPool) -> R + Send>(&self, _py: Python, f: F) -> R {
+        _py.allow_threads(|| f(&mut self.pool.lock().unwrap()))
+    }
+}
+
+#[pymethods]
+impl OpponentPool {
+    #[new]
+    #[args(k_factor = "DEFAULT_K_FACTOR", seed = "None")]
+    fn new(k_factor: f64, seed: Option<u64>) -> Self {
+        OpponentPool {
+            pool: Mutex::new(arena::OpponentPool::new(k_factor, seed)),
+            stops: StopFlags::default(),
+        }
+    }
+
+    /// Add an opponent built from a TOML engine config and/or an NNUE network
+    /// file (handcrafted evaluation otherwise). `weight` is its relative
+    /// probability of being sampled.
+    #[args(
+        config_path = "None",
+        nnue_path = "None",
+        depth = "None",
+        weight = "1.0",
+        elo = "DEFAULT_ELO"
+    )]
+    #[allow(clippy::too_many_arguments)]
+    fn add(
+        &self,
+        _py: Python,
+        name: &str,
+        config_path: Option<&str>,
+        nnue_path: Option<String>,
+        depth: Option<u32>,
+        weight: f64,
+        elo: f64,
+    ) -> PyResult<()> {
+        // parse arguments
+        let mut config = match config_path {
+            Some(path) => EngineConfig::from_file(Path::new(path)).map_err(PyValueError::new_err)?,
+            None => EngineConfig::default(),
+        };
+        if nnue_path.is_some() {
+            config.evaluator = "nnue".to_string();
+            config.nnue_path = nnue_path;
+        }
+        let evaluator = eval::from_config(&config).map_err(PyValueError::new_err)?;
+
+        let mut opponent = Opponent::new(
+            name,
+            evaluator,
+            depth.or(config.depth).unwrap_or(DEFAULT_SELFPLAY_DEPTH),
+            config.hash_mb,
+        );
+        opponent.movetime = config.movetime_ms.map(Duration::from_millis);
+        opponent.weight = weight;
+        opponent.rating = Rating::new(elo);
+        self.with_pool(_py, |pool| pool.add(opponent)).map_err(PyValueError::new_err)
+    }
+
+    /// Add an opponent played by an external UCI engine, searching
+    /// `movetime_ms` per move if given, else to `depth` (the engine's default
+    /// depth being that of the self-play)
+    #[args(depth = "None", movetime_ms = "None", weight = "1.0", elo = "DEFAULT_ELO")]
+    fn add_uci(
+        &self,
+        _py: Python,
+        name: &str,
+        engine: PyRef<UciEngine>,
+        depth: Option<u32>,
+        movetime_ms: Option<u64>,
+        weight: f64,
+        elo: f64,
+    ) -> PyResult<()> {
+        let mut opponent = Opponent::uci(
+            name,
+            engine.engine.clone(),
+            depth.unwrap_or(DEFAULT_SELFPLAY_DEPTH),
+            movetime_ms.map(Duration::from_millis),
+        );
+        opponent.weight = weight;
+        opponent.rating = Rating::new(elo);
+        self.with_pool(_py, |pool| pool.add(opponent)).map_err(PyValueError::new_err)
+    }
+
+    fn remove(&self, _py: Python, name: &str) -> PyResult<()> {
+        self.with_pool(_py, |pool| pool.remove(name))
+            .map_err(PyValueError::new_err)?;
+        Ok(())
+    }
+
+    fn set_weight(&self, _py: Python, name: &str, weight: f64) -> PyResult<()> {
+        self.with_pool(_py, |pool| pool.set_weight(name, weight)).map_err(PyValueError::new_err)
+    }
+
+    /// Name of an opponent drawn according to the weights (None if all are 0)
+    #[args(exclude = "None")]
+    fn sample(&self, _py: Python, exclude: Option<&str>) -> Option<String> {
+        self.with_pool(_py, |pool| {
+            pool.sample(exclude).map(|i| pool.opponents()[i].name.clone())
+        })
+    }
+
+    /// Elo and Glicko-2 ratings, record and weight of each opponent, by name
+    fn ratings<'a>(&self, _py: Python<'a>) -> PyResult<&'a PyDict> {
+        let ratings: Vec<(String, Rating, f64)> = self.with_pool(_py, |pool| {
+            pool.opponents()
+                .iter()
+                .map(|o| {
+                    let mut rating = o.rating.clone();
+                    rating.history.clear();
+                    (o.name.clone(), rating, o.weight)
+                })
+                .collect()
+        });
+        let ratings_py = PyDict::new(_py);
+        for (name, rating, weight) in ratings {
+            let rating_py = PyDict::new(_py);
+            rating_py.set_item("elo", rating.elo)?;
+            rating_py.set_item("glicko", rating.glicko.rating)?;
+            rating_py.set_item("glicko_rd", rating.glicko.rd)?;
+            rating_py.set_item("glicko_volatility", rating.glicko.volatility)?;
+            rating_py.set_item("games", rating.games)?;
+            rating_py.set_item("wins", rating.wins)?;
+            rating_py.set_item("draws", rating.draws)?;
+            rating_py.set_item("losses", rating.losses)?;
+            rating_py.set_item("weight", weight)?;
+            ratings_py.set_item(name, rating_py)?;
+        }
+        Ok(ratings_py)
+    }
+
+    /// Ratings of an opponent after each of its games, for plotting
+    fn rating_history<'a>(&self, _py: Python<'a>, name: &str) -> PyResult<Vec<&'a PyDict>> {
+        let history: Vec<RatingPoint> = self
+            .with_pool(_py, |pool| {
+                pool.opponents()
+                    .iter()
+                    .find(|o| o.name == name)
+                    .map(|o| o.rating.history.clone())
+            })
+            .ok_or_else(|| PyValueError::new_err(format!("no opponent named '{}'", name)))?;
+        let history_py = history
+            .iter()
+            .map(|point| {
+                let point_py = PyDict::new(_py);
+                point_py.set_item("games", point.games).unwrap();
+                point_py.set_item("elo", point.elo).unwrap();
+                point_py.set_item("glicko", point.glicko.rating).unwrap();
+                point_py.set_item("glicko_rd", point.glicko.rd).unwrap();
+                point_py
+            })
+            .collect();
+        Ok(history_py)
+    }
+
+    /// Play `games` games of `learner` (an entry of the pool) against sampled
+    /// opponents without holding the GIL, update the ratings and return the
+    /// games with the opponent's name and the learner's color. `adjudication`
+    /// sets resign/draw rules (keys of the `[adjudication]` config table);
+    /// `openings` (an `OpeningSuite`) gives the starting positions.
+    #[args(
+        games = "1",
+        random_plies = "8",
+        max_plies = "None",
+        threads = "1",
+        adjudication = "None",
+        openings = "None"
+    )]
+    #[allow(clippy::too_many_arguments)]
+    fn play<'a>(
+        &self,
+        _py: Python<'a>,
+        learner: &str,
+        games: usize,
+        random_plies: u32,
+        max_plies: Option<u32>,
+        threads: usize,
+        adjudication: Option<&PyDict>,
+        openings: Option<PyRef<OpeningSuite>>,
+    ) -> PyResult<Vec<&'a PyDict>> {
+        // parse arguments
+        let mut adjudication = convert_py_adjudication(Adjudication::default(), adjudication)?;
+        if let Some(max_plies) = max_plies {
+            adjudication.max_plies = max_plies;
+        }
+
+        let arena_config = ArenaConfig {
+            games,
+            openings: openings.map(|openings| openings.suite.clone()),
+            random_plies,
+            adjudication,
+            threads,
+        };
+
+        let stop = self.stops.new_flag();
+        let arena_games = self
+            .with_pool(_py, |pool| pool.play(learner, &arena_config, &stop))
+            .map_err(PyValueError::new_err)?;
+
+        let games_py = arena_games
+            .iter()
+            .map(|arena_game| {
+                let game_py = PyDict::new(_py);
+                game_to_py_object(&arena_game.game, game_py);
+                game_py.set_item("opponent", &arena_game.opponent).unwrap();
+                game_py
+                    .set_item("learner_color", player_enum_to_string(&arena_game.learner_color))
+                    .unwrap();
+                game_py
+            })
+            .collect();
+        Ok(games_py)
+    }
+
+    /// Stop a running `play`; it returns the games finished so far
+    fn stop(&self) {
+        self.stops.stop_all();
+    }
+}
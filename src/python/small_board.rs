@@ -0,0 +1,91 @@
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+
+use crate::small_board::SmallState;
+use crate::{player_enum_to_string, Color};
+
+use super::convert_py_player;
+
+/// Position of a reduced variant ("los_alamos" 6x6 or "gardner" 5x5) for
+/// faster prototyping, with its own move generator. Moves are written in
+/// coordinate notation with the promotion piece appended ("b5b6q").
+#[pyclass]
+pub struct SmallBoard {
+    state: SmallState,
+}
+
+#[pymethods]
+impl SmallBoard {
+    #[new]
+    #[args(variant = "\"los_alamos\"")]
+    fn new(variant: &str) -> PyResult<Self> {
+        let state = SmallState::new_variant(variant).map_err(PyValueError::new_err)?;
+        Ok(SmallBoard { state })
+    }
+
+    #[getter]
+    fn variant(&self) -> String {
+        self.state.variant.name.clone()
+    }
+
+    #[getter]
+    fn width(&self) -> usize {
+        self.state.variant.width
+    }
+
+    #[getter]
+    fn height(&self) -> usize {
+        self.state.variant.height
+    }
+
+    #[getter]
+    fn board(&self) -> Vec<Vec<isize>> {
+        self.state.board.clone()
+    }
+
+    #[getter]
+    fn current_player(&self) -> &str {
+        player_enum_to_string(&self.state.current_player)
+    }
+
+    /// Replace the position (same variant and size)
+    fn set_board(&mut self, board: Vec<Vec<isize>>, player: &str) -> PyResult<()> {
+        // parse arguments
+        let player: Color = convert_py_player(player)?;
+
+        self.state = self.state.with_board(board, player).map_err(PyValueError::new_err)?;
+        Ok(())
+    }
+
+    fn legal_moves(&self) -> Vec<String> {
+        let moves = self.state.legal_moves();
+        moves.iter().map(|&_move| self.state.move_to_string(_move)).collect()
+    }
+
+    /// Play a legal move for the side to move
+    fn push(&mut self, _move: &str) -> PyResult<()> {
+        let _move = self.state.parse_move(_move).map_err(PyValueError::new_err)?;
+        self.state = self.state.make_move(_move);
+        Ok(())
+    }
+
+    fn is_check(&self) -> bool {
+        self.state.is_checked(self.state.current_player)
+    }
+
+    /// "checkmate", "stalemate", "insufficient_material" or None if the game goes on
+    fn outcome(&self) -> Option<&'static str> {
+        self.state.outcome()
+    }
+
+    fn perft(&self, _py: Python, depth: u32) -> u64 {
+        let state = &self.state;
+        _py.allow_threads(|| state.perft(depth))
+    }
+
+    fn copy(&self) -> SmallBoard {
+        SmallBoard {
+            state: self.state.clone(),
+        }
+    }
+}
@@ -0,0 +1,106 @@
+use pyo3::exceptions::{PyRuntimeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::selfplay::DEFAULT_SELFPLAY_DEPTH;
+use crate::uci_client::{self, UciLimit, DEFAULT_UCI_TIMEOUT};
+
+use super::{convert_py_state, search_info_to_py_object};
+
+/// External UCI engine (e.g. Stockfish) run as a child process, to label
+/// positions or to serve as an `OpponentPool` opponent. An engine that
+/// crashes or hangs is relaunched and the search retried once.
+#[pyclass]
+pub struct UciEngine {
+    pub(super) engine: Arc<uci_client::UciEngine>,
+}
+
+#[pymethods]
+impl UciEngine {
+    /// Launch `path` with `args` and set the UCI `options` ({name: value});
+    /// `timeout_ms` bounds the handshake and the searches without a movetime
+    #[new]
+    #[args(args = "None", options = "None", timeout_ms = "None")]
+    fn new(
+        _py: Python,
+        path: &str,
+        args: Option<Vec<String>>,
+        options: Option<&PyDict>,
+        timeout_ms: Option<u64>,
+    ) -> PyResult<Self> {
+        // parse arguments
+        let mut options_vec: Vec<(String, String)> = vec![];
+        if let Some(options) = options {
+            for (name, value) in options.iter() {
+                options_vec.push((name.str()?.to_string(), value.str()?.to_string()));
+            }
+        }
+        let timeout = timeout_ms.map_or(DEFAULT_UCI_TIMEOUT, Duration::from_millis);
+        let args = args.unwrap_or_default();
+
+        let engine = _py
+            .allow_threads(|| uci_client::UciEngine::start(path, &args, &options_vec, timeout))
+            .map_err(PyRuntimeError::new_err)?;
+        Ok(UciEngine {
+            engine: Arc::new(engine),
+        })
+    }
+
+    /// Search the position with one limit (`depth` by default) and return
+    /// depth, seldepth, score (side to move's point of view), mate (moves,
+    /// None without an announced mate), nodes, time_ms, pv and best_move
+    #[args(depth = "None", movetime_ms = "None", nodes = "None")]
+    fn analyse<'a>(
+        &self,
+        _py: Python<'a>,
+        state_py: &'a PyDict,
+        depth: Option<u32>,
+        movetime_ms: Option<u64>,
+        nodes: Option<u64>,
+    ) -> PyResult<&'a PyDict> {
+        // parse state
+        let state = convert_py_state(_py, state_py)?;
+
+        // parse arguments
+        let limit = match (depth, movetime_ms, nodes) {
+            (_, None, None) => UciLimit::Depth(depth.unwrap_or(DEFAULT_SELFPLAY_DEPTH)),
+            (None, Some(movetime_ms), None) => UciLimit::MoveTime(Duration::from_millis(movetime_ms)),
+            (None, None, Some(nodes)) => UciLimit::Nodes(nodes),
+            _ => {
+                return Err(PyValueError::new_err(
+                    "only one of depth, movetime_ms and nodes can be given",
+                ))
+            }
+        };
+
+        let engine = &self.engine;
+        let analysis = _py
+            .allow_threads(|| engine.analyse(&state, limit))
+            .map_err(PyRuntimeError::new_err)?;
+        let analysis_py = PyDict::new(_py);
+        search_info_to_py_object(&analysis.to_search_info(), analysis_py);
+        analysis_py.del_item("hashfull")?;
+        analysis_py.set_item("mate", analysis.mate)?;
+        Ok(analysis_py)
+    }
+
+    /// "id name" sent by the engine
+    #[getter]
+    fn name(&self) -> Option<String> {
+        self.engine.name()
+    }
+
+    /// Times the engine was relaunched after crashing or hanging
+    #[getter]
+    fn restarts(&self) -> usize {
+        self.engine.restarts()
+    }
+
+    /// Stop the engine process (the next `analyse` relaunches it)
+    fn quit(&self, _py: Python) {
+        let engine = &self.engine;
+        _py.allow_threads(|| engine.quit());
+    }
+}
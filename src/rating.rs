@@ -0,0 +1,185 @@
+use std::f64::consts::PI;
+
+//
+// Constants
+//
+pub const DEFAULT_ELO: f64 = 1500.0;
+pub const DEFAULT_K_FACTOR: f64 = 32.0;
+const GLICKO_DEFAULT_RD: f64 = 350.0;
+const GLICKO_DEFAULT_VOLATILITY: f64 = 0.06;
+/// constrains the volatility change, 0.3 to 1.2 are reasonable
+const GLICKO_TAU: f64 = 0.5;
+/// Glicko-2 scale factor between the Glicko and Glicko-2 scales
+const GLICKO_SCALE: f64 = 173.7178;
+const GLICKO_EPSILON: f64 = 0.000001;
+
+//
+// Structs
+//
+/// Glicko-2 rating on the Glicko scale (same scale as Elo)
+#[derive(Debug, Copy, Clone)]
+pub struct Glicko2 {
+    pub rating: f64,
+    /// rating deviation: uncertainty of the rating
+    pub rd: f64,
+    pub volatility: f64,
+}
+
+impl Glicko2 {
+    pub fn new(rating: f64) -> Self {
+        Glicko2 {
+            rating,
+            rd: GLICKO_DEFAULT_RD,
+            volatility: GLICKO_DEFAULT_VOLATILITY,
+        }
+    }
+
+    /// Rating after a rating period with `results` (opponent before the games,
+    /// score 1, 0.5 or 0), following Glickman's "Example of the Glicko-2 system"
+    pub fn update(&self, results: &[(Glicko2, f64)]) -> Glicko2 {
+        let mu = (self.rating - DEFAULT_ELO) / GLICKO_SCALE;
+        let phi = self.rd / GLICKO_SCALE;
+        if results.is_empty() {
+            let phi = (phi * phi + self.volatility * self.volatility).sqrt();
+            return Glicko2 {
+                rd: phi * GLICKO_SCALE,
+                ..*self
+            };
+        }
+
+        // estimated variance and improvement from the game outcomes
+        let mut variance_inv = 0.0;
+        let mut improvement = 0.0;
+        for (opponent, score) in results.iter() {
+            let mu_j = (opponent.rating - DEFAULT_ELO) / GLICKO_SCALE;
+            let g = glicko_g(opponent.rd / GLICKO_SCALE);
+            let expected = 1.0 / (1.0 + (-g * (mu - mu_j)).exp());
+            variance_inv += g * g * expected * (1.0 - expected);
+            improvement += g * (score - expected);
+        }
+        let variance = 1.0 / variance_inv;
+        let delta = variance * improvement;
+
+        // new volatility (Illinois algorithm)
+        let a = (self.volatility * self.volatility).ln();
+        let f = |x: f64| {
+            let ex = x.exp();
+            let d = phi * phi + variance + ex;
+            ex * (delta * delta - phi * phi - variance - ex) / (2.0 * d * d)
+                - (x - a) / (GLICKO_TAU * GLICKO_TAU)
+        };
+        let mut lower = a;
+        let mut upper = if delta * delta > phi * phi + variance {
+            (delta * delta - phi * phi - variance).ln()
+        } else {
+            let mut k = 1.0;
+            while f(a - k * GLICKO_TAU) < 0.0 {
+                k += 1.0;
+            }
+            a - k * GLICKO_TAU
+        };
+        let mut f_lower = f(lower);
+        let mut f_upper = f(upper);
+        while (upper - lower).abs() > GLICKO_EPSILON {
+            let c = lower + (lower - upper) * f_lower / (f_upper - f_lower);
+            let f_c = f(c);
+            if f_c * f_upper <= 0.0 {
+                lower = upper;
+                f_lower = f_upper;
+            } else {
+                f_lower /= 2.0;
+            }
+            upper = c;
+            f_upper = f_c;
+        }
+        let volatility = (lower / 2.0).exp();
+
+        let phi_star = (phi * phi + volatility * volatility).sqrt();
+        let phi = 1.0 / (1.0 / (phi_star * phi_star) + variance_inv).sqrt();
+        let mu = mu + phi * phi * improvement;
+        Glicko2 {
+            rating: mu * GLICKO_SCALE + DEFAULT_ELO,
+            rd: phi * GLICKO_SCALE,
+            volatility,
+        }
+    }
+}
+
+/// Ratings after a game, for plotting training progress
+#[derive(Debug, Copy, Clone)]
+pub struct RatingPoint {
+    pub games: u32,
+    pub elo: f64,
+    pub glicko: Glicko2,
+}
+
+/// Elo and Glicko-2 ratings of a player with its record and rating history
+#[derive(Debug, Clone)]
+pub struct Rating {
+    pub elo: f64,
+    pub glicko: Glicko2,
+    pub games: u32,
+    pub wins: u32,
+    pub draws: u32,
+    pub losses: u32,
+    pub history: Vec<RatingPoint>,
+}
+
+impl Rating {
+    pub fn new(elo: f64) -> Self {
+        Rating {
+            elo,
+            glicko: Glicko2::new(elo),
+            games: 0,
+            wins: 0,
+            draws: 0,
+            losses: 0,
+            history: vec![],
+        }
+    }
+
+    /// Update after a game against an opponent rated `opponent_elo` and
+    /// `opponent_glicko` before the game, each game being its own Glicko-2
+    /// rating period
+    pub fn record(
+        &mut self,
+        opponent_elo: f64,
+        opponent_glicko: Glicko2,
+        score: f64,
+        k_factor: f64,
+    ) {
+        self.elo = elo_update(self.elo, opponent_elo, score, k_factor);
+        self.glicko = self.glicko.update(&[(opponent_glicko, score)]);
+        self.games += 1;
+        if score == 1.0 {
+            self.wins += 1;
+        } else if score == 0.0 {
+            self.losses += 1;
+        } else {
+            self.draws += 1;
+        }
+        self.history.push(RatingPoint {
+            games: self.games,
+            elo: self.elo,
+            glicko: self.glicko,
+        });
+    }
+}
+
+/// Expected score of a player rated `rating` against one rated `opponent`
+pub fn expected_score(rating: f64, opponent: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf((opponent - rating) / 400.0))
+}
+
+/// Elo rating after a game with `score` (1, 0.5 or 0)
+pub fn elo_update(rating: f64, opponent: f64, score: f64, k_factor: f64) -> f64 {
+    rating + k_factor * (score - expected_score(rating, opponent))
+}
+
+// HELPER FUNCTIONS
+// ---------------------------------------------------------
+// ---------------------------------------------------------
+
+fn glicko_g(phi: f64) -> f64 {
+    1.0 / (1.0 + 3.0 * phi * phi / (PI * PI)).sqrt()
+}
@@ -0,0 +1,97 @@
+use crate::{player_enum_to_string, Board, Color, ID_TO_ICON};
+
+//
+// Structs
+//
+/// What the text and SVG renderers draw around the board. The default draws
+/// the ranks on the left (right in SVG) and the files at the bottom, white
+/// at the bottom, without metadata.
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct RenderOptions {
+    /// rank and file labels on all four sides
+    pub labels_all_sides: bool,
+    /// black at the bottom
+    pub flipped: bool,
+    /// move number written under the board
+    pub move_number: Option<u32>,
+    /// side to move, written under the board (and marked by a dot in SVG)
+    pub side_to_move: Option<Color>,
+}
+
+impl RenderOptions {
+    /// Board rows (0 is rank 8) from the top of the image to its bottom
+    pub fn rows(&self) -> Vec<usize> {
+        self.oriented((0..8).collect())
+    }
+
+    /// Board columns (0 is file a) from the left of the image to its right
+    pub fn cols(&self) -> Vec<usize> {
+        self.oriented((0..8).collect())
+    }
+
+    /// "Move 12, white to move" from the metadata to draw (None without)
+    pub fn caption(&self) -> Option<String> {
+        let mut parts: Vec<String> = vec![];
+        if let Some(move_number) = self.move_number {
+            parts.push(format!("Move {}", move_number));
+        }
+        if let Some(player) = self.side_to_move {
+            parts.push(format!(
+                "{} to move",
+                player_enum_to_string(&player).to_lowercase()
+            ));
+        }
+        if parts.is_empty() {
+            return None;
+        }
+        Some(parts.join(", "))
+    }
+
+    fn oriented(&self, mut indices: Vec<usize>) -> Vec<usize> {
+        if self.flipped {
+            indices.reverse();
+        }
+        indices
+    }
+}
+
+/// Text diagram of `board` with the labels and metadata of `options`, as
+/// printed by `render_board`
+pub fn board_to_text(board: &Board, options: &RenderOptions) -> String {
+    let border = "   ------------------------";
+    let files: String = options
+        .cols()
+        .iter()
+        .map(|&col| format!("  {}", file_label(col)))
+        .collect();
+    let mut text = String::from("\n");
+    if options.labels_all_sides {
+        text.push_str(&format!("  {}\n", files));
+    }
+    text.push_str(border);
+    for row in options.rows() {
+        text.push_str(&format!("\n{} |", rank_label(row)));
+        for col in options.cols() {
+            text.push_str(&format!(" {} ", ID_TO_ICON[&board[row][col]]));
+        }
+        text.push('|');
+        if options.labels_all_sides {
+            text.push_str(&format!(" {}", rank_label(row)));
+        }
+    }
+    text.push_str(&format!("\n{}\n  {}\n", border, files));
+    if let Some(caption) = options.caption() {
+        text.push_str(&format!("{}\n", caption));
+    }
+    text
+}
+
+/// "a" to "h" for board columns 0 to 7
+pub fn file_label(col: usize) -> char {
+    (b'a' + col as u8) as char
+}
+
+/// 8 to 1 for board rows 0 to 7
+pub fn rank_label(row: usize) -> usize {
+    8 - row
+}
@@ -0,0 +1,277 @@
+use std::fmt::Write;
+
+use crate::render::RenderOptions;
+use crate::svg::board_to_svg;
+use crate::{from_fen, next_state, parse_move, Color};
+
+//
+// Constants
+//
+/// scores are clamped to this many centipawns (mates included) for the
+/// graph and the move classification
+const SCORE_CLAMP: isize = 1000;
+/// centipawns lost by a move for each annotation
+const INACCURACY_LOSS: isize = 50;
+const MISTAKE_LOSS: isize = 100;
+const BLUNDER_LOSS: isize = 200;
+const GRAPH_WIDTH: usize = 640;
+const GRAPH_HEIGHT: usize = 160;
+
+//
+// Structs
+//
+/// Evaluation of a game position before a move
+#[derive(Debug, Clone)]
+pub struct MoveAnalysis {
+    /// score of the position from white's point of view, in centipawns
+    pub score: isize,
+    /// move the analysis preferred, shown next to bad moves
+    pub best_move: Option<String>,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Annotation {
+    Inaccuracy,
+    Mistake,
+    Blunder,
+}
+
+impl Annotation {
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            Annotation::Inaccuracy => "?!",
+            Annotation::Mistake => "?",
+            Annotation::Blunder => "??",
+        }
+    }
+
+    pub fn to_str(self) -> &'static str {
+        match self {
+            Annotation::Inaccuracy => "inaccuracy",
+            Annotation::Mistake => "mistake",
+            Annotation::Blunder => "blunder",
+        }
+    }
+}
+
+/// Score clamped to the centipawns a mate counts for when grading moves
+pub fn clamp_score(score: isize) -> isize {
+    score.clamp(-SCORE_CLAMP, SCORE_CLAMP)
+}
+
+/// Annotation of a move losing `loss` centipawns (None for a good move)
+pub fn annotate_loss(loss: isize) -> Option<Annotation> {
+    if loss >= BLUNDER_LOSS {
+        return Some(Annotation::Blunder);
+    } else if loss >= MISTAKE_LOSS {
+        return Some(Annotation::Mistake);
+    } else if loss >= INACCURACY_LOSS {
+        return Some(Annotation::Inaccuracy);
+    }
+    None
+}
+
+/// Centipawns lost by each move (the score drop for its player between the
+/// position before it and the next one) and its annotation, `first_ply`
+/// being 0 if white plays the first move and 1 if black does; the last move,
+/// having no next score, loses nothing
+pub fn classify_moves(
+    analysis: &[MoveAnalysis],
+    first_ply: usize,
+) -> Vec<(isize, Option<Annotation>)> {
+    let clamped: Vec<isize> = analysis
+        .iter()
+        .map(|a| clamp_score(a.score))
+        .collect();
+    (0..clamped.len())
+        .map(|ply| {
+            let loss = match clamped.get(ply + 1) {
+                // white moves at the even plies counted from white's first move
+                Some(next) if (first_ply + ply) % 2 == 0 => clamped[ply] - next,
+                Some(next) => next - clamped[ply],
+                None => 0,
+            };
+            (loss.max(0), annotate_loss(loss))
+        })
+        .collect()
+}
+
+/// Standalone HTML page reviewing a game: an evaluation graph, then for each
+/// move the board after it, its score and its annotation. `fens` are the
+/// positions before each move (like `Game::fens`) and `analysis` has one
+/// entry per move.
+pub fn export_analysis_html(
+    title: &str,
+    fens: &[String],
+    moves: &[String],
+    analysis: &[MoveAnalysis],
+    result: &str,
+) -> Result<String, String> {
+    if fens.len() != moves.len() || analysis.len() != moves.len() {
+        return Err(format!(
+            "expected one FEN and one analysis per move, got {} moves, {} FENs and {} analyses",
+            moves.len(),
+            fens.len(),
+            analysis.len()
+        ));
+    }
+    let first_ply = match fens.first().map(|fen| from_fen(fen)) {
+        Some(Ok(state)) if state.current_player == Color::Black => 1,
+        _ => 0,
+    };
+    let classification = classify_moves(analysis, first_ply);
+
+    let mut html = String::new();
+    write!(
+        html,
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{0}</title>\n\
+         <style>\n\
+         body {{ font-family: sans-serif; max-width: 960px; margin: auto; }}\n\
+         .moves {{ display: flex; flex-wrap: wrap; gap: 12px; }}\n\
+         .move {{ width: 300px; }}\n\
+         .move svg {{ width: 300px; height: 300px; }}\n\
+         .inaccuracy {{ color: #b7791f; }}\n\
+         .mistake {{ color: #dd6b20; }}\n\
+         .blunder {{ color: #c53030; font-weight: bold; }}\n\
+         </style>\n</head>\n<body>\n<h1>{0}</h1>\n<p>Result: {1}</p>\n",
+        escape_html(title),
+        escape_html(result)
+    )
+    .unwrap();
+    html.push_str(&eval_graph_svg(analysis, &classification, first_ply));
+
+    let annotated: Vec<String> = moves
+        .iter()
+        .zip(classification.iter())
+        .enumerate()
+        .filter_map(|(ply, (_move, (_, annotation)))| {
+            annotation.map(|annotation| {
+                format!(
+                    "<li class=\"{}\">{}{} {}{}</li>",
+                    annotation.to_str(),
+                    move_number(first_ply + ply),
+                    escape_html(_move),
+                    annotation.symbol(),
+                    best_move_hint(&analysis[ply])
+                )
+            })
+        })
+        .collect();
+    if !annotated.is_empty() {
+        write!(
+            html,
+            "<h2>Annotations</h2>\n<ul>\n{}\n</ul>\n",
+            annotated.join("\n")
+        )
+        .unwrap();
+    }
+
+    html.push_str("<h2>Moves</h2>\n<div class=\"moves\">\n");
+    for (ply, ((fen, _move), (loss, annotation))) in fens
+        .iter()
+        .zip(moves.iter())
+        .zip(classification.iter())
+        .enumerate()
+    {
+        let state = from_fen(fen).map_err(|e| format!("move {}: {}", ply + 1, e))?;
+        let move_struct = parse_move(_move).map_err(|e| format!("move {}: {}", ply + 1, e))?;
+        let (after, _) = next_state(&state, state.current_player, move_struct);
+        let (class, symbol) = match annotation {
+            Some(annotation) => (annotation.to_str(), annotation.symbol()),
+            None => ("", ""),
+        };
+        write!(
+            html,
+            "<div class=\"move\">\n<div class=\"{}\">{}{}{}</div>\n{}\n\
+             <div>eval {:+.2}{}</div>\n</div>\n",
+            class,
+            move_number(first_ply + ply),
+            escape_html(_move),
+            symbol,
+            board_to_svg(&after.board, None, &RenderOptions::default()),
+            analysis[ply].score as f64 / 100.0,
+            if *loss > 0 {
+                format!(", loses {:.2}", *loss as f64 / 100.0)
+            } else {
+                String::new()
+            }
+        )
+        .unwrap();
+    }
+    html.push_str("</div>\n</body>\n</html>\n");
+    Ok(html)
+}
+
+// HELPER FUNCTIONS
+// ---------------------------------------------------------
+// ---------------------------------------------------------
+
+/// "1. " before white's moves, "1... " before black's
+fn move_number(ply: usize) -> String {
+    if ply % 2 == 0 {
+        format!("{}. ", ply / 2 + 1)
+    } else {
+        format!("{}... ", ply / 2 + 1)
+    }
+}
+
+fn best_move_hint(analysis: &MoveAnalysis) -> String {
+    match &analysis.best_move {
+        Some(best_move) => format!(" (best was {})", escape_html(best_move)),
+        None => String::new(),
+    }
+}
+
+/// Line of the clamped scores, white advantage up, with a dot on each
+/// annotated move
+fn eval_graph_svg(
+    analysis: &[MoveAnalysis],
+    classification: &[(isize, Option<Annotation>)],
+    first_ply: usize,
+) -> String {
+    let x = |ply: usize| ply * GRAPH_WIDTH / analysis.len().max(2).saturating_sub(1);
+    let y = |score: isize| {
+        (SCORE_CLAMP - clamp_score(score)) as usize * GRAPH_HEIGHT / (2 * SCORE_CLAMP) as usize
+    };
+    let points: Vec<String> = analysis
+        .iter()
+        .enumerate()
+        .map(|(ply, a)| format!("{},{}", x(ply), y(a.score)))
+        .collect();
+    let mut svg = String::new();
+    write!(
+        svg,
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{0}\" height=\"{1}\" viewBox=\"0 0 {0} {1}\">\n\
+         <rect width=\"{0}\" height=\"{1}\" fill=\"#f7fafc\" stroke=\"dimgray\"/>\n\
+         <line x1=\"0\" y1=\"{2}\" x2=\"{0}\" y2=\"{2}\" stroke=\"dimgray\" stroke-dasharray=\"4\"/>\n\
+         <polyline points=\"{3}\" fill=\"none\" stroke=\"#2b6cb0\" stroke-width=\"2\"/>\n",
+        GRAPH_WIDTH,
+        GRAPH_HEIGHT,
+        GRAPH_HEIGHT / 2,
+        points.join(" ")
+    )
+    .unwrap();
+    for (ply, (a, (_, annotation))) in analysis.iter().zip(classification.iter()).enumerate() {
+        if let Some(annotation) = annotation {
+            writeln!(
+                svg,
+                "<circle class=\"{}\" cx=\"{}\" cy=\"{}\" r=\"4\" fill=\"currentColor\"><title>{}{}</title></circle>",
+                annotation.to_str(),
+                x(ply),
+                y(a.score),
+                move_number(first_ply + ply),
+                annotation.to_str()
+            )
+            .unwrap();
+        }
+    }
+    svg.push_str("</svg>\n");
+    svg
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
@@ -0,0 +1,265 @@
+use std::fmt;
+use std::sync::Arc;
+
+use crate::selfplay::{all_moves, only_kings_left, GameResult};
+use crate::small_board::SmallBoard;
+use crate::{get_other_player, king_is_checked, next_state, Color, MoveStruct, State};
+
+//
+// Types
+//
+/// name of a variant and the constructor of its rules
+type Variant = (&'static str, fn() -> Arc<dyn Rules>);
+
+//
+// Constants
+//
+/// plies without captures or pawn moves after which the game is drawn
+pub const FIFTY_MOVES_PLIES: u32 = 100;
+/// Variants by name, as set by the `engine.variant` config key
+const VARIANTS: [Variant; 3] = [
+    ("standard", || Arc::new(Standard)),
+    ("los_alamos", || Arc::new(SmallBoard::los_alamos())),
+    ("gardner", || Arc::new(SmallBoard::gardner())),
+];
+
+//
+// Structs
+//
+/// Why a game is over
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Terminal {
+    /// the side to move is mated
+    Checkmate,
+    Stalemate,
+    /// FIFTY_MOVES_PLIES plies without a capture or a pawn move
+    FiftyMoves,
+    InsufficientMaterial,
+}
+
+impl Terminal {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Terminal::Checkmate => "checkmate",
+            Terminal::Stalemate => "stalemate",
+            Terminal::FiftyMoves => "fifty_moves",
+            Terminal::InsufficientMaterial => "insufficient_material",
+        }
+    }
+
+    /// Winner of a game ended this way with `loser_to_move` to play (None
+    /// for draws)
+    pub fn winner(&self, loser_to_move: Color) -> Option<Color> {
+        match self {
+            Terminal::Checkmate => Some(get_other_player(loser_to_move)),
+            _ => None,
+        }
+    }
+
+    /// Result of a game ended this way with `loser_to_move` to play
+    pub fn result(&self, loser_to_move: Color) -> GameResult {
+        match self.winner(loser_to_move) {
+            Some(Color::White) => GameResult::WhiteWin,
+            Some(Color::Black) => GameResult::BlackWin,
+            None => GameResult::Draw,
+        }
+    }
+}
+
+/// Move generation and game end of a chess variant. Every method defaults to
+/// the standard rules, so a variant only overrides what it changes.
+pub trait Rules: fmt::Debug + Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// Legal moves of `player`, normal moves before castles
+    fn legal_moves(&self, state: &State, player: Color) -> Vec<MoveStruct> {
+        all_moves(state, player)
+    }
+
+    /// Position after `player` plays `_move` (a legal move)
+    fn make_move(&self, state: &State, player: Color, _move: MoveStruct) -> State {
+        next_state(state, player, _move).0
+    }
+
+    /// How the game ended with `player` to move (None while it goes on),
+    /// `legal_moves` being those of `player`
+    fn terminal(
+        &self,
+        state: &State,
+        player: Color,
+        legal_moves: &[MoveStruct],
+    ) -> Option<Terminal> {
+        if legal_moves.is_empty() {
+            if king_is_checked(state, player) {
+                return Some(Terminal::Checkmate);
+            }
+            return Some(Terminal::Stalemate);
+        }
+        if state.halfmove_clock >= FIFTY_MOVES_PLIES {
+            return Some(Terminal::FiftyMoves);
+        }
+        if only_kings_left(state) {
+            return Some(Terminal::InsufficientMaterial);
+        }
+        None
+    }
+}
+
+/// Standard chess
+#[derive(Debug, Copy, Clone)]
+pub struct Standard;
+
+impl Rules for Standard {
+    fn name(&self) -> &'static str {
+        "standard"
+    }
+}
+
+/// How the game ended in `state` with its side to move to play (None while
+/// it goes on)
+pub fn game_status(rules: &dyn Rules, state: &State) -> Option<Terminal> {
+    let legal_moves = rules.legal_moves(state, state.current_player);
+    rules.terminal(state, state.current_player, &legal_moves)
+}
+
+/// Whether the side to move of `state` has no legal move without being in
+/// check (a draw)
+pub fn is_stalemate(rules: &dyn Rules, state: &State) -> bool {
+    game_status(rules, state) == Some(Terminal::Stalemate)
+}
+
+/// Rules of the variant named `name`
+pub fn rules_for(name: &str) -> Result<Arc<dyn Rules>, String> {
+    VARIANTS
+        .iter()
+        .find(|(variant, _)| *variant == name)
+        .map(|(_, rules)| rules())
+        .ok_or_else(|| {
+            format!(
+                "unsupported variant '{}': expected one of {:?}",
+                name,
+                VARIANTS
+                    .iter()
+                    .map(|(variant, _)| *variant)
+                    .collect::<Vec<_>>()
+            )
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::small_board::SmallState;
+    use crate::{convert_move_union_to_string, from_fen};
+
+    fn perft(rules: &dyn Rules, state: &State, depth: u32) -> u64 {
+        if depth == 0 {
+            return 1;
+        }
+        rules
+            .legal_moves(state, state.current_player)
+            .into_iter()
+            .map(|_move| perft(rules, &rules.make_move(state, state.current_player, _move), depth - 1))
+            .sum()
+    }
+
+    #[test]
+    fn variants_by_name() {
+        for &(name, _) in VARIANTS.iter() {
+            assert_eq!(rules_for(name).unwrap().name(), name);
+        }
+        assert!(rules_for("crazyhouse").unwrap_err().contains("los_alamos"));
+    }
+
+    #[test]
+    fn standard_perft() {
+        let rules = rules_for("standard").unwrap();
+        let state = from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1").unwrap();
+        assert_eq!(perft(&*rules, &state, 1), 20);
+        assert_eq!(perft(&*rules, &state, 2), 400);
+    }
+
+    // a pawn reaching the last rank is listed once per promotion piece,
+    // capturing or not and for either side, and becomes that piece
+    #[test]
+    fn standard_underpromotions() {
+        let rules = rules_for("standard").unwrap();
+        for &(fen, from, to, row) in [
+            ("3r4/4P3/k7/8/8/8/8/4K3 w - - 0 1", "e7", ["e8", "d8"], 0),
+            ("4k3/8/8/8/8/8/4p3/3R3K b - - 0 1", "e2", ["e1", "d1"], 7),
+        ]
+        .iter()
+        {
+            let state = from_fen(fen).unwrap();
+            let player = state.current_player;
+            let moves: Vec<String> = rules
+                .legal_moves(&state, player)
+                .iter()
+                .map(convert_move_union_to_string)
+                .filter(|_move| _move.starts_with(from))
+                .collect();
+            assert_eq!(moves.len(), 8, "{:?}", moves);
+            for (i, &square) in to.iter().enumerate() {
+                for &(suffix, piece_id) in [("", 2), ("r", 3), ("b", 4), ("n", 5)].iter() {
+                    let _move = format!("{}{}{}", from, square, suffix);
+                    assert!(moves.contains(&_move), "{} not in {:?}", _move, moves);
+                    let played = rules
+                        .legal_moves(&state, player)
+                        .into_iter()
+                        .find(|m| convert_move_union_to_string(m) == _move)
+                        .unwrap();
+                    let after = rules.make_move(&state, player, played);
+                    let col = if i == 0 { 4 } else { 3 };
+                    assert_eq!(after.board[row][col], piece_id * player.to_int());
+                }
+            }
+        }
+    }
+
+    // the 8x8 generator restricted to the corner finds the moves of the
+    // small-board generator
+    #[test]
+    fn small_board_perft_matches_small_state() {
+        for name in ["los_alamos", "gardner"].iter() {
+            let rules = rules_for(name).unwrap();
+            let small = SmallState::new_variant(name).unwrap();
+            for depth in 1..=3 {
+                assert_eq!(perft(&*rules, &small.to_state(), depth), small.perft(depth), "{} {}", name, depth);
+            }
+        }
+    }
+
+    #[test]
+    fn small_board_promotions() {
+        let rules = rules_for("los_alamos").unwrap();
+        let small = SmallState::new_variant("los_alamos").unwrap();
+        let board = vec![
+            vec![0, 0, 0, 0, 0, -1],
+            vec![6, 0, 0, 0, 0, 0],
+            vec![0, 0, 0, 0, 0, 0],
+            vec![0, 0, 0, 0, 0, 0],
+            vec![0, 0, 0, 0, -6, 0],
+            vec![0, 1, 0, 0, 0, 0],
+        ];
+        let state = small.with_board(board.clone(), Color::White).unwrap().to_state();
+        let promotions: Vec<Option<isize>> = rules
+            .legal_moves(&state, Color::White)
+            .iter()
+            .filter(|_move| unsafe { _move.data.normal_move }.0 == (1, 0))
+            .map(|_move| _move.promotion)
+            .collect();
+        assert_eq!(promotions, vec![Some(2), Some(3), Some(5)]);
+
+        let state = small.with_board(board, Color::Black).unwrap().to_state();
+        let moves = rules.legal_moves(&state, Color::Black);
+        assert!(!moves.iter().any(|_move| unsafe { _move.data.normal_move }.1 .0 > 5));
+        // the corner's first rank is the 8x8 board's third
+        let to_knight = moves
+            .into_iter()
+            .find(|_move| convert_move_union_to_string(_move) == "e4e3n")
+            .unwrap();
+        let after = rules.make_move(&state, Color::Black, to_knight);
+        assert_eq!(after.board[5][4], -5);
+        assert_eq!(after.board[4][4], 0);
+    }
+}
@@ -0,0 +1,233 @@
+use crate::{
+    convert_move_union_to_string, convert_square_to_string, get_all_possible_moves,
+    get_other_player, is_promotion, king_is_checked, next_state_promoting, Castle, Color,
+    MoveStruct, MoveUnion, Square, State, BISHOP_ID, KING_ID, KNIGHT_ID, PAWN_ID, QUEEN_ID,
+    ROOK_ID,
+};
+
+//
+// Constants
+//
+/// SAN letter of each piece id (pawns have none)
+const PIECE_LETTERS: [char; 7] = [' ', 'K', 'Q', 'R', 'B', 'N', ' '];
+
+/// Resolve a move in standard algebraic notation ("Nbd7", "exd5", "O-O-O",
+/// "e8=Q+") against the legal moves of the side to move, the piece after "="
+/// being the promotion piece of the move.
+pub fn parse_san(state: &State, san: &str) -> Result<MoveStruct, String> {
+    let player = state.current_player;
+    let text = san.trim_end_matches(|c| "+#!?".contains(c));
+    let (moves, castle_moves) = get_all_possible_moves(state, player, false);
+
+    // castling
+    let castle = match (text, player) {
+        ("O-O", Color::White) | ("0-0", Color::White) => Some(Castle::KingSideWhite),
+        ("O-O-O", Color::White) | ("0-0-0", Color::White) => Some(Castle::QueenSideWhite),
+        ("O-O", Color::Black) | ("0-0", Color::Black) => Some(Castle::KingSideBlack),
+        ("O-O-O", Color::Black) | ("0-0-0", Color::Black) => Some(Castle::QueenSideBlack),
+        _ => None,
+    };
+    if let Some(castle) = castle {
+        if !castle_moves.contains(&castle) {
+            return Err(format!("Illegal move '{}'", san));
+        }
+        return Ok(MoveStruct {
+            is_castle: true,
+            data: MoveUnion { castle },
+            promotion: None,
+        });
+    }
+
+    // piece, disambiguation, capture, destination and promotion
+    let (text, promotion) = match text.find('=') {
+        Some(i) => (&text[..i], Some(san_promotion(san))),
+        None => (text, None),
+    };
+    let mut chars: Vec<char> = text.chars().filter(|&c| c != 'x' && c != '-').collect();
+    let piece_id = match chars.first() {
+        Some('K') => KING_ID,
+        Some('Q') => QUEEN_ID,
+        Some('R') => ROOK_ID,
+        Some('B') => BISHOP_ID,
+        Some('N') => KNIGHT_ID,
+        _ => PAWN_ID,
+    };
+    if piece_id != PAWN_ID {
+        chars.remove(0);
+    }
+    if chars.len() < 2 {
+        return Err(format!("Invalid SAN move '{}'", san));
+    }
+    let to = parse_square(chars[chars.len() - 2], chars[chars.len() - 1])
+        .ok_or(format!("Invalid SAN move '{}'", san))?;
+    let mut from_col: Option<isize> = None;
+    let mut from_row: Option<isize> = None;
+    for &c in chars[..chars.len() - 2].iter() {
+        match c {
+            'a'..='h' => from_col = Some(c as isize - 'a' as isize),
+            '1'..='8' => from_row = Some(8 - (c as isize - '0' as isize)),
+            _ => return Err(format!("Invalid SAN move '{}'", san)),
+        }
+    }
+    if piece_id == PAWN_ID && from_col.is_none() {
+        // "e4" is a push, captures always name the file of the pawn
+        from_col = Some(to.1);
+    }
+
+    let candidates: Vec<_> = moves
+        .iter()
+        .filter(|&&(from, dest)| {
+            dest == to
+                && state.board[from.0 as usize][from.1 as usize].abs() == piece_id
+                && from_col.map_or(true, |col| from.1 == col)
+                && from_row.map_or(true, |row| from.0 == row)
+        })
+        .collect();
+    match candidates.len() {
+        0 => Err(format!("Illegal move '{}'", san)),
+        1 => Ok(MoveStruct {
+            is_castle: false,
+            data: MoveUnion {
+                normal_move: *candidates[0],
+            },
+            promotion,
+        }),
+        _ => Err(format!("Ambiguous move '{}'", san)),
+    }
+}
+
+/// Piece a SAN move promotes to (positive id): the piece after its "=" ("e8=N"),
+/// a queen without one
+pub fn san_promotion(san: &str) -> isize {
+    match san.find('=').and_then(|i| san[i + 1..].chars().next()) {
+        Some('R') => ROOK_ID,
+        Some('B') => BISHOP_ID,
+        Some('N') => KNIGHT_ID,
+        _ => QUEEN_ID,
+    }
+}
+
+/// Standard algebraic notation of `_move` (a legal move of the side to move
+/// promoting to `promotion`): "Nbd7", "exd5", "O-O-O", "e8=Q+"
+pub fn to_san(state: &State, _move: &MoveStruct, promotion: isize) -> String {
+    let player = state.current_player;
+    let mut san = if _move.is_castle {
+        match unsafe { _move.data.castle } {
+            Castle::KingSideWhite | Castle::KingSideBlack => "O-O".to_string(),
+            Castle::QueenSideWhite | Castle::QueenSideBlack => "O-O-O".to_string(),
+        }
+    } else {
+        let (from, to) = unsafe { _move.data.normal_move };
+        let piece_id = state.board[from.0 as usize][from.1 as usize].abs();
+        let is_capture = state.board[to.0 as usize][to.1 as usize] != 0 || from.1 != to.1;
+        let destination = convert_square_to_string(to);
+        if piece_id == PAWN_ID {
+            let mut san = if is_capture {
+                format!("{}x{}", &convert_square_to_string(from)[..1], destination)
+            } else {
+                destination
+            };
+            if is_promotion(state, _move) {
+                san.push('=');
+                san.push(PIECE_LETTERS[promotion as usize]);
+            }
+            san
+        } else {
+            let is_capture = state.board[to.0 as usize][to.1 as usize] != 0;
+            // the other pieces of the same type reaching the destination
+            let (moves, _) = get_all_possible_moves(state, player, false);
+            let rivals: Vec<Square> = moves
+                .iter()
+                .filter(|&&(other, dest)| {
+                    dest == to
+                        && other != from
+                        && state.board[other.0 as usize][other.1 as usize].abs() == piece_id
+                })
+                .map(|&(other, _)| other)
+                .collect();
+            let from_square = convert_square_to_string(from);
+            let disambiguation = if rivals.is_empty() {
+                ""
+            } else if rivals.iter().all(|other| other.1 != from.1) {
+                &from_square[..1]
+            } else if rivals.iter().all(|other| other.0 != from.0) {
+                &from_square[1..]
+            } else {
+                &from_square[..]
+            };
+            format!(
+                "{}{}{}{}",
+                PIECE_LETTERS[piece_id as usize],
+                disambiguation,
+                if is_capture { "x" } else { "" },
+                destination
+            )
+        }
+    };
+    let (new_state, _) = next_state_promoting(state, player, _move.clone(), promotion);
+    let other_player = get_other_player(player);
+    if king_is_checked(&new_state, other_player) {
+        let (moves, castle_moves) = get_all_possible_moves(&new_state, other_player, false);
+        san.push(if moves.is_empty() && castle_moves.is_empty() {
+            '#'
+        } else {
+            '+'
+        });
+    }
+    san
+}
+
+/// Move string of `_move` (a move of `player` promoting to `promotion`)
+/// annotated for display: "x" between the squares of a capture, "=Q" (or the
+/// piece promoted to) after a promotion, then "+" if it gives check or "#" if
+/// it mates ("e4xd5", "e7e8=Q+", "CASTLE_KING_SIDE_WHITE#")
+pub fn annotate_move(state: &State, player: Color, _move: MoveStruct, promotion: isize) -> String {
+    // the promotion is annotated below rather than as a UCI suffix
+    let mut annotated = convert_move_union_to_string(&MoveStruct {
+        promotion: None,
+        .._move.clone()
+    });
+    let (new_state, _) = next_state_promoting(state, player, _move.clone(), promotion);
+    if !_move.is_castle {
+        let (from, to) = unsafe { _move.data.normal_move };
+        let piece_id = state.board[from.0 as usize][from.1 as usize];
+        if state.board[to.0 as usize][to.1 as usize] != 0 {
+            annotated.insert(2, 'x');
+        }
+        if piece_id.abs() == PAWN_ID
+            && new_state.board[to.0 as usize][to.1 as usize].abs() != PAWN_ID
+        {
+            annotated.push_str(match promotion {
+                ROOK_ID => "=R",
+                BISHOP_ID => "=B",
+                KNIGHT_ID => "=N",
+                _ => "=Q",
+            });
+        }
+    }
+    let other_player = get_other_player(player);
+    if king_is_checked(&new_state, other_player) {
+        let (moves, castle_moves) = get_all_possible_moves(&new_state, other_player, false);
+        annotated.push(if moves.is_empty() && castle_moves.is_empty() {
+            '#'
+        } else {
+            '+'
+        });
+    }
+    annotated
+}
+
+// HELPER FUNCTIONS
+// ---------------------------------------------------------
+// ---------------------------------------------------------
+
+/// (row, col) of a square like "e4"
+fn parse_square(file: char, rank: char) -> Option<(isize, isize)> {
+    if !('a'..='h').contains(&file) || !('1'..='8').contains(&rank) {
+        return None;
+    }
+    Some((
+        8 - (rank as isize - '0' as isize),
+        file as isize - 'a' as isize,
+    ))
+}
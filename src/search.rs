@@ -0,0 +1,1326 @@
+use std::mem;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::Instant;
+
+use crate::eval::{Evaluator, Handcrafted};
+use crate::rules::{Rules, Standard, Terminal, FIFTY_MOVES_PLIES};
+use crate::selfplay::only_kings_left;
+use crate::tt::{position_key, Bound, TTStats, TranspositionTable};
+use crate::{
+    convert_move_union_to_string, get_other_player, legal_move_strings, null_move, parse_move,
+    Color, MoveStruct, State, DEFAULT_EVAL_WEIGHTS, ID_TO_VALUE, KING_ID, PAWN_ID, QUEEN_ID,
+};
+
+//
+// Constants
+//
+pub const MATE_SCORE: isize = 1_000_000;
+pub const INFINITY: isize = MATE_SCORE + 1;
+pub const MAX_DEPTH: u32 = 64;
+pub const EVAL_HISTOGRAM_MIN: isize = -1000;
+pub const EVAL_HISTOGRAM_BUCKET: isize = 50;
+const EVAL_HISTOGRAM_BUCKETS: usize = 41;
+/// singular extensions are tried from this remaining depth
+const SINGULAR_MIN_DEPTH: u32 = 4;
+/// the TT move is singular when every other move scores this much (per ply
+/// of depth) below its TT score
+const SINGULAR_MARGIN: isize = 25;
+/// null-move pruning is tried from this remaining depth
+const NULL_MOVE_MIN_DEPTH: u32 = 3;
+/// depth reduction of the search after a null move
+const NULL_MOVE_REDUCTION: u32 = 2;
+/// moves the remaining clock time is spread over
+const MOVES_TO_GO: u32 = 30;
+/// the hard deadline of a clock search is this many times its soft budget
+const HARD_TIME_FACTOR: u32 = 3;
+/// iterations allowed past the soft budget in one search
+const MAX_TIME_EXTENSIONS: usize = 2;
+/// score drop of an iteration (centipawns) that extends the search
+const EXTENSION_SCORE_DROP: isize = 30;
+/// time lost on each move outside the search (GUI, bridge) unless configured
+pub const DEFAULT_MOVE_OVERHEAD_MS: u64 = 10;
+
+//
+// Structs
+//
+
+/// Result of one completed iteration of the iterative deepening loop
+#[derive(Clone)]
+pub struct SearchInfo {
+    pub depth: u32,
+    /// deepest ply visited in this iteration: `depth` plus the singular
+    /// extensions on the way (there is no quiescence search), at most twice
+    /// `depth`
+    pub seldepth: u32,
+    pub score: isize,
+    pub nodes: u64,
+    pub time_ms: u128,
+    /// transposition table occupancy in permill
+    pub hashfull: u32,
+    pub pv: Vec<MoveStruct>,
+    /// score and PV of the best root moves, best first (`score` and `pv`
+    /// alone unless `Searcher::multipv` is above 1)
+    pub lines: Vec<(isize, Vec<MoveStruct>)>,
+    /// counters of the search up to this iteration
+    pub stats: SearchStats,
+    /// the root moves searched up to this iteration, best first
+    pub root_moves: Vec<RootMoveStats>,
+    /// how the time manager spent the search (returned info only)
+    pub time: Option<TimeReport>,
+}
+
+/// Time budget of a search: no iteration is started past `soft` unless the
+/// last one was unstable, and the one running is cut at `hard`
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct TimeBudget {
+    pub soft: Duration,
+    pub hard: Duration,
+}
+
+impl TimeBudget {
+    /// A fixed movetime, spent in full: iterations start until it runs out
+    pub fn fixed(movetime: Duration) -> Self {
+        TimeBudget {
+            soft: movetime,
+            hard: movetime,
+        }
+    }
+}
+
+/// Time manager decisions of one search. The budgets are None without a
+/// movetime or clock.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TimeReport {
+    pub soft_ms: Option<u128>,
+    pub hard_ms: Option<u128>,
+    pub used_ms: u128,
+    /// "depth", "mate", "soft_limit", "hard_limit", "nodes", "stopped" or
+    /// "pv_error"
+    pub stop: &'static str,
+    /// iterations started past the soft budget, in order
+    pub extensions: Vec<TimeExtension>,
+}
+
+/// An iteration started past the soft budget because the previous one was
+/// unstable
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct TimeExtension {
+    /// depth of the iteration started
+    pub depth: u32,
+    /// "best_move_changed" or "score_drop"
+    pub reason: &'static str,
+    pub elapsed_ms: u128,
+}
+
+/// A root move and how the search saw it in each completed iteration
+#[derive(Clone)]
+pub struct RootMoveStats {
+    pub _move: MoveStruct,
+    pub iterations: Vec<RootMoveIteration>,
+}
+
+/// Score of a root move in one iteration: exact for the best move (and each
+/// multipv line), an upper bound for the moves that did not beat it
+#[derive(Debug, Copy, Clone)]
+pub struct RootMoveIteration {
+    pub depth: u32,
+    pub score: isize,
+    /// nodes spent below the move in this iteration
+    pub nodes: u64,
+    /// 0 for the best move of the iteration
+    pub rank: usize,
+}
+
+/// Counters collected over a whole search (all iterations)
+#[derive(Clone)]
+pub struct SearchStats {
+    /// interior nodes by the bound of their score: exact (PV nodes), lower
+    /// (cut nodes, a move failed high) and upper (all nodes, every move
+    /// failed low)
+    pub pv_nodes: u64,
+    pub cut_nodes: u64,
+    pub all_nodes: u64,
+    /// moves failing high, split below by the kind of move
+    pub beta_cutoffs: u64,
+    pub tt_move_cutoffs: u64,
+    pub capture_cutoffs: u64,
+    pub quiet_cutoffs: u64,
+    /// beta cutoffs by the first move searched (move ordering quality)
+    pub first_move_cutoffs: u64,
+    /// nodes cut by a transposition table score before searching any move
+    pub tt_cutoffs: u64,
+    pub leaf_nodes: u64,
+    /// TT moves extended by one ply because no other move came close
+    pub singular_extensions: u64,
+    /// nodes cut because passing still failed high
+    pub null_move_cutoffs: u64,
+    /// static evals at the leaves, in buckets of EVAL_HISTOGRAM_BUCKET centipawns
+    /// starting at EVAL_HISTOGRAM_MIN (outliers go to the first/last bucket)
+    pub eval_histogram: Vec<u64>,
+}
+
+impl Default for SearchStats {
+    fn default() -> Self {
+        Self {
+            pv_nodes: 0,
+            cut_nodes: 0,
+            all_nodes: 0,
+            beta_cutoffs: 0,
+            tt_move_cutoffs: 0,
+            capture_cutoffs: 0,
+            quiet_cutoffs: 0,
+            first_move_cutoffs: 0,
+            tt_cutoffs: 0,
+            leaf_nodes: 0,
+            singular_extensions: 0,
+            null_move_cutoffs: 0,
+            eval_histogram: vec![0; EVAL_HISTOGRAM_BUCKETS],
+        }
+    }
+}
+
+impl SearchStats {
+    /// add the counters of `other`
+    fn add(&mut self, other: &SearchStats) {
+        self.pv_nodes += other.pv_nodes;
+        self.cut_nodes += other.cut_nodes;
+        self.all_nodes += other.all_nodes;
+        self.beta_cutoffs += other.beta_cutoffs;
+        self.tt_move_cutoffs += other.tt_move_cutoffs;
+        self.capture_cutoffs += other.capture_cutoffs;
+        self.quiet_cutoffs += other.quiet_cutoffs;
+        self.first_move_cutoffs += other.first_move_cutoffs;
+        self.tt_cutoffs += other.tt_cutoffs;
+        self.leaf_nodes += other.leaf_nodes;
+        self.singular_extensions += other.singular_extensions;
+        self.null_move_cutoffs += other.null_move_cutoffs;
+        for (bucket, count) in self.eval_histogram.iter_mut().zip(other.eval_histogram.iter()) {
+            *bucket += count;
+        }
+    }
+
+    fn record_leaf(&mut self, score: isize) {
+        self.leaf_nodes += 1;
+        let bucket = (score - EVAL_HISTOGRAM_MIN).div_euclid(EVAL_HISTOGRAM_BUCKET);
+        let bucket = bucket.clamp(0, EVAL_HISTOGRAM_BUCKETS as isize - 1) as usize;
+        self.eval_histogram[bucket] += 1;
+    }
+}
+
+/// Root moves searched by one thread of a split search (see
+/// `Searcher::threads`): index in the root order, move, score, PV and nodes
+struct SplitResult {
+    searched: Vec<(usize, MoveStruct, isize, Vec<MoveStruct>, u64)>,
+    nodes: u64,
+    seldepth: u32,
+    stats: SearchStats,
+    timed_out: bool,
+}
+
+/// Prior over the root moves, e.g. from a policy network or an action mask
+#[derive(Clone)]
+pub struct RootPolicy {
+    /// root moves not listed have a prior of 0
+    pub priors: Vec<(MoveStruct, f64)>,
+    /// skip the root moves with a lower prior
+    pub min_prior: Option<f64>,
+    /// only search the `top_k` root moves with the highest priors
+    pub top_k: Option<usize>,
+}
+
+impl RootPolicy {
+    fn prior(&self, _move: &MoveStruct) -> f64 {
+        self.priors
+            .iter()
+            .find(|(m, _)| same_move(m, _move))
+            .map_or(0.0, |(_, prior)| *prior)
+    }
+
+    /// sort `moves` by decreasing prior (stable, so ties keep the capture
+    /// ordering) and drop the pruned ones; all moves are kept if the policy
+    /// would prune every one of them
+    fn apply(&self, moves: &mut Vec<MoveStruct>) {
+        let mut scored: Vec<(f64, MoveStruct)> = moves.drain(..).map(|m| (self.prior(&m), m)).collect();
+        scored.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(std::cmp::Ordering::Equal));
+        let all_moves: Vec<MoveStruct> = scored.iter().map(|(_, m)| m.clone()).collect();
+        if let Some(min_prior) = self.min_prior {
+            scored.retain(|(prior, _)| *prior >= min_prior);
+        }
+        if let Some(top_k) = self.top_k {
+            scored.truncate(top_k);
+        }
+        if scored.is_empty() {
+            *moves = all_moves;
+        } else {
+            *moves = scored.into_iter().map(|(_, m)| m).collect();
+        }
+    }
+}
+
+/// Limits of `Searcher::search`, each optional: the `depth`, `nodes` and
+/// `movetime_ms` of the search, the clocks `wtime_ms` / `btime_ms` and
+/// increments `winc_ms` / `binc_ms` (the side to move's clock sets the
+/// movetime when it is not given), the `multipv` best lines to find and the
+/// `searchmoves` to search at the root (every legal move by default).
+/// `move_overhead_ms` and `latency_ms` are the time each move loses outside
+/// the search (GUI and bridge processing, network round trip), taken off the
+/// movetime so that the clock does not run out.
+#[derive(Debug, Clone)]
+pub struct SearchLimits {
+    pub depth: Option<u32>,
+    pub nodes: Option<u64>,
+    pub movetime_ms: Option<u64>,
+    pub wtime_ms: Option<u64>,
+    pub btime_ms: Option<u64>,
+    pub winc_ms: Option<u64>,
+    pub binc_ms: Option<u64>,
+    pub multipv: usize,
+    pub searchmoves: Option<Vec<String>>,
+    pub move_overhead_ms: u64,
+    pub latency_ms: u64,
+}
+
+impl Default for SearchLimits {
+    fn default() -> Self {
+        SearchLimits {
+            depth: None,
+            nodes: None,
+            movetime_ms: None,
+            wtime_ms: None,
+            btime_ms: None,
+            winc_ms: None,
+            binc_ms: None,
+            multipv: 1,
+            searchmoves: None,
+            move_overhead_ms: DEFAULT_MOVE_OVERHEAD_MS,
+            latency_ms: 0,
+        }
+    }
+}
+
+impl SearchLimits {
+    /// Time each move loses outside the search
+    pub fn overhead(&self) -> Duration {
+        Duration::from_millis(self.move_overhead_ms + self.latency_ms)
+    }
+
+    /// Search time of `player`, less the overhead: all of `movetime_ms`, or
+    /// else a soft budget from its clock and increment with a hard deadline
+    /// `HARD_TIME_FACTOR` times later (at most half the clock)
+    pub fn time_budget(&self, player: Color) -> Option<TimeBudget> {
+        let (clock, increment) = match player {
+            Color::White => (self.wtime_ms, self.winc_ms),
+            Color::Black => (self.btime_ms, self.binc_ms),
+        };
+        if let Some(movetime_ms) = self.movetime_ms {
+            return Some(TimeBudget::fixed(
+                Duration::from_millis(movetime_ms).saturating_sub(self.overhead()),
+            ));
+        }
+        clock.map(|clock| {
+            let remaining = Duration::from_millis(clock);
+            let overhead = self.overhead();
+            let soft = allocate_time(remaining, Duration::from_millis(increment.unwrap_or(0)), overhead);
+            let cap = (remaining.saturating_sub(overhead) / 2).saturating_sub(overhead);
+            TimeBudget {
+                soft,
+                hard: (soft * HARD_TIME_FACTOR).min(cap).max(soft),
+            }
+        })
+    }
+
+    /// Root policy searching only `searchmoves` (None without them), which
+    /// must be legal in `state`
+    pub fn root_policy(&self, state: &State) -> Result<Option<RootPolicy>, String> {
+        let searchmoves = match &self.searchmoves {
+            Some(searchmoves) => searchmoves,
+            None => return Ok(None),
+        };
+        let legal_moves = legal_move_strings(state);
+        let mut priors: Vec<(MoveStruct, f64)> = vec![];
+        for _move in searchmoves {
+            if !legal_moves.contains(_move) {
+                return Err(format!("'{}' is not a legal move", _move));
+            }
+            priors.push((parse_move(_move)?, 1.0));
+        }
+        Ok(Some(RootPolicy {
+            priors,
+            min_prior: Some(1.0),
+            top_k: None,
+        }))
+    }
+}
+
+/// Negamax alpha-beta searcher driven by iterative deepening.
+/// The search can be interrupted at any time through the shared `stop` flag
+/// (or when the time budget runs out), in which case the last fully completed
+/// iteration is kept.
+pub struct Searcher<'a> {
+    pub evaluator: Arc<dyn Evaluator>,
+    /// move generation and end of game of the variant played
+    pub rules: Arc<dyn Rules>,
+    /// root move ordering and pruning (None searches every root move)
+    pub root_policy: Option<RootPolicy>,
+    /// replay the PV of each iteration and check every move is legal (debug)
+    pub verify_pv: bool,
+    /// reproducible results: the movetime is ignored and each search starts
+    /// from an empty private table of the size of the shared one (left as it
+    /// was), so only depth and node limits end it and the same position
+    /// always gives the same result
+    pub deterministic: bool,
+    /// threads of a deterministic search: with more than one, the root moves
+    /// of each iteration are dealt to them in a fixed order, each thread
+    /// searching its moves one after another on its own private table (a
+    /// share of the shared one's size), so neither the split nor the table
+    /// replacements depend on timing. Other searches use one thread.
+    pub threads: usize,
+    /// why the PV of the last iteration failed verification; the search stops
+    /// there, keeping the previous iteration
+    pub pv_error: Option<String>,
+    pub stats: SearchStats,
+    /// stop after this many nodes (depth 1 is always completed)
+    pub max_nodes: Option<u64>,
+    /// number of best root moves searched each iteration, each with its own PV
+    pub multipv: usize,
+    stop: Arc<AtomicBool>,
+    tt: &'a mut TranspositionTable,
+    /// tables of the threads of a deterministic search, see `threads`
+    split_tables: Vec<TranspositionTable>,
+    nodes: u64,
+    seldepth: u32,
+    /// depth of the current iteration, bounding the plies extensions reach
+    root_depth: u32,
+    /// the node being entered follows a null move (no two nulls in a row)
+    after_null_move: bool,
+    /// root moves already searched for a better line of this iteration
+    excluded_root_moves: Vec<MoveStruct>,
+    /// score and nodes of each root move in the current iteration
+    root_iteration: Vec<(MoveStruct, isize, u64)>,
+    root_moves: Vec<RootMoveStats>,
+    start: Instant,
+    deadline: Option<Instant>,
+    node_limit: Option<u64>,
+    timed_out: bool,
+}
+
+impl<'a> Searcher<'a> {
+    pub fn new(stop: Arc<AtomicBool>, tt: &'a mut TranspositionTable) -> Self {
+        Self {
+            evaluator: Arc::new(Handcrafted {
+                weights: DEFAULT_EVAL_WEIGHTS,
+            }),
+            rules: Arc::new(Standard),
+            root_policy: None,
+            verify_pv: false,
+            deterministic: false,
+            threads: 1,
+            pv_error: None,
+            stats: SearchStats::default(),
+            max_nodes: None,
+            multipv: 1,
+            stop,
+            tt,
+            split_tables: vec![],
+            nodes: 0,
+            seldepth: 0,
+            root_depth: 0,
+            after_null_move: false,
+            excluded_root_moves: vec![],
+            root_iteration: vec![],
+            root_moves: vec![],
+            start: Instant::now(),
+            deadline: None,
+            node_limit: None,
+            timed_out: false,
+        }
+    }
+
+    /// Search the side to move of `state` within `limits` (see
+    /// `iterative_deepening`): the node limit and multipv of the searcher are
+    /// taken from `limits`, and its root policy too when `searchmoves` is set
+    pub fn search<F>(
+        &mut self,
+        state: &State,
+        limits: &SearchLimits,
+        on_iteration: F,
+    ) -> Result<Option<SearchInfo>, String>
+    where
+        F: FnMut(&SearchInfo),
+    {
+        if limits.multipv == 0 {
+            return Err("multipv must be at least 1".to_string());
+        }
+        if let Some(root_policy) = limits.root_policy(state)? {
+            self.root_policy = Some(root_policy);
+        }
+        self.max_nodes = limits.nodes;
+        self.multipv = limits.multipv;
+        let player = state.current_player;
+        Ok(self.iterative_deepening(
+            state,
+            player,
+            limits.depth.unwrap_or(MAX_DEPTH),
+            limits.time_budget(player),
+            on_iteration,
+        ))
+    }
+
+    /// search depth 1, 2, ... up to `max_depth` (or until stopped / out of time),
+    /// calling `on_iteration` after each completed depth.
+    /// Depth 1 is always completed so that a move is available.
+    pub fn iterative_deepening<F>(
+        &mut self,
+        state: &State,
+        player: Color,
+        max_depth: u32,
+        time: Option<TimeBudget>,
+        on_iteration: F,
+    ) -> Option<SearchInfo>
+    where
+        F: FnMut(&SearchInfo),
+    {
+        if !self.deterministic {
+            return self.deepen(state, player, max_depth, time, on_iteration);
+        }
+        if self.threads > 1 {
+            // the shared table is set aside (a single entry stands in for
+            // it) and gets the counters of the thread tables
+            let size_mb = (self.tt.size_mb() / self.threads).max(1);
+            self.split_tables = (0..self.threads).map(|_| TranspositionTable::new(size_mb)).collect();
+            let mut unused = TranspositionTable::new(0);
+            mem::swap(self.tt, &mut unused);
+            let info = self.deepen(state, player, max_depth, None, on_iteration);
+            mem::swap(self.tt, &mut unused);
+            self.tt.stats = TTStats::default();
+            for table in mem::take(&mut self.split_tables) {
+                self.tt.stats.add(&table.stats);
+            }
+            return info;
+        }
+        // swap in an empty table for the search, then the shared one back
+        // with the counters of the search
+        let mut private = TranspositionTable::new(self.tt.size_mb());
+        mem::swap(self.tt, &mut private);
+        let info = self.deepen(state, player, max_depth, None, on_iteration);
+        mem::swap(self.tt, &mut private);
+        self.tt.stats = private.stats;
+        info
+    }
+
+    fn deepen<F>(
+        &mut self,
+        state: &State,
+        player: Color,
+        max_depth: u32,
+        time: Option<TimeBudget>,
+        mut on_iteration: F,
+    ) -> Option<SearchInfo>
+    where
+        F: FnMut(&SearchInfo),
+    {
+        self.nodes = 0;
+        self.start = Instant::now();
+        self.deadline = None;
+        self.node_limit = None;
+        self.timed_out = false;
+        self.stats = SearchStats::default();
+        self.pv_error = None;
+        self.root_moves.clear();
+        self.tt.new_search();
+        let mut root = *state;
+        root.current_player = player;
+        let max_depth = max_depth.clamp(1, MAX_DEPTH);
+        let mut best: Option<SearchInfo> = None;
+        let mut stop = "depth";
+        let mut extensions: Vec<TimeExtension> = vec![];
+
+        for depth in 1..=max_depth {
+            self.seldepth = 0;
+            self.root_depth = depth;
+            self.root_iteration.clear();
+            let lines = self.search_lines(&root, player, depth);
+            if self.stopped() {
+                stop = self.stop_reason();
+                break;
+            }
+            self.record_root_iteration(depth);
+            let (score, pv) = lines[0].clone();
+            let info = SearchInfo {
+                depth,
+                seldepth: self.seldepth,
+                score,
+                nodes: self.nodes,
+                time_ms: self.start.elapsed().as_millis(),
+                hashfull: self.hashfull(),
+                pv,
+                lines,
+                stats: self.stats.clone(),
+                root_moves: self.root_moves.clone(),
+                time: None,
+            };
+            if self.verify_pv {
+                if let Err(e) = verify_pv(&*self.rules, &root, player, &info.pv) {
+                    self.pv_error = Some(format!("depth {}: {}", depth, e));
+                    stop = "pv_error";
+                    break;
+                }
+            }
+            on_iteration(&info);
+            let mate_found = score.abs() >= MATE_SCORE - MAX_DEPTH as isize;
+            let previous = best.replace(info);
+            let info = best.as_ref().unwrap();
+            // no point in searching deeper once the game tree is exhausted
+            if mate_found || info.pv.is_empty() {
+                stop = "mate";
+                break;
+            }
+            if let Some(time) = time {
+                let elapsed = self.start.elapsed();
+                if elapsed >= time.soft {
+                    // an unstable iteration may go on until the hard deadline
+                    let reason = previous.as_ref().and_then(|previous| instability(previous, info));
+                    match reason {
+                        Some(reason) if elapsed < time.hard && extensions.len() < MAX_TIME_EXTENSIONS => {
+                            extensions.push(TimeExtension {
+                                depth: depth + 1,
+                                reason,
+                                elapsed_ms: elapsed.as_millis(),
+                            });
+                        }
+                        _ => {
+                            stop = "soft_limit";
+                            break;
+                        }
+                    }
+                }
+                self.deadline = Some(self.start + time.hard);
+            }
+            if let Some(max_nodes) = self.max_nodes {
+                if self.nodes >= max_nodes {
+                    stop = "nodes";
+                    break;
+                }
+                self.node_limit = Some(max_nodes);
+            }
+        }
+        let time = TimeReport {
+            soft_ms: time.map(|time| time.soft.as_millis()),
+            hard_ms: time.map(|time| time.hard.as_millis()),
+            used_ms: self.start.elapsed().as_millis(),
+            stop,
+            extensions,
+        };
+        best.map(|mut info| {
+            info.time = Some(time);
+            info
+        })
+    }
+
+    /// search the root `multipv` times, each without the best moves of the
+    /// previous lines, and return the lines found, best first (fewer when the
+    /// root has fewer moves, garbage once stopped)
+    fn search_lines(&mut self, root: &State, player: Color, depth: u32) -> Vec<(isize, Vec<MoveStruct>)> {
+        if !self.split_tables.is_empty() {
+            return self.search_lines_split(root, player, depth);
+        }
+        let mut lines: Vec<(isize, Vec<MoveStruct>)> = vec![];
+        self.excluded_root_moves.clear();
+        while lines.len() < self.multipv.max(1) {
+            let mut pv: Vec<MoveStruct> = vec![];
+            let score = self.negamax(root, player, depth, 0, -INFINITY, INFINITY, &mut pv);
+            if self.stopped() || (!lines.is_empty() && pv.is_empty()) {
+                break;
+            }
+            let no_moves = pv.is_empty();
+            if let Some(_move) = pv.first() {
+                self.excluded_root_moves.push(_move.clone());
+            }
+            lines.push((score, pv));
+            if no_moves {
+                break;
+            }
+        }
+        self.excluded_root_moves.clear();
+        // a later line can beat an earlier one once the table has more entries
+        lines.sort_by_key(|(score, _)| -score);
+        lines
+    }
+
+    /// `search_lines` on the threads of a deterministic search (see
+    /// `threads`): root move i, in the order of the previous iteration, goes
+    /// to thread i % threads, which searches its moves with its own best
+    /// score as alpha (a full window for several lines). Ties go to the
+    /// earlier move, and a node limit is shared out evenly, so the result
+    /// only depends on the position, the limits and the number of threads.
+    fn search_lines_split(&mut self, root: &State, player: Color, depth: u32) -> Vec<(isize, Vec<MoveStruct>)> {
+        self.nodes += 1;
+        let mut moves = ordered_moves(&*self.rules, root, player);
+        if moves.is_empty() {
+            let score = match self.rules.terminal(root, player, &moves) {
+                Some(Terminal::Checkmate) => -MATE_SCORE,
+                _ => 0,
+            };
+            return vec![(score, vec![])];
+        }
+        if let Some(root_policy) = &self.root_policy {
+            root_policy.apply(&mut moves);
+        }
+        let previous_rank = |_move: &MoveStruct| {
+            self.root_moves
+                .iter()
+                .position(|stats| same_move(&stats._move, _move))
+                .unwrap_or(usize::MAX)
+        };
+        moves.sort_by_cached_key(previous_rank);
+
+        let threads = self.split_tables.len();
+        let full_window = self.multipv > 1;
+        let node_limit = self.node_limit.map(|node_limit| {
+            let threads = threads as u64;
+            (node_limit.saturating_sub(self.nodes) + threads - 1) / threads
+        });
+        let (stop, evaluator, rules) = (&self.stop, &self.evaluator, &self.rules);
+        let mut tables = mem::take(&mut self.split_tables);
+        let results: Vec<SplitResult> = thread::scope(|scope| {
+            let handles: Vec<_> = tables
+                .iter_mut()
+                .enumerate()
+                .map(|(t, tt)| {
+                    let moves: Vec<(usize, MoveStruct)> =
+                        moves.iter().cloned().enumerate().skip(t).step_by(threads).collect();
+                    scope.spawn(move || {
+                        let mut worker = Searcher::new(stop.clone(), tt);
+                        worker.evaluator = evaluator.clone();
+                        worker.rules = rules.clone();
+                        worker.root_depth = depth;
+                        worker.node_limit = node_limit;
+                        let mut searched: Vec<(usize, MoveStruct, isize, Vec<MoveStruct>, u64)> = vec![];
+                        let mut alpha = -INFINITY;
+                        for (index, _move) in moves {
+                            let new_state = worker.rules.make_move(root, player, _move.clone());
+                            let nodes_before = worker.nodes;
+                            let mut child_pv: Vec<MoveStruct> = vec![];
+                            let score = -worker.negamax(
+                                &new_state,
+                                get_other_player(player),
+                                depth - 1,
+                                1,
+                                -INFINITY,
+                                -alpha,
+                                &mut child_pv,
+                            );
+                            if worker.stopped() {
+                                break;
+                            }
+                            if !full_window {
+                                alpha = alpha.max(score);
+                            }
+                            let mut pv = vec![_move.clone()];
+                            pv.extend(child_pv);
+                            searched.push((index, _move, score, pv, worker.nodes - nodes_before));
+                        }
+                        SplitResult {
+                            searched,
+                            nodes: worker.nodes,
+                            seldepth: worker.seldepth,
+                            stats: worker.stats,
+                            timed_out: worker.timed_out,
+                        }
+                    })
+                })
+                .collect();
+            handles.into_iter().map(|handle| handle.join().unwrap()).collect()
+        });
+        self.split_tables = tables;
+
+        let mut searched = vec![];
+        for result in results {
+            self.nodes += result.nodes;
+            self.seldepth = self.seldepth.max(result.seldepth);
+            self.stats.add(&result.stats);
+            self.timed_out |= result.timed_out;
+            searched.extend(result.searched);
+        }
+        if self.stopped() {
+            return vec![];
+        }
+        searched.sort_by_key(|(index, ..)| *index);
+        for (_, _move, score, _, nodes) in searched.iter() {
+            self.record_root_move(_move, *score, *nodes);
+        }
+        searched.sort_by_key(|(index, _, score, ..)| (-score, *index));
+        searched
+            .into_iter()
+            .take(self.multipv.max(1))
+            .map(|(_, _, score, pv, _)| (score, pv))
+            .collect()
+    }
+
+    /// occupancy of the table searched in permill (the average of the
+    /// thread tables of a split search)
+    fn hashfull(&self) -> u32 {
+        if self.split_tables.is_empty() {
+            return self.tt.hashfull();
+        }
+        let total: u32 = self.split_tables.iter().map(|table| table.hashfull()).sum();
+        total / self.split_tables.len() as u32
+    }
+
+    /// add the root moves of the iteration at `depth` to `root_moves`,
+    /// ranked by score, and keep `root_moves` best first
+    fn record_root_iteration(&mut self, depth: u32) {
+        let mut ranked = std::mem::take(&mut self.root_iteration);
+        ranked.sort_by_key(|(_, score, _)| -score);
+        for (rank, (_move, score, nodes)) in ranked.into_iter().enumerate() {
+            let iteration = RootMoveIteration {
+                depth,
+                score,
+                nodes,
+                rank,
+            };
+            match self.root_moves.iter_mut().find(|stats| same_move(&stats._move, &_move)) {
+                Some(stats) => stats.iterations.push(iteration),
+                None => self.root_moves.push(RootMoveStats {
+                    _move,
+                    iterations: vec![iteration],
+                }),
+            }
+        }
+        let last_rank = |stats: &RootMoveStats| {
+            let last = stats.iterations.last().unwrap();
+            (std::cmp::Reverse(last.depth), last.rank)
+        };
+        self.root_moves.sort_by_key(last_rank);
+    }
+
+    fn stopped(&self) -> bool {
+        self.timed_out || self.stop.load(Ordering::Relaxed)
+    }
+
+    /// which limit ended the search once `stopped`
+    fn stop_reason(&self) -> &'static str {
+        if self.stop.load(Ordering::Relaxed) {
+            return "stopped";
+        }
+        if self.deadline.map_or(false, |deadline| Instant::now() >= deadline) {
+            return "hard_limit";
+        }
+        "nodes"
+    }
+
+    fn check_time(&mut self) {
+        if let Some(deadline) = self.deadline {
+            if Instant::now() >= deadline {
+                self.timed_out = true;
+            }
+        }
+        if let Some(node_limit) = self.node_limit {
+            if self.nodes >= node_limit {
+                self.timed_out = true;
+            }
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn negamax(
+        &mut self,
+        state: &State,
+        player: Color,
+        depth: u32,
+        ply: u32,
+        mut alpha: isize,
+        beta: isize,
+        pv: &mut Vec<MoveStruct>,
+    ) -> isize {
+        self.nodes += 1;
+        self.seldepth = self.seldepth.max(ply);
+        let after_null_move = std::mem::replace(&mut self.after_null_move, false);
+        self.check_time();
+        if self.stopped() {
+            return 0;
+        }
+
+        // draws by rule, whatever the table holds for the position (the root
+        // still needs a move); a mate on the hundredth ply is still a mate
+        if ply > 0 && (state.halfmove_clock >= FIFTY_MOVES_PLIES || only_kings_left(state)) {
+            let moves = self.rules.legal_moves(state, player);
+            return match self.rules.terminal(state, player, &moves) {
+                Some(Terminal::Checkmate) => -MATE_SCORE + ply as isize,
+                _ => 0,
+            };
+        }
+
+        // transposition table lookup (never cut at the root, we need a move there)
+        let key = position_key(state);
+        let mut tt_move: Option<MoveStruct> = None;
+        // TT score usable as a lower bound for the singular extension test
+        let mut singular_beta: Option<isize> = None;
+        if let Some(entry) = self.tt.probe(key) {
+            if ply > 0 && entry.depth >= depth {
+                let score = score_from_tt(entry.score, ply);
+                let cutoff = match entry.bound {
+                    Bound::Exact => true,
+                    Bound::Lower => score >= beta,
+                    Bound::Upper => score <= alpha,
+                };
+                if cutoff {
+                    self.tt.stats.cutoffs += 1;
+                    self.stats.tt_cutoffs += 1;
+                    return score;
+                }
+            }
+            tt_move = entry.best_move;
+            let score = score_from_tt(entry.score, ply);
+            if ply > 0
+                && ply < 2 * self.root_depth
+                && depth >= SINGULAR_MIN_DEPTH
+                && entry.depth + 3 >= depth
+                && entry.bound != Bound::Upper
+                && score.abs() < MATE_SCORE - MAX_DEPTH as isize
+            {
+                singular_beta = Some(score - SINGULAR_MARGIN * depth as isize);
+            }
+        }
+
+        let mut moves = ordered_moves(&*self.rules, state, player);
+        if ply == 0 && !moves.is_empty() {
+            if let Some(root_policy) = &self.root_policy {
+                root_policy.apply(&mut moves);
+            }
+        }
+        if let Some(tt_move) = &tt_move {
+            if let Some(index) = moves.iter().position(|m| same_move(m, tt_move)) {
+                let _move = moves.remove(index);
+                moves.insert(0, _move);
+            }
+        }
+        if moves.is_empty() {
+            return match self.rules.terminal(state, player, &moves) {
+                Some(Terminal::Checkmate) => -MATE_SCORE + ply as isize,
+                _ => 0,
+            };
+        }
+        if ply == 0 && !self.excluded_root_moves.is_empty() {
+            let excluded = &self.excluded_root_moves;
+            moves.retain(|m| !excluded.iter().any(|e| same_move(e, m)));
+            if moves.is_empty() {
+                return -INFINITY;
+            }
+        }
+        if depth == 0 {
+            let score = self.evaluator.evaluate(state, player);
+            self.stats.record_leaf(score);
+            return score;
+        }
+
+        // null-move pruning: if passing still fails high in a reduced search,
+        // a real move would too (skipped without pieces, where passing can be
+        // better than any move)
+        if ply > 0
+            && !after_null_move
+            && depth >= NULL_MOVE_MIN_DEPTH
+            && beta.abs() < MATE_SCORE - MAX_DEPTH as isize
+            && has_pieces(state, player)
+            && self.evaluator.evaluate(state, player) >= beta
+        {
+            if let Ok(passed) = null_move(state) {
+                self.after_null_move = true;
+                let score = -self.negamax(
+                    &passed,
+                    get_other_player(player),
+                    depth - 1 - NULL_MOVE_REDUCTION,
+                    ply + 1,
+                    -beta,
+                    -beta + 1,
+                    &mut vec![],
+                );
+                if self.stopped() {
+                    return 0;
+                }
+                if score >= beta {
+                    self.stats.null_move_cutoffs += 1;
+                    // unproven mates are not returned
+                    return beta;
+                }
+            }
+        }
+
+        // singular extension: the TT move (first) is extended by one ply when a
+        // reduced search of the other moves fails low against its TT score
+        let mut extension = 0;
+        if let (Some(singular_beta), Some(tt_move)) = (singular_beta, &tt_move) {
+            if same_move(&moves[0], tt_move)
+                && self.is_singular(state, player, &moves[1..], depth, ply, singular_beta)
+            {
+                self.stats.singular_extensions += 1;
+                extension = 1;
+            }
+            if self.stopped() {
+                return 0;
+            }
+        }
+
+        let alpha_orig = alpha;
+        let mut best_score = -INFINITY;
+        let mut best_move: Option<MoveStruct> = None;
+        for (i, _move) in moves.into_iter().enumerate() {
+            // kind of move, to attribute a beta cutoff
+            let is_tt_move = i == 0 && tt_move.as_ref().map_or(false, |m| same_move(m, &_move));
+            let is_capture = !_move.is_castle && {
+                let (_, to) = unsafe { _move.data.normal_move };
+                state.board[to.0 as usize][to.1 as usize] != 0
+            };
+            let new_state = self.rules.make_move(state, player, _move.clone());
+            let nodes_before = self.nodes;
+            let mut child_pv: Vec<MoveStruct> = vec![];
+            let score = -self.negamax(
+                &new_state,
+                get_other_player(player),
+                depth - 1 + if i == 0 { extension } else { 0 },
+                ply + 1,
+                -beta,
+                -alpha,
+                &mut child_pv,
+            );
+            if self.stopped() {
+                return 0;
+            }
+            if ply == 0 {
+                self.record_root_move(&_move, score, self.nodes - nodes_before);
+            }
+            if score > best_score {
+                best_score = score;
+                best_move = Some(_move.clone());
+                if score > alpha {
+                    alpha = score;
+                    pv.clear();
+                    pv.push(_move);
+                    pv.extend(child_pv);
+                }
+            }
+            if alpha >= beta {
+                self.stats.beta_cutoffs += 1;
+                if i == 0 {
+                    self.stats.first_move_cutoffs += 1;
+                }
+                if is_tt_move {
+                    self.stats.tt_move_cutoffs += 1;
+                } else if is_capture {
+                    self.stats.capture_cutoffs += 1;
+                } else {
+                    self.stats.quiet_cutoffs += 1;
+                }
+                break;
+            }
+        }
+
+        let bound = if best_score <= alpha_orig {
+            self.stats.all_nodes += 1;
+            Bound::Upper
+        } else if best_score >= beta {
+            self.stats.cut_nodes += 1;
+            Bound::Lower
+        } else {
+            self.stats.pv_nodes += 1;
+            Bound::Exact
+        };
+        // a root searched without its best moves has no score to keep
+        if ply > 0 || self.excluded_root_moves.is_empty() {
+            self.tt
+                .store(key, depth, score_to_tt(best_score, ply), bound, best_move);
+        }
+        best_score
+    }
+
+    /// keep the latest score of a root move in this iteration (a move searched
+    /// again for a later multipv line) and add up its nodes
+    fn record_root_move(&mut self, _move: &MoveStruct, score: isize, nodes: u64) {
+        match self.root_iteration.iter_mut().find(|(m, _, _)| same_move(m, _move)) {
+            Some(entry) => {
+                entry.1 = score;
+                entry.2 += nodes;
+            }
+            None => self.root_iteration.push((_move.clone(), score, nodes)),
+        }
+    }
+
+    /// whether all `other_moves` score below `singular_beta` in a null window
+    /// search at half the depth
+    fn is_singular(
+        &mut self,
+        state: &State,
+        player: Color,
+        other_moves: &[MoveStruct],
+        depth: u32,
+        ply: u32,
+        singular_beta: isize,
+    ) -> bool {
+        let reduced_depth = (depth - 1) / 2;
+        for _move in other_moves.iter() {
+            let new_state = self.rules.make_move(state, player, _move.clone());
+            let score = -self.negamax(
+                &new_state,
+                get_other_player(player),
+                reduced_depth - 1,
+                ply + 1,
+                -singular_beta,
+                -singular_beta + 1,
+                &mut vec![],
+            );
+            if self.stopped() || score >= singular_beta {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+/// Time to search a move with `remaining` on the clock and `increment`
+/// added after each move, `overhead` being lost outside the search on every
+/// move: it is kept in reserve for the coming moves and taken off this one
+pub fn allocate_time(remaining: Duration, increment: Duration, overhead: Duration) -> Duration {
+    let reserve = overhead * MOVES_TO_GO;
+    let budget = remaining.saturating_sub(reserve) / MOVES_TO_GO + increment * 3 / 4;
+    budget
+        .min(remaining.saturating_sub(overhead) / 2)
+        .saturating_sub(overhead)
+}
+
+// HELPER FUNCTIONS
+// ---------------------------------------------------------
+// ---------------------------------------------------------
+
+/// why the iteration `info` is unstable after `previous`, extending the
+/// search past its soft budget (None if stable)
+fn instability(previous: &SearchInfo, info: &SearchInfo) -> Option<&'static str> {
+    if !same_move(&previous.pv[0], &info.pv[0]) {
+        return Some("best_move_changed");
+    }
+    if info.score <= previous.score - EXTENSION_SCORE_DROP {
+        return Some("score_drop");
+    }
+    None
+}
+
+/// whether `player` has a piece other than its king and pawns
+fn has_pieces(state: &State, player: Color) -> bool {
+    state.board.iter().any(|row| {
+        row.iter().any(|&piece_id| {
+            piece_id * player.to_int() > 0
+                && piece_id.abs() != KING_ID
+                && piece_id.abs() != PAWN_ID
+        })
+    })
+}
+
+/// mate scores are stored relative to the node, not to the root
+fn score_to_tt(score: isize, ply: u32) -> isize {
+    if score >= MATE_SCORE - MAX_DEPTH as isize {
+        score + ply as isize
+    } else if score <= -MATE_SCORE + MAX_DEPTH as isize {
+        score - ply as isize
+    } else {
+        score
+    }
+}
+
+fn score_from_tt(score: isize, ply: u32) -> isize {
+    if score >= MATE_SCORE - MAX_DEPTH as isize {
+        score - ply as isize
+    } else if score <= -MATE_SCORE + MAX_DEPTH as isize {
+        score + ply as isize
+    } else {
+        score
+    }
+}
+
+fn same_move(a: &MoveStruct, b: &MoveStruct) -> bool {
+    unsafe {
+        match (a.is_castle, b.is_castle) {
+            (false, false) => {
+                a.data.normal_move == b.data.normal_move
+                    && a.promotion.unwrap_or(QUEEN_ID) == b.promotion.unwrap_or(QUEEN_ID)
+            }
+            (true, true) => a.data.castle == b.data.castle,
+            _ => false,
+        }
+    }
+}
+
+/// Replay `pv` from `state`, checking that each move is legal
+fn verify_pv(rules: &dyn Rules, state: &State, player: Color, pv: &[MoveStruct]) -> Result<(), String> {
+    let mut state = *state;
+    let mut player = player;
+    for (ply, _move) in pv.iter().enumerate() {
+        if !rules.legal_moves(&state, player).iter().any(|m| same_move(m, _move)) {
+            return Err(format!(
+                "illegal PV move {} at ply {}",
+                convert_move_union_to_string(_move),
+                ply
+            ));
+        }
+        state = rules.make_move(&state, player, _move.clone());
+        player = get_other_player(player);
+    }
+    Ok(())
+}
+
+/// legal moves, captures first (most valuable victim), castles last
+fn ordered_moves(rules: &dyn Rules, state: &State, player: Color) -> Vec<MoveStruct> {
+    let mut moves = rules.legal_moves(state, player);
+    moves.sort_by_key(|_move| {
+        if _move.is_castle {
+            return 1;
+        }
+        let (_, to) = unsafe { _move.data.normal_move };
+        let captured = state.board[to.0 as usize][to.1 as usize];
+        -ID_TO_VALUE[&captured]
+    });
+    moves
+}
+
+/// `std::time::Instant::now` panics on wasm32-unknown-unknown: there the clock
+/// stands still, so time limits are ignored and reported times are 0
+#[cfg(target_arch = "wasm32")]
+#[derive(Copy, Clone, PartialEq, PartialOrd)]
+struct Instant(Duration);
+
+#[cfg(target_arch = "wasm32")]
+impl Instant {
+    fn now() -> Self {
+        Instant(Duration::from_secs(0))
+    }
+
+    fn elapsed(&self) -> Duration {
+        Duration::from_secs(0)
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+impl std::ops::Add<Duration> for Instant {
+    type Output = Instant;
+
+    fn add(self, other: Duration) -> Instant {
+        Instant(self.0 + other)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::from_fen;
+
+    const MIDDLEGAME_FEN: &str = "r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 4 4";
+
+    fn deterministic_search(
+        tt: &mut TranspositionTable,
+        fen: &str,
+        threads: usize,
+        multipv: usize,
+        max_nodes: Option<u64>,
+    ) -> SearchInfo {
+        let state = from_fen(fen).unwrap();
+        let mut searcher = Searcher::new(Arc::new(AtomicBool::new(false)), tt);
+        searcher.deterministic = true;
+        searcher.threads = threads;
+        searcher.multipv = multipv;
+        searcher.max_nodes = max_nodes;
+        searcher
+            .iterative_deepening(&state, state.current_player, 3, None, |_| {})
+            .unwrap()
+    }
+
+    /// depth, lines, nodes, seldepth and root move scores
+    type Summary = (u32, Vec<(isize, Vec<String>)>, u64, u32, Vec<isize>);
+
+    /// what must be reproduced: the lines, nodes, seldepth and root move scores
+    fn summary(info: &SearchInfo) -> Summary {
+        let lines = info
+            .lines
+            .iter()
+            .map(|(score, pv)| (*score, pv.iter().map(convert_move_union_to_string).collect()))
+            .collect();
+        let root_scores = info
+            .root_moves
+            .iter()
+            .map(|stats| stats.iterations.last().unwrap().score)
+            .collect();
+        (info.depth, lines, info.nodes, info.seldepth, root_scores)
+    }
+
+    #[test]
+    fn split_search_is_reproducible() {
+        for (multipv, max_nodes) in [(1, None), (3, None), (1, Some(1000))] {
+            let mut tt = TranspositionTable::new(1);
+            let first = deterministic_search(&mut tt, MIDDLEGAME_FEN, 3, multipv, max_nodes);
+            // a table filled by another search changes nothing
+            let mut searcher = Searcher::new(Arc::new(AtomicBool::new(false)), &mut tt);
+            let state = from_fen(MIDDLEGAME_FEN).unwrap();
+            searcher.iterative_deepening(&state, Color::White, 2, None, |_| {});
+            let second = deterministic_search(&mut tt, MIDDLEGAME_FEN, 3, multipv, max_nodes);
+            assert_eq!(summary(&first), summary(&second));
+            assert_eq!(first.lines.len(), multipv);
+        }
+    }
+
+    #[test]
+    fn split_search_leaves_the_shared_table() {
+        let mut tt = TranspositionTable::new(1);
+        let info = deterministic_search(&mut tt, MIDDLEGAME_FEN, 2, 1, None);
+        assert!(info.nodes > 0);
+        assert!(tt.stats.stores > 0);
+        let key = position_key(&from_fen(MIDDLEGAME_FEN).unwrap());
+        assert!(tt.probe(key).is_none());
+    }
+
+    fn search_score(fen: &str, depth: u32) -> isize {
+        let state = from_fen(fen).unwrap();
+        let mut tt = TranspositionTable::new(1);
+        let mut searcher = Searcher::new(Arc::new(AtomicBool::new(false)), &mut tt);
+        searcher
+            .iterative_deepening(&state, state.current_player, depth, None, |_| {})
+            .unwrap()
+            .score
+    }
+
+    // a line reaching the hundredth quiet ply or bare kings is a draw, not
+    // the evaluation of its last position, unless it ends in mate
+    #[test]
+    fn draws_by_rule_inside_the_search() {
+        let queen_up = "7k/8/8/8/8/8/8/KQ6 w - - 99 80";
+        assert_eq!(search_score(queen_up, 3), 0);
+        assert!(search_score(&queen_up.replace(" 99 80", " 0 80"), 3) > 500);
+        assert_eq!(search_score("6k1/5ppp/8/8/8/8/8/R5K1 w - - 99 80", 2), MATE_SCORE - 1);
+        assert_eq!(search_score("k7/8/8/8/3K4/8/8/8 w - - 0 1", 2), 0);
+    }
+
+    // seldepth counts the extended plies only: never below the depth or
+    // past the extension bound
+    #[test]
+    fn seldepth_is_the_depth_with_extensions() {
+        let state = from_fen(MIDDLEGAME_FEN).unwrap();
+        let mut tt = TranspositionTable::new(1);
+        let mut searcher = Searcher::new(Arc::new(AtomicBool::new(false)), &mut tt);
+        let mut iterations: Vec<(u32, u32)> = vec![];
+        searcher.iterative_deepening(&state, state.current_player, 3, None, |info| {
+            iterations.push((info.depth, info.seldepth))
+        });
+        assert_eq!(iterations.len(), 3);
+        for (depth, seldepth) in iterations {
+            assert!(depth <= seldepth && seldepth <= 2 * depth, "{} {}", depth, seldepth);
+        }
+    }
+
+    #[test]
+    fn split_search_finds_the_mate() {
+        let mut tt = TranspositionTable::new(1);
+        let info = deterministic_search(&mut tt, "6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1", 4, 1, None);
+        assert_eq!(convert_move_union_to_string(&info.pv[0]), "a1a8");
+        assert_eq!(info.score, MATE_SCORE - 1);
+    }
+}
@@ -0,0 +1,476 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, SyncSender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::book::Book;
+use crate::draw::{likely_draw, DRAW_STABLE_MOVES};
+use crate::eval::{Evaluator, NoisyEvaluator};
+use crate::openings::OpeningSuite;
+use crate::rng::Rng;
+use crate::rules::{Rules, Terminal};
+use crate::search::{SearchInfo, SearchStats, Searcher, TimeBudget, TimeReport};
+use crate::tt::{position_key, TranspositionTable};
+use crate::{
+    get_all_possible_moves, is_promotion, to_fen, Color, MoveStruct, MoveUnion, State,
+    DEFAULT_BOARD, KING_ID, PROMOTION_IDS,
+};
+
+//
+// Constants
+//
+/// finished games buffered per worker before the workers wait for the consumer
+const GAMES_BUFFERED_PER_WORKER: usize = 4;
+const RECV_SLICE: Duration = Duration::from_millis(100);
+/// search depth of the self-play moves when neither the caller nor the config sets one
+pub const DEFAULT_SELFPLAY_DEPTH: u32 = 2;
+pub const DEFAULT_MAX_PLIES: u32 = 200;
+
+//
+// Structs
+//
+#[derive(Debug, Clone)]
+pub struct SelfPlayConfig {
+    pub depth: u32,
+    pub movetime: Option<Duration>,
+    /// the games start from positions of this suite (the initial position if None)
+    pub openings: Option<Arc<OpeningSuite>>,
+    /// the first plies are played at random to diversify the games
+    pub random_plies: u32,
+    pub adjudication: Adjudication,
+    /// transposition table size of each worker
+    pub hash_mb: usize,
+    /// book learning: the moves come from this book while in book, and every
+    /// finished game updates it
+    pub book: Option<Arc<Mutex<Book>>>,
+    /// rules of the variant the games are played in
+    pub rules: Arc<dyn Rules>,
+    /// standard deviation in centipawns of the Gaussian noise added to the
+    /// evaluations, drawn anew for each game (0 disables it)
+    pub eval_noise: f64,
+}
+
+/// Rules ending games early to generate data faster. Scores are those of the
+/// searched moves (random opening moves are ignored), from white's point of view.
+#[derive(Debug, Copy, Clone)]
+pub struct Adjudication {
+    /// a side resigns once its score stayed at or below `-resign_score` for
+    /// `resign_moves` consecutive moves (None never resigns)
+    pub resign_score: Option<isize>,
+    pub resign_moves: u32,
+    /// a draw is adjudicated after ply `draw_min_ply` once the score stayed
+    /// within `draw_score` of 0 for `draw_moves` consecutive moves (None never)
+    pub draw_score: Option<isize>,
+    pub draw_moves: u32,
+    pub draw_min_ply: u32,
+    /// adjudicate a draw once `draw::likely_draw` holds with at least
+    /// DRAW_STABLE_MOVES scores (any ply)
+    pub likely_draw: bool,
+    /// games reaching this length are adjudicated as draws
+    pub max_plies: u32,
+}
+
+impl Default for Adjudication {
+    fn default() -> Self {
+        Self {
+            resign_score: None,
+            resign_moves: 4,
+            draw_score: None,
+            draw_moves: 8,
+            draw_min_ply: 80,
+            likely_draw: false,
+            max_plies: DEFAULT_MAX_PLIES,
+        }
+    }
+}
+
+impl Adjudication {
+    /// result and termination if the game should end in `state` after
+    /// `scores` (the scores of the searched moves) at ply `ply`
+    fn adjudicate(&self, state: &State, scores: &[isize], ply: u32) -> Option<(GameResult, &'static str)> {
+        let last = |moves: u32| {
+            let moves = moves.max(1) as usize;
+            if scores.len() >= moves {
+                Some(&scores[scores.len() - moves..])
+            } else {
+                None
+            }
+        };
+        if let (Some(threshold), Some(last)) = (self.resign_score, last(self.resign_moves)) {
+            if last.iter().all(|&score| score <= -threshold) {
+                return Some((GameResult::BlackWin, "resignation"));
+            }
+            if last.iter().all(|&score| score >= threshold) {
+                return Some((GameResult::WhiteWin, "resignation"));
+            }
+        }
+        if let (Some(threshold), Some(last)) = (self.draw_score, last(self.draw_moves)) {
+            if ply >= self.draw_min_ply && last.iter().all(|&score| score.abs() <= threshold) {
+                return Some((GameResult::Draw, "adjudicated_draw"));
+            }
+        }
+        if self.likely_draw && scores.len() >= DRAW_STABLE_MOVES && likely_draw(state, scores) {
+            return Some((GameResult::Draw, "likely_draw"));
+        }
+        if ply >= self.max_plies {
+            return Some((GameResult::Draw, "max_plies"));
+        }
+        None
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum GameResult {
+    WhiteWin,
+    BlackWin,
+    Draw,
+}
+
+impl GameResult {
+    pub fn to_str(&self) -> &'static str {
+        match self {
+            GameResult::WhiteWin => "1-0",
+            GameResult::BlackWin => "0-1",
+            GameResult::Draw => "1/2-1/2",
+        }
+    }
+}
+
+/// A finished self-play game
+#[derive(Clone)]
+pub struct Game {
+    /// name of the starting position when it came from an opening suite
+    pub opening: Option<String>,
+    /// FEN of the position before each move
+    pub fens: Vec<String>,
+    pub moves: Vec<MoveStruct>,
+    /// search score of each move from white's point of view (0 for random moves)
+    pub scores: Vec<isize>,
+    /// time manager decisions of each move (None for random, book and UCI moves)
+    pub times: Vec<Option<TimeReport>>,
+    pub result: GameResult,
+    /// "checkmate", "stalemate", "repetition", "fifty_moves",
+    /// "insufficient_material", "resignation", "adjudicated_draw",
+    /// "likely_draw" or "max_plies"
+    pub termination: &'static str,
+    pub nodes: u64,
+    pub time_ms: u128,
+}
+
+/// Play one game from `start`, `search` choosing the moves after the
+/// `random_plies` random ones; None if `stop` was raised meanwhile
+pub fn play_game<F>(
+    rules: &dyn Rules,
+    start: State,
+    random_plies: u32,
+    adjudication: &Adjudication,
+    rng: &mut Rng,
+    stop: &AtomicBool,
+    mut search: F,
+) -> Option<Game>
+where
+    F: FnMut(&State, Color) -> Option<SearchInfo>,
+{
+    let start_time = Instant::now();
+    let mut state = start;
+    let mut repetitions: HashMap<u64, u32> = HashMap::new();
+    let mut fens: Vec<String> = vec![];
+    let mut moves: Vec<MoveStruct> = vec![];
+    let mut scores: Vec<isize> = vec![];
+    let mut times: Vec<Option<TimeReport>> = vec![];
+    let mut nodes: u64 = 0;
+
+    let (result, termination) = loop {
+        let player = state.current_player;
+        let legal_moves = rules.legal_moves(&state, player);
+        match rules.terminal(&state, player, &legal_moves) {
+            Some(Terminal::Checkmate) => break (winner(player), Terminal::Checkmate.name()),
+            Some(terminal) => break (GameResult::Draw, terminal.name()),
+            None => {}
+        }
+        let repetition = repetitions.entry(position_key(&state)).or_insert(0);
+        *repetition += 1;
+        if *repetition >= 3 {
+            break (GameResult::Draw, "repetition");
+        }
+        let searched_scores = &scores[scores.len().min(random_plies as usize)..];
+        if let Some(adjudicated) = adjudication.adjudicate(&state, searched_scores, moves.len() as u32) {
+            break adjudicated;
+        }
+
+        let (_move, score, time) = if (moves.len() as u32) < random_plies {
+            (legal_moves[rng.below(legal_moves.len())].clone(), 0, None)
+        } else {
+            let info = search(&state, player);
+            if stop.load(Ordering::SeqCst) {
+                return None;
+            }
+            let info = info?;
+            nodes += info.nodes;
+            let _move = info.pv.first()?.clone();
+            let score = match player {
+                Color::White => info.score,
+                Color::Black => -info.score,
+            };
+            (_move, score, info.time)
+        };
+        fens.push(to_fen(state));
+        state = rules.make_move(&state, player, _move.clone());
+        moves.push(_move);
+        scores.push(score);
+        times.push(time);
+    };
+
+    Some(Game {
+        opening: None,
+        fens,
+        moves,
+        scores,
+        times,
+        result,
+        termination,
+        nodes,
+        time_ms: start_time.elapsed().as_millis(),
+    })
+}
+
+/// Search `state` for a self-play or arena move
+pub fn search_move(
+    state: &State,
+    player: Color,
+    evaluator: &Arc<dyn Evaluator>,
+    depth: u32,
+    movetime: Option<Duration>,
+    tt: &mut TranspositionTable,
+    stop: &Arc<AtomicBool>,
+) -> Option<SearchInfo> {
+    let mut searcher = Searcher::new(stop.clone(), tt);
+    searcher.evaluator = evaluator.clone();
+    searcher.iterative_deepening(state, player, depth, movetime.map(TimeBudget::fixed), |_| {})
+}
+
+/// `workers` threads playing games continuously and sending them through a
+/// bounded channel; workers wait when the consumer falls behind
+pub struct SelfPlayPool {
+    stop: Arc<AtomicBool>,
+    receiver: Mutex<Option<Receiver<Game>>>,
+    workers: Mutex<Vec<JoinHandle<()>>>,
+}
+
+impl SelfPlayPool {
+    pub fn start(
+        workers: usize,
+        config: SelfPlayConfig,
+        evaluator: Arc<dyn Evaluator>,
+        seed: Option<u64>,
+    ) -> Self {
+        let workers = workers.max(1);
+        let stop = Arc::new(AtomicBool::new(false));
+        let (sender, receiver) = mpsc::sync_channel(workers * GAMES_BUFFERED_PER_WORKER);
+        let mut seeds = match seed {
+            Some(seed) => Rng::new(seed),
+            None => Rng::from_entropy(),
+        };
+        let handles = (0..workers)
+            .map(|_| {
+                let worker_seed = seeds.next_u64();
+                let config = config.clone();
+                let evaluator = evaluator.clone();
+                let stop = stop.clone();
+                let sender: SyncSender<Game> = sender.clone();
+                thread::spawn(move || {
+                    let mut rng = Rng::new(worker_seed);
+                    let mut tt = TranspositionTable::new(config.hash_mb);
+                    while !stop.load(Ordering::SeqCst) {
+                        let opening = config.openings.as_ref().map(|suite| suite.sample(&mut rng));
+                        let start = match opening {
+                            Some(opening) => opening.state,
+                            None => State::new(DEFAULT_BOARD, Color::White, true, true, true, true),
+                        };
+                        let opening_name = opening.map(|opening| opening.name.clone());
+                        let mut book_rng = Rng::new(rng.next_u64());
+                        let evaluator: Arc<dyn Evaluator> = if config.eval_noise > 0.0 {
+                            Arc::new(NoisyEvaluator {
+                                inner: evaluator.clone(),
+                                sigma: config.eval_noise,
+                                seed: rng.next_u64(),
+                            })
+                        } else {
+                            evaluator.clone()
+                        };
+                        let search = |state: &State, player| {
+                            if let Some(book) = &config.book {
+                                if let Some(_move) = book.lock().unwrap().probe(state, &mut book_rng) {
+                                    return Some(book_move_info(_move));
+                                }
+                            }
+                            search_move(
+                                state,
+                                player,
+                                &evaluator,
+                                config.depth,
+                                config.movetime,
+                                &mut tt,
+                                &stop,
+                            )
+                        };
+                        let mut game = match play_game(
+                            &*config.rules,
+                            start,
+                            config.random_plies,
+                            &config.adjudication,
+                            &mut rng,
+                            &stop,
+                            search,
+                        ) {
+                            Some(game) => game,
+                            None => break,
+                        };
+                        game.opening = opening_name;
+                        if let Some(book) = &config.book {
+                            book.lock().unwrap().learn(&start, &game.moves, game.result);
+                        }
+                        // the receiver is gone once the pool is stopped
+                        if sender.send(game).is_err() {
+                            break;
+                        }
+                    }
+                })
+            })
+            .collect();
+        Self {
+            stop,
+            receiver: Mutex::new(Some(receiver)),
+            workers: Mutex::new(handles),
+        }
+    }
+
+    /// Wait up to `timeout` (forever if None) for a first game, then take the
+    /// games already finished, `max_games` at most. Returns early (possibly
+    /// empty) when the pool is stopped.
+    pub fn get_games(&self, timeout: Option<Duration>, max_games: usize) -> Vec<Game> {
+        let mut games: Vec<Game> = vec![];
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        while max_games > 0 && !self.stop.load(Ordering::SeqCst) {
+            // wait in short slices so that `stop` never waits for a long `get_games`
+            let wait = match deadline {
+                Some(deadline) => deadline
+                    .saturating_duration_since(Instant::now())
+                    .min(RECV_SLICE),
+                None => RECV_SLICE,
+            };
+            let receiver = self.receiver.lock().unwrap();
+            let receiver = match &*receiver {
+                Some(receiver) => receiver,
+                None => break,
+            };
+            match receiver.recv_timeout(wait) {
+                Ok(game) => {
+                    games.push(game);
+                    while games.len() < max_games {
+                        match receiver.try_recv() {
+                            Ok(game) => games.push(game),
+                            Err(_) => break,
+                        }
+                    }
+                    break;
+                }
+                Err(RecvTimeoutError::Disconnected) => break,
+                Err(RecvTimeoutError::Timeout) => {
+                    if let Some(deadline) = deadline {
+                        if Instant::now() >= deadline {
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+        games
+    }
+
+    pub fn is_running(&self) -> bool {
+        !self.stop.load(Ordering::SeqCst)
+    }
+
+    /// Stop the workers (games in progress and unread games are dropped) and
+    /// wait for them
+    pub fn stop(&self) {
+        self.stop.store(true, Ordering::SeqCst);
+        // unblock the workers waiting to send a game
+        *self.receiver.lock().unwrap() = None;
+        for worker in self.workers.lock().unwrap().drain(..) {
+            worker.join().ok();
+        }
+    }
+}
+
+impl Drop for SelfPlayPool {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+// HELPER FUNCTIONS
+// ---------------------------------------------------------
+// ---------------------------------------------------------
+
+/// a book move played as if searched, scored 0 like the random moves
+fn book_move_info(_move: MoveStruct) -> SearchInfo {
+    SearchInfo {
+        depth: 0,
+        seldepth: 0,
+        score: 0,
+        nodes: 0,
+        time_ms: 0,
+        hashfull: 0,
+        pv: vec![_move.clone()],
+        lines: vec![(0, vec![_move])],
+        stats: SearchStats::default(),
+        root_moves: vec![],
+        time: None,
+    }
+}
+
+/// legal moves of `player`, each promotion once per piece of `PROMOTION_IDS`
+pub(crate) fn all_moves(state: &State, player: Color) -> Vec<MoveStruct> {
+    let (moves, castle_moves) = get_all_possible_moves(state, player, false);
+    let mut all_moves: Vec<MoveStruct> = vec![];
+    for &x in moves.iter() {
+        let _move = MoveStruct {
+            is_castle: false,
+            data: MoveUnion { normal_move: x },
+            promotion: None,
+        };
+        if is_promotion(state, &_move) {
+            all_moves.extend(PROMOTION_IDS.iter().map(|&piece_id| MoveStruct {
+                promotion: Some(piece_id),
+                .._move.clone()
+            }));
+        } else {
+            all_moves.push(_move);
+        }
+    }
+    all_moves.extend(castle_moves.iter().map(|&x| MoveStruct {
+        is_castle: true,
+        data: MoveUnion { castle: x },
+        promotion: None,
+    }));
+    all_moves
+}
+
+/// the side that mated `loser`
+pub(crate) fn winner(loser: Color) -> GameResult {
+    match loser {
+        Color::White => GameResult::BlackWin,
+        Color::Black => GameResult::WhiteWin,
+    }
+}
+
+pub(crate) fn only_kings_left(state: &State) -> bool {
+    state.board.iter().all(|row| {
+        row.iter()
+            .all(|&piece_id| piece_id == 0 || piece_id.abs() == KING_ID)
+    })
+}
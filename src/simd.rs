@@ -0,0 +1,243 @@
+//! Vectorized evaluation kernels. Each kernel picks an AVX2 version at run
+//! time when the CPU has it and falls back to the scalar loop otherwise;
+//! both give the same results.
+
+#[cfg(target_arch = "x86_64")]
+use std::arch::x86_64::*;
+
+/// `acc[i] += weights[i]` over the shorter of the two slices, wrapping on
+/// overflow
+pub fn add_i16s(acc: &mut [i32], weights: &[i16]) {
+    #[cfg(target_arch = "x86_64")]
+    {
+        if is_x86_feature_detected!("avx2") {
+            // SAFETY: the CPU supports AVX2
+            unsafe { add_i16s_avx2(acc, weights) };
+            return;
+        }
+    }
+    add_i16s_scalar(acc, weights);
+}
+
+/// Sum of `acc[i].clamp(0, max) * weights[i]` over the shorter of the two
+/// slices (the clipped ReLU output layer of the network). `max` times any
+/// i16 must fit in an i32; a negative `max` clips everything to 0.
+pub fn clipped_dot(acc: &[i32], weights: &[i16], max: i32) -> i64 {
+    let max = max.max(0);
+    #[cfg(target_arch = "x86_64")]
+    {
+        if is_x86_feature_detected!("avx2") {
+            // SAFETY: the CPU supports AVX2
+            return unsafe { clipped_dot_avx2(acc, weights, max) };
+        }
+    }
+    clipped_dot_scalar(acc, weights, max)
+}
+
+/// Length of a piece-square table: 64 squares for each piece id from -6
+/// to 6, the empty square (id 0) included
+pub const PST_SIZE: usize = 13 * 64;
+
+/// Sum of `pst[(piece + 6) * 64 + square]` over the board, squares
+/// numbered row by row from a8, wrapping on overflow. Ids outside -6..=6
+/// count as empty squares.
+pub fn pst_sum(board: &[[isize; 8]; 8], pst: &[i32; PST_SIZE]) -> i32 {
+    #[cfg(target_arch = "x86_64")]
+    {
+        if is_x86_feature_detected!("avx2") {
+            // SAFETY: the CPU supports AVX2
+            return unsafe { pst_sum_avx2(board, pst) };
+        }
+    }
+    pst_sum_scalar(board, pst)
+}
+
+// HELPER FUNCTIONS
+// ---------------------------------------------------------
+// ---------------------------------------------------------
+
+fn add_i16s_scalar(acc: &mut [i32], weights: &[i16]) {
+    for (acc, &w) in acc.iter_mut().zip(weights.iter()) {
+        *acc = acc.wrapping_add(w as i32);
+    }
+}
+
+fn clipped_dot_scalar(acc: &[i32], weights: &[i16], max: i32) -> i64 {
+    acc.iter()
+        .zip(weights.iter())
+        .map(|(&acc, &w)| acc.clamp(0, max) as i64 * w as i64)
+        .sum()
+}
+
+fn pst_sum_scalar(board: &[[isize; 8]; 8], pst: &[i32; PST_SIZE]) -> i32 {
+    let mut sum = 0i32;
+    for (row, pieces) in board.iter().enumerate() {
+        for (file, &piece) in pieces.iter().enumerate() {
+            let piece = if (-6..=6).contains(&piece) { piece } else { 0 };
+            sum = sum.wrapping_add(pst[(piece + 6) as usize * 64 + row * 8 + file]);
+        }
+    }
+    sum
+}
+
+/// 4 squares at a time: blank out the invalid ids, then gather the table
+/// entries at `(piece + 6) * 64 + square`
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2")]
+unsafe fn pst_sum_avx2(board: &[[isize; 8]; 8], pst: &[i32; PST_SIZE]) -> i32 {
+    let pieces = board.as_ptr() as *const __m256i;
+    let below = _mm256_set1_epi64x(-7);
+    let above = _mm256_set1_epi64x(7);
+    let step = _mm256_set1_epi64x(4);
+    let mut squares = _mm256_set_epi64x(6 * 64 + 3, 6 * 64 + 2, 6 * 64 + 1, 6 * 64);
+    let mut sum = _mm_setzero_si128();
+    for i in 0..16 {
+        let ids = _mm256_loadu_si256(pieces.add(i));
+        let valid = _mm256_and_si256(_mm256_cmpgt_epi64(ids, below), _mm256_cmpgt_epi64(above, ids));
+        let index = _mm256_add_epi64(_mm256_slli_epi64(_mm256_and_si256(ids, valid), 6), squares);
+        sum = _mm_add_epi32(sum, _mm256_i64gather_epi32(pst.as_ptr(), index, 4));
+        squares = _mm256_add_epi64(squares, step);
+    }
+    let mut lanes_sum = [0i32; 4];
+    _mm_storeu_si128(lanes_sum.as_mut_ptr() as *mut __m128i, sum);
+    lanes_sum.iter().fold(0i32, |sum, &lane| sum.wrapping_add(lane))
+}
+
+/// 8 lanes at a time, the remainder with the scalar loop
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2")]
+unsafe fn add_i16s_avx2(acc: &mut [i32], weights: &[i16]) {
+    let len = acc.len().min(weights.len());
+    let lanes = len - len % 8;
+    for i in (0..lanes).step_by(8) {
+        let w = _mm256_cvtepi16_epi32(_mm_loadu_si128(weights.as_ptr().add(i) as *const __m128i));
+        let a = _mm256_loadu_si256(acc.as_ptr().add(i) as *const __m256i);
+        _mm256_storeu_si256(acc.as_mut_ptr().add(i) as *mut __m256i, _mm256_add_epi32(a, w));
+    }
+    add_i16s_scalar(&mut acc[lanes..len], &weights[lanes..len]);
+}
+
+/// 8 lanes at a time: clamp and multiply in i32, then widen the products
+/// to i64 before summing so that long layers cannot overflow
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2")]
+unsafe fn clipped_dot_avx2(acc: &[i32], weights: &[i16], max: i32) -> i64 {
+    let len = acc.len().min(weights.len());
+    let lanes = len - len % 8;
+    let zero = _mm256_setzero_si256();
+    let max_v = _mm256_set1_epi32(max);
+    let mut sum = _mm256_setzero_si256();
+    for i in (0..lanes).step_by(8) {
+        let a = _mm256_loadu_si256(acc.as_ptr().add(i) as *const __m256i);
+        let a = _mm256_min_epi32(_mm256_max_epi32(a, zero), max_v);
+        let w = _mm256_cvtepi16_epi32(_mm_loadu_si128(weights.as_ptr().add(i) as *const __m128i));
+        let product = _mm256_mullo_epi32(a, w);
+        sum = _mm256_add_epi64(sum, _mm256_cvtepi32_epi64(_mm256_castsi256_si128(product)));
+        sum = _mm256_add_epi64(sum, _mm256_cvtepi32_epi64(_mm256_extracti128_si256(product, 1)));
+    }
+    let mut lanes_sum = [0i64; 4];
+    _mm256_storeu_si256(lanes_sum.as_mut_ptr() as *mut __m256i, sum);
+    lanes_sum.iter().sum::<i64>() + clipped_dot_scalar(&acc[lanes..len], &weights[lanes..len], max)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::Rng;
+
+    #[test]
+    fn avx2_and_scalar_paths_agree() {
+        #[cfg(target_arch = "x86_64")]
+        {
+            if !is_x86_feature_detected!("avx2") {
+                return;
+            }
+            let mut rng = Rng::new(7);
+            for len in (1..64).step_by(2) {
+                let mut acc: Vec<i32> = (0..len).map(|_| rng.next_u64() as i32).collect();
+                let weights: Vec<i16> = (0..len).map(|_| rng.next_u64() as i16).collect();
+                let max = rng.below(70_000) as i32 - 4_000;
+
+                let dot = clipped_dot_scalar(&acc, &weights, max.max(0));
+                assert_eq!(unsafe { clipped_dot_avx2(&acc, &weights, max.max(0)) }, dot);
+                assert_eq!(clipped_dot(&acc, &weights, max), dot);
+
+                let mut scalar_acc = acc.clone();
+                add_i16s_scalar(&mut scalar_acc, &weights);
+                unsafe { add_i16s_avx2(&mut acc, &weights) };
+                assert_eq!(acc, scalar_acc);
+            }
+        }
+    }
+
+    #[test]
+    fn avx2_tails_match_the_scalar_loop() {
+        #[cfg(target_arch = "x86_64")]
+        {
+            if !is_x86_feature_detected!("avx2") {
+                return;
+            }
+            let mut rng = Rng::new(11);
+            for len in (1..=40).filter(|len| len % 8 != 0) {
+                let acc: Vec<i32> = (0..len).map(|_| rng.below(2_000) as i32 - 500).collect();
+                let weights: Vec<i16> = (0..len).map(|_| rng.next_u64() as i16).collect();
+                for tail in len - len % 8..len {
+                    let mut only_tail = vec![0; len];
+                    only_tail[tail] = acc[tail];
+                    assert_eq!(
+                        unsafe { clipped_dot_avx2(&only_tail, &weights, 1_000) },
+                        clipped_dot_scalar(&only_tail, &weights, 1_000)
+                    );
+                }
+
+                // a longer accumulator only takes the first `len` weights
+                let mut avx2_acc = acc.clone();
+                avx2_acc.push(3);
+                let mut scalar_acc = avx2_acc.clone();
+                unsafe { add_i16s_avx2(&mut avx2_acc, &weights) };
+                add_i16s_scalar(&mut scalar_acc, &weights);
+                assert_eq!(avx2_acc, scalar_acc);
+                assert_eq!(avx2_acc[len], 3);
+            }
+        }
+    }
+
+    #[test]
+    fn pst_sum_paths_agree() {
+        let mut rng = Rng::new(13);
+        let mut pst = [0i32; PST_SIZE];
+        for entry in pst.iter_mut() {
+            *entry = rng.next_u64() as i32;
+        }
+        for _ in 0..200 {
+            let mut board = [[0isize; 8]; 8];
+            for square in board.iter_mut().flat_map(|row| row.iter_mut()) {
+                *square = match rng.below(4) {
+                    0 => 0,
+                    1 => rng.below(13) as isize - 6,
+                    2 => rng.below(3) as isize * 2 - 2,
+                    _ => rng.next_u64() as isize,
+                };
+            }
+            let sum = pst_sum_scalar(&board, &pst);
+            assert_eq!(pst_sum(&board, &pst), sum);
+            #[cfg(target_arch = "x86_64")]
+            {
+                if is_x86_feature_detected!("avx2") {
+                    assert_eq!(unsafe { pst_sum_avx2(&board, &pst) }, sum);
+                }
+            }
+        }
+
+        let mut board = [[0isize; 8]; 8];
+        board[0][4] = -1;
+        board[7][4] = 1;
+        board[6][0] = 7;
+        let mut pst = [0i32; PST_SIZE];
+        pst[5 * 64 + 4] = 10;
+        pst[7 * 64 + 60] = 20;
+        pst[6 * 64 + 48] = 300;
+        pst[6 * 64 + 1] = 4_000;
+        assert_eq!(pst_sum_scalar(&board, &pst), 4_330);
+    }
+}
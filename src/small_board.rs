@@ -0,0 +1,583 @@
+use crate::rules::Rules;
+use crate::selfplay::all_moves;
+use crate::{
+    get_other_player, next_state, Board, Color, MoveStruct, State, BISHOP_ID, EMPTY_SQUARE_ID,
+    KING_ID, KNIGHT_ID, PAWN_ID, QUEEN_ID, ROOK_ID,
+};
+
+//
+// Constants
+//
+const LOS_ALAMOS_BOARD: [[isize; 6]; 6] = [
+    [-3, -5, -2, -1, -5, -3],
+    [-6, -6, -6, -6, -6, -6],
+    [0, 0, 0, 0, 0, 0],
+    [0, 0, 0, 0, 0, 0],
+    [6, 6, 6, 6, 6, 6],
+    [3, 5, 2, 1, 5, 3],
+];
+const GARDNER_BOARD: [[isize; 5]; 5] = [
+    [-3, -5, -4, -2, -1],
+    [-6, -6, -6, -6, -6],
+    [0, 0, 0, 0, 0],
+    [6, 6, 6, 6, 6],
+    [3, 5, 4, 2, 1],
+];
+const KNIGHT_STEPS: [(isize, isize); 8] = [
+    (-2, -1),
+    (-2, 1),
+    (-1, -2),
+    (-1, 2),
+    (1, -2),
+    (1, 2),
+    (2, -1),
+    (2, 1),
+];
+const KING_STEPS: [(isize, isize); 8] = [
+    (-1, -1),
+    (-1, 0),
+    (-1, 1),
+    (0, -1),
+    (0, 1),
+    (1, -1),
+    (1, 0),
+    (1, 1),
+];
+const ROOK_STEPS: [(isize, isize); 4] = [(-1, 0), (1, 0), (0, -1), (0, 1)];
+const BISHOP_STEPS: [(isize, isize); 4] = [(-1, -1), (-1, 1), (1, -1), (1, 1)];
+
+//
+// Structs
+//
+/// Rules of a reduced variant: no castling and no en passant, like the
+/// classic small-board variants
+#[derive(Debug, Clone, PartialEq)]
+pub struct SmallVariant {
+    pub name: String,
+    pub width: usize,
+    pub height: usize,
+    pub pawn_double_step: bool,
+    /// piece ids a pawn may promote to (positive)
+    pub promotions: Vec<isize>,
+}
+
+impl SmallVariant {
+    /// 6x6 without bishops, pawns promoting to a queen, rook or knight
+    pub fn los_alamos() -> Self {
+        SmallVariant {
+            name: "los_alamos".to_string(),
+            width: 6,
+            height: 6,
+            pawn_double_step: false,
+            promotions: vec![QUEEN_ID, ROOK_ID, KNIGHT_ID],
+        }
+    }
+
+    /// 5x5 with one piece of each kind
+    pub fn gardner() -> Self {
+        SmallVariant {
+            name: "gardner".to_string(),
+            width: 5,
+            height: 5,
+            pawn_double_step: false,
+            promotions: vec![QUEEN_ID, ROOK_ID, BISHOP_ID, KNIGHT_ID],
+        }
+    }
+}
+
+/// Rules of a small variant for the 8x8 `State`, the variant's board being
+/// its top-left corner (see `SmallState::to_state`): moves leaving the corner
+/// and castles are dropped, and black pawns promote on the corner's last row
+#[derive(Debug, Clone)]
+pub struct SmallBoard {
+    name: &'static str,
+    variant: SmallVariant,
+}
+
+/// A move on a small board; `promotion` is the positive piece id
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct SmallMove {
+    pub from: (usize, usize),
+    pub to: (usize, usize),
+    pub promotion: Option<isize>,
+}
+
+/// Position of a reduced variant with a runtime board size. Row 0 is the last
+/// rank and white pawns move towards it, as on the 8x8 board.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SmallState {
+    pub variant: SmallVariant,
+    pub board: Vec<Vec<isize>>,
+    pub current_player: Color,
+}
+
+impl SmallState {
+    /// Initial position of a named variant: "los_alamos" (6x6, no bishops)
+    /// or "gardner" (5x5)
+    pub fn new_variant(name: &str) -> Result<Self, String> {
+        let (variant, board): (SmallVariant, Vec<Vec<isize>>) = match name {
+            "los_alamos" => (
+                SmallVariant::los_alamos(),
+                LOS_ALAMOS_BOARD.iter().map(|row| row.to_vec()).collect(),
+            ),
+            "gardner" => (
+                SmallVariant::gardner(),
+                GARDNER_BOARD.iter().map(|row| row.to_vec()).collect(),
+            ),
+            _ => return Err(format!("unknown small variant '{}'", name)),
+        };
+        Ok(SmallState {
+            variant,
+            board,
+            current_player: Color::White,
+        })
+    }
+
+    /// Position with a custom board of the same variant
+    pub fn with_board(
+        &self,
+        board: Vec<Vec<isize>>,
+        current_player: Color,
+    ) -> Result<Self, String> {
+        if board.len() != self.variant.height
+            || board.iter().any(|row| row.len() != self.variant.width)
+        {
+            return Err(format!(
+                "the board must be {}x{}",
+                self.variant.width, self.variant.height
+            ));
+        }
+        if board.iter().flatten().any(|piece| piece.abs() > PAWN_ID) {
+            return Err("invalid piece id on the board".to_string());
+        }
+        Ok(SmallState {
+            variant: self.variant.clone(),
+            board,
+            current_player,
+        })
+    }
+
+    /// The position in the top-left corner of an 8x8 state, for the rules
+    /// of `SmallBoard`
+    pub fn to_state(&self) -> State {
+        let mut board: Board = [[EMPTY_SQUARE_ID; 8]; 8];
+        for (row, pieces) in self.board.iter().enumerate() {
+            board[row][..pieces.len()].copy_from_slice(pieces);
+        }
+        State::new(board, self.current_player, false, false, false, false)
+    }
+
+    pub fn legal_moves(&self) -> Vec<SmallMove> {
+        let player = self.current_player;
+        self
+            .pseudo_legal_moves(player)
+            .into_iter()
+            .filter(|&_move| !self.make_move(_move).is_checked(player))
+            .collect()
+    }
+
+    /// Play a move without checking its legality; the side to move changes
+    pub fn make_move(&self, _move: SmallMove) -> SmallState {
+        let mut new_state = self.clone();
+        let piece = self.board[_move.from.0][_move.from.1];
+        new_state.board[_move.from.0][_move.from.1] = EMPTY_SQUARE_ID;
+        new_state.board[_move.to.0][_move.to.1] = match _move.promotion {
+            Some(promotion) => promotion * piece.signum(),
+            None => piece,
+        };
+        new_state.current_player = get_other_player(self.current_player);
+        new_state
+    }
+
+    pub fn is_checked(&self, player: Color) -> bool {
+        let king = player.to_int() * KING_ID;
+        for row in 0..self.variant.height {
+            for col in 0..self.variant.width {
+                if self.board[row][col] == king {
+                    return self.is_attacked((row, col), get_other_player(player));
+                }
+            }
+        }
+        // no king: nothing to protect
+        false
+    }
+
+    /// "checkmate", "stalemate" or "insufficient_material" when the game is
+    /// over, None otherwise
+    pub fn outcome(&self) -> Option<&'static str> {
+        if self
+            .board
+            .iter()
+            .flatten()
+            .all(|&piece| piece.abs() == KING_ID || piece == EMPTY_SQUARE_ID)
+        {
+            return Some("insufficient_material");
+        }
+        if !self.legal_moves().is_empty() {
+            return None;
+        }
+        if self.is_checked(self.current_player) {
+            return Some("checkmate");
+        }
+        Some("stalemate")
+    }
+
+    /// Number of leaf nodes of the legal move tree, to validate the generator
+    pub fn perft(&self, depth: u32) -> u64 {
+        if depth == 0 {
+            return 1;
+        }
+        let moves = self.legal_moves();
+        if depth == 1 {
+            return moves.len() as u64;
+        }
+        moves
+            .iter()
+            .map(|&_move| self.make_move(_move).perft(depth - 1))
+            .sum()
+    }
+
+    /// Coordinate notation with files from 'a' and ranks from 1 ("b2b3", "a5a6q")
+    pub fn move_to_string(&self, _move: SmallMove) -> String {
+        let mut text = format!(
+            "{}{}",
+            self.square_to_string(_move.from),
+            self.square_to_string(_move.to)
+        );
+        if let Some(promotion) = _move.promotion {
+            text.push(promotion_char(promotion));
+        }
+        text
+    }
+
+    /// The legal move written `text` (see `move_to_string`)
+    pub fn parse_move(&self, text: &str) -> Result<SmallMove, String> {
+        self.legal_moves()
+            .into_iter()
+            .find(|&_move| self.move_to_string(_move) == text)
+            .ok_or(format!("Illegal move '{}'", text))
+    }
+
+    fn square_to_string(&self, square: (usize, usize)) -> String {
+        format!(
+            "{}{}",
+            (b'a' + square.1 as u8) as char,
+            self.variant.height - square.0
+        )
+    }
+
+    fn pseudo_legal_moves(&self, player: Color) -> Vec<SmallMove> {
+        let mut moves: Vec<SmallMove> = vec![];
+        let sign = player.to_int();
+        for row in 0..self.variant.height {
+            for col in 0..self.variant.width {
+                let piece = self.board[row][col];
+                if piece * sign <= 0 {
+                    continue;
+                }
+                let from = (row, col);
+                let targets = match piece.abs() {
+                    KING_ID => self.step_targets(from, &KING_STEPS, player),
+                    QUEEN_ID => {
+                        let mut targets = self.slide_targets(from, &ROOK_STEPS, player);
+                        targets.extend(self.slide_targets(from, &BISHOP_STEPS, player));
+                        targets
+                    }
+                    ROOK_ID => self.slide_targets(from, &ROOK_STEPS, player),
+                    BISHOP_ID => self.slide_targets(from, &BISHOP_STEPS, player),
+                    KNIGHT_ID => self.step_targets(from, &KNIGHT_STEPS, player),
+                    _ => self.pawn_targets(from, player),
+                };
+                let last_rank = match player {
+                    Color::White => 0,
+                    Color::Black => self.variant.height - 1,
+                };
+                for to in targets {
+                    if piece.abs() == PAWN_ID && to.0 == last_rank {
+                        for &promotion in self.variant.promotions.iter() {
+                            moves.push(SmallMove {
+                                from,
+                                to,
+                                promotion: Some(promotion),
+                            });
+                        }
+                    } else {
+                        moves.push(SmallMove {
+                            from,
+                            to,
+                            promotion: None,
+                        });
+                    }
+                }
+            }
+        }
+        moves
+    }
+
+    fn step_targets(
+        &self,
+        from: (usize, usize),
+        steps: &[(isize, isize)],
+        player: Color,
+    ) -> Vec<(usize, usize)> {
+        steps
+            .iter()
+            .filter_map(|&step| self.offset(from, step))
+            .filter(|&to| self.board[to.0][to.1] * player.to_int() <= 0)
+            .collect()
+    }
+
+    fn slide_targets(
+        &self,
+        from: (usize, usize),
+        steps: &[(isize, isize)],
+        player: Color,
+    ) -> Vec<(usize, usize)> {
+        let mut targets: Vec<(usize, usize)> = vec![];
+        for &step in steps.iter() {
+            let mut square = from;
+            while let Some(to) = self.offset(square, step) {
+                let piece = self.board[to.0][to.1];
+                if piece * player.to_int() > 0 {
+                    break;
+                }
+                targets.push(to);
+                if piece != EMPTY_SQUARE_ID {
+                    break;
+                }
+                square = to;
+            }
+        }
+        targets
+    }
+
+    fn pawn_targets(&self, from: (usize, usize), player: Color) -> Vec<(usize, usize)> {
+        let mut targets: Vec<(usize, usize)> = vec![];
+        let (forward, start_row) = match player {
+            Color::White => (-1, self.variant.height - 2),
+            Color::Black => (1, 1),
+        };
+        if let Some(one) = self.offset(from, (forward, 0)) {
+            if self.board[one.0][one.1] == EMPTY_SQUARE_ID {
+                targets.push(one);
+                if self.variant.pawn_double_step && from.0 == start_row {
+                    if let Some(two) = self.offset(one, (forward, 0)) {
+                        if self.board[two.0][two.1] == EMPTY_SQUARE_ID {
+                            targets.push(two);
+                        }
+                    }
+                }
+            }
+        }
+        for &side in [-1, 1].iter() {
+            if let Some(to) = self.offset(from, (forward, side)) {
+                if self.board[to.0][to.1] * player.to_int() < 0 {
+                    targets.push(to);
+                }
+            }
+        }
+        targets
+    }
+
+    /// is `square` attacked by a piece of `by`
+    fn is_attacked(&self, square: (usize, usize), by: Color) -> bool {
+        let sign = by.to_int();
+        let piece_at =
+            |step: (isize, isize)| self.offset(square, step).map(|at| self.board[at.0][at.1]);
+        if KNIGHT_STEPS
+            .iter()
+            .any(|&step| piece_at(step) == Some(sign * KNIGHT_ID))
+        {
+            return true;
+        }
+        if KING_STEPS
+            .iter()
+            .any(|&step| piece_at(step) == Some(sign * KING_ID))
+        {
+            return true;
+        }
+        // a pawn attacks forward, so it stands one row behind the square
+        let pawn_row = match by {
+            Color::White => 1,
+            Color::Black => -1,
+        };
+        if [-1, 1]
+            .iter()
+            .any(|&side| piece_at((pawn_row, side)) == Some(sign * PAWN_ID))
+        {
+            return true;
+        }
+        let sliders = [(&ROOK_STEPS, ROOK_ID), (&BISHOP_STEPS, BISHOP_ID)];
+        for (steps, slider_id) in sliders.iter() {
+            for &step in steps.iter() {
+                let mut at = square;
+                while let Some(next) = self.offset(at, step) {
+                    let piece = self.board[next.0][next.1];
+                    if piece == sign * slider_id || piece == sign * QUEEN_ID {
+                        return true;
+                    }
+                    if piece != EMPTY_SQUARE_ID {
+                        break;
+                    }
+                    at = next;
+                }
+            }
+        }
+        false
+    }
+
+    fn offset(&self, square: (usize, usize), step: (isize, isize)) -> Option<(usize, usize)> {
+        let row = square.0 as isize + step.0;
+        let col = square.1 as isize + step.1;
+        if row < 0
+            || col < 0
+            || row >= self.variant.height as isize
+            || col >= self.variant.width as isize
+        {
+            return None;
+        }
+        Some((row as usize, col as usize))
+    }
+}
+
+impl SmallBoard {
+    pub fn los_alamos() -> Self {
+        SmallBoard {
+            name: "los_alamos",
+            variant: SmallVariant::los_alamos(),
+        }
+    }
+
+    pub fn gardner() -> Self {
+        SmallBoard {
+            name: "gardner",
+            variant: SmallVariant::gardner(),
+        }
+    }
+
+    fn last_rank(&self, player: Color) -> isize {
+        match player {
+            Color::White => 0,
+            Color::Black => self.variant.height as isize - 1,
+        }
+    }
+}
+
+impl Rules for SmallBoard {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn legal_moves(&self, state: &State, player: Color) -> Vec<MoveStruct> {
+        let mut moves: Vec<MoveStruct> = vec![];
+        for _move in all_moves(state, player) {
+            if _move.is_castle {
+                continue;
+            }
+            let (from, to) = unsafe { _move.data.normal_move };
+            if to.0 >= self.variant.height as isize || to.1 >= self.variant.width as isize {
+                continue;
+            }
+            let pawn = state.board[from.0 as usize][from.1 as usize].abs() == PAWN_ID;
+            if pawn && (to.0 - from.0).abs() == 2 && !self.variant.pawn_double_step {
+                continue;
+            }
+            if !pawn || to.0 != self.last_rank(player) {
+                moves.push(_move);
+            } else if _move.promotion.is_none() {
+                // the 8x8 generator does not promote on the corner's last row
+                moves.extend(self.variant.promotions.iter().map(|&piece_id| MoveStruct {
+                    promotion: Some(piece_id),
+                    .._move.clone()
+                }));
+            } else if self.variant.promotions.contains(&_move.promotion.unwrap_or(QUEEN_ID)) {
+                moves.push(_move);
+            }
+        }
+        moves
+    }
+
+    fn make_move(&self, state: &State, player: Color, _move: MoveStruct) -> State {
+        let mut new_state = next_state(state, player, _move.clone()).0;
+        if !_move.is_castle {
+            let (from, to) = unsafe { _move.data.normal_move };
+            let pawn = state.board[from.0 as usize][from.1 as usize].abs() == PAWN_ID;
+            if pawn && to.0 == self.last_rank(player) {
+                new_state.board[to.0 as usize][to.1 as usize] =
+                    _move.promotion.unwrap_or(QUEEN_ID) * player.to_int();
+            }
+        }
+        new_state
+    }
+}
+
+// HELPER FUNCTIONS
+// ---------------------------------------------------------
+// ---------------------------------------------------------
+
+fn promotion_char(piece_id: isize) -> char {
+    match piece_id {
+        QUEEN_ID => 'q',
+        ROOK_ID => 'r',
+        BISHOP_ID => 'b',
+        _ => 'n',
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn perft_of_the_initial_positions() {
+        let los_alamos = SmallState::new_variant("los_alamos").unwrap();
+        let counts: Vec<u64> = (1..=4).map(|depth| los_alamos.perft(depth)).collect();
+        assert_eq!(counts, vec![10, 100, 1212, 14332]);
+        let gardner = SmallState::new_variant("gardner").unwrap();
+        let counts: Vec<u64> = (1..=4).map(|depth| gardner.perft(depth)).collect();
+        assert_eq!(counts, vec![7, 53, 506, 4775]);
+    }
+
+    // pawns step once and promote to the pieces of the variant; a pinned
+    // piece and the king keep out of check
+    #[test]
+    fn promotions_steps_and_pins() {
+        let los_alamos = SmallState::new_variant("los_alamos").unwrap();
+        let state = los_alamos
+            .with_board(
+                vec![
+                    vec![0, 0, 0, 0, 0, -1],
+                    vec![6, 0, 0, 0, 0, 0],
+                    vec![0, 0, 0, 0, 0, 0],
+                    vec![0, 0, 0, 0, 0, 0],
+                    vec![0, 0, 0, 0, 0, 6],
+                    vec![0, 1, 0, 0, 0, 0],
+                ],
+                Color::White,
+            )
+            .unwrap();
+        let moves: Vec<String> = state.legal_moves().iter().map(|&m| state.move_to_string(m)).collect();
+        for _move in ["a5a6q", "a5a6r", "a5a6n", "f2f3"].iter() {
+            assert!(moves.contains(&_move.to_string()), "{} not in {:?}", _move, moves);
+        }
+        assert!(!moves.contains(&"f2f4".to_string()));
+        assert!(!moves.contains(&"a5a6b".to_string()));
+        assert_eq!(moves.len(), 3 + 1 + 5);
+
+        let gardner = SmallState::new_variant("gardner").unwrap();
+        let state = gardner
+            .with_board(
+                vec![
+                    vec![0, 0, 0, 0, -3],
+                    vec![0, 0, 0, 0, 0],
+                    vec![0, 0, 0, 0, 0],
+                    vec![0, 0, 0, 0, 5],
+                    vec![0, 0, 0, 0, 1],
+                ],
+                Color::White,
+            )
+            .unwrap();
+        let moves: Vec<String> = state.legal_moves().iter().map(|&m| state.move_to_string(m)).collect();
+        assert_eq!(moves, vec!["e1d2", "e1d1"]);
+    }
+}
@@ -0,0 +1,210 @@
+use std::fmt::Write;
+
+use crate::render::{file_label, rank_label, RenderOptions};
+use crate::{Board, Color, BISHOP_ID, KING_ID, KNIGHT_ID, PAWN_ID, QUEEN_ID, ROOK_ID};
+
+//
+// Constants
+//
+const SQUARE_SIZE: usize = 45;
+const LIGHT_SQUARE_COLOR: &str = "#f0d9b5";
+const DARK_SQUARE_COLOR: &str = "#b58863";
+/// overlay of the squares controlled by white and by black
+const WHITE_CONTROL_COLOR: &str = "#2b6cb0";
+const BLACK_CONTROL_COLOR: &str = "#c53030";
+/// opacity of the overlay on the most controlled square
+const MAX_OVERLAY_OPACITY: f64 = 0.6;
+/// width of the label margins when labels are drawn on all four sides
+const LABEL_MARGIN: usize = 16;
+/// height of the strip under the board holding the caption
+const CAPTION_HEIGHT: usize = 20;
+
+/// SVG image of `board` (rank 8 at the top unless `options` flip it), with
+/// file and rank labels and the metadata of `options` under the board.
+/// With a `heatmap` (see `tactics::control_heatmap`), each square is tinted
+/// blue when white controls it and red when black does, more opaque the more
+/// it is controlled, and its net control is written in its corner.
+pub fn board_to_svg(
+    board: &Board,
+    heatmap: Option<&[[i32; 8]; 8]>,
+    options: &RenderOptions,
+) -> String {
+    let size = 8 * SQUARE_SIZE;
+    let margin = if options.labels_all_sides {
+        LABEL_MARGIN
+    } else {
+        0
+    };
+    let caption = options.caption();
+    let width = size + 2 * margin;
+    let height = width + if caption.is_some() { CAPTION_HEIGHT } else { 0 };
+    let mut svg = String::new();
+    write!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{0}" height="{1}" viewBox="0 0 {0} {1}">"#,
+        width, height
+    )
+    .unwrap();
+
+    let max_control = heatmap.map_or(0, |heatmap| {
+        heatmap
+            .iter()
+            .flat_map(|row| row.iter())
+            .map(|control| control.abs())
+            .max()
+            .unwrap_or(0)
+    });
+    let (rows, cols) = (options.rows(), options.cols());
+    for (y_index, &row) in rows.iter().enumerate() {
+        for (x_index, &col) in cols.iter().enumerate() {
+            let piece_id = board[row][col];
+            let (x, y) = (
+                margin + x_index * SQUARE_SIZE,
+                margin + y_index * SQUARE_SIZE,
+            );
+            let color = if (row + col) % 2 == 0 {
+                LIGHT_SQUARE_COLOR
+            } else {
+                DARK_SQUARE_COLOR
+            };
+            write!(
+                svg,
+                r#"<rect x="{}" y="{}" width="{2}" height="{2}" fill="{3}"/>"#,
+                x, y, SQUARE_SIZE, color
+            )
+            .unwrap();
+
+            if let Some(heatmap) = heatmap {
+                let control = heatmap[row][col];
+                if control != 0 {
+                    let color = if control > 0 {
+                        WHITE_CONTROL_COLOR
+                    } else {
+                        BLACK_CONTROL_COLOR
+                    };
+                    let opacity = MAX_OVERLAY_OPACITY * control.abs() as f64 / max_control as f64;
+                    write!(
+                        svg,
+                        r#"<rect x="{}" y="{}" width="{2}" height="{2}" fill="{3}" fill-opacity="{4:.2}"/>"#,
+                        x, y, SQUARE_SIZE, color, opacity
+                    )
+                    .unwrap();
+                    write!(
+                        svg,
+                        r#"<text x="{}" y="{}" font-size="10" font-family="sans-serif" fill="black">{:+}</text>"#,
+                        x + 2,
+                        y + 10,
+                        control
+                    )
+                    .unwrap();
+                }
+            }
+
+            if piece_id != 0 {
+                let (fill, stroke) = if piece_id > 0 {
+                    ("#fff", "#000")
+                } else {
+                    ("#000", "#fff")
+                };
+                write!(
+                    svg,
+                    r#"<text x="{}" y="{}" font-size="{}" text-anchor="middle" fill="{}" stroke="{}" stroke-width="0.8">{}</text>"#,
+                    x + SQUARE_SIZE / 2,
+                    y + SQUARE_SIZE * 4 / 5,
+                    SQUARE_SIZE * 4 / 5,
+                    fill,
+                    stroke,
+                    piece_glyph(piece_id)
+                )
+                .unwrap();
+            }
+        }
+    }
+
+    // coordinates
+    for i in 0..8 {
+        if options.labels_all_sides {
+            let center = margin + i * SQUARE_SIZE + SQUARE_SIZE / 2;
+            let (near, far) = (margin / 2, margin + size + margin / 2);
+            for (x, y, label) in [
+                (center, near, file_label(cols[i]).to_string()),
+                (center, far, file_label(cols[i]).to_string()),
+                (near, center, rank_label(rows[i]).to_string()),
+                (far, center, rank_label(rows[i]).to_string()),
+            ]
+            .iter()
+            {
+                write!(
+                    svg,
+                    r#"<text x="{}" y="{}" font-size="11" font-family="sans-serif" text-anchor="middle" dominant-baseline="middle" fill="dimgray">{}</text>"#,
+                    x, y, label
+                )
+                .unwrap();
+            }
+            continue;
+        }
+        write!(
+            svg,
+            r#"<text x="{}" y="{}" font-size="9" font-family="sans-serif" fill="dimgray">{}</text>"#,
+            i * SQUARE_SIZE + SQUARE_SIZE - 8,
+            size - 2,
+            file_label(cols[i])
+        )
+        .unwrap();
+        write!(
+            svg,
+            r#"<text x="{}" y="{}" font-size="9" font-family="sans-serif" fill="dimgray">{}</text>"#,
+            size - 8,
+            i * SQUARE_SIZE + SQUARE_SIZE - 12,
+            rank_label(rows[i])
+        )
+        .unwrap();
+    }
+
+    // metadata
+    if let Some(caption) = caption {
+        let y = width + CAPTION_HEIGHT / 2;
+        let mut x = margin + 4;
+        if let Some(player) = options.side_to_move {
+            let fill = if player == Color::White {
+                "#fff"
+            } else {
+                "#000"
+            };
+            write!(
+                svg,
+                r##"<circle cx="{}" cy="{}" r="5" fill="{}" stroke="#000"/>"##,
+                x + 5,
+                y,
+                fill
+            )
+            .unwrap();
+            x += 16;
+        }
+        write!(
+            svg,
+            r#"<text x="{}" y="{}" font-size="12" font-family="sans-serif" dominant-baseline="middle" fill="black">{}</text>"#,
+            x, y, caption
+        )
+        .unwrap();
+    }
+    svg.push_str("</svg>");
+    svg
+}
+
+// HELPER FUNCTIONS
+// ---------------------------------------------------------
+// ---------------------------------------------------------
+
+/// Solid glyph of the piece type, the color being given by the fill
+fn piece_glyph(piece_id: isize) -> char {
+    match piece_id.abs() {
+        KING_ID => '♚',
+        QUEEN_ID => '♛',
+        ROOK_ID => '♜',
+        BISHOP_ID => '♝',
+        KNIGHT_ID => '♞',
+        PAWN_ID => '♟',
+        _ => '?',
+    }
+}
@@ -0,0 +1,658 @@
+use crate::{
+    get_color, get_other_player, square_is_on_board, Board, Color, Move, Square, BISHOP_ID, KING_ID,
+    KNIGHT_ID, PAWN_ID, QUEEN_ID, ROOK_ID,
+};
+
+//
+// Constants
+//
+const KNIGHT_STEPS: [Square; 8] = [
+    (-2, -1),
+    (-2, 1),
+    (2, -1),
+    (2, 1),
+    (-1, -2),
+    (-1, 2),
+    (1, -2),
+    (1, 2),
+];
+const KING_STEPS: [Square; 8] = [
+    (1, 0),
+    (-1, 0),
+    (0, 1),
+    (0, -1),
+    (1, 1),
+    (1, -1),
+    (-1, 1),
+    (-1, -1),
+];
+const ROOK_STEPS: [Square; 4] = [(-1, 0), (1, 0), (0, -1), (0, 1)];
+const BISHOP_STEPS: [Square; 4] = [(-1, -1), (-1, 1), (1, -1), (1, 1)];
+
+//
+// Structs
+//
+/// A piece of `player` that the opponent can win material against
+#[derive(Debug, Clone)]
+pub struct Threat {
+    pub square: Square,
+    pub piece: isize,
+    pub attackers: Vec<Square>,
+    pub defenders: Vec<Square>,
+    /// material the opponent wins by starting the exchange (centipawns, > 0)
+    pub loss: i32,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum MotifKind {
+    Fork,
+    Skewer,
+    DiscoveredAttack,
+}
+
+impl MotifKind {
+    pub fn to_str(self) -> &'static str {
+        match self {
+            MotifKind::Fork => "fork",
+            MotifKind::Skewer => "skewer",
+            MotifKind::DiscoveredAttack => "discovered_attack",
+        }
+    }
+}
+
+/// A tactical pattern in favour of one player
+#[derive(Debug, Clone)]
+pub struct Motif {
+    pub kind: MotifKind,
+    /// the forking piece, the skewering slider or the unmasked slider
+    pub attacker: Square,
+    /// forked pieces, front and back piece of a skewer, or the discovered target
+    pub targets: Vec<Square>,
+    /// discovered attacks found in a position: own piece that unmasks the
+    /// attack when it moves (None once the attack has been discovered)
+    pub blocker: Option<Square>,
+}
+
+/// Piece ids of the pieces attacking a square, per color
+#[derive(Debug, Default, Clone)]
+pub struct SquareControl {
+    pub white: Vec<isize>,
+    pub black: Vec<isize>,
+}
+
+/// Pieces of `player` (king excluded) that are attacked and insufficiently
+/// defended: the opponent wins material on the square according to SEE.
+/// Sorted by decreasing loss.
+pub fn get_threats(board: &Board, player: Color) -> Vec<Threat> {
+    let other_player = get_other_player(player);
+    let mut threats: Vec<Threat> = vec![];
+    for row in 0..8 {
+        for col in 0..8 {
+            let piece = board[row][col];
+            let square = (row as isize, col as isize);
+            if get_color(piece) != Some(player) || piece.abs() == KING_ID {
+                continue;
+            }
+            let attacker_squares = attackers(board, square, other_player);
+            if attacker_squares.is_empty() {
+                continue;
+            }
+            let loss = see(board, square, other_player);
+            if loss > 0 {
+                threats.push(Threat {
+                    square,
+                    piece,
+                    attackers: attacker_squares,
+                    defenders: attackers(board, square, player),
+                    loss,
+                });
+            }
+        }
+    }
+    threats.sort_by_key(|threat| std::cmp::Reverse(threat.loss));
+    threats
+}
+
+/// Piece ids of the white and black pieces attacking each square (direct
+/// attacks only, a piece behind another one on the same line is not counted)
+pub fn control_map(board: &Board) -> [[SquareControl; 8]; 8] {
+    let mut map: [[SquareControl; 8]; 8] = Default::default();
+    for (row, map_row) in map.iter_mut().enumerate() {
+        for (col, control) in map_row.iter_mut().enumerate() {
+            let square = (row as isize, col as isize);
+            let attacking_pieces = |color: Color| -> Vec<isize> {
+                attackers(board, square, color)
+                    .iter()
+                    .map(|&from| piece_at(board, from))
+                    .collect()
+            };
+            control.white = attacking_pieces(Color::White);
+            control.black = attacking_pieces(Color::Black);
+        }
+    }
+    map
+}
+
+/// Net control of each square: number of white attackers minus number of
+/// black attackers (same counting as `control_map`)
+pub fn control_heatmap(board: &Board) -> [[i32; 8]; 8] {
+    let mut heatmap = [[0; 8]; 8];
+    for (row, map_row) in control_map(board).iter().enumerate() {
+        for (col, control) in map_row.iter().enumerate() {
+            heatmap[row][col] = control.white.len() as i32 - control.black.len() as i32;
+        }
+    }
+    heatmap
+}
+
+/// Update `map`, the control map of `before`, to the position `after` (the
+/// position after the move just played), recomputing only the squares whose
+/// attackers can change: the squares that changed, the squares along each line
+/// from them up to the first piece, and the knight jumps from them.
+/// Returns the recomputed squares.
+pub fn update_control_map(
+    map: &mut [[SquareControl; 8]; 8],
+    before: &Board,
+    after: &Board,
+) -> Vec<Square> {
+    let mut affected = [[false; 8]; 8];
+    for row in 0..8 {
+        for col in 0..8 {
+            if before[row][col] == after[row][col] {
+                continue;
+            }
+            let changed = (row as isize, col as isize);
+            affected[row][col] = true;
+            for step in KNIGHT_STEPS.iter() {
+                let square = (changed.0 + step.0, changed.1 + step.1);
+                if square_is_on_board(square) {
+                    affected[square.0 as usize][square.1 as usize] = true;
+                }
+            }
+            // a piece further along a line is blocked by the first one on
+            // `after`, unless that one changed too (its own lines cover it)
+            for step in KING_STEPS.iter() {
+                let mut square = (changed.0 + step.0, changed.1 + step.1);
+                while square_is_on_board(square) {
+                    affected[square.0 as usize][square.1 as usize] = true;
+                    if piece_at(after, square) != 0 {
+                        break;
+                    }
+                    square = (square.0 + step.0, square.1 + step.1);
+                }
+            }
+        }
+    }
+
+    let mut squares: Vec<Square> = vec![];
+    for row in 0..8 {
+        for col in 0..8 {
+            if !affected[row][col] {
+                continue;
+            }
+            let square = (row as isize, col as isize);
+            let attacking_pieces = |color: Color| -> Vec<isize> {
+                attackers(after, square, color)
+                    .iter()
+                    .map(|&from| piece_at(after, from))
+                    .collect()
+            };
+            map[row][col].white = attacking_pieces(Color::White);
+            map[row][col].black = attacking_pieces(Color::Black);
+            squares.push(square);
+        }
+    }
+    squares
+}
+
+/// Squares of the `color` pieces attacking `square` (whatever stands on it)
+pub fn attackers(board: &Board, square: Square, color: Color) -> Vec<Square> {
+    let sign = color.to_int();
+    let mut squares: Vec<Square> = vec![];
+
+    // pawns: white pawns move towards row 0, so they attack from the row below
+    let pawn_row = square.0 + sign;
+    for pawn_col in [square.1 - 1, square.1 + 1].iter() {
+        let from = (pawn_row, *pawn_col);
+        if square_is_on_board(from) && piece_at(board, from) == PAWN_ID * sign {
+            squares.push(from);
+        }
+    }
+    for step in KNIGHT_STEPS.iter() {
+        let from = (square.0 + step.0, square.1 + step.1);
+        if square_is_on_board(from) && piece_at(board, from) == KNIGHT_ID * sign {
+            squares.push(from);
+        }
+    }
+    for step in KING_STEPS.iter() {
+        let from = (square.0 + step.0, square.1 + step.1);
+        if square_is_on_board(from) && piece_at(board, from) == KING_ID * sign {
+            squares.push(from);
+        }
+    }
+    // sliders: first piece along each ray
+    for (steps, slider) in [(ROOK_STEPS, ROOK_ID), (BISHOP_STEPS, BISHOP_ID)].iter() {
+        for step in steps.iter() {
+            if let Some(from) = first_piece_on_ray(board, square, *step) {
+                let piece = piece_at(board, from);
+                if piece == *slider * sign || piece == QUEEN_ID * sign {
+                    squares.push(from);
+                }
+            }
+        }
+    }
+    squares
+}
+
+/// Squares of the `color` pieces attacking `square` directly or x-raying it
+/// through `color` pieces attacking it along the same line (a rook behind a
+/// rook, a bishop or queen behind a pawn), front pieces first on each line
+pub fn xray_attackers(board: &Board, square: Square, color: Color) -> Vec<Square> {
+    let sign = color.to_int();
+    let mut squares: Vec<Square> = vec![];
+    for step in KNIGHT_STEPS.iter() {
+        let from = (square.0 + step.0, square.1 + step.1);
+        if square_is_on_board(from) && piece_at(board, from) == KNIGHT_ID * sign {
+            squares.push(from);
+        }
+    }
+    for (steps, slider) in [(ROOK_STEPS, ROOK_ID), (BISHOP_STEPS, BISHOP_ID)].iter() {
+        for step in steps.iter() {
+            let mut from = (square.0 + step.0, square.1 + step.1);
+            let mut distance = 1;
+            while square_is_on_board(from) {
+                let piece = piece_at(board, from);
+                if piece != 0 {
+                    // pawns and the king only attack next to them, pawns
+                    // towards the opponent (white pawns from the row below)
+                    let attacks = piece == *slider * sign
+                        || piece == QUEEN_ID * sign
+                        || (distance == 1 && piece == KING_ID * sign)
+                        || (distance == 1
+                            && piece == PAWN_ID * sign
+                            && *slider == BISHOP_ID
+                            && step.0 == sign);
+                    if !attacks {
+                        break;
+                    }
+                    squares.push(from);
+                }
+                from = (from.0 + step.0, from.1 + step.1);
+                distance += 1;
+            }
+        }
+    }
+    squares
+}
+
+/// Attack/defense balance of `player`: over its attacked pieces (king
+/// excluded), defenders minus attackers counted with `xray_attackers`, minus
+/// the same sum for the opponent
+pub fn attack_defense_balance(board: &Board, player: Color) -> i32 {
+    let mut balance = 0;
+    for row in 0..8 {
+        for col in 0..8 {
+            let piece = board[row][col];
+            let owner = match get_color(piece) {
+                Some(owner) if piece.abs() != KING_ID => owner,
+                _ => continue,
+            };
+            let square = (row as isize, col as isize);
+            let attackers = xray_attackers(board, square, get_other_player(owner)).len() as i32;
+            if attackers == 0 {
+                continue;
+            }
+            let defenders = xray_attackers(board, square, owner).len() as i32;
+            let sign = if owner == player { 1 } else { -1 };
+            balance += sign * (defenders - attackers);
+        }
+    }
+    balance
+}
+
+/// Static exchange evaluation: material won by `side` when it starts capturing
+/// on `square` and both sides keep recapturing with their least valuable
+/// attacker while it pays off (x-ray attackers join the exchange as the
+/// pieces in front of them leave). Pins are ignored.
+pub fn see(board: &Board, square: Square, side: Color) -> i32 {
+    let mut board = *board;
+    let mut gain: Vec<i32> = vec![see_value(piece_at(&board, square))];
+    let mut side = side;
+    let mut attacker = least_valuable_attacker(&board, square, side);
+    if attacker.is_none() {
+        return 0;
+    }
+    while let Some(from) = attacker {
+        let d = gain.len();
+        gain.push(see_value(piece_at(&board, from)) - gain[d - 1]);
+        board[square.0 as usize][square.1 as usize] = piece_at(&board, from);
+        board[from.0 as usize][from.1 as usize] = 0;
+        side = get_other_player(side);
+        attacker = least_valuable_attacker(&board, square, side);
+    }
+    // the last entry is speculative (nobody recaptured), unwind from the one before
+    let mut d = gain.len() - 1;
+    while d > 1 {
+        d -= 1;
+        gain[d - 1] = -(-gain[d - 1]).max(gain[d]);
+    }
+    gain[0]
+}
+
+/// Static exchange evaluation of a move: material its side wins (or loses if
+/// negative) once the opponent has recaptured on the target square as long as
+/// it pays off. A quiet move to an attacked square scores the piece it risks.
+pub fn see_move(board: &Board, _move: Move) -> i32 {
+    let (from, to) = _move;
+    let piece = piece_at(board, from);
+    let side = match get_color(piece) {
+        Some(side) => side,
+        None => return 0,
+    };
+    let captured = see_value(piece_at(board, to));
+    let mut board = *board;
+    board[to.0 as usize][to.1 as usize] = piece;
+    board[from.0 as usize][from.1 as usize] = 0;
+    // recapturing is optional, so the opponent never loses by it
+    captured - see(&board, to, get_other_player(side)).max(0)
+}
+
+/// Tactical motifs in favour of `player`:
+/// - forks: a knight or pawn attacking two or more enemy pieces that are each
+///   the king, more valuable than the forking piece or undefended
+/// - skewers: a slider attacking an enemy piece with a less valuable (non pawn)
+///   enemy piece behind it on the same line
+/// - discovered attacks: a slider whose line to an enemy piece (not a pawn)
+///   is only blocked by one of `player`'s pieces
+///
+/// With `last_move` (the move `player` just played, `board` being the position
+/// after it) only the motifs created by that move are returned: forks and
+/// skewers by the moved piece, and attacks discovered by leaving its square.
+pub fn find_motifs(board: &Board, player: Color, last_move: Option<Move>) -> Vec<Motif> {
+    let other_player = get_other_player(player);
+    let mut motifs: Vec<Motif> = vec![];
+    for row in 0..8 {
+        for col in 0..8 {
+            let piece = board[row][col];
+            let square = (row as isize, col as isize);
+            if get_color(piece) != Some(player) {
+                continue;
+            }
+            let moved_piece = match last_move {
+                Some((_, _to)) => _to == square,
+                None => true,
+            };
+
+            // forks
+            if moved_piece && (piece.abs() == KNIGHT_ID || piece.abs() == PAWN_ID) {
+                let targets: Vec<Square> = attacks_from(board, square)
+                    .into_iter()
+                    .filter(|&target| {
+                        let target_piece = piece_at(board, target);
+                        get_color(target_piece) == Some(other_player)
+                            && (target_piece.abs() == KING_ID
+                                || see_value(target_piece) > see_value(piece)
+                                || attackers(board, target, other_player).is_empty())
+                    })
+                    .collect();
+                if targets.len() >= 2 {
+                    motifs.push(Motif {
+                        kind: MotifKind::Fork,
+                        attacker: square,
+                        targets,
+                        blocker: None,
+                    });
+                }
+            }
+
+            // skewers and discovered attacks along the slider's lines
+            for step in slider_steps(piece).iter() {
+                let first = match first_piece_on_ray(board, square, *step) {
+                    Some(first) => first,
+                    None => continue,
+                };
+                let second = first_piece_on_ray(board, first, *step);
+                let first_piece = piece_at(board, first);
+                if get_color(first_piece) == Some(other_player) {
+                    if let Some(second) = second {
+                        let second_piece = piece_at(board, second);
+                        if moved_piece
+                            && get_color(second_piece) == Some(other_player)
+                            && second_piece.abs() != PAWN_ID
+                            && see_value(first_piece) > see_value(second_piece)
+                        {
+                            motifs.push(Motif {
+                                kind: MotifKind::Skewer,
+                                attacker: square,
+                                targets: vec![first, second],
+                                blocker: None,
+                            });
+                        }
+                    }
+                    // attack just discovered: the moved piece left this line
+                    if let Some((_from, _to)) = last_move {
+                        if first_piece.abs() != PAWN_ID
+                            && _to != square
+                            && is_between(square, first, _from)
+                        {
+                            motifs.push(Motif {
+                                kind: MotifKind::DiscoveredAttack,
+                                attacker: square,
+                                targets: vec![first],
+                                blocker: None,
+                            });
+                        }
+                    }
+                } else if last_move.is_none() {
+                    if let Some(second) = second {
+                        let second_piece = piece_at(board, second);
+                        if get_color(second_piece) == Some(other_player) && second_piece.abs() != PAWN_ID {
+                            motifs.push(Motif {
+                                kind: MotifKind::DiscoveredAttack,
+                                attacker: square,
+                                targets: vec![second],
+                                blocker: Some(first),
+                            });
+                        }
+                    }
+                }
+            }
+        }
+    }
+    motifs
+}
+
+// HELPER FUNCTIONS
+// ---------------------------------------------------------
+// ---------------------------------------------------------
+
+/// squares attacked by the piece on `from`
+fn attacks_from(board: &Board, from: Square) -> Vec<Square> {
+    let piece = piece_at(board, from);
+    let steps: &[Square] = match piece.abs() {
+        KNIGHT_ID => &KNIGHT_STEPS,
+        KING_ID => &KING_STEPS,
+        PAWN_ID => {
+            // white pawns move towards row 0
+            let row = from.0 - piece.signum();
+            return [(row, from.1 - 1), (row, from.1 + 1)]
+                .iter()
+                .cloned()
+                .filter(|&square| square_is_on_board(square))
+                .collect();
+        }
+        _ => {
+            let mut squares: Vec<Square> = vec![];
+            for step in slider_steps(piece).iter() {
+                let mut current = (from.0 + step.0, from.1 + step.1);
+                while square_is_on_board(current) {
+                    squares.push(current);
+                    if piece_at(board, current) != 0 {
+                        break;
+                    }
+                    current = (current.0 + step.0, current.1 + step.1);
+                }
+            }
+            return squares;
+        }
+    };
+    steps
+        .iter()
+        .map(|step| (from.0 + step.0, from.1 + step.1))
+        .filter(|&square| square_is_on_board(square))
+        .collect()
+}
+
+fn slider_steps(piece: isize) -> Vec<Square> {
+    match piece.abs() {
+        QUEEN_ID => ROOK_STEPS.iter().chain(BISHOP_STEPS.iter()).cloned().collect(),
+        ROOK_ID => ROOK_STEPS.to_vec(),
+        BISHOP_ID => BISHOP_STEPS.to_vec(),
+        _ => vec![],
+    }
+}
+
+/// `square` lies strictly between `a` and `b` on a rank, file or diagonal
+fn is_between(a: Square, b: Square, square: Square) -> bool {
+    let step = ((b.0 - a.0).signum(), (b.1 - a.1).signum());
+    let mut current = (a.0 + step.0, a.1 + step.1);
+    while current != b && square_is_on_board(current) {
+        if current == square {
+            return true;
+        }
+        current = (current.0 + step.0, current.1 + step.1);
+    }
+    false
+}
+
+/// piece values used by SEE (the king can only capture last)
+fn see_value(piece: isize) -> i32 {
+    match piece.abs() {
+        6 => 100,
+        5 => 300,
+        4 => 300,
+        3 => 500,
+        2 => 900,
+        1 => 20000,
+        _ => 0,
+    }
+}
+
+fn least_valuable_attacker(board: &Board, square: Square, side: Color) -> Option<Square> {
+    let from = attackers(board, square, side)
+        .into_iter()
+        .min_by_key(|&from| see_value(piece_at(board, from)))?;
+    // the king cannot capture a defended piece
+    if piece_at(board, from).abs() == KING_ID
+        && !attackers(board, square, get_other_player(side)).is_empty()
+    {
+        return None;
+    }
+    Some(from)
+}
+
+fn first_piece_on_ray(board: &Board, square: Square, step: Square) -> Option<Square> {
+    let mut current = (square.0 + step.0, square.1 + step.1);
+    while square_is_on_board(current) {
+        if piece_at(board, current) != 0 {
+            return Some(current);
+        }
+        current = (current.0 + step.0, current.1 + step.1);
+    }
+    None
+}
+
+fn piece_at(board: &Board, square: Square) -> isize {
+    board[square.0 as usize][square.1 as usize]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::from_fen;
+
+    fn board(fen: &str) -> Board {
+        from_fen(fen).unwrap().board
+    }
+
+    // cxd6 exd6 Rxd6 wins a knight for a pawn
+    #[test]
+    fn threats() {
+        let board = board("4k3/4p3/3n4/2P5/8/8/8/3RK3 w - - 0 1");
+        let threats = get_threats(&board, Color::Black);
+        assert_eq!(threats.len(), 1);
+        assert_eq!(threats[0].square, (2, 3));
+        assert_eq!(threats[0].piece, -KNIGHT_ID);
+        assert_eq!(threats[0].attackers.len(), 2);
+        assert!(threats[0].attackers.contains(&(3, 2)));
+        assert!(threats[0].attackers.contains(&(7, 3)));
+        assert_eq!(threats[0].defenders, [(1, 4)]);
+        assert_eq!(threats[0].loss, 300);
+        assert!(get_threats(&board, Color::White).is_empty());
+    }
+
+    #[test]
+    fn threats_by_decreasing_loss() {
+        // hanging rook and knight; the pawn defended by the king is safe
+        let board = board("4k3/3p4/1n6/7r/8/8/8/1R1QK2R w - - 0 1");
+        let threats = get_threats(&board, Color::Black);
+        let squares: Vec<Square> = threats.iter().map(|threat| threat.square).collect();
+        assert_eq!(squares, [(3, 7), (2, 1)]);
+        assert_eq!(threats[0].loss, 500);
+        assert_eq!(threats[1].loss, 300);
+    }
+
+    #[test]
+    fn exchanges() {
+        // the rook takes a knight defended by a pawn and is taken back
+        let board = board("4k3/4p3/3n4/8/8/8/8/3RK3 w - - 0 1");
+        assert_eq!(see(&board, (2, 3), Color::White), -200);
+        assert_eq!(see_move(&board, ((7, 3), (2, 3))), -200);
+        assert!(get_threats(&board, Color::Black).is_empty());
+    }
+
+    type MotifFields = (MotifKind, Square, Vec<Square>, Option<Square>);
+
+    fn motifs(board: &Board, player: Color, last_move: Option<Move>) -> Vec<MotifFields> {
+        find_motifs(board, player, last_move)
+            .into_iter()
+            .map(|motif| (motif.kind, motif.attacker, motif.targets, motif.blocker))
+            .collect()
+    }
+
+    #[test]
+    fn forks() {
+        // Nc7+ forks the king and the rook
+        let board = board("r3k3/2N5/8/8/8/8/8/4K3 b - - 0 1");
+        let fork = (MotifKind::Fork, (1, 2), vec![(0, 0), (0, 4)], None);
+        assert_eq!(motifs(&board, Color::White, None), std::slice::from_ref(&fork));
+        assert_eq!(motifs(&board, Color::White, Some(((3, 3), (1, 2)))), [fork]);
+        // not created by the last move
+        assert!(motifs(&board, Color::White, Some(((7, 3), (7, 4)))).is_empty());
+        // a pawn defended by another one is not worth forking
+        let board = self::board("7k/2N5/p7/1p6/8/8/8/4K3 b - - 0 1");
+        assert!(motifs(&board, Color::White, None).is_empty());
+    }
+
+    #[test]
+    fn skewers() {
+        let board = board("8/8/8/8/R3k2q/8/8/4K3 b - - 0 1");
+        let skewer = (MotifKind::Skewer, (4, 0), vec![(4, 4), (4, 7)], None);
+        assert_eq!(motifs(&board, Color::White, None), [skewer]);
+        // the queen in front of the king pins it instead
+        let board = self::board("8/8/8/8/R3q2k/8/8/4K3 w - - 0 1");
+        assert!(motifs(&board, Color::White, None).is_empty());
+    }
+
+    #[test]
+    fn discovered_attacks() {
+        // the knight on e2 masks the rook's attack on the king
+        let board = board("4k3/8/8/8/8/8/4N3/4R1K1 w - - 0 1");
+        let attack = (MotifKind::DiscoveredAttack, (7, 4), vec![(0, 4)], Some((6, 4)));
+        assert_eq!(motifs(&board, Color::White, None), [attack]);
+        // Nc3+ discovers it
+        let board = self::board("4k3/8/8/8/8/2N5/8/4R1K1 b - - 0 1");
+        let attack = (MotifKind::DiscoveredAttack, (7, 4), vec![(0, 4)], None);
+        assert_eq!(motifs(&board, Color::White, Some(((6, 4), (5, 2)))), [attack]);
+    }
+}
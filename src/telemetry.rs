@@ -0,0 +1,148 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::convert_move_union_to_string;
+use crate::json::Json;
+use crate::search::{SearchInfo, SearchStats, EVAL_HISTOGRAM_BUCKET, EVAL_HISTOGRAM_MIN};
+use crate::selfplay::{Game, GameResult};
+use crate::tt::TTStats;
+
+static REPORT_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+fn unix_time_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+/// Write `report` as `<kind>-<unix ms>-<counter>.json` in `dir` (created if needed)
+pub fn write_report(dir: &Path, kind: &str, report: &Json) -> io::Result<PathBuf> {
+    fs::create_dir_all(dir)?;
+    let counter = REPORT_COUNTER.fetch_add(1, Ordering::Relaxed);
+    let path = dir.join(format!("{}-{}-{}.json", kind, unix_time_ms(), counter));
+    fs::write(&path, report.to_string())?;
+    Ok(path)
+}
+
+fn iteration_to_json(info: &SearchInfo) -> Json {
+    Json::object()
+        .with("depth", info.depth)
+        .with("seldepth", info.seldepth)
+        .with("score", info.score)
+        .with("nodes", info.nodes)
+        .with("time_ms", info.time_ms as u64)
+        .with("hashfull", info.hashfull)
+        .with(
+            "pv",
+            info.pv
+                .iter()
+                .map(convert_move_union_to_string)
+                .collect::<Vec<String>>(),
+        )
+}
+
+pub fn tt_stats_to_json(stats: &TTStats) -> Json {
+    Json::object()
+        .with("probes", stats.probes)
+        .with("hits", stats.hits)
+        .with("cutoffs", stats.cutoffs)
+        .with("stores", stats.stores)
+        .with("replacements", stats.replacements)
+        .with("updates", stats.updates)
+        .with("rejections", stats.rejections)
+}
+
+pub fn search_stats_to_json(stats: &SearchStats) -> Json {
+    Json::object()
+        .with("pv_nodes", stats.pv_nodes)
+        .with("cut_nodes", stats.cut_nodes)
+        .with("all_nodes", stats.all_nodes)
+        .with("beta_cutoffs", stats.beta_cutoffs)
+        .with("tt_move_cutoffs", stats.tt_move_cutoffs)
+        .with("capture_cutoffs", stats.capture_cutoffs)
+        .with("quiet_cutoffs", stats.quiet_cutoffs)
+        .with("first_move_cutoffs", stats.first_move_cutoffs)
+        .with("tt_cutoffs", stats.tt_cutoffs)
+        .with("leaf_nodes", stats.leaf_nodes)
+        .with("singular_extensions", stats.singular_extensions)
+        .with("null_move_cutoffs", stats.null_move_cutoffs)
+        .with(
+            "eval_histogram",
+            Json::object()
+                .with("min_cp", EVAL_HISTOGRAM_MIN)
+                .with("bucket_cp", EVAL_HISTOGRAM_BUCKET)
+                .with("counts", stats.eval_histogram.clone()),
+        )
+}
+
+/// Report of one search: every completed iteration plus pruning and TT counters
+pub fn search_report(
+    iterations: &[SearchInfo],
+    stats: &SearchStats,
+    tt_stats: &TTStats,
+    hashfull: u32,
+) -> Json {
+    let last = iterations.last();
+    let time_ms = last.map(|info| info.time_ms as u64).unwrap_or(0);
+    let nodes = last.map(|info| info.nodes).unwrap_or(0);
+    let nps = (nodes * 1000).checked_div(time_ms).unwrap_or(0);
+    Json::object()
+        .with("kind", "search")
+        .with("timestamp_ms", unix_time_ms())
+        .with("depth", last.map(|info| info.depth))
+        .with("score", last.map(|info| info.score))
+        .with("nodes", nodes)
+        .with("time_ms", time_ms)
+        .with("nps", nps)
+        .with(
+            "iterations",
+            iterations.iter().map(iteration_to_json).collect::<Vec<Json>>(),
+        )
+        .with("pruning", search_stats_to_json(stats))
+        .with("tt", tt_stats_to_json(tt_stats).with("hashfull", hashfull))
+}
+
+/// Report of a batch of self-play games: results, terminations, lengths and speed
+pub fn selfplay_report(games: &[Game]) -> Json {
+    let count_results = |result: GameResult| games.iter().filter(|game| game.result == result).count();
+    let mut terminations = Json::object();
+    let all_terminations = [
+        "checkmate",
+        "stalemate",
+        "repetition",
+        "fifty_moves",
+        "insufficient_material",
+        "resignation",
+        "adjudicated_draw",
+        "likely_draw",
+        "max_plies",
+    ];
+    for termination in all_terminations.iter() {
+        let count = games.iter().filter(|game| game.termination == *termination).count();
+        terminations = terminations.with(termination, count);
+    }
+    let plies: Vec<usize> = games.iter().map(|game| game.moves.len()).collect();
+    let nodes: u64 = games.iter().map(|game| game.nodes).sum();
+    let time_ms: u64 = games.iter().map(|game| game.time_ms as u64).sum();
+    let nps = (nodes * 1000).checked_div(time_ms).unwrap_or(0);
+    Json::object()
+        .with("kind", "selfplay")
+        .with("timestamp_ms", unix_time_ms())
+        .with("games", games.len())
+        .with(
+            "results",
+            Json::object()
+                .with("white_wins", count_results(GameResult::WhiteWin))
+                .with("black_wins", count_results(GameResult::BlackWin))
+                .with("draws", count_results(GameResult::Draw)),
+        )
+        .with("terminations", terminations)
+        .with("plies", plies)
+        .with("nodes", nodes)
+        .with("time_ms", time_ms)
+        .with("nps", nps)
+}
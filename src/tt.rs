@@ -0,0 +1,492 @@
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::mem::size_of;
+use std::path::Path;
+
+use crate::zobrist;
+use crate::{Castle, MoveStruct, MoveUnion, State, PROMOTION_IDS};
+
+//
+// Constants
+//
+pub const DEFAULT_HASH_MB: usize = 16;
+const HASHFULL_SAMPLE: usize = 1000;
+const FILE_MAGIC: &[u8; 4] = b"GCTT";
+/// 2 since the keys are Zobrist hashes: the SipHash keys of version 1
+/// files match no position anymore
+const FILE_VERSION: u32 = 2;
+/// move kind byte of a promotion, before the piece id
+const PROMOTION_KIND: u8 = 10;
+
+//
+// Structs
+//
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Bound {
+    Exact,
+    Lower,
+    Upper,
+}
+
+#[derive(Clone)]
+pub struct TTEntry {
+    pub key: u64,
+    pub depth: u32,
+    pub score: isize,
+    pub bound: Bound,
+    pub best_move: Option<MoveStruct>,
+    generation: u8,
+}
+
+/// Counters collected since the last `new_search`
+#[derive(Debug, Default, Copy, Clone)]
+pub struct TTStats {
+    pub probes: u64,
+    pub hits: u64,
+    pub cutoffs: u64,
+    pub stores: u64,
+    /// an entry of another position was overwritten
+    pub replacements: u64,
+    /// the entry of the same position was refreshed
+    pub updates: u64,
+    /// the store was dropped to keep a deeper entry of another position
+    pub rejections: u64,
+}
+
+impl TTStats {
+    /// add the counters of `other`
+    pub fn add(&mut self, other: &TTStats) {
+        self.probes += other.probes;
+        self.hits += other.hits;
+        self.cutoffs += other.cutoffs;
+        self.stores += other.stores;
+        self.replacements += other.replacements;
+        self.updates += other.updates;
+        self.rejections += other.rejections;
+    }
+}
+
+/// Search results keyed by `position_key`, the Zobrist hash of the position
+pub struct TranspositionTable {
+    entries: Vec<Option<TTEntry>>,
+    generation: u8,
+    pub stats: TTStats,
+}
+
+impl TranspositionTable {
+    pub fn new(size_mb: usize) -> Self {
+        Self {
+            entries: vec![None; num_entries(size_mb)],
+            generation: 0,
+            stats: TTStats::default(),
+        }
+    }
+
+    /// Resize the table to `size_mb`, which empties it unless the size is unchanged
+    pub fn resize(&mut self, size_mb: usize) {
+        if num_entries(size_mb) != self.entries.len() {
+            *self = Self::new(size_mb);
+        }
+    }
+
+    pub fn size_mb(&self) -> usize {
+        self.size_bytes() / (1024 * 1024)
+    }
+
+    pub fn size_bytes(&self) -> usize {
+        self.entries.len() * size_of::<Option<TTEntry>>()
+    }
+
+    pub fn clear(&mut self) {
+        for entry in self.entries.iter_mut() {
+            *entry = None;
+        }
+        self.generation = 0;
+        self.stats = TTStats::default();
+    }
+
+    /// start a new search: older entries become replaceable and stats are reset
+    pub fn new_search(&mut self) {
+        self.generation = self.generation.wrapping_add(1);
+        self.stats = TTStats::default();
+    }
+
+    pub fn probe(&mut self, key: u64) -> Option<TTEntry> {
+        self.stats.probes += 1;
+        let index = self.index(key);
+        match &self.entries[index] {
+            Some(entry) if entry.key == key => {
+                self.stats.hits += 1;
+                Some(entry.clone())
+            }
+            _ => None,
+        }
+    }
+
+    pub fn store(
+        &mut self,
+        key: u64,
+        depth: u32,
+        score: isize,
+        bound: Bound,
+        best_move: Option<MoveStruct>,
+    ) {
+        let index = self.index(key);
+        let generation = self.generation;
+        match &self.entries[index] {
+            Some(entry) if entry.key == key => self.stats.updates += 1,
+            Some(entry) if entry.generation == generation && entry.depth > depth => {
+                self.stats.rejections += 1;
+                return;
+            }
+            Some(_) => self.stats.replacements += 1,
+            None => {}
+        }
+        self.stats.stores += 1;
+        self.entries[index] = Some(TTEntry {
+            key,
+            depth,
+            score,
+            bound,
+            best_move,
+            generation,
+        });
+    }
+
+    /// occupancy in permill (entries written by the current search), UCI style
+    pub fn hashfull(&self) -> u32 {
+        let sample = HASHFULL_SAMPLE.min(self.entries.len());
+        let used = self.entries[..sample]
+            .iter()
+            .filter(|entry| match entry {
+                Some(entry) => entry.generation == self.generation,
+                None => false,
+            })
+            .count();
+        (used * 1000 / sample) as u32
+    }
+
+    /// write all entries to `path`, returns the number of entries written
+    pub fn save(&self, path: &Path) -> io::Result<usize> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        let entries: Vec<&TTEntry> = self.entries.iter().flatten().collect();
+        writer.write_all(FILE_MAGIC)?;
+        writer.write_all(&FILE_VERSION.to_le_bytes())?;
+        writer.write_all(&(entries.len() as u64).to_le_bytes())?;
+        for entry in entries.iter() {
+            writer.write_all(&entry.key.to_le_bytes())?;
+            writer.write_all(&entry.depth.to_le_bytes())?;
+            writer.write_all(&(entry.score as i64).to_le_bytes())?;
+            writer.write_all(&[bound_to_u8(entry.bound)])?;
+            writer.write_all(&move_to_bytes(&entry.best_move))?;
+        }
+        writer.flush()?;
+        Ok(entries.len())
+    }
+
+    /// merge the entries saved in `path` into the table (the table size may differ),
+    /// returns the number of entries read
+    pub fn load(&mut self, path: &Path) -> io::Result<usize> {
+        let mut reader = BufReader::new(File::open(path)?);
+        let mut magic = [0u8; 4];
+        reader.read_exact(&mut magic)?;
+        if &magic != FILE_MAGIC {
+            return Err(invalid_data("not a transposition table file"));
+        }
+        let version = read_u32(&mut reader)?;
+        if version != FILE_VERSION {
+            return Err(invalid_data(&format!(
+                "transposition table file version {} (expected {}), save it again",
+                version, FILE_VERSION
+            )));
+        }
+        let num_entries = read_u64(&mut reader)? as usize;
+        for _ in 0..num_entries {
+            let key = read_u64(&mut reader)?;
+            let depth = read_u32(&mut reader)?;
+            let score = read_u64(&mut reader)? as i64 as isize;
+            let mut bytes = [0u8; 6];
+            reader.read_exact(&mut bytes)?;
+            let bound = bound_from_u8(bytes[0])?;
+            let best_move = move_from_bytes(&bytes[1..])?;
+            self.store(key, depth, score, bound, best_move);
+        }
+        Ok(num_entries)
+    }
+
+    fn index(&self, key: u64) -> usize {
+        (key % self.entries.len() as u64) as usize
+    }
+}
+
+// HELPER FUNCTIONS
+// ---------------------------------------------------------
+// ---------------------------------------------------------
+
+fn read_u32(reader: &mut impl Read) -> io::Result<u32> {
+    let mut bytes = [0u8; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_u64(reader: &mut impl Read) -> io::Result<u64> {
+    let mut bytes = [0u8; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_string())
+}
+
+fn bound_to_u8(bound: Bound) -> u8 {
+    match bound {
+        Bound::Exact => 0,
+        Bound::Lower => 1,
+        Bound::Upper => 2,
+    }
+}
+
+fn bound_from_u8(byte: u8) -> io::Result<Bound> {
+    match byte {
+        0 => Ok(Bound::Exact),
+        1 => Ok(Bound::Lower),
+        2 => Ok(Bound::Upper),
+        _ => Err(invalid_data("bad bound")),
+    }
+}
+
+/// [kind, from_row, from_col, to_row, to_col] with kind 0 = none, 1 = normal,
+/// 2 = castle, PROMOTION_KIND + piece id = normal with a promotion piece
+fn move_to_bytes(best_move: &Option<MoveStruct>) -> [u8; 5] {
+    unsafe {
+        match best_move {
+            None => [0; 5],
+            Some(MoveStruct {
+                is_castle: false,
+                data: MoveUnion { normal_move },
+                promotion,
+            }) => [
+                promotion.map_or(1, |piece_id| PROMOTION_KIND + piece_id as u8),
+                normal_move.0 .0 as u8,
+                normal_move.0 .1 as u8,
+                normal_move.1 .0 as u8,
+                normal_move.1 .1 as u8,
+            ],
+            Some(MoveStruct {
+                is_castle: true,
+                data: MoveUnion { castle },
+                ..
+            }) => {
+                let castle_id = match castle {
+                    Castle::KingSideWhite => 0,
+                    Castle::QueenSideWhite => 1,
+                    Castle::KingSideBlack => 2,
+                    Castle::QueenSideBlack => 3,
+                };
+                [2, castle_id, 0, 0, 0]
+            }
+        }
+    }
+}
+
+fn move_from_bytes(bytes: &[u8]) -> io::Result<Option<MoveStruct>> {
+    let promotion = bytes[0]
+        .checked_sub(PROMOTION_KIND)
+        .map(|piece_id| piece_id as isize)
+        .filter(|piece_id| PROMOTION_IDS.contains(piece_id));
+    match bytes[0] {
+        0 => Ok(None),
+        kind if kind == 1 || promotion.is_some() => {
+            if bytes[1..5].iter().any(|&x| x > 7) {
+                return Err(invalid_data("bad move"));
+            }
+            let _from = (bytes[1] as isize, bytes[2] as isize);
+            let _to = (bytes[3] as isize, bytes[4] as isize);
+            Ok(Some(MoveStruct {
+                is_castle: false,
+                data: MoveUnion {
+                    normal_move: (_from, _to),
+                },
+                promotion,
+            }))
+        }
+        2 => {
+            let castle = match bytes[1] {
+                0 => Castle::KingSideWhite,
+                1 => Castle::QueenSideWhite,
+                2 => Castle::KingSideBlack,
+                3 => Castle::QueenSideBlack,
+                _ => return Err(invalid_data("bad castle")),
+            };
+            Ok(Some(MoveStruct {
+                is_castle: true,
+                data: MoveUnion { castle },
+                promotion: None,
+            }))
+        }
+        _ => Err(invalid_data("bad move")),
+    }
+}
+
+/// entries of a table of `size_mb`
+fn num_entries(size_mb: usize) -> usize {
+    (size_mb * 1024 * 1024 / size_of::<Option<TTEntry>>()).max(1)
+}
+
+/// hash of everything that defines a position (board, side to move, castling
+/// rights, en passant file): its Zobrist hash, never failing (see
+/// `zobrist::hash_valid_pieces`)
+pub fn position_key(state: &State) -> u64 {
+    zobrist::hash_valid_pieces(state)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+    use std::path::PathBuf;
+
+    use crate::{
+        convert_move_union_to_string, parse_move, BISHOP_ID, KNIGHT_ID, QUEEN_ID, ROOK_ID,
+    };
+
+    fn temp_path(name: &str) -> PathBuf {
+        env::temp_dir().join(format!("gym_chess_tt_{}_{}.bin", name, std::process::id()))
+    }
+
+    fn promoting(_move: &str, piece_id: isize) -> MoveStruct {
+        MoveStruct {
+            promotion: Some(piece_id),
+            ..parse_move(_move).unwrap()
+        }
+    }
+
+    #[test]
+    fn promotion_best_move_round_trip() {
+        let path = temp_path("promotion");
+        let pieces = [QUEEN_ID, ROOK_ID, BISHOP_ID, KNIGHT_ID];
+        let mut tt = TranspositionTable::new(1);
+        for (key, &piece_id) in pieces.iter().enumerate() {
+            tt.store(
+                key as u64,
+                3,
+                100,
+                Bound::Exact,
+                Some(promoting("e7e8", piece_id)),
+            );
+        }
+        assert_eq!(tt.save(&path).unwrap(), 4);
+
+        // magic, version and count, then the key, depth, score and bound of
+        // the first entry before its move kind byte
+        let bytes = fs::read(&path).unwrap();
+        assert_eq!(bytes[16 + 21], PROMOTION_KIND + QUEEN_ID as u8);
+
+        let mut loaded = TranspositionTable::new(1);
+        assert_eq!(loaded.load(&path).unwrap(), 4);
+        fs::remove_file(&path).unwrap();
+        for (key, &piece_id) in pieces.iter().enumerate() {
+            let best_move = loaded.probe(key as u64).unwrap().best_move.unwrap();
+            assert_eq!(
+                convert_move_union_to_string(&best_move),
+                convert_move_union_to_string(&promoting("e7e8", piece_id))
+            );
+            assert_eq!(best_move.promotion, Some(piece_id));
+        }
+    }
+
+    fn saved_table(path: &Path) -> TranspositionTable {
+        let mut tt = TranspositionTable::new(1);
+        tt.store(1, 5, -250, Bound::Exact, Some(parse_move("e2e4").unwrap()));
+        tt.store(
+            2,
+            3,
+            40,
+            Bound::Lower,
+            Some(parse_move("CASTLE_KING_SIDE_WHITE").unwrap()),
+        );
+        tt.store(3, 1, 7, Bound::Upper, None);
+        assert_eq!(tt.save(path).unwrap(), 3);
+        tt
+    }
+
+    #[test]
+    fn save_load_round_trip() {
+        let path = temp_path("round_trip");
+        let mut tt = saved_table(&path);
+        let mut loaded = TranspositionTable::new(1);
+        assert_eq!(loaded.load(&path).unwrap(), 3);
+        fs::remove_file(&path).unwrap();
+        for key in 1..=3 {
+            let entry = tt.probe(key).unwrap();
+            let loaded_entry = loaded.probe(key).unwrap();
+            assert_eq!(loaded_entry.depth, entry.depth);
+            assert_eq!(loaded_entry.score, entry.score);
+            assert_eq!(loaded_entry.bound, entry.bound);
+            assert_eq!(
+                loaded_entry
+                    .best_move
+                    .as_ref()
+                    .map(convert_move_union_to_string),
+                entry.best_move.as_ref().map(convert_move_union_to_string)
+            );
+        }
+    }
+
+    #[test]
+    fn load_rejects_version_1() {
+        let path = temp_path("version_1");
+        let mut bytes = FILE_MAGIC.to_vec();
+        bytes.extend(1u32.to_le_bytes());
+        bytes.extend(0u64.to_le_bytes());
+        fs::write(&path, bytes).unwrap();
+        let error = TranspositionTable::new(1).load(&path).unwrap_err();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert!(error.to_string().contains("version 1"));
+    }
+
+    #[test]
+    fn load_rejects_truncated_files() {
+        let path = temp_path("truncated");
+        saved_table(&path);
+        let bytes = fs::read(&path).unwrap();
+        // in the header, in the count, inside the last entry
+        for len in [2, 12, bytes.len() - 1] {
+            fs::write(&path, &bytes[..len]).unwrap();
+            let error = TranspositionTable::new(1).load(&path).unwrap_err();
+            assert_eq!(error.kind(), io::ErrorKind::UnexpectedEof);
+        }
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn load_into_a_table_of_another_size() {
+        let path = temp_path("other_size");
+        saved_table(&path);
+        let mut larger = TranspositionTable::new(4);
+        assert_eq!(larger.load(&path).unwrap(), 3);
+        assert!((1..=3).all(|key| larger.probe(key).is_some()));
+        // a single entry keeps the deepest one
+        let mut single = TranspositionTable::new(0);
+        assert_eq!(single.entries.len(), 1);
+        assert_eq!(single.load(&path).unwrap(), 3);
+        fs::remove_file(&path).unwrap();
+        assert_eq!(single.probe(1).unwrap().depth, 5);
+        assert!(single.probe(2).is_none() && single.probe(3).is_none());
+    }
+
+    #[test]
+    fn resize() {
+        let mut tt = TranspositionTable::new(1);
+        tt.store(1, 3, 10, Bound::Exact, None);
+        tt.resize(1);
+        assert!(tt.probe(1).is_some());
+        let size_bytes = tt.size_bytes();
+        tt.resize(2);
+        assert!(tt.size_bytes() > size_bytes);
+        assert!(tt.probe(1).is_none());
+    }
+}
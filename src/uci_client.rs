@@ -0,0 +1,430 @@
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::env::{action_to_move, legal_moves, move_to_action};
+use crate::search::{SearchInfo, SearchStats, MATE_SCORE};
+use crate::{
+    convert_move_union_to_string, next_state, parse_move, to_fen, MoveStruct,
+    State,
+};
+
+//
+// Constants
+//
+/// time allowed for the handshake and for searches without a movetime
+pub const DEFAULT_UCI_TIMEOUT: Duration = Duration::from_secs(10);
+/// a timed search may overrun its movetime by this much before "stop" is sent,
+/// and the engine has this much more to answer "stop" before it is restarted
+const SEARCH_GRACE: Duration = Duration::from_secs(2);
+/// tries of a search, the engine being restarted after each failure
+const MAX_ATTEMPTS: u32 = 2;
+
+//
+// Structs
+//
+/// How long the external engine searches a position
+#[derive(Debug, Copy, Clone)]
+pub enum UciLimit {
+    Depth(u32),
+    MoveTime(Duration),
+    Nodes(u64),
+}
+
+impl UciLimit {
+    fn to_go(self) -> String {
+        match self {
+            UciLimit::Depth(depth) => format!("go depth {}", depth),
+            UciLimit::MoveTime(movetime) => format!("go movetime {}", movetime.as_millis()),
+            UciLimit::Nodes(nodes) => format!("go nodes {}", nodes),
+        }
+    }
+}
+
+/// Result of a search of the external engine, moves in this crate's format
+#[derive(Clone)]
+pub struct UciAnalysis {
+    pub best_move: MoveStruct,
+    pub depth: u32,
+    pub seldepth: u32,
+    /// centipawns from the side to move's point of view; announced mates are
+    /// scored like the search does (MATE_SCORE minus the plies to mate)
+    pub score: isize,
+    /// moves to mate announced by the engine, negative when being mated
+    pub mate: Option<i32>,
+    pub nodes: u64,
+    pub time_ms: u128,
+    /// principal variation, cut at the first move this crate rejects
+    pub pv: Vec<MoveStruct>,
+}
+
+impl UciAnalysis {
+    /// as if found by `Searcher`, so that the engine can stand in for it
+    pub fn to_search_info(&self) -> SearchInfo {
+        let best_move = convert_move_union_to_string(&self.best_move);
+        let mut pv = self.pv.clone();
+        if pv.first().map(convert_move_union_to_string) != Some(best_move) {
+            pv = vec![self.best_move.clone()];
+        }
+        SearchInfo {
+            depth: self.depth,
+            seldepth: self.seldepth,
+            score: self.score,
+            nodes: self.nodes,
+            time_ms: self.time_ms,
+            hashfull: 0,
+            lines: vec![(self.score, pv.clone())],
+            pv,
+            stats: SearchStats::default(),
+            root_moves: vec![],
+            time: None,
+        }
+    }
+}
+
+/// An external UCI engine (e.g. Stockfish) run as a child process. Searches
+/// are serialized; an engine that exits, stops answering or sends an illegal
+/// move is killed and relaunched, and the search tried again once.
+pub struct UciEngine {
+    path: String,
+    args: Vec<String>,
+    /// `setoption` values sent after each launch
+    options: Vec<(String, String)>,
+    pub timeout: Duration,
+    process: Mutex<Option<UciProcess>>,
+    restarts: AtomicUsize,
+}
+
+impl UciEngine {
+    /// Launch the engine and go through the UCI handshake
+    pub fn start(
+        path: &str,
+        args: &[String],
+        options: &[(String, String)],
+        timeout: Duration,
+    ) -> Result<Self, String> {
+        let process = UciProcess::launch(path, args, options, timeout)?;
+        Ok(UciEngine {
+            path: path.to_string(),
+            args: args.to_vec(),
+            options: options.to_vec(),
+            timeout,
+            process: Mutex::new(Some(process)),
+            restarts: AtomicUsize::new(0),
+        })
+    }
+
+    /// "id name" of the running engine
+    pub fn name(&self) -> Option<String> {
+        let process = self.process.lock().unwrap();
+        process.as_ref().and_then(|process| process.name.clone())
+    }
+
+    /// times the engine was relaunched after a failure
+    pub fn restarts(&self) -> usize {
+        self.restarts.load(Ordering::SeqCst)
+    }
+
+    /// Search `state` (side to move `state.current_player`) within `limit`
+    pub fn analyse(&self, state: &State, limit: UciLimit) -> Result<UciAnalysis, String> {
+        let mut process = self.process.lock().unwrap();
+        let mut error = String::new();
+        for _ in 0..MAX_ATTEMPTS {
+            if process.is_none() {
+                *process = Some(UciProcess::launch(
+                    &self.path,
+                    &self.args,
+                    &self.options,
+                    self.timeout,
+                )?);
+            }
+            match process.as_mut().unwrap().search(state, limit, self.timeout) {
+                Ok(analysis) => return Ok(analysis),
+                Err(e) => {
+                    error = e;
+                    process.take().unwrap().kill();
+                    self.restarts.fetch_add(1, Ordering::SeqCst);
+                }
+            }
+        }
+        Err(format!("{}: {}", self.path, error))
+    }
+
+    /// Ask the engine to quit (it is relaunched by the next `analyse`)
+    pub fn quit(&self) {
+        if let Some(process) = self.process.lock().unwrap().take() {
+            process.kill();
+        }
+    }
+}
+
+impl Drop for UciEngine {
+    fn drop(&mut self) {
+        self.quit();
+    }
+}
+
+struct UciProcess {
+    child: Child,
+    stdin: ChildStdin,
+    /// lines of the engine's stdout, read by a separate thread so that reads
+    /// can time out
+    lines: Receiver<String>,
+    name: Option<String>,
+}
+
+impl UciProcess {
+    fn launch(
+        path: &str,
+        args: &[String],
+        options: &[(String, String)],
+        timeout: Duration,
+    ) -> Result<Self, String> {
+        let mut child = Command::new(path)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|e| format!("{}: {}", path, e))?;
+        let stdin = child.stdin.take().unwrap();
+        let stdout = child.stdout.take().unwrap();
+        let (sender, lines) = mpsc::channel();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                let line = match line {
+                    Ok(line) => line,
+                    Err(_) => break,
+                };
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+        let mut process = UciProcess {
+            child,
+            stdin,
+            lines,
+            name: None,
+        };
+
+        let handshake = |process: &mut UciProcess| -> Result<(), String> {
+            let deadline = Instant::now() + timeout;
+            process.send("uci")?;
+            let mut name = None;
+            let answered = process.read_until(deadline, |line| {
+                if let Some(id) = line.strip_prefix("id name ") {
+                    name = Some(id.trim().to_string());
+                }
+                line == "uciok"
+            })?;
+            if !answered {
+                return Err("no 'uciok' from the engine".to_string());
+            }
+            process.name = name;
+            for (option, value) in options {
+                process.send(&format!("setoption name {} value {}", option, value))?;
+            }
+            process.wait_ready(deadline)
+        };
+        if let Err(e) = handshake(&mut process) {
+            process.kill();
+            return Err(format!("{}: {}", path, e));
+        }
+        Ok(process)
+    }
+
+    fn send(&mut self, command: &str) -> Result<(), String> {
+        writeln!(self.stdin, "{}", command)
+            .and_then(|_| self.stdin.flush())
+            .map_err(|e| format!("engine is gone ({})", e))
+    }
+
+    /// Pass the engine's lines to `on_line` until it returns true (Ok(true))
+    /// or `deadline` passes (Ok(false)); Err if the engine exited
+    fn read_until<F: FnMut(&str) -> bool>(
+        &mut self,
+        deadline: Instant,
+        mut on_line: F,
+    ) -> Result<bool, String> {
+        loop {
+            let wait = deadline.saturating_duration_since(Instant::now());
+            match self.lines.recv_timeout(wait) {
+                Ok(line) => {
+                    if on_line(line.trim()) {
+                        return Ok(true);
+                    }
+                }
+                Err(RecvTimeoutError::Timeout) => return Ok(false),
+                Err(RecvTimeoutError::Disconnected) => {
+                    return Err("engine exited".to_string());
+                }
+            }
+        }
+    }
+
+    fn wait_ready(&mut self, deadline: Instant) -> Result<(), String> {
+        self.send("isready")?;
+        if !self.read_until(deadline, |line| line == "readyok")? {
+            return Err("no 'readyok' from the engine".to_string());
+        }
+        Ok(())
+    }
+
+    fn search(
+        &mut self,
+        state: &State,
+        limit: UciLimit,
+        timeout: Duration,
+    ) -> Result<UciAnalysis, String> {
+        let start = Instant::now();
+        self.wait_ready(start + timeout)?;
+        self.send(&format!("position fen {}", to_fen(*state)))?;
+        self.send(&limit.to_go())?;
+
+        let deadline = match limit {
+            UciLimit::MoveTime(movetime) => start + movetime + SEARCH_GRACE,
+            _ => start + timeout,
+        };
+        let mut info = InfoLine::default();
+        let mut best_move: Option<String> = None;
+        let mut on_line = |line: &str| {
+            let mut tokens = line.split_whitespace();
+            match tokens.next() {
+                Some("info") => info.update(line),
+                Some("bestmove") => {
+                    best_move = Some(tokens.next().unwrap_or("").to_string());
+                    return true;
+                }
+                _ => {}
+            }
+            false
+        };
+        if !self.read_until(deadline, &mut on_line)? {
+            self.send("stop")?;
+            if !self.read_until(Instant::now() + SEARCH_GRACE, &mut on_line)? {
+                return Err("no 'bestmove' from the engine".to_string());
+            }
+        }
+
+        let best_move = best_move.unwrap_or_default();
+        let best_move = uci_to_move(state, &best_move)
+            .map_err(|e| format!("bad 'bestmove {}': {}", best_move, e))?;
+        let mut pv: Vec<MoveStruct> = vec![];
+        let mut pv_state = *state;
+        for uci in info.pv.iter() {
+            let _move = match uci_to_move(&pv_state, uci) {
+                Ok(_move) => _move,
+                Err(_) => break,
+            };
+            pv_state = next_state(&pv_state, pv_state.current_player, _move.clone()).0;
+            pv.push(_move);
+        }
+        let score = match info.mate {
+            Some(mate) if mate > 0 => MATE_SCORE - (2 * mate - 1) as isize,
+            Some(mate) => -MATE_SCORE - 2 * mate as isize,
+            None => info.score_cp,
+        };
+        Ok(UciAnalysis {
+            best_move,
+            depth: info.depth,
+            seldepth: info.seldepth.max(info.depth),
+            score,
+            mate: info.mate,
+            nodes: info.nodes,
+            time_ms: start.elapsed().as_millis(),
+            pv,
+        })
+    }
+
+    /// "quit", then kill the engine if it is still running
+    fn kill(mut self) {
+        self.send("quit").ok();
+        let deadline = Instant::now() + Duration::from_millis(200);
+        while Instant::now() < deadline {
+            if let Ok(Some(_)) = self.child.try_wait() {
+                return;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        self.child.kill().ok();
+        self.child.wait().ok();
+    }
+}
+
+/// Latest values of the engine's "info" lines (lines without a score, like
+/// "info string" or "info currmove", leave the score and pv alone)
+#[derive(Default)]
+struct InfoLine {
+    depth: u32,
+    seldepth: u32,
+    score_cp: isize,
+    mate: Option<i32>,
+    nodes: u64,
+    pv: Vec<String>,
+}
+
+impl InfoLine {
+    fn update(&mut self, line: &str) {
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        // bounds are only partial results of the iteration
+        if tokens
+            .iter()
+            .any(|&t| t == "lowerbound" || t == "upperbound")
+        {
+            return;
+        }
+        let mut i = 1;
+        while i < tokens.len() {
+            let next = tokens.get(i + 1).copied().unwrap_or("");
+            // unknown keywords (multipv, wdl, ...) are skipped one token at a
+            // time, their values never being keywords
+            let mut step = 2;
+            match tokens[i] {
+                "string" => return,
+                "depth" => self.depth = next.parse().unwrap_or(self.depth),
+                "seldepth" => self.seldepth = next.parse().unwrap_or(self.seldepth),
+                "nodes" => self.nodes = next.parse().unwrap_or(self.nodes),
+                "score" => {
+                    let value = tokens.get(i + 2).copied().unwrap_or("");
+                    match next {
+                        "cp" => {
+                            if let Ok(cp) = value.parse() {
+                                self.score_cp = cp;
+                                self.mate = None;
+                            }
+                        }
+                        "mate" => self.mate = value.parse().ok().or(self.mate),
+                        _ => {}
+                    }
+                    step = 3;
+                }
+                "pv" => {
+                    self.pv = tokens[i + 1..].iter().map(|t| t.to_string()).collect();
+                    return;
+                }
+                _ => step = 1,
+            }
+            i += step;
+        }
+    }
+}
+
+// HELPER FUNCTIONS
+// ---------------------------------------------------------
+// ---------------------------------------------------------
+
+/// Legal move of `state` for a UCI move ("e1g1" castles, promotions are
+/// played as queens)
+pub fn uci_to_move(state: &State, uci: &str) -> Result<MoveStruct, String> {
+    parse_move(uci)?;
+    let _move = action_to_move(state, move_to_action(uci), false)?;
+    if !legal_moves(state, false).contains(&_move) {
+        return Err(format!("Illegal move '{}'", uci));
+    }
+    parse_move(&_move)
+}
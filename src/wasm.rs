@@ -0,0 +1,59 @@
+//! WebAssembly interface for `wasm32-unknown-unknown`, built with
+//! `cargo build --target wasm32-unknown-unknown --no-default-features --features wasm`
+//! and bound for JavaScript with `wasm-bindgen`.
+//!
+//! Entry points take and return strings, moves in UCI notation ("e1g1"
+//! castles, "e7e8n" underpromotes); errors (a bad FEN, an illegal move) are
+//! thrown as JavaScript exceptions carrying the message.
+use std::cell::RefCell;
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+
+use wasm_bindgen::prelude::*;
+
+use crate::search::Searcher;
+use crate::tt::TranspositionTable;
+use crate::{from_fen, legal_move_strings_uci, make_uci_move_str, move_to_uci, try_to_fen};
+
+const WASM_HASH_MB: usize = 4;
+
+thread_local! {
+    static TT: RefCell<TranspositionTable> = RefCell::new(TranspositionTable::new(WASM_HASH_MB));
+}
+
+fn to_js_error(error: String) -> JsValue {
+    JsValue::from_str(&error)
+}
+
+/// Space separated legal moves ("e2e4 g1f3 ... e1g1 ... e7e8q e7e8r e7e8b e7e8n")
+#[wasm_bindgen]
+pub fn legal_moves(fen: &str) -> Result<String, JsValue> {
+    let state = from_fen(fen).map_err(to_js_error)?;
+    Ok(legal_move_strings_uci(&state).join(" "))
+}
+
+/// FEN of the position after the move ("e7e8" promotes to a queen)
+#[wasm_bindgen]
+pub fn make_move(fen: &str, _move: &str) -> Result<String, JsValue> {
+    let state = from_fen(fen).map_err(to_js_error)?;
+    let new_state = make_uci_move_str(&state, _move).map_err(to_js_error)?;
+    try_to_fen(new_state).map_err(to_js_error)
+}
+
+/// "<best move> <score>" (best move is empty when there is no legal move)
+#[wasm_bindgen]
+pub fn search(fen: &str, depth: u32) -> Result<String, JsValue> {
+    let state = from_fen(fen).map_err(to_js_error)?;
+    let info = TT.with(|tt| {
+        let mut tt = tt.borrow_mut();
+        let mut searcher = Searcher::new(Arc::new(AtomicBool::new(false)), &mut tt);
+        searcher.iterative_deepening(&state, state.current_player, depth, None, |_| {})
+    });
+    Ok(match info {
+        Some(info) => {
+            let best_move = info.pv.first().map(|_move| move_to_uci(&state, _move));
+            format!("{} {}", best_move.unwrap_or_default(), info.score)
+        }
+        None => "".to_string(),
+    })
+}
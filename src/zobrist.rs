@@ -0,0 +1,153 @@
+use lazy_static::lazy_static;
+
+use crate::rng::Rng;
+use crate::{Color, State, EMPTY_SQUARE_ID, PAWN_ID};
+
+//
+// Constants
+//
+/// fixed so that hashes are the same from run to run (saved tables, books)
+const ZOBRIST_SEED: u64 = 0x5A0B_0157_C4E5_5EED;
+
+//
+// Structs
+//
+/// Random keys xored together into the hash of a position: one per piece
+/// and square, one for black to move, one per castling right and one per en
+/// passant file
+pub struct ZobristKeys {
+    /// by piece (white king to black pawn) and square (row * 8 + col)
+    pub pieces: [[u64; 64]; 12],
+    pub black_to_move: u64,
+    /// white king side, white queen side, black king side, black queen side
+    pub castling: [u64; 4],
+    pub en_passant: [u64; 8],
+}
+
+lazy_static! {
+    pub static ref ZOBRIST_KEYS: ZobristKeys = {
+        let mut rng = Rng::new(ZOBRIST_SEED);
+        let mut keys = ZobristKeys {
+            pieces: [[0; 64]; 12],
+            black_to_move: rng.next_u64(),
+            castling: [0; 4],
+            en_passant: [0; 8],
+        };
+        for piece in keys.pieces.iter_mut() {
+            for key in piece.iter_mut() {
+                *key = rng.next_u64();
+            }
+        }
+        for key in keys.castling.iter_mut().chain(keys.en_passant.iter_mut()) {
+            *key = rng.next_u64();
+        }
+        keys
+    };
+}
+
+/// Zobrist hash of the board, side to move, castling rights and en passant
+/// file of `state`, failing on a piece id other than 1 to 6 (either sign)
+/// or an en passant square off the board
+pub fn hash(state: &State) -> Result<u64, String> {
+    for (row, pieces) in state.board.iter().enumerate() {
+        for (col, &piece_id) in pieces.iter().enumerate() {
+            if piece_id != EMPTY_SQUARE_ID && piece_index(piece_id).is_none() {
+                return Err(format!("Invalid piece id {} at board[{}][{}]", piece_id, row, col));
+            }
+        }
+    }
+    if let Some(square) = state.en_passant_square {
+        if en_passant_key(&ZOBRIST_KEYS, square.1).is_none() {
+            return Err(format!("Invalid en passant square {:?}", square));
+        }
+    }
+    Ok(hash_valid_pieces(state))
+}
+
+/// `hash` without the checks: squares with an invalid piece id are hashed
+/// as empty, and an en passant square off the board is ignored
+pub fn hash_valid_pieces(state: &State) -> u64 {
+    let keys = &*ZOBRIST_KEYS;
+    let mut hash: u64 = 0;
+    for (row, pieces) in state.board.iter().enumerate() {
+        for (col, &piece_id) in pieces.iter().enumerate() {
+            if let Some(index) = piece_index(piece_id) {
+                hash ^= keys.pieces[index][row * 8 + col];
+            }
+        }
+    }
+    if state.current_player == Color::Black {
+        hash ^= keys.black_to_move;
+    }
+    let rights = [
+        state.white_king_castle_is_possible,
+        state.white_queen_castle_is_possible,
+        state.black_king_castle_is_possible,
+        state.black_queen_castle_is_possible,
+    ];
+    for (&right, &key) in rights.iter().zip(keys.castling.iter()) {
+        if right {
+            hash ^= key;
+        }
+    }
+    if let Some(key) = state.en_passant_square.and_then(|(_, col)| en_passant_key(keys, col)) {
+        hash ^= key;
+    }
+    hash
+}
+
+// HELPER FUNCTIONS
+// ---------------------------------------------------------
+// ---------------------------------------------------------
+
+/// 0 to 5 for the white king to pawn, 6 to 11 for black's (None for an
+/// empty square or an invalid id)
+fn piece_index(piece_id: isize) -> Option<usize> {
+    if piece_id == EMPTY_SQUARE_ID || piece_id.abs() > PAWN_ID {
+        return None;
+    }
+    let index = (piece_id.abs() - 1) as usize;
+    if piece_id > 0 {
+        return Some(index);
+    }
+    Some(index + PAWN_ID as usize)
+}
+
+fn en_passant_key(keys: &ZobristKeys, col: isize) -> Option<u64> {
+    if !(0..8).contains(&col) {
+        return None;
+    }
+    Some(keys.en_passant[col as usize])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{from_fen, next_state, parse_move};
+
+    const START_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+    fn play(fen: &str, moves: &[&str]) -> State {
+        let mut state = from_fen(fen).unwrap();
+        for _move in moves {
+            state = next_state(&state, state.current_player, parse_move(_move).unwrap()).0;
+        }
+        state
+    }
+
+    #[test]
+    fn same_position_by_two_move_orders_has_the_same_hash() {
+        let king_walk = ["e2e4", "e7e5", "e1e2", "e8e7", "e2e1", "e7e8"];
+        let mut first = king_walk.to_vec();
+        first.extend(["g1f3", "b8c6"]);
+        let mut second = vec!["g1f3", "b8c6"];
+        second.extend(king_walk);
+        let first = play(START_FEN, &first);
+        let second = play(START_FEN, &second);
+        assert_eq!(first.board, second.board);
+        assert_eq!(hash_valid_pieces(&first), hash_valid_pieces(&second));
+        // the same board with the castling rights kept hashes differently
+        let castling = play(START_FEN, &["e2e4", "e7e5", "g1f3", "b8c6"]);
+        assert_ne!(hash_valid_pieces(&first), hash_valid_pieces(&castling));
+    }
+}